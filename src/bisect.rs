@@ -0,0 +1,208 @@
+use crate::{
+    changes,
+    context::RunContext,
+    failure::{Classify, Failure},
+    filesystem::Fs,
+    git::Checkout,
+    update::{self, RepoMatcher, Version},
+};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env::current_dir,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use structopt::StructOpt;
+
+/// `bisect` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct Bisect {
+    /// The path where Diener should search for `Cargo.toml` files.
+    ///
+    /// Can be passed multiple times and may be a glob pattern like `runtimes/*`.
+    /// If not given, the current directory will be taken.
+    #[structopt(long)]
+    path: Vec<PathBuf>,
+
+    /// A rev of the Polkadot SDK for which the command succeeds.
+    #[structopt(long)]
+    good: String,
+
+    /// A rev of the Polkadot SDK for which the command fails.
+    #[structopt(long)]
+    bad: String,
+
+    /// The url of the repository to bisect.
+    ///
+    /// Defaults to the `git` url of the Polkadot SDK dependencies, which must be the same in all
+    /// of them.
+    #[structopt(long)]
+    git: Option<String>,
+
+    /// The command that checks a rev, e.g. `cargo check`.
+    ///
+    /// It is run in the working directory, a non-zero exit code marks the rev as bad.
+    #[structopt(required = true, last = true)]
+    command: Vec<String>,
+}
+
+impl Bisect {
+    /// Run this subcommand.
+    ///
+    /// The manifests are written directly while bisecting, as the command needs them on disk.
+    /// Their original content and the one of the `Cargo.lock` files the command may update are
+    /// restored at the end.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        if ctx.dry_run {
+            return Err(anyhow!(
+                "`bisect` needs to write the manifests to run the command, it can't run with \
+                 `--dry-run`."
+            )
+            .failure(Failure::Usage));
+        }
+        let repos: RepoMatcher = ctx.config.match_repos().into();
+
+        let mut originals = BTreeMap::new();
+        let mut urls = BTreeSet::new();
+        let manifests = ctx.manifests(&self.path)?;
+        // The lock files of the workspaces, `None` if they don't exist yet.
+        let mut locks = BTreeMap::new();
+        let dirs = manifests
+            .iter()
+            .filter_map(|m| m.parent().map(Path::to_path_buf))
+            .chain(current_dir().ok());
+        for dir in dirs {
+            let lock = dir.join("Cargo.lock");
+            locks
+                .entry(lock)
+                .or_insert_with_key(|lock| ctx.changes.fs().read(lock).ok());
+        }
+        for manifest in manifests {
+            let content = ctx.changes.read(&manifest)?;
+            let mut doc = ctx.changes.read_toml(&manifest)?;
+            update::for_each_managed_dependency(&mut doc, &repos, |_, dep| {
+                if let Some(git) = dep.get("git").and_then(|g| g.as_str()) {
+                    urls.insert(git.to_string());
+                }
+            });
+            originals.insert(manifest, content);
+        }
+
+        let url = match (self.git.clone(), urls.len()) {
+            (Some(url), _) => url,
+            (None, 1) => urls.pop_first().expect("There is exactly one url; qed"),
+            (None, 0) => bail!("No Polkadot SDK git dependencies found."),
+            (None, _) => bail!(
+                "The Polkadot SDK dependencies use different git urls, pass `--git`: {}",
+                urls.into_iter().collect::<Vec<_>>().join(", ")
+            ),
+        };
+
+        ctx.ensure_online("Bisecting")?;
+        let checkout = Checkout::history(&url, &self.bad)?;
+        let commits = checkout.commits_between(&self.good, &self.bad)?;
+        ensure!(
+            !commits.is_empty(),
+            "There are no commits between `{}` and `{}`.",
+            self.good,
+            self.bad
+        );
+
+        let fs = ctx.changes.fs();
+        let res = self.bisect(fs, &commits, &originals, &repos);
+
+        for (manifest, content) in &originals {
+            if let Err(e) = fs.write(manifest, content) {
+                log::error!("Failed to restore {}: {}", manifest.display(), e);
+            }
+        }
+        for (lock, content) in &locks {
+            let res = match content {
+                Some(content) => fs.write(lock, content),
+                None if fs.is_file(lock) => fs.remove_file(lock),
+                None => Ok(()),
+            };
+            if let Err(e) = res {
+                log::error!("Failed to restore {}: {}", lock.display(), e);
+            }
+        }
+        log::info!(
+            "Restored {} manifest(s) and their `Cargo.lock`.",
+            originals.len()
+        );
+
+        let first_bad = res?;
+        log::info!("`{}` is the first bad commit.", first_bad);
+        println!("{first_bad}");
+        Ok(())
+    }
+
+    /// Binary search the first commit of `commits` for which the command fails.
+    ///
+    /// The commit before `commits` is good and the last one is bad.
+    fn bisect<'a>(
+        &self,
+        fs: &dyn Fs,
+        commits: &'a [String],
+        originals: &BTreeMap<PathBuf, String>,
+        repos: &RepoMatcher,
+    ) -> Result<&'a str> {
+        // The commit before `first` is good, the commit at `last` is bad.
+        let (mut first, mut last) = (0, commits.len() - 1);
+        while first < last {
+            let mid = first + (last - first) / 2;
+            let rev = &commits[mid];
+            log::info!("Checking `{}`, {} commit(s) left.", rev, last - first);
+
+            self.rewrite(fs, originals, repos, rev)?;
+            if self.check()? {
+                first = mid + 1;
+            } else {
+                last = mid;
+            }
+        }
+
+        Ok(&commits[last])
+    }
+
+    /// Write the `originals` with all Polkadot SDK dependencies pointing to `rev`.
+    fn rewrite(
+        &self,
+        fs: &dyn Fs,
+        originals: &BTreeMap<PathBuf, String>,
+        repos: &RepoMatcher,
+        rev: &str,
+    ) -> Result<()> {
+        let version = Version::Rev(rev.into());
+        for (manifest, content) in originals {
+            let mut doc = changes::parse_toml(manifest, content)?;
+            update::update_document(&mut doc, repos, |_| true, |_| true, &self.git, &version);
+            fs.write(manifest, &doc.to_string())
+                .with_context(|| anyhow!("Failed to write {}", manifest.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Run the command, returns `true` if it succeeded.
+    fn check(&self) -> Result<bool> {
+        let (program, args) = self
+            .command
+            .split_first()
+            .expect("`command` is required; qed");
+        let status = Command::new(program)
+            .args(args)
+            .status()
+            .with_context(|| anyhow!("Failed to execute `{}`", self.command.join(" ")))?;
+        log::info!(
+            "`{}` {}.",
+            self.command.join(" "),
+            if status.success() {
+                "succeeded"
+            } else {
+                "failed"
+            }
+        );
+        Ok(status.success())
+    }
+}
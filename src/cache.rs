@@ -0,0 +1,464 @@
+use crate::{context::RunContext, format::OutputFormat, http, incremental, metadata, update, walk};
+use anyhow::{anyhow, bail, ensure, Context, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    env::current_dir,
+    fmt, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+use structopt::StructOpt;
+use walkdir::WalkDir;
+
+/// How old cache entries get before `cache gc` removes them by default.
+const DEFAULT_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// The prefix of the temporary checkouts, see [`Checkout`](crate::git::Checkout).
+const CHECKOUT_PREFIX: &str = "diener-";
+
+/// The on-disk caches, configured in `[cache]`.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct CacheConfig {
+    /// How old entries get before `cache gc` removes them, 30 days by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ttl: Option<Ttl>,
+}
+
+impl CacheConfig {
+    /// How old entries get before `cache gc` removes them.
+    pub(crate) fn ttl(&self) -> Duration {
+        self.ttl.map_or(DEFAULT_TTL, |ttl| ttl.0)
+    }
+}
+
+/// A duration like `30d`, `12h`, `90m` or `45s`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) struct Ttl(Duration);
+
+impl FromStr for Ttl {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let unit = s
+            .char_indices()
+            .find(|(_, c)| !c.is_ascii_digit())
+            .map_or(s.len(), |(i, _)| i);
+        let (value, unit) = s.split_at(unit);
+        let value = value
+            .parse::<u64>()
+            .map_err(|_| anyhow!("Invalid duration `{s}`, expected e.g. `30d` or `12h`."))?;
+        let seconds = match unit {
+            "d" => 24 * 60 * 60,
+            "h" => 60 * 60,
+            "m" => 60,
+            "s" => 1,
+            _ => bail!("Invalid duration `{s}`, the unit must be `d`, `h`, `m` or `s`."),
+        };
+        Ok(Self(Duration::from_secs(value * seconds)))
+    }
+}
+
+impl TryFrom<String> for Ttl {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<Ttl> for String {
+    fn from(ttl: Ttl) -> Self {
+        let seconds = ttl.0.as_secs();
+        match seconds {
+            s if s % (24 * 60 * 60) == 0 => format!("{}d", s / (24 * 60 * 60)),
+            s if s % (60 * 60) == 0 => format!("{}h", s / (60 * 60)),
+            s if s % 60 == 0 => format!("{}m", s / 60),
+            s => format!("{s}s"),
+        }
+    }
+}
+
+/// A cache diener keeps on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum CacheKind {
+    /// The responses of the forge APIs, revalidated with their `ETag`.
+    Api,
+    /// The results of `cargo metadata`, see `--cache-metadata`.
+    Metadata,
+    /// The hashes of `update --incremental`.
+    Incremental,
+    /// Temporary git checkouts left behind by runs that were killed.
+    Checkouts,
+}
+
+impl CacheKind {
+    const ALL: [Self; 4] = [
+        Self::Api,
+        Self::Metadata,
+        Self::Incremental,
+        Self::Checkouts,
+    ];
+
+    /// What is cached, for messages.
+    fn name(self) -> &'static str {
+        match self {
+            Self::Api => "API responses",
+            Self::Metadata => "cargo metadata",
+            Self::Incremental => "incremental hashes",
+            Self::Checkouts => "git checkouts",
+        }
+    }
+}
+
+impl FromStr for CacheKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "api" => Ok(Self::Api),
+            "metadata" => Ok(Self::Metadata),
+            "incremental" => Ok(Self::Incremental),
+            "checkouts" => Ok(Self::Checkouts),
+            _ => bail!(
+                "Invalid cache `{s}`, expected `api`, `metadata`, `incremental` or `checkouts`."
+            ),
+        }
+    }
+}
+
+/// A file or directory of a cache.
+struct Entry {
+    kind: CacheKind,
+    path: PathBuf,
+    bytes: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Entry {
+    fn new(kind: CacheKind, path: PathBuf) -> Self {
+        let files = WalkDir::new(&path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter_map(|e| e.metadata().ok())
+            .filter(|m| m.is_file())
+            .collect::<Vec<_>>();
+        Self {
+            kind,
+            bytes: files.iter().map(|m| m.len()).sum(),
+            modified: files.iter().filter_map(|m| m.modified().ok()).max(),
+            path,
+        }
+    }
+
+    /// Returns `true` if the entry wasn't modified for longer than `ttl`.
+    fn is_older_than(&self, ttl: Duration) -> bool {
+        self.modified
+            .and_then(|m| m.elapsed().ok())
+            .is_none_or(|age| age > ttl)
+    }
+}
+
+/// The cache options shared by all actions.
+#[derive(Debug, StructOpt)]
+pub(crate) struct Scope {
+    /// The path where Diener should search for the workspaces whose `cargo metadata` cache is
+    /// managed.
+    ///
+    /// Can be passed multiple times and may be a glob pattern like `runtimes/*`.
+    /// If not given, the current directory will be taken.
+    #[structopt(long)]
+    path: Vec<PathBuf>,
+
+    /// Only manage the given cache, `api`, `metadata`, `incremental` or `checkouts`.
+    ///
+    /// Can be passed multiple times, all caches are managed if none is given.
+    #[structopt(long = "cache", value_name = "CACHE")]
+    kinds: Vec<CacheKind>,
+}
+
+/// `cache` subcommand options.
+#[derive(Debug, StructOpt)]
+pub enum Cache {
+    /// Print the location, the number of entries and the size of every cache.
+    Info {
+        #[structopt(flatten)]
+        scope: Scope,
+
+        /// The output format, `text` or `json`.
+        ///
+        /// Defaults to the `format` of the configuration or `text`.
+        #[structopt(long)]
+        format: Option<OutputFormat>,
+    },
+    /// Remove all entries of the caches.
+    Clear {
+        #[structopt(flatten)]
+        scope: Scope,
+    },
+    /// Remove the entries that were not used for longer than the TTL.
+    Gc {
+        #[structopt(flatten)]
+        scope: Scope,
+
+        /// Remove the entries older than this, e.g. `7d` or `12h`.
+        ///
+        /// Defaults to the `ttl` of `[cache]` in the configuration or 30 days.
+        #[structopt(long)]
+        ttl: Option<Ttl>,
+    },
+}
+
+/// The entries of one cache, as printed by `cache info`.
+#[derive(Debug, Serialize)]
+struct Summary {
+    cache: CacheKind,
+    name: &'static str,
+    /// The directories the entries are in.
+    locations: Vec<PathBuf>,
+    entries: usize,
+    bytes: u64,
+}
+
+impl Cache {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &RunContext) -> Result<()> {
+        match self {
+            Self::Info { scope, format } => {
+                let format = format.or(ctx.config.format()).unwrap_or(OutputFormat::Text);
+                ensure!(
+                    format != OutputFormat::Github,
+                    "`cache info` doesn't support the `github` format."
+                );
+                info(&scope, ctx, format)
+            }
+            Self::Clear { scope } => remove(&scope, ctx, None),
+            Self::Gc { scope, ttl } => {
+                let ttl = ttl.map_or_else(|| ctx.config.cache().ttl(), |ttl| ttl.0);
+                remove(&scope, ctx, Some(ttl))
+            }
+        }
+    }
+}
+
+/// Print the summary of every cache of `scope`.
+fn info(scope: &Scope, ctx: &RunContext, format: OutputFormat) -> Result<()> {
+    let locations = scope.locations(ctx)?;
+    let entries = scope.entries(&locations);
+    let summaries = scope
+        .kinds()
+        .into_iter()
+        .map(|kind| {
+            let of_kind = entries.iter().filter(|e| e.kind == kind);
+            Summary {
+                cache: kind,
+                name: kind.name(),
+                locations: locations
+                    .iter()
+                    .filter(|(k, _)| *k == kind)
+                    .map(|(_, dir)| dir.clone())
+                    .collect(),
+                entries: of_kind.clone().count(),
+                bytes: of_kind.map(|e| e.bytes).sum(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&summaries)?),
+        OutputFormat::Text | OutputFormat::Github => {
+            for summary in &summaries {
+                println!(
+                    "{:<20} {:>6} entries {:>10}",
+                    summary.name,
+                    summary.entries,
+                    ByteSize(summary.bytes)
+                );
+                for location in &summary.locations {
+                    println!("  {}", location.display());
+                }
+            }
+            println!(
+                "{:<20} {:>6} entries {:>10}",
+                "Total",
+                entries.len(),
+                ByteSize(entries.iter().map(|e| e.bytes).sum())
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove the entries of the caches of `scope`, only the ones older than `ttl` if given.
+fn remove(scope: &Scope, ctx: &RunContext, ttl: Option<Duration>) -> Result<()> {
+    let locations = scope.locations(ctx)?;
+    let entries = scope
+        .entries(&locations)
+        .into_iter()
+        .filter(|e| ttl.is_none_or(|ttl| e.is_older_than(ttl)))
+        .collect::<Vec<_>>();
+
+    let bytes = entries.iter().map(|e| e.bytes).sum::<u64>();
+    if ctx.dry_run {
+        for entry in &entries {
+            log::info!("Would remove {}.", entry.path.display());
+        }
+        log::info!(
+            "Dry run, not removing {} cache entries ({}).",
+            entries.len(),
+            ByteSize(bytes)
+        );
+        return Ok(());
+    }
+
+    for entry in &entries {
+        log::debug!("Removing {}.", entry.path.display());
+        let removed = if entry.path.is_dir() {
+            fs::remove_dir_all(&entry.path)
+        } else {
+            fs::remove_file(&entry.path)
+        };
+        removed.with_context(|| anyhow!("Failed to remove {}", entry.path.display()))?;
+    }
+    log::info!(
+        "Removed {} cache entries ({}).",
+        entries.len(),
+        ByteSize(bytes)
+    );
+
+    Ok(())
+}
+
+impl Scope {
+    /// The managed caches.
+    fn kinds(&self) -> Vec<CacheKind> {
+        if self.kinds.is_empty() {
+            CacheKind::ALL.to_vec()
+        } else {
+            CacheKind::ALL
+                .into_iter()
+                .filter(|k| self.kinds.contains(k))
+                .collect()
+        }
+    }
+
+    /// Returns the directories of the managed caches.
+    fn locations(&self, ctx: &RunContext) -> Result<Vec<(CacheKind, PathBuf)>> {
+        let working_dir = current_dir().with_context(|| "Working directory is invalid.")?;
+        let mut locations = Vec::new();
+        for kind in self.kinds() {
+            match kind {
+                CacheKind::Api => locations.extend(http::cache_dir().map(|dir| (kind, dir))),
+                CacheKind::Metadata => {
+                    for dir in update::search_dirs(ctx.changes.fs(), &self.path)? {
+                        locations.extend(
+                            walk::workspace_roots(ctx.changes.fs(), &dir, &ctx.exclusions)
+                                .into_iter()
+                                .map(|root| (kind, root.join(metadata::CACHE_DIR))),
+                        );
+                    }
+                }
+                CacheKind::Incremental => {
+                    locations.push((kind, working_dir.join(incremental::CACHE_DIR)))
+                }
+                CacheKind::Checkouts => locations.push((kind, std::env::temp_dir())),
+            }
+        }
+        locations.dedup();
+        Ok(locations)
+    }
+
+    /// Returns all entries in the cache `locations`.
+    fn entries(&self, locations: &[(CacheKind, PathBuf)]) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        for (kind, dir) in locations {
+            let Ok(dir_entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in dir_entries.filter_map(|e| e.ok()) {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let is_entry = match kind {
+                    CacheKind::Api => true,
+                    // The incremental hashes and the metadata share the directory.
+                    CacheKind::Metadata => name.starts_with("metadata-"),
+                    CacheKind::Incremental => !name.starts_with("metadata-"),
+                    CacheKind::Checkouts => is_leftover_checkout(&name),
+                };
+                if is_entry {
+                    entries.push(Entry::new(*kind, entry.path()));
+                }
+            }
+        }
+        entries
+    }
+}
+
+/// Returns `true` if `name` is a temporary checkout of a diener process that is no longer
+/// running.
+///
+/// The checkouts are named `diener-<pid>-<n>` and removed at the end of the run, they are only
+/// left behind by runs that were killed. The checkouts of this and of other running processes
+/// are still in use.
+fn is_leftover_checkout(name: &str) -> bool {
+    let Some((pid, n)) = name
+        .strip_prefix(CHECKOUT_PREFIX)
+        .and_then(|rest| rest.split_once('-'))
+    else {
+        return false;
+    };
+    let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let Ok(pid) = pid.parse::<u32>() else {
+        return false;
+    };
+    is_number(n) && pid != std::process::id() && !is_running(pid)
+}
+
+/// Returns `true` if the process `pid` is running.
+///
+/// If this can't be determined, the process is assumed to be running, so its checkouts are kept.
+fn is_running(pid: u32) -> bool {
+    if cfg!(target_os = "linux") {
+        return Path::new("/proc").join(pid.to_string()).exists();
+    }
+
+    let output = if cfg!(windows) {
+        Command::new("tasklist")
+            .args(["/NH", "/FI", &format!("PID eq {pid}")])
+            .output()
+    } else {
+        Command::new("ps").args(["-p", &pid.to_string()]).output()
+    };
+    match output {
+        Ok(output) if cfg!(windows) => {
+            String::from_utf8_lossy(&output.stdout).contains(&format!(" {pid} "))
+        }
+        Ok(output) => output.status.success(),
+        Err(_) => true,
+    }
+}
+
+/// A number of bytes formatted for humans, e.g. `1.5 MiB`.
+struct ByteSize(u64);
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+        let mut size = self.0 as f64;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+        let size = if unit == 0 {
+            format!("{} {}", self.0, UNITS[0])
+        } else {
+            format!("{size:.1} {}", UNITS[unit])
+        };
+        f.pad(&size)
+    }
+}
@@ -0,0 +1,893 @@
+use crate::{
+    diff,
+    failure::{Classify, Failure},
+    filesystem::{Fs, RealFs},
+    format::OutputFormat,
+    manifest, oplog,
+    timings::{self, Phase},
+};
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+use toml_edit::Document;
+
+/// The number of read files [`Changes`] keeps by default, see `--max-buffered`.
+const DEFAULT_MAX_BUFFERED: usize = 256;
+
+/// The content of the files read through [`Changes`] that are not staged yet, so staging them
+/// doesn't read them a second time.
+///
+/// At most `capacity` files are kept, the ones read first are dropped first.
+struct ReadCache {
+    capacity: usize,
+    files: HashMap<PathBuf, String>,
+    /// The paths of `files` in the order they were read.
+    order: VecDeque<PathBuf>,
+}
+
+impl ReadCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            files: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.shrink();
+    }
+
+    fn insert(&mut self, path: PathBuf, content: String) {
+        if self.files.insert(path.clone(), content).is_none() {
+            self.order.push_back(path);
+        }
+        self.shrink();
+    }
+
+    fn remove(&mut self, path: &Path) -> Option<String> {
+        let content = self.files.remove(path)?;
+        self.order.retain(|p| p != path);
+        Some(content)
+    }
+
+    fn clear(&mut self) {
+        self.files.clear();
+        self.order.clear();
+    }
+
+    /// Drop the files read first until at most `capacity` are left.
+    fn shrink(&mut self) {
+        while self.files.len() > self.capacity {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            self.files.remove(&oldest);
+        }
+    }
+}
+
+/// The cache files staged in [`Changes`], e.g. the hashes of `--incremental`.
+///
+/// The handle can be cloned and passed to the code filling the caches. Unlike the modified
+/// files they are not part of the diff, they are only written by [`Changes::apply`], after all
+/// modified files were written. Failing to write them only logs a warning.
+#[derive(Clone, Default)]
+pub(crate) struct StagedCaches(Arc<Mutex<BTreeMap<PathBuf, String>>>);
+
+impl StagedCaches {
+    /// Stage writing `content` to the cache file at `path`.
+    pub(crate) fn write(&self, path: impl Into<PathBuf>, content: impl Into<String>) {
+        self.lock().insert(path.into(), content.into());
+    }
+
+    /// Write all staged cache files to `fs`.
+    pub(crate) fn store(&self, fs: &dyn Fs) {
+        for (path, content) in std::mem::take(&mut *self.lock()) {
+            match fs.write(&path, &content) {
+                Ok(()) => log::debug!("Stored the cache at {}", path.display()),
+                Err(e) => log::warn!("Failed to store the cache at {}: {:?}", path.display(), e),
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<PathBuf, String>> {
+        self.0.lock().expect("Staging doesn't panic; qed")
+    }
+}
+
+/// A file modification staged in [`Changes`].
+struct FileChange {
+    /// The content of the file before diener touched it, `None` for new files.
+    original: Option<String>,
+    content: String,
+}
+
+/// All modifications to the filesystem done by a subcommand.
+///
+/// Subcommands don't write to the filesystem directly, they stage their modifications here.
+/// Reading a file through [`Changes`] returns the staged content, so subsequent modifications
+/// of the same file build on each other. At the end of the run the changes are either applied
+/// or only printed as diff when running with `--dry-run`.
+///
+/// The modified files are kept in memory, together with their original content, until the
+/// changes are applied, so they can be applied all or nothing. Of the read files that are not
+/// modified, at most `--max-buffered` are kept, see [`ReadCache`].
+pub(crate) struct Changes {
+    /// The filesystem all files are read from and written to.
+    fs: Arc<dyn Fs>,
+    files: BTreeMap<PathBuf, FileChange>,
+    read: Mutex<ReadCache>,
+    /// Directories that are moved as `(from, to)`, applied before any file is written.
+    moves: Vec<(PathBuf, PathBuf)>,
+    caches: StagedCaches,
+    /// The maximum number of files read or written at the same time, see `--jobs`.
+    jobs: usize,
+}
+
+impl Default for Changes {
+    fn default() -> Self {
+        Self::new(Arc::new(RealFs))
+    }
+}
+
+impl Changes {
+    /// Create an empty set of changes on top of `fs`.
+    pub(crate) fn new(fs: Arc<dyn Fs>) -> Self {
+        Self {
+            fs,
+            files: BTreeMap::new(),
+            read: Mutex::new(ReadCache::new(DEFAULT_MAX_BUFFERED)),
+            moves: Vec::new(),
+            caches: StagedCaches::default(),
+            jobs: default_jobs(),
+        }
+    }
+
+    /// Read and write at most `jobs` files at the same time.
+    pub(crate) fn set_jobs(&mut self, jobs: usize) {
+        self.jobs = jobs.max(1);
+    }
+
+    /// Keep at most `max` read files that are not modified in memory.
+    pub(crate) fn set_max_buffered(&mut self, max: usize) {
+        self.read
+            .get_mut()
+            .expect("Reading doesn't panic; qed")
+            .set_capacity(max);
+    }
+
+    /// Returns the maximum number of files read or written at the same time.
+    pub(crate) fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    /// Returns the filesystem the changes are staged on top of.
+    pub(crate) fn fs(&self) -> &dyn Fs {
+        &*self.fs
+    }
+
+    /// Returns the cache files that are written together with the changes.
+    pub(crate) fn caches(&self) -> StagedCaches {
+        self.caches.clone()
+    }
+
+    /// Returns an empty set of changes on top of the same filesystem.
+    pub(crate) fn scratch(&self) -> Self {
+        let mut scratch = Self::new(self.fs.clone());
+        scratch.jobs = self.jobs;
+        scratch.set_max_buffered(
+            self.read
+                .lock()
+                .expect("Reading doesn't panic; qed")
+                .capacity,
+        );
+        scratch
+    }
+
+    /// Returns the path where the file at `path` is located before any move is applied.
+    fn source_path(&self, path: &Path) -> PathBuf {
+        self.moves
+            .iter()
+            .rev()
+            .fold(path.to_path_buf(), |path, (from, to)| {
+                match path.strip_prefix(to) {
+                    Ok(rest) => from.join(rest),
+                    Err(_) => path,
+                }
+            })
+    }
+
+    /// Returns `true` if the file at `path` exists or is staged to be created.
+    pub(crate) fn exists(&self, path: &Path) -> bool {
+        self.files.contains_key(path) || self.fs.is_file(&self.source_path(path))
+    }
+
+    /// Read the file at `path`, returning the staged content if there is any.
+    pub(crate) fn read(&self, path: &Path) -> Result<String> {
+        if let Some(change) = self.files.get(path) {
+            return Ok(change.content.clone());
+        }
+
+        let content = self
+            .fs
+            .read(&self.source_path(path))
+            .with_context(|| anyhow!("Failed to read {}", path.display()))?;
+        oplog::record("file-opened", json!({ "path": path }));
+        self.read
+            .lock()
+            .expect("Reading doesn't panic; qed")
+            .insert(path.to_path_buf(), content.clone());
+        Ok(content)
+    }
+
+    /// Drop the read content of the file at `path`, as it won't be modified.
+    ///
+    /// Staging the file later reads it again.
+    pub(crate) fn forget(&self, path: &Path) {
+        self.read
+            .lock()
+            .expect("Reading doesn't panic; qed")
+            .remove(path);
+    }
+
+    /// Read all files at `paths` like [`Changes::read`], at most `jobs` at the same time.
+    ///
+    /// Returns the contents in the order of `paths`.
+    pub(crate) fn read_all(&self, paths: &[PathBuf]) -> Vec<Result<String>> {
+        if self.jobs == 1 {
+            return paths.iter().map(|p| self.read(p)).collect();
+        }
+
+        paths
+            .chunks(self.jobs)
+            .flat_map(|batch| {
+                std::thread::scope(|s| {
+                    let reads = batch
+                        .iter()
+                        .map(|p| s.spawn(|| self.read(p)))
+                        .collect::<Vec<_>>();
+                    reads
+                        .into_iter()
+                        .map(|r| r.join().expect("Reading doesn't panic; qed"))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect()
+    }
+
+    /// Read and parse the manifest at `path`, returning the staged content if there is any.
+    pub(crate) fn read_toml(&self, path: &Path) -> Result<Document> {
+        parse_toml(path, &self.read(path)?)
+    }
+
+    /// Stage writing `content` to the file at `path`.
+    pub(crate) fn write(&mut self, path: impl Into<PathBuf>, content: impl Into<String>) {
+        let path = path.into();
+        let content = content.into();
+
+        if let Some(change) = self.files.get_mut(&path) {
+            change.content = content;
+        } else {
+            let read = self.read.get_mut().expect("Reading doesn't panic; qed");
+            let original = match read.remove(&path) {
+                Some(original) => Some(original),
+                None => self.fs.read(&self.source_path(&path)).ok(),
+            };
+            // Writing a file unchanged is a no-op, no need to keep both copies in memory.
+            if original.as_ref() == Some(&content) {
+                return;
+            }
+            self.files.insert(path, FileChange { original, content });
+        }
+    }
+
+    /// Stage moving the directory `from` to `to`.
+    pub(crate) fn rename(&mut self, from: impl Into<PathBuf>, to: impl Into<PathBuf>) {
+        // The read files are known by their path before the move.
+        self.read
+            .get_mut()
+            .expect("Reading doesn't panic; qed")
+            .clear();
+        self.moves.push((from.into(), to.into()));
+    }
+
+    /// Returns all files whose content is modified.
+    pub(crate) fn modified_files(&self) -> impl Iterator<Item = &Path> {
+        self.files
+            .iter()
+            .filter(|(_, c)| c.original.as_ref() != Some(&c.content))
+            .map(|(p, _)| p.as_path())
+    }
+
+    /// Returns `(path, original content, new content)` of all files whose content is modified.
+    ///
+    /// The original content is `None` for new files.
+    pub(crate) fn modified(&self) -> impl Iterator<Item = (&Path, Option<&str>, &str)> {
+        self.files
+            .iter()
+            .filter(|(_, c)| c.original.as_ref() != Some(&c.content))
+            .map(|(p, c)| (p.as_path(), c.original.as_deref(), c.content.as_str()))
+    }
+
+    /// Returns all staged directory moves as `(from, to)`.
+    pub(crate) fn moves(&self) -> &[(PathBuf, PathBuf)] {
+        &self.moves
+    }
+
+    /// Returns all paths that are modified, including both sides of moved directories.
+    pub(crate) fn touched_paths(&self) -> Vec<PathBuf> {
+        self.moves
+            .iter()
+            .flat_map(|(from, to)| [from.clone(), to.clone()])
+            .chain(self.modified_files().map(Path::to_path_buf))
+            .collect()
+    }
+
+    /// Log how many files are modified in each of the workspaces at `roots`.
+    ///
+    /// Files are counted for the closest root above them.
+    pub(crate) fn report_by_root(&self, roots: &[PathBuf]) {
+        let mut counts = BTreeMap::<Option<&Path>, usize>::new();
+        for file in self.modified_files() {
+            let root = roots
+                .iter()
+                .filter(|r| file.starts_with(r))
+                .max_by_key(|r| r.components().count());
+            *counts.entry(root.map(PathBuf::as_path)).or_default() += 1;
+        }
+
+        for root in roots {
+            let count = counts
+                .get(&Some(root.as_path()))
+                .copied()
+                .unwrap_or_default();
+            log::info!("{}: {} file(s) modified.", root.display(), count);
+        }
+        if let Some(count) = counts.get(&None) {
+            log::info!("Outside of a workspace: {} file(s) modified.", count);
+        }
+        log::info!(
+            "{} workspace(s), {} file(s) modified in total.",
+            roots.len(),
+            counts.values().sum::<usize>()
+        );
+    }
+
+    /// Print a unified diff of all staged modifications, colored if `color` is `true`.
+    pub(crate) fn print_diff(&self, color: bool) {
+        print!("{}", self.diff(color));
+    }
+
+    /// Returns a unified diff of all staged modifications, colored if `color` is `true`.
+    pub(crate) fn diff(&self, color: bool) -> String {
+        let mut out = String::new();
+        for (from, to) in &self.moves {
+            out.push_str(&format!("Move {} -> {}\n", from.display(), to.display()));
+        }
+
+        for (path, change) in &self.files {
+            let diff = diff::unified_diff(
+                path,
+                change.original.as_deref().unwrap_or_default(),
+                &change.content,
+            );
+            if color {
+                out.push_str(&diff::colorize(&diff));
+            } else {
+                out.push_str(&diff);
+            }
+        }
+        out
+    }
+
+    /// Report all staged modifications as findings of `--check` and fail if there are any.
+    ///
+    /// `message` describes why the files are modified, e.g. `Dependency is not up to date.`.
+    pub(crate) fn check(&self, format: OutputFormat, color: bool, message: &str) -> Result<()> {
+        let modified = self.modified().collect::<Vec<_>>();
+
+        match format {
+            OutputFormat::Text => self.print_diff(color),
+            OutputFormat::Json => {
+                let files = modified
+                    .iter()
+                    .map(|(path, original, content)| {
+                        serde_json::json!({
+                            "path": path,
+                            "lines": diff::changed_lines(original.unwrap_or_default(), content),
+                            "message": message,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                println!("{}", serde_json::to_string_pretty(&files)?);
+            }
+            OutputFormat::Github => {
+                let cwd = std::env::current_dir().unwrap_or_default();
+                for (path, original, content) in &modified {
+                    let file = path.strip_prefix(&cwd).unwrap_or(path);
+                    for (first, last) in diff::changed_lines(original.unwrap_or_default(), content)
+                    {
+                        println!(
+                            "::warning file={},line={},endLine={}::{}",
+                            manifest::manifest_path(file),
+                            first,
+                            last,
+                            message
+                        );
+                    }
+                }
+            }
+        }
+
+        if !modified.is_empty() || !self.moves.is_empty() {
+            return Err(anyhow!("{} file(s) are not up to date.", modified.len())
+                .failure(Failure::NotUpToDate));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the modified files rebased from `root` to `output`, e.g. for `--output-dir`.
+    ///
+    /// Every modified file is written to the same path relative to `output` as it has relative
+    /// to `root`, leaving the files below `root` untouched. The staged cache files are dropped.
+    /// Fails for staged directory moves and for files outside of `root`.
+    pub(crate) fn relocate(self, root: &Path, output: &Path) -> Result<Self> {
+        if let Some((from, _)) = self.moves.first() {
+            bail!(
+                "Can not write to an output directory, {} would be moved.",
+                from.display()
+            );
+        }
+
+        let mut relocated = self.scratch();
+        for (path, _, content) in self.modified() {
+            let relative = match path.strip_prefix(root) {
+                Ok(relative) => relative,
+                Err(_) if path.is_relative() => path,
+                Err(_) => bail!(
+                    "Can not write {} to the output directory, it is outside of {}.",
+                    path.display(),
+                    root.display()
+                ),
+            };
+            relocated.write(output.join(relative), content);
+        }
+        Ok(relocated)
+    }
+
+    /// Apply all staged modifications to the filesystem.
+    ///
+    /// Either all modifications are applied or none. Every file is first written to a temporary
+    /// file next to it, which is then renamed over the original file. If anything fails, the
+    /// already applied modifications are rolled back and the error names the file that caused
+    /// the abort. The staged cache files are only written if all modifications were applied.
+    pub(crate) fn apply(self) -> Result<()> {
+        let fs = self.fs();
+        let mut moved = Vec::new();
+        for (from, to) in &self.moves {
+            if let Err(e) = move_dir(fs, from, to) {
+                rollback_moves(fs, &moved);
+                return Err(e);
+            }
+            moved.push((from.as_path(), to.as_path()));
+        }
+
+        let files = self
+            .files
+            .iter()
+            .filter(|(_, c)| c.original.as_ref() != Some(&c.content))
+            .collect::<Vec<_>>();
+
+        // Write all temporary files first, this is where most errors happen. They are written
+        // in batches of `jobs` files at the same time.
+        let mut staged = Vec::<PathBuf>::new();
+        for batch in files.chunks(self.jobs) {
+            let written = std::thread::scope(|s| {
+                let writes = batch
+                    .iter()
+                    .map(|(path, change)| {
+                        s.spawn(move || {
+                            let tmp = temp_path(path);
+                            timings::measure(Phase::Write, Some(path), || {
+                                fs.write(&tmp, &change.content)
+                            })
+                            .map(|()| tmp)
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                writes
+                    .into_iter()
+                    .map(|w| w.join().expect("Writing doesn't panic; qed"))
+                    .collect::<Vec<_>>()
+            });
+
+            let mut failed = None;
+            for ((path, _), res) in batch.iter().zip(written) {
+                match res {
+                    Ok(tmp) => staged.push(tmp),
+                    Err(e) => {
+                        failed.get_or_insert((path, e));
+                    }
+                }
+            }
+            if let Some((path, e)) = failed {
+                staged.iter().for_each(|tmp| remove_temp(fs, tmp));
+                rollback_moves(fs, &moved);
+                return Err(anyhow!(e)
+                    .context(format!("Failed to write {}", path.display()))
+                    .context("Aborted, no file was modified."));
+            }
+        }
+
+        for (i, ((path, _), tmp)) in files.iter().zip(&staged).enumerate() {
+            if let Err(e) = timings::measure(Phase::Write, Some(path), || fs.rename(tmp, path)) {
+                files[..i].iter().for_each(|(p, c)| restore(fs, p, c));
+                staged[i..].iter().for_each(|tmp| remove_temp(fs, tmp));
+                rollback_moves(fs, &moved);
+                return Err(anyhow!(e)
+                    .context(format!("Failed to write {}", path.display()))
+                    .context("Aborted, all modifications were rolled back."));
+            }
+            log::debug!("Wrote {}", path.display());
+            oplog::record("file-written", json!({ "path": path }));
+        }
+
+        self.caches.store(fs);
+        Ok(())
+    }
+}
+
+/// Parse `content` as the manifest at `path`.
+///
+/// Fails with a [`ParseError`] pointing to the location of the error.
+pub(crate) fn parse_toml(path: &Path, content: &str) -> Result<Document> {
+    timings::measure(Phase::Parse, Some(path), || {
+        let mut doc = Document::from_str(content)
+            .map_err(|e| anyhow::Error::new(ParseError::new(path, content, &e)))
+            .failure(Failure::Parse)?;
+        manifest::keep_dotted_comments(&mut doc);
+        Ok(doc)
+    })
+}
+
+/// A manifest that failed to parse.
+///
+/// Shows the path with the line and column of the error and the line itself, like:
+///
+/// ```text
+/// Failed to parse manifest at runtime/Cargo.toml:3:14: expected newline, `#`
+///   |
+/// 3 | version = 0.1.0"
+///   |              ^
+/// ```
+#[derive(Debug)]
+pub(crate) struct ParseError {
+    path: PathBuf,
+    /// The line of the error, starting at `1`.
+    line: usize,
+    /// The column of the error in characters, starting at `1`.
+    column: usize,
+    /// The content of the line with the error.
+    source_line: String,
+    message: String,
+}
+
+impl ParseError {
+    fn new(path: &Path, content: &str, error: &toml_edit::TomlError) -> Self {
+        let offset = error.span().map_or(0, |span| span.start);
+        let before = content.get(..offset).unwrap_or(content);
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+        Self {
+            path: path.into(),
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            source_line: content[line_start..]
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .into(),
+            message: error
+                .message()
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self.line.to_string().len();
+        writeln!(
+            f,
+            "Failed to parse manifest at {}:{}:{}: {}",
+            self.path.display(),
+            self.line,
+            self.column,
+            self.message
+        )?;
+        writeln!(f, "{:width$} |", "")?;
+        writeln!(f, "{} | {}", self.line, self.source_line)?;
+        write!(f, "{:width$} | {:>column$}", "", "^", column = self.column)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Returns the default of `--jobs`, the number of CPUs.
+fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Move the directory `from` to `to`, creating the parent directories of `to`.
+fn move_dir(fs: &dyn Fs, from: &Path, to: &Path) -> Result<()> {
+    fs.rename(from, to)
+        .with_context(|| anyhow!("Failed to move {} to {}", from.display(), to.display()))?;
+    log::info!("Moved {} to {}.", from.display(), to.display());
+    oplog::record("file-moved", json!({ "from": from, "to": to }));
+    Ok(())
+}
+
+/// Undo the given `(from, to)` moves in reverse order.
+fn rollback_moves(fs: &dyn Fs, moved: &[(&Path, &Path)]) {
+    for (from, to) in moved.iter().rev() {
+        if let Err(e) = fs.rename(to, from) {
+            log::error!(
+                "Failed to move {} back to {}: {}",
+                to.display(),
+                from.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Returns the path of the temporary file used while writing `path`.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{name}.diener-tmp"))
+}
+
+fn remove_temp(fs: &dyn Fs, tmp: &Path) {
+    if let Err(e) = fs.remove_file(tmp) {
+        log::error!("Failed to remove {}: {}", tmp.display(), e);
+    }
+}
+
+/// Restore the content of the file at `path` before `change` was applied.
+fn restore(fs: &dyn Fs, path: &Path, change: &FileChange) {
+    let res = match &change.original {
+        Some(original) => fs.write(path, original),
+        None => fs.remove_file(path),
+    };
+    if let Err(e) = res {
+        log::error!("Failed to restore {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{MemoryFs, SkipFn};
+    use std::io;
+
+    /// Read `/a` and `/b`, modify both behind the back of [`Changes`] and stage their first
+    /// content again.
+    ///
+    /// Returns the files that are staged as modified, which are the ones whose original content
+    /// was read again instead of taken from the read files.
+    fn reread(max_buffered: usize, forget: bool) -> Vec<PathBuf> {
+        let fs = Arc::new(MemoryFs::new().with_file("/a", "a").with_file("/b", "b"));
+        let mut changes = Changes::new(fs.clone());
+        changes.set_max_buffered(max_buffered);
+
+        for path in ["/a", "/b"] {
+            changes.read(Path::new(path)).unwrap();
+        }
+        if forget {
+            changes.forget(Path::new("/b"));
+        }
+        for path in ["/a", "/b"] {
+            fs.write(Path::new(path), "changed").unwrap();
+        }
+        changes.write("/a", "a");
+        changes.write("/b", "b");
+
+        changes.modified_files().map(Path::to_path_buf).collect()
+    }
+
+    #[test]
+    fn staging_read_files_does_not_read_them_again() {
+        assert!(reread(256, false).is_empty());
+    }
+
+    #[test]
+    fn at_most_max_buffered_read_files_are_kept() {
+        assert_eq!(reread(1, false), [PathBuf::from("/a")]);
+        assert_eq!(reread(0, false), [PathBuf::from("/a"), PathBuf::from("/b")]);
+    }
+
+    #[test]
+    fn forgotten_files_are_read_again() {
+        assert_eq!(reread(256, true), [PathBuf::from("/b")]);
+    }
+
+    /// Stage writing `/ws/a` and the cache file `/ws/cache`.
+    fn with_cache() -> (Arc<MemoryFs>, Changes) {
+        let fs = Arc::new(MemoryFs::new().with_file("/ws/a", "a"));
+        let mut changes = Changes::new(fs.clone());
+        changes.write("/ws/a", "b");
+        changes.caches().write("/ws/cache", "hashes");
+        (fs, changes)
+    }
+
+    #[test]
+    fn caches_are_written_after_the_changes() {
+        let (fs, changes) = with_cache();
+        assert_eq!(changes.touched_paths(), [PathBuf::from("/ws/a")]);
+        assert!(!changes.diff(false).contains("cache"));
+        assert!(!fs.is_file(Path::new("/ws/cache")));
+
+        changes.apply().unwrap();
+        assert_eq!(fs.read(Path::new("/ws/a")).unwrap(), "b");
+        assert_eq!(fs.read(Path::new("/ws/cache")).unwrap(), "hashes");
+    }
+
+    #[test]
+    fn relocated_changes_drop_the_caches() {
+        let (fs, changes) = with_cache();
+        let changes = changes
+            .relocate(Path::new("/ws"), Path::new("/out"))
+            .unwrap();
+
+        changes.apply().unwrap();
+        assert_eq!(fs.read(Path::new("/out/a")).unwrap(), "b");
+        assert!(!fs.is_file(Path::new("/ws/cache")));
+        assert!(!fs.is_file(Path::new("/out/cache")));
+    }
+
+    /// A [`MemoryFs`] on which writing to `fail` and moving anything to `fail` fails.
+    struct FailingFs {
+        files: MemoryFs,
+        fail: PathBuf,
+    }
+
+    impl FailingFs {
+        fn check(&self, path: &Path) -> io::Result<()> {
+            if path == self.fail {
+                return Err(io::ErrorKind::PermissionDenied.into());
+            }
+            Ok(())
+        }
+    }
+
+    impl Fs for FailingFs {
+        fn read(&self, path: &Path) -> io::Result<String> {
+            self.files.read(path)
+        }
+
+        fn write(&self, path: &Path, content: &str) -> io::Result<()> {
+            self.check(path)?;
+            self.files.write(path, content)
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            self.check(to)?;
+            self.files.rename(from, to)
+        }
+
+        fn remove_file(&self, path: &Path) -> io::Result<()> {
+            self.files.remove_file(path)
+        }
+
+        fn is_file(&self, path: &Path) -> bool {
+            self.files.is_file(path)
+        }
+
+        fn is_dir(&self, path: &Path) -> bool {
+            self.files.is_dir(path)
+        }
+
+        fn walk(
+            &self,
+            root: &Path,
+            follow_links: bool,
+            skip: SkipFn,
+        ) -> Box<dyn Iterator<Item = PathBuf>> {
+            self.files.walk(root, follow_links, skip)
+        }
+
+        fn glob(&self, pattern: &str) -> io::Result<Vec<PathBuf>> {
+            self.files.glob(pattern)
+        }
+    }
+
+    const ORIGINAL: [(&str, &str); 2] = [("/ws/a", "a"), ("/ws/dir/c", "c")];
+
+    /// Modify `/ws/a`, create `/ws/b` and move `/ws/dir` to `/ws/moved` on a filesystem that
+    /// fails for `fail`, then apply the changes.
+    ///
+    /// Returns the error of applying them and the files afterwards.
+    fn apply(fail: &str) -> (Option<String>, BTreeMap<PathBuf, String>) {
+        let fs = Arc::new(FailingFs {
+            files: ORIGINAL
+                .into_iter()
+                .fold(MemoryFs::new(), |fs, (path, content)| {
+                    fs.with_file(path, content)
+                }),
+            fail: fail.into(),
+        });
+        let mut changes = Changes::new(fs.clone());
+        changes.write("/ws/a", "modified");
+        changes.write("/ws/b", "new");
+        changes.rename("/ws/dir", "/ws/moved");
+
+        let error = changes.apply().err().map(|e| format!("{e:#}"));
+        (error, fs.files.files())
+    }
+
+    fn files(files: &[(&str, &str)]) -> BTreeMap<PathBuf, String> {
+        files
+            .iter()
+            .map(|(path, content)| (path.into(), content.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn apply_writes_all_modifications() {
+        let (error, written) = apply("/nothing");
+        assert_eq!(error, None);
+        assert_eq!(
+            written,
+            files(&[
+                ("/ws/a", "modified"),
+                ("/ws/b", "new"),
+                ("/ws/moved/c", "c")
+            ])
+        );
+    }
+
+    #[test]
+    fn failed_writes_modify_nothing() {
+        let (error, written) = apply("/ws/.b.diener-tmp");
+        let error = error.unwrap();
+        assert!(
+            error.starts_with("Aborted, no file was modified"),
+            "{error}"
+        );
+        assert!(error.contains("Failed to write /ws/b"), "{error}");
+        assert_eq!(written, files(&ORIGINAL));
+    }
+
+    #[test]
+    fn failed_renames_roll_back_all_modifications() {
+        let (error, written) = apply("/ws/b");
+        let error = error.unwrap();
+        assert!(
+            error.starts_with("Aborted, all modifications were rolled back"),
+            "{error}"
+        );
+        assert_eq!(written, files(&ORIGINAL));
+    }
+
+    #[test]
+    fn failed_moves_modify_nothing() {
+        let (error, written) = apply("/ws/moved");
+        assert!(error.unwrap().contains("Failed to move /ws/dir"));
+        assert_eq!(written, files(&ORIGINAL));
+    }
+}
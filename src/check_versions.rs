@@ -0,0 +1,194 @@
+use crate::{
+    changes::Changes,
+    context::RunContext,
+    forge,
+    git::{Checkout, GitRef},
+    update,
+    walk::Exclusions,
+    workspacify::manifest_iter,
+};
+use anyhow::{anyhow, ensure, Context, Result};
+use semver::{Version, VersionReq};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+
+/// `check-versions` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct CheckVersions {
+    /// The path where Diener should search for `Cargo.toml` files.
+    ///
+    /// Can be passed multiple times and may be a glob pattern like `runtimes/*`.
+    /// If not given, the current directory will be taken.
+    #[structopt(long)]
+    path: Vec<PathBuf>,
+}
+
+/// A `version` requirement of a git dependency.
+struct Requirement {
+    manifest: PathBuf,
+    /// The name of the package.
+    name: String,
+    version: String,
+}
+
+impl CheckVersions {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &RunContext) -> Result<()> {
+        let repos = ctx.config.match_repos().into();
+        let mut requirements = BTreeMap::<(String, GitRef), Vec<Requirement>>::new();
+        for manifest in ctx.manifests(&self.path)? {
+            let mut doc = ctx.changes.read_toml(&manifest)?;
+
+            update::for_each_managed_dependency(&mut doc, &repos, |name, dep| {
+                let (Some(dep), Some(git), Some(version)) = (
+                    dep.as_table_like(),
+                    dep.get("git").and_then(|g| g.as_str()),
+                    dep.get("version").and_then(|v| v.as_str()),
+                ) else {
+                    return;
+                };
+
+                requirements
+                    .entry((git.into(), GitRef::from_dependency(dep)))
+                    .or_default()
+                    .push(Requirement {
+                        manifest: manifest.clone(),
+                        name: name.into(),
+                        version: version.into(),
+                    });
+            });
+        }
+
+        if requirements.is_empty() {
+            log::info!("No git dependencies with a `version` requirement found.");
+            return Ok(());
+        }
+
+        ctx.ensure_online("Fetching the manifests of the git dependencies")?;
+
+        let mut mismatches = 0;
+        for ((git, reference), requirements) in requirements {
+            let reference = resolve(ctx, &git, &reference);
+            let checkout = Checkout::manifests_only(&git, &reference)?;
+            let versions = package_versions(&ctx.changes, checkout.path())?;
+
+            for requirement in requirements {
+                let version_req = VersionReq::parse(&requirement.version).with_context(|| {
+                    anyhow!(
+                        "Invalid version requirement `{}` for `{}` in {}",
+                        requirement.version,
+                        requirement.name,
+                        requirement.manifest.display()
+                    )
+                })?;
+
+                match versions.get(&requirement.name) {
+                    None => {
+                        log::error!(
+                            "{}: `{}` does not exist in {} ({}).",
+                            requirement.manifest.display(),
+                            requirement.name,
+                            git,
+                            reference
+                        );
+                        mismatches += 1;
+                    }
+                    Some(version) if !version_req.matches(version) => {
+                        log::error!(
+                            "{}: `{}` requires version `{}`, but {} ({}) has version `{}`.",
+                            requirement.manifest.display(),
+                            requirement.name,
+                            requirement.version,
+                            git,
+                            reference,
+                            version
+                        );
+                        mismatches += 1;
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        ensure!(
+            mismatches == 0,
+            "Found {} version mismatch(es).",
+            mismatches
+        );
+        log::info!("All version requirements are satisfied.");
+        Ok(())
+    }
+}
+
+/// Resolve `reference` of repositories on a known forge to the commit it points to.
+///
+/// This makes sure that all reports refer to the exact same commit. Other repositories and
+/// failed lookups return `reference` as is.
+fn resolve(ctx: &RunContext, git: &str, reference: &GitRef) -> GitRef {
+    let Some((forge, repo)) = forge::for_url(git, ctx.config.forges(), ctx.changes.caches())
+        .filter(|_| !matches!(reference, GitRef::Rev(_)))
+    else {
+        return reference.clone();
+    };
+
+    match forge.commit_sha(&repo, reference.name()) {
+        Ok(sha) => {
+            log::info!("{} ({}) points to {}.", git, reference, sha);
+            GitRef::Rev(sha)
+        }
+        Err(e) => {
+            log::debug!("Failed to resolve {} of {}: {:?}", reference, git, e);
+            reference.clone()
+        }
+    }
+}
+
+/// Returns the version of every package in the checkout at `dir`.
+pub(crate) fn package_versions(changes: &Changes, dir: &Path) -> Result<HashMap<String, Version>> {
+    let workspace_version = changes
+        .read_toml(&dir.join("Cargo.toml"))
+        .ok()
+        .and_then(|doc| {
+            doc.get("workspace")
+                .and_then(|w| w.get("package"))
+                .and_then(|p| p.get("version"))
+                .and_then(|v| v.as_str())
+                .map(ToString::to_string)
+        });
+
+    let mut versions = HashMap::new();
+    for manifest in manifest_iter(changes.fs(), dir, &Exclusions::default()) {
+        let doc = match changes.read_toml(&manifest) {
+            Ok(doc) => doc,
+            Err(e) => {
+                log::debug!("Skipping {}: {:?}", manifest.display(), e);
+                continue;
+            }
+        };
+
+        let Some(package) = doc.get("package") else {
+            continue;
+        };
+        let Some(name) = package.get("name").and_then(|n| n.as_str()) else {
+            continue;
+        };
+
+        let version = match package.get("version") {
+            Some(v) if v.as_str().is_some() => v.as_str().map(ToString::to_string),
+            // `version.workspace = true`
+            Some(v) if v.get("workspace").and_then(|w| w.as_bool()) == Some(true) => {
+                workspace_version.clone()
+            }
+            _ => None,
+        };
+
+        if let Some(version) = version.and_then(|v| Version::parse(&v).ok()) {
+            versions.insert(name.to_string(), version);
+        }
+    }
+
+    Ok(versions)
+}
@@ -0,0 +1,635 @@
+//! The command line interface of diener.
+
+use crate::{
+    bisect, cache,
+    changes::Changes,
+    check_versions, companion, compare_lock,
+    config::Config,
+    context::RunContext,
+    dev_setup, diff, doctor, env,
+    failure::{Classify, Failure},
+    filesystem::RealFs,
+    fix_links, format, git,
+    hooks::Hooks,
+    init, lint, metadata, move_crate, network, oplog, patch, plan, release_prep, rename_crate,
+    sbom, schema, serve, set, set_metadata, snapshot, stats, summary, timings, tree, tui, update,
+    verify, verify_tags, where_declared, workspace_deps, workspacify,
+};
+use anyhow::Context;
+use env_logger::Env;
+use serde_json::json;
+use std::{io::IsTerminal, process::ExitCode};
+use structopt::{
+    clap::{crate_name, crate_version, ArgMatches},
+    StructOpt,
+};
+
+/// diener is a tool for easily finding and changing Polkadot SDK dependency versions.
+/// diener will not modified the cargo.lock file but update specific dependencies in the Cargo.toml files or the project.
+#[derive(Debug, StructOpt)]
+pub(crate) enum SubCommands {
+    /// Update all `Cargo.toml` files at a given path to some specific path/branch/commit.
+    Update(update::Update),
+    /// Patch all crates from a given cargo workspace in another given cargo workspace.
+    ///
+    /// This will get all crates from a given cargo workspace and add a patch
+    /// section for each of these crates to the workspace `Cargo.toml` of a
+    /// given cargo workspace. Essentially this is the same as using
+    /// `.cargo/config`, but using a non-deprecated way.
+    Patch(patch::Patch),
+    /// Creates a workspace from the supplied directory tree.
+    ///
+    /// This can be ran on existing workspaces to make sure everything is properly setup.
+    ///
+    /// - Every dependency residing in the tree will be rewritten into a `path` dependency.
+    /// - The top level `Cargo.toml` `workspace.members` array will be filled with all crates.
+    ///     - It will also be sorted alphabetically
+    /// - The path dependency entries will be sorted into a canonical order.
+    Workspacify(workspacify::Workspacify),
+    /// Interactively select Polkadot SDK dependencies and switch them to a branch/tag/rev or path.
+    ///
+    /// Presents all discovered Polkadot SDK dependencies in a table. Crates can be selected,
+    /// the new source can be chosen and the resulting changes can be previewed before applying
+    /// them.
+    Tui(tui::Tui),
+    /// Move external dependencies shared by multiple members into `[workspace.dependencies]`.
+    ///
+    /// Every external dependency that is used with the same source by more than `--threshold`
+    /// members is declared once in the workspace manifest. The members are rewritten to
+    /// `workspace = true`, keeping their own features, `optional` and `default-features`.
+    WorkspaceDeps(workspace_deps::WorkspaceDeps),
+    /// Rename a crate of the workspace.
+    ///
+    /// Updates the `[package].name` of the crate, the dependency entries of all dependents
+    /// (including `[workspace.dependencies]` and `[patch]` sections) and all feature references
+    /// like `old-name/std`. The `workspace.members` list is path based and stays untouched.
+    RenameCrate(rename_crate::RenameCrate),
+    /// Move the directory of a crate of the workspace.
+    ///
+    /// Updates all relative `path` dependencies pointing to the crate, the entry in
+    /// `workspace.members` and the relative `path` dependencies of the moved crate itself.
+    MoveCrate(move_crate::MoveCrate),
+    /// Check the `version` requirements of git pinned Polkadot SDK dependencies.
+    ///
+    /// For every Polkadot SDK git dependency that also has a `version` requirement, the
+    /// manifests of the pinned branch/tag/rev are fetched and the requirement is checked against
+    /// the version of the crate found there.
+    CheckVersions(check_versions::CheckVersions),
+    /// Print a summary of all manifests at a given path.
+    ///
+    /// Shows the number of workspaces and crates, the kind of dependencies, the distinct
+    /// branches/tags/revs used per git repository and the largest dependency tables.
+    Stats(stats::Stats),
+    /// Verify that a workspace still resolves, e.g. after running other diener subcommands.
+    ///
+    /// Runs `cargo metadata` (and optionally `cargo check`) and maps failures back to the
+    /// `[patch]` sections and git/path dependencies that are involved.
+    Verify(verify::Verify),
+    /// Apply a plan written by `--plan-out`.
+    ///
+    /// Performs exactly the modifications of the plan. Fails without modifying anything if
+    /// any of the files changed since the plan was created.
+    ApplyPlan(plan::ApplyPlan),
+    /// Set package metadata fields like `license` or `repository` in all crates.
+    ///
+    /// The formatting of the manifests is kept. Fields inherited from the workspace with
+    /// `key.workspace = true` are left untouched, fields in `[workspace.package]` are updated.
+    SetMetadata(set_metadata::SetMetadata),
+    /// Rewrite the `repository`, `homepage` and `documentation` of all crates from templates.
+    ///
+    /// Links that still point to archived repositories, like the ones merged into the Polkadot
+    /// SDK, are reported.
+    FixLinks(fix_links::FixLinks),
+    /// Check all manifests of a workspace against a set of lints.
+    ///
+    /// Every lint has an ID and a default level, that can be changed with `--allow`, `--warn`
+    /// and `--deny`. Lints with an auto-fix are fixed with `--fix`.
+    Lint(lint::Lint),
+    /// Find the first Polkadot SDK commit that breaks the tree.
+    ///
+    /// Binary searches the commits between `--good` and `--bad`. For every checked commit all
+    /// Polkadot SDK dependencies are pointed to it and the given command is run. The manifests
+    /// are restored at the end.
+    Bisect(bisect::Bisect),
+    /// Store the content of all `Cargo.toml` files of a workspace under `.diener/snapshots`.
+    Snapshot(snapshot::Snapshot),
+    /// Restore the `Cargo.toml` files of a snapshot taken with `snapshot`.
+    Restore(snapshot::Restore),
+    /// Point the Polkadot SDK dependencies to a pull request or branch to build a companion.
+    Companion(companion::Companion),
+    /// Convert the Polkadot SDK git and path dependencies to crates.io dependencies for a release.
+    ReleasePrep(release_prep::ReleasePrep),
+    /// Switch the crates.io dependencies on the Polkadot SDK to git or a local checkout.
+    DevSetup(dev_setup::DevSetup),
+    /// Show every manifest that declares or patches a package and how.
+    Where(where_declared::Where),
+    /// Show the dependency tree of the workspace, restricted to the Polkadot SDK crates.
+    Tree(tree::Tree),
+    /// Check that all Polkadot SDK dependencies use tags of the same release family.
+    VerifyTags(verify_tags::VerifyTags),
+    /// Set or remove keys of all dependencies matching a filter.
+    Set(set::Set),
+    /// Serve JSON requests on stdin for editors and bots, keeping the caches warm.
+    Serve(serve::Serve),
+    /// Export the workspace members and their Polkadot SDK git pins as CycloneDX or SPDX.
+    Sbom(sbom::Sbom),
+    /// Compare the Polkadot SDK crates of two `Cargo.lock` files or of two git refs.
+    ///
+    /// Lists the added, removed and changed versions and revs as Markdown for release notes or
+    /// as JSON.
+    CompareLock(compare_lock::CompareLock),
+    /// Diagnose common problems of the tree without modifying it.
+    ///
+    /// Reports manifests that fail to parse, duplicate package names, crates missing from
+    /// `workspace.members`, mixed Polkadot SDK pins and stale patches, each with its severity
+    /// and the diener command to fix it.
+    Doctor(doctor::Doctor),
+    /// Print the JSON Schema of `.diener.toml` or of the plans written by `--plan-out`.
+    ///
+    /// Editors use it to validate and complete the files, CI to reject invalid ones before
+    /// running diener.
+    Schema(schema::Schema),
+    /// Print the effective configuration, the workspace roots, the caches and the network mode.
+    ///
+    /// Shows why diener behaves differently in CI than locally.
+    Env(env::Env),
+    /// Show, clear or garbage collect the on-disk caches.
+    ///
+    /// Covers the API responses, the `cargo metadata` results, the incremental hashes and the
+    /// git checkouts left behind by killed runs.
+    Cache(cache::Cache),
+    /// Write a `.diener.toml`, asking for the values after inspecting the tree.
+    ///
+    /// Suggests the Polkadot SDK repositories and the branch the dependencies use.
+    Init(init::Init),
+}
+
+impl SubCommands {
+    /// Run the subcommand, staging its changes in `ctx`.
+    pub(crate) fn run(self, ctx: &mut RunContext) -> anyhow::Result<()> {
+        match self {
+            SubCommands::Update(update) => update.run(ctx),
+            SubCommands::Patch(patch) => patch.run(ctx),
+            SubCommands::Workspacify(workspacify) => workspacify.run(ctx),
+            SubCommands::Tui(tui) => tui.run(ctx),
+            SubCommands::WorkspaceDeps(workspace_deps) => workspace_deps.run(ctx),
+            SubCommands::RenameCrate(rename_crate) => rename_crate.run(ctx),
+            SubCommands::MoveCrate(move_crate) => move_crate.run(ctx),
+            SubCommands::CheckVersions(check_versions) => check_versions.run(ctx),
+            SubCommands::Stats(stats) => stats.run(ctx),
+            SubCommands::Verify(verify) => verify.run(ctx),
+            SubCommands::ApplyPlan(apply_plan) => apply_plan.run(ctx),
+            SubCommands::SetMetadata(set_metadata) => set_metadata.run(ctx),
+            SubCommands::FixLinks(fix_links) => fix_links.run(ctx),
+            SubCommands::Lint(lint) => lint.run(ctx),
+            SubCommands::Bisect(bisect) => bisect.run(ctx),
+            SubCommands::Snapshot(snapshot) => snapshot.run(ctx),
+            SubCommands::Restore(restore) => restore.run(ctx),
+            SubCommands::Companion(companion) => companion.run(ctx),
+            SubCommands::ReleasePrep(release_prep) => release_prep.run(ctx),
+            SubCommands::DevSetup(dev_setup) => dev_setup.run(ctx),
+            SubCommands::Where(where_declared) => where_declared.run(ctx),
+            SubCommands::Tree(tree) => tree.run(ctx),
+            SubCommands::VerifyTags(verify_tags) => verify_tags.run(ctx),
+            SubCommands::Set(set) => set.run(ctx),
+            SubCommands::Serve(serve) => serve.run(ctx),
+            SubCommands::Sbom(sbom) => sbom.run(ctx),
+            SubCommands::CompareLock(compare_lock) => compare_lock.run(ctx),
+            SubCommands::Doctor(doctor) => doctor.run(ctx),
+            SubCommands::Schema(schema) => schema.run(ctx),
+            SubCommands::Env(env) => env.run(ctx),
+            SubCommands::Cache(cache) => cache.run(ctx),
+            SubCommands::Init(init) => init.run(ctx),
+        }
+    }
+}
+
+/// Cli options of Diener
+#[derive(Debug, StructOpt)]
+#[structopt(
+    about = "Diener - dependency diener for replacing Polkadot SDK versions in `Cargo.toml` files"
+)]
+struct Options {
+    #[structopt(subcommand)]
+    subcommand: SubCommands,
+
+    /// Don't write any file, print the diff of all changes that would be made instead.
+    #[structopt(long, global = true)]
+    dry_run: bool,
+
+    /// Skip directories with the given name when searching for manifests.
+    ///
+    /// Can be passed multiple times. `target`, `node_modules`, `vendor` and hidden directories
+    /// are always skipped.
+    #[structopt(long, global = true)]
+    exclude_dir: Vec<String>,
+
+    /// Process exactly the manifests listed in the given file, `-` for stdin, instead of
+    /// searching for them.
+    ///
+    /// Expects the path of one `Cargo.toml` per line. Can't be combined with `--path`.
+    #[structopt(long, global = true, value_name = "FILE")]
+    manifest_list: Option<std::path::PathBuf>,
+
+    /// When to color diffs, `auto`, `always` or `never`.
+    #[structopt(long, global = true, default_value = "auto")]
+    color: diff::ColorChoice,
+
+    /// Never access the network.
+    ///
+    /// Cargo is invoked with `--offline` and operations that require the network fail.
+    #[structopt(long, global = true)]
+    offline: bool,
+
+    /// Only consider the dependencies built for the given target triple, e.g.
+    /// `wasm32-unknown-unknown`.
+    ///
+    /// Passed to `cargo metadata`, so the subcommands using the resolved dependency graph like
+    /// `patch` and `tree` ignore the dependencies of other platforms.
+    #[structopt(long, global = true)]
+    filter_platform: Option<String>,
+
+    /// The maximum number of manifests read or written at the same time.
+    ///
+    /// Defaults to the number of CPUs. Lower values go easy on network filesystems, `1` reads
+    /// and writes one file after the other.
+    #[structopt(long, global = true)]
+    jobs: Option<std::num::NonZeroUsize>,
+
+    /// The maximum number of read manifests that are kept in memory until they are modified.
+    ///
+    /// Defaults to 256. Manifests that are dropped are read again when they are modified, the
+    /// modified manifests are always kept until they are written.
+    #[structopt(long, global = true)]
+    max_buffered: Option<usize>,
+
+    /// Persist the results of `cargo metadata` under `.diener/cache` in the workspace.
+    ///
+    /// The cache is invalidated by any change to a manifest or the `Cargo.lock`.
+    #[structopt(long, global = true)]
+    cache_metadata: bool,
+
+    /// Don't write any file, write the plan of all changes to the given file instead.
+    ///
+    /// The plan can be reviewed and applied later with `diener apply-plan`.
+    #[structopt(long, global = true, conflicts_with_all = &["dry-run", "commit"])]
+    plan_out: Option<std::path::PathBuf>,
+
+    /// Don't modify any file in place, write the modified files to the given directory instead.
+    ///
+    /// The files keep their path relative to the working directory, so the directory can be
+    /// overlaid on the source tree.
+    #[structopt(long, global = true, conflicts_with_all = &["dry-run", "plan-out", "commit"])]
+    output_dir: Option<std::path::PathBuf>,
+
+    /// Commit all modified files on a new git branch.
+    ///
+    /// Only the files modified by diener are staged.
+    #[structopt(long, global = true, conflicts_with = "dry-run")]
+    commit: bool,
+
+    /// The name of the branch created by `--commit`.
+    ///
+    /// Defaults to `diener/<subcommand>-<timestamp>`.
+    #[structopt(long, global = true, requires = "commit")]
+    branch_name: Option<String>,
+
+    /// The commit message used by `--commit`.
+    ///
+    /// Defaults to the diener command line, e.g. `diener update --branch stable2407`.
+    #[structopt(long, global = true, requires = "commit")]
+    message: Option<String>,
+
+    /// Push the branch of `--commit` and open a pull request for it on the forge hosting it.
+    ///
+    /// Supports GitHub, GitLab (as merge request) and the self-hosted instances of `[[forges]]`.
+    /// The body of the pull request is the summary of `--markdown-summary`. Requires a token in
+    /// `GITHUB_TOKEN` or `GH_TOKEN` for GitHub, `GITLAB_TOKEN` for GitLab or the `token-env` of
+    /// the forge.
+    #[structopt(long, global = true, requires = "commit")]
+    open_pr: bool,
+
+    /// Add this label to the pull request of `--open-pr`.
+    ///
+    /// Can be passed multiple times, adds to the `labels` of `[pull-request]` in the
+    /// configuration.
+    #[structopt(long, global = true, requires = "open-pr")]
+    pr_label: Vec<String>,
+
+    /// Request a review of the pull request of `--open-pr` from this user or `org/team`.
+    ///
+    /// Can be passed multiple times, adds to the `reviewers` of `[pull-request]` in the
+    /// configuration.
+    #[structopt(long, global = true, requires = "open-pr")]
+    pr_reviewer: Vec<String>,
+
+    /// Fail with exit code 4 if nothing was modified.
+    #[structopt(long, global = true)]
+    fail_if_unchanged: bool,
+
+    /// Don't run the hooks configured in `[hooks]` of the configuration.
+    #[structopt(long, global = true)]
+    no_hooks: bool,
+
+    /// Don't ask for confirmation before modifying many files.
+    ///
+    /// The number of files is configured by `confirm-threshold`, 100 by default.
+    #[structopt(long, global = true)]
+    yes: bool,
+
+    /// Write a Markdown summary of the modifications to this file, e.g. for the description of a
+    /// pull request.
+    #[structopt(long, global = true)]
+    markdown_summary: Option<std::path::PathBuf>,
+
+    /// Append a JSON record per action, e.g. every file read and written, to this file.
+    ///
+    /// The records are written independently of the log level.
+    #[structopt(long, global = true)]
+    log_file: Option<std::path::PathBuf>,
+
+    /// Report the time spent per phase and the slowest files to stderr.
+    #[structopt(long, global = true)]
+    timings: bool,
+
+    /// The format of the `--timings` report, `text` (default) or `json`.
+    #[structopt(long, global = true, requires = "timings")]
+    timings_format: Option<format::OutputFormat>,
+}
+
+/// Returns the command line diener was invoked with, without the `--commit` and `--open-pr`
+/// options.
+fn invocation() -> String {
+    let mut args = std::env::args().skip(1);
+    let mut invocation = vec![crate_name!().to_string()];
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--commit" | "--open-pr" => {}
+            "--branch-name" | "--message" | "--pr-label" | "--pr-reviewer" => {
+                args.next();
+            }
+            _ if [
+                "--branch-name=",
+                "--message=",
+                "--pr-label=",
+                "--pr-reviewer=",
+            ]
+            .iter()
+            .any(|prefix| arg.starts_with(prefix)) => {}
+            _ => invocation.push(arg),
+        }
+    }
+
+    invocation.join(" ")
+}
+
+/// Returns `true` if the modifications of `changes` should be applied.
+///
+/// Asks for confirmation on the terminal if more than `threshold` paths are modified, with a
+/// threshold of `0` never.
+fn confirm(changes: &Changes, threshold: usize) -> anyhow::Result<bool> {
+    let paths = changes.touched_paths();
+    if threshold == 0 || paths.len() <= threshold {
+        return Ok(true);
+    }
+    anyhow::ensure!(
+        std::io::stdin().is_terminal(),
+        "{} paths would be modified, more than the `confirm-threshold` of {}. Pass `--yes` to \
+         modify them without confirmation.",
+        paths.len(),
+        threshold
+    );
+
+    eprintln!("{} paths will be modified, e.g.:", paths.len());
+    for path in paths.iter().take(10) {
+        eprintln!("  {}", path.display());
+    }
+    eprint!("Continue? [y/N] ");
+
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .context("Failed to read the confirmation.")?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Parse the command line and run the subcommand.
+///
+/// Exits with the code of the [`Failure`] class of the error, see the crate documentation.
+pub fn main() -> ExitCode {
+    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    log::info!("Running {} v{}", crate_name!(), crate_version!());
+
+    let matches = match Options::clap().get_matches_safe() {
+        Ok(matches) => matches,
+        // `--help` and `--version`.
+        Err(e) if !e.use_stderr() => {
+            println!("{}", e.message);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{}", e.message);
+            return ExitCode::from(Failure::Usage.exit_code());
+        }
+    };
+
+    match run_matches(&matches) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::from(Failure::of(&e).map_or(1, Failure::exit_code))
+        }
+    }
+}
+
+/// Run the subcommand of the parsed command line, reporting the timings.
+fn run_matches(matches: &ArgMatches) -> anyhow::Result<()> {
+    let subcommand = matches.subcommand_name().unwrap_or_default().to_string();
+    let options = Options::from_clap(matches);
+
+    let timings = options
+        .timings
+        .then(|| options.timings_format.unwrap_or(format::OutputFormat::Text));
+    if timings == Some(format::OutputFormat::Github) {
+        return Err(
+            anyhow::anyhow!("`--timings` doesn't support the `github` format.")
+                .failure(Failure::Usage),
+        );
+    }
+    if timings.is_some() {
+        timings::enable();
+    }
+    if let Some(log_file) = &options.log_file {
+        oplog::enable(log_file)?;
+        oplog::record(
+            "run-started",
+            json!({ "version": crate_version!(), "args": std::env::args().collect::<Vec<_>>() }),
+        );
+    }
+
+    let start = std::time::Instant::now();
+    let res = run(options, &subcommand);
+    if let Some(format) = timings {
+        timings::report(format, start.elapsed())?;
+    }
+    match &res {
+        Ok(()) => oplog::record("run-finished", json!({ "ok": true })),
+        Err(e) => {
+            oplog::record("error", json!({ "error": format!("{e:#}") }));
+            oplog::record("run-finished", json!({ "ok": false }));
+        }
+    }
+    res
+}
+
+/// Run the subcommand and apply its changes.
+fn run(options: Options, subcommand: &str) -> anyhow::Result<()> {
+    let working_dir = std::env::current_dir().context("Working directory is invalid.")?;
+    let config = Config::load(&working_dir)?;
+    network::enable(config.network().resolve()?);
+    let mut changes = Changes::default();
+    if let Some(jobs) = options.jobs {
+        changes.set_jobs(jobs.get());
+    }
+    if let Some(max) = options.max_buffered {
+        changes.set_max_buffered(max);
+    }
+    let manifest_list = options
+        .manifest_list
+        .map(|list| update::read_manifest_list(changes.fs(), &list))
+        .transpose()?;
+    let mut ctx = RunContext {
+        root: working_dir.clone(),
+        exclusions: config.exclusions(options.exclude_dir),
+        config,
+        changes,
+        color: options.color.enabled(),
+        failures: Vec::new(),
+        offline: options.offline,
+        filter_platform: options.filter_platform,
+        metadata: metadata::MetadataCache::new(options.cache_metadata),
+        manifest_list,
+        dry_run: options.dry_run,
+    };
+    if options.open_pr {
+        ctx.ensure_online("`--open-pr`")?;
+    }
+
+    options.subcommand.run(&mut ctx)?;
+
+    let changes = ctx.changes;
+    let hooks = match ctx.config.hooks() {
+        Some(hooks) if !options.no_hooks => hooks.clone(),
+        _ => Hooks::default(),
+    };
+    let unchanged = changes.touched_paths().is_empty();
+    if let Some(path) = &options.markdown_summary {
+        std::fs::write(
+            path,
+            summary::markdown(&changes, &working_dir, &invocation()),
+        )
+        .with_context(|| format!("Failed to write the summary to {}", path.display()))?;
+        log::info!("Wrote the summary to {}.", path.display());
+    }
+    if options.dry_run {
+        log::info!(
+            "Dry run, not writing {} modified file(s).",
+            changes.modified_files().count()
+        );
+        changes.print_diff(ctx.color);
+    } else if let Some(plan_out) = options.plan_out {
+        let plan = serde_json::to_string_pretty(&plan::Plan::new(invocation(), &changes))?;
+        std::fs::write(&plan_out, plan)
+            .with_context(|| format!("Failed to write plan to {}", plan_out.display()))?;
+        log::info!(
+            "Wrote the plan of {} modified file(s) to {}.",
+            changes.modified_files().count(),
+            plan_out.display()
+        );
+    } else if !options.yes && !confirm(&changes, ctx.config.confirm_threshold())? {
+        anyhow::bail!("Aborted, no file was modified.");
+    } else if let Some(output_dir) = options.output_dir {
+        let output_dir = working_dir.join(output_dir);
+        let changes = changes.relocate(&working_dir, &output_dir)?;
+        let files = changes.modified_files().map(Into::into).collect::<Vec<_>>();
+        changes.apply()?;
+        log::info!(
+            "Wrote {} modified file(s) to {}.",
+            files.len(),
+            output_dir.display()
+        );
+        hooks.run(&files)?;
+    } else if options.commit {
+        let paths = changes.touched_paths();
+        let files = changes.modified_files().map(Into::into).collect::<Vec<_>>();
+        let body = options
+            .open_pr
+            .then(|| summary::markdown(&changes, &working_dir, &invocation()));
+        let caches = changes.caches();
+        changes.apply()?;
+        hooks.run(&files)?;
+
+        let branch = options.branch_name.unwrap_or_else(|| {
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default();
+            format!("diener/{subcommand}-{timestamp}")
+        });
+        let message = options.message.unwrap_or_else(invocation);
+        let root = git::commit_on_branch(&paths, &branch, &message)?;
+
+        if let (Some(root), Some(body)) = (root, body) {
+            let mut pull_request = ctx.config.pull_request().cloned().unwrap_or_default();
+            pull_request.extend(options.pr_label, options.pr_reviewer);
+            let opened = pull_request.open(
+                ctx.config.forges(),
+                caches.clone(),
+                &root,
+                &branch,
+                &message,
+                &body,
+            );
+            // The changes are applied already.
+            caches.store(&RealFs);
+            opened?;
+        }
+    } else {
+        let files = changes.modified_files().map(Into::into).collect::<Vec<_>>();
+        changes.apply()?;
+        hooks.run(&files)?;
+    }
+
+    if !ctx.failures.is_empty() {
+        log::error!("The following file(s) failed:");
+        for (path, error) in &ctx.failures {
+            oplog::record(
+                "error",
+                json!({ "path": path, "error": format!("{error:#}") }),
+            );
+            let error = format!("{error:#}").replace('\n', "\n    ");
+            log::error!("  {}: {}", path.display(), error);
+        }
+        let error = anyhow::anyhow!("Failed to process {} file(s).", ctx.failures.len());
+        // Keep the class, e.g. for the exit code, if all files failed the same way.
+        let class = Failure::of(&ctx.failures[0].1);
+        return Err(match class {
+            Some(class)
+                if ctx
+                    .failures
+                    .iter()
+                    .all(|(_, e)| Failure::of(e) == Some(class)) =>
+            {
+                error.failure(class)
+            }
+            _ => error,
+        });
+    }
+
+    if options.fail_if_unchanged && unchanged {
+        return Err(anyhow::anyhow!("Nothing was modified.").failure(Failure::NothingToDo));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use crate::{
+    context::RunContext,
+    forge, git,
+    update::{self, RepoMatcher, Version},
+};
+use anyhow::{anyhow, bail, Result};
+use std::{collections::BTreeSet, path::PathBuf, str::FromStr};
+use structopt::StructOpt;
+
+/// The Polkadot SDK change the companion builds against, as given to `companion`.
+#[derive(Debug)]
+enum Change {
+    /// A pull request, with the url of the repository if it was given as url.
+    PullRequest { repo: Option<String>, number: u64 },
+    /// A branch of the Polkadot SDK repository.
+    Branch(String),
+}
+
+impl FromStr for Change {
+    type Err = anyhow::Error;
+
+    /// Parses `123`, `#123`, `!123`, `https://github.com/<owner>/<repo>/pull/123`,
+    /// `https://gitlab.com/<group>/<project>/-/merge_requests/123` or a branch name.
+    fn from_str(s: &str) -> Result<Self> {
+        if let Ok(number) = s.trim_start_matches(['#', '!']).parse() {
+            return Ok(Self::PullRequest { repo: None, number });
+        }
+
+        if let Some((repo, rest)) = s
+            .split_once("/-/merge_requests/")
+            .or_else(|| s.split_once("/pull/"))
+        {
+            let number = rest
+                .split(['/', '#', '?'])
+                .next()
+                .and_then(|n| n.parse().ok());
+            return match (git_url_parse::GitUrl::parse(repo).is_ok(), number) {
+                (true, Some(number)) => Ok(Self::PullRequest {
+                    repo: Some(repo.into()),
+                    number,
+                }),
+                _ => bail!("Invalid pull request url `{s}`."),
+            };
+        }
+
+        if s.is_empty() {
+            bail!("Expected a pull request number or a branch name.");
+        }
+        Ok(Self::Branch(s.into()))
+    }
+}
+
+/// `companion` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct Companion {
+    /// The path where Diener should search for `Cargo.toml` files.
+    ///
+    /// Can be passed multiple times and may be a glob pattern like `runtimes/*`.
+    /// If not given, the current directory will be taken.
+    #[structopt(long)]
+    path: Vec<PathBuf>,
+
+    /// The url of the Polkadot SDK repository.
+    ///
+    /// Defaults to the `git` url of the configuration or of the Polkadot SDK dependencies, which
+    /// must be the same in all of them.
+    #[structopt(long)]
+    git: Option<String>,
+
+    /// Pin the dependencies to the current head commit instead of the branch.
+    #[structopt(long)]
+    rev: bool,
+
+    /// The pull request, e.g. `1234`, `#1234` or its url, or the branch to build against.
+    change: Change,
+}
+
+impl Companion {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        let manifests = ctx.manifests(&self.path)?;
+        let repos: RepoMatcher = ctx.config.match_repos().into();
+
+        let url = match self
+            .git
+            .clone()
+            .or_else(|| ctx.config.git().map(Into::into))
+        {
+            Some(url) => url,
+            None => {
+                let mut urls = BTreeSet::new();
+                for manifest in &manifests {
+                    let mut doc = ctx.changes.read_toml(manifest)?;
+                    update::for_each_managed_dependency(&mut doc, &repos, |_, dep| {
+                        if let Some(git) = dep.get("git").and_then(|g| g.as_str()) {
+                            urls.insert(git.to_string());
+                        }
+                    });
+                }
+
+                match urls.len() {
+                    1 => urls.pop_first().expect("There is exactly one url; qed"),
+                    0 => bail!("No Polkadot SDK git dependencies found, pass `--git`."),
+                    _ => bail!(
+                        "The Polkadot SDK dependencies use different git urls, pass `--git`: {}",
+                        urls.into_iter().collect::<Vec<_>>().join(", ")
+                    ),
+                }
+            }
+        };
+
+        ctx.ensure_online("Resolving the companion")?;
+        let (git, version) = match self.change {
+            Change::PullRequest { repo, number } => {
+                let repo_url = repo.unwrap_or_else(|| url.clone());
+                let Some((forge, repo)) =
+                    forge::for_url(&repo_url, ctx.config.forges(), ctx.changes.caches())
+                else {
+                    bail!(
+                        "{} is not hosted on a known forge, configure it in `[[forges]]` or pass \
+                         a branch instead.",
+                        repo_url
+                    );
+                };
+                let pr = forge.pull_request(&repo, number)?;
+                let head = pr.repository.ok_or_else(|| {
+                    anyhow!("The repository of the head of #{} was deleted.", number)
+                })?;
+                log::info!(
+                    "#{} of {} is at `{}` ({}) in {}.",
+                    number,
+                    repo,
+                    pr.branch,
+                    pr.sha,
+                    head
+                );
+
+                let version = if self.rev {
+                    Version::Rev(pr.sha)
+                } else {
+                    Version::Branch(pr.branch)
+                };
+                // Keep the url as written for pull requests that are not from a fork.
+                if git::repository_id(&head) == git::repository_id(&url) {
+                    (url, version)
+                } else {
+                    (head, version)
+                }
+            }
+            Change::Branch(branch) if self.rev => {
+                let rev = git::branch_head(&url, &branch)?;
+                log::info!("`{}` of {} is at {}.", branch, url, rev);
+                (url, Version::Rev(rev))
+            }
+            Change::Branch(branch) => (url, Version::Branch(branch)),
+        };
+
+        // Pull requests from forks switch the dependencies to the fork.
+        let git = Some(git);
+        let targets = vec![(repos, version)];
+        for manifest in &manifests {
+            let mut doc = ctx.changes.read_toml(manifest)?;
+            for (repos, version) in &targets {
+                update::update_document(&mut doc, repos, |_| true, |_| true, &git, version);
+            }
+            ctx.changes.write(manifest, doc.to_string());
+            update::update_workspace_dependencies(manifest, &targets, &git, &mut ctx.changes)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+use crate::{
+    context::RunContext,
+    failure::{Classify, Failure},
+    git,
+    update::{RepoMatcher, SDK_CRATE_PREFIXES},
+};
+use anyhow::{anyhow, bail, Context, Error, Result};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use structopt::StructOpt;
+use toml_edit::{Document, Item};
+
+/// `compare-lock` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct CompareLock {
+    /// The old `Cargo.lock`, or the old git ref with `--git`.
+    old: String,
+
+    /// The new `Cargo.lock`, or the new git ref with `--git`.
+    new: String,
+
+    /// Compare the `Cargo.lock` of the git refs `OLD` and `NEW`, e.g. `v1.0.0 HEAD`.
+    #[structopt(long)]
+    git: bool,
+
+    /// The directory of the `Cargo.lock` compared with `--git`.
+    ///
+    /// Uses the working directory if none is supplied.
+    #[structopt(long, requires = "git")]
+    path: Option<PathBuf>,
+
+    /// The output format, `markdown` or `json`.
+    #[structopt(long, default_value = "markdown")]
+    format: LockFormat,
+}
+
+/// The format of the output of `compare-lock`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LockFormat {
+    Markdown,
+    Json,
+}
+
+impl FromStr for LockFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" => Ok(Self::Markdown),
+            "json" => Ok(Self::Json),
+            _ => bail!("Unknown format `{s}`, expected `markdown` or `json`."),
+        }
+    }
+}
+
+/// A package of a `Cargo.lock`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+struct Locked {
+    version: String,
+    /// The url of the git repository, `None` for registry packages.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    git: Option<String>,
+    /// The commit of git packages.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rev: Option<String>,
+}
+
+impl fmt::Display for Locked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.version)?;
+        if let Some(rev) = &self.rev {
+            write!(f, " (`{}`)", &rev[..rev.len().min(10)])?;
+        }
+        Ok(())
+    }
+}
+
+/// The SDK packages that differ between two `Cargo.lock` files.
+#[derive(Debug, Default, Serialize)]
+struct LockDiff {
+    added: BTreeMap<String, BTreeSet<Locked>>,
+    removed: BTreeMap<String, BTreeSet<Locked>>,
+    /// The packages locked at other versions or revs, with the old and the new ones.
+    changed: BTreeMap<String, Change>,
+}
+
+/// The locked versions of a package before and after.
+#[derive(Debug, Serialize)]
+struct Change {
+    old: BTreeSet<Locked>,
+    new: BTreeSet<Locked>,
+}
+
+impl CompareLock {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &RunContext) -> Result<()> {
+        let repos: RepoMatcher = ctx.config.match_repos().into();
+
+        let (old, new) = if self.git {
+            let dir = ctx.workspace_dir(self.path.as_deref())?;
+            (lock_at_ref(&dir, &self.old)?, lock_at_ref(&dir, &self.new)?)
+        } else {
+            (
+                ctx.changes.read(Path::new(&self.old))?,
+                ctx.changes.read(Path::new(&self.new))?,
+            )
+        };
+        let old = sdk_packages(&parse_lock(&self.old, &old)?, &repos);
+        let new = sdk_packages(&parse_lock(&self.new, &new)?, &repos);
+
+        let mut diff = LockDiff::default();
+        for name in old.keys().chain(new.keys()).collect::<BTreeSet<_>>() {
+            match (old.get(name), new.get(name)) {
+                (Some(old), Some(new)) if old != new => {
+                    diff.changed.insert(
+                        name.clone(),
+                        Change {
+                            old: old.clone(),
+                            new: new.clone(),
+                        },
+                    );
+                }
+                (Some(old), None) => {
+                    diff.removed.insert(name.clone(), old.clone());
+                }
+                (None, Some(new)) => {
+                    diff.added.insert(name.clone(), new.clone());
+                }
+                _ => {}
+            }
+        }
+
+        match self.format {
+            LockFormat::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+            LockFormat::Markdown => print!("{}", markdown(&diff, &self.old, &self.new)),
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the content of the `Cargo.lock` in `dir` at the git ref `reference`.
+fn lock_at_ref(dir: &Path, reference: &str) -> Result<String> {
+    git::run(dir, &["show", &format!("{reference}:./Cargo.lock")])
+        .with_context(|| anyhow!("Failed to read the `Cargo.lock` of `{}`", reference))
+}
+
+/// Parse the `Cargo.lock` `content`, read from `source`.
+fn parse_lock(source: &str, content: &str) -> Result<Document> {
+    content
+        .parse::<Document>()
+        .with_context(|| anyhow!("Failed to parse `Cargo.lock` of {}", source))
+        .failure(Failure::Parse)
+}
+
+/// Returns the locked packages of the Polkadot SDK in `lock` by name.
+///
+/// These are the git packages of one of `repos` and the registry packages whose name looks like
+/// an SDK crate.
+fn sdk_packages(lock: &Document, repos: &RepoMatcher) -> BTreeMap<String, BTreeSet<Locked>> {
+    let mut packages = BTreeMap::<String, BTreeSet<Locked>>::new();
+    let Some(entries) = lock.get("package").and_then(Item::as_array_of_tables) else {
+        return packages;
+    };
+    for package in entries {
+        let get = |k| package.get(k).and_then(Item::as_str);
+        let (Some(name), Some(version)) = (get("name"), get("version")) else {
+            continue;
+        };
+
+        let source = get("source");
+        let locked = match source.and_then(|s| s.strip_prefix("git+")) {
+            Some(source) => {
+                let (url, rev) = source.split_once('#').unwrap_or((source, ""));
+                let url = url.split('?').next().unwrap_or(url);
+                if !repos.matches(url) {
+                    continue;
+                }
+                Locked {
+                    version: version.into(),
+                    git: Some(url.into()),
+                    rev: (!rev.is_empty()).then(|| rev.into()),
+                }
+            }
+            // The crates of the workspace have no source, the releases of the SDK are only
+            // recognized by their name.
+            None if source.is_some() && SDK_CRATE_PREFIXES.iter().any(|p| name.starts_with(p)) => {
+                Locked {
+                    version: version.into(),
+                    git: None,
+                    rev: None,
+                }
+            }
+            None => continue,
+        };
+        packages.entry(name.into()).or_default().insert(locked);
+    }
+    packages
+}
+
+/// Render `diff` between the lock files `old` and `new` as Markdown for release notes.
+fn markdown(diff: &LockDiff, old: &str, new: &str) -> String {
+    let join = |locked: &BTreeSet<Locked>| {
+        locked
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let mut out = String::new();
+    let _ = writeln!(out, "## Polkadot SDK changes\n");
+    let _ = writeln!(
+        out,
+        "From `{old}` to `{new}`: {} changed, {} added, {} removed.",
+        diff.changed.len(),
+        diff.added.len(),
+        diff.removed.len()
+    );
+
+    if !diff.changed.is_empty() {
+        out.push_str("\n### Changed\n\n| Crate | Old | New |\n| --- | --- | --- |\n");
+        for (name, change) in &diff.changed {
+            let _ = writeln!(
+                out,
+                "| `{name}` | {} | {} |",
+                join(&change.old),
+                join(&change.new)
+            );
+        }
+    }
+    for (title, packages) in [("Added", &diff.added), ("Removed", &diff.removed)] {
+        if packages.is_empty() {
+            continue;
+        }
+        let _ = writeln!(out, "\n### {title}\n\n| Crate | Version |\n| --- | --- |");
+        for (name, locked) in packages {
+            let _ = writeln!(out, "| `{name}` | {} |", join(locked));
+        }
+    }
+
+    out
+}
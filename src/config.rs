@@ -0,0 +1,250 @@
+use crate::{
+    cache::CacheConfig,
+    failure::{Classify, Failure},
+    forge::ForgeConfig,
+    format::OutputFormat,
+    hooks::Hooks,
+    network::NetworkConfig,
+    pull_request::PullRequestConfig,
+    walk::Exclusions,
+    workspacify::PathStyle,
+};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The name of the project configuration file.
+pub(crate) const CONFIG_FILE: &str = ".diener.toml";
+
+/// The number of files diener modifies without asking for confirmation by default.
+const DEFAULT_CONFIRM_THRESHOLD: usize = 100;
+
+/// The repository that is managed when nothing else is configured.
+pub(crate) const DEFAULT_REPOSITORY: &str = "polkadot-sdk";
+
+/// The git url of the Polkadot SDK, used when no other url is given or configured.
+pub(crate) const POLKADOT_SDK_GIT: &str = "https://github.com/paritytech/polkadot-sdk";
+
+/// The configuration of diener, loaded from `.diener.toml` and the user configuration.
+///
+/// All values are defaults, the command line flags take precedence. New values need to be added
+/// to the schema of `diener schema config`.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct Config {
+    /// The configuration files the values were loaded from, the user configuration first.
+    #[serde(skip)]
+    files: Vec<PathBuf>,
+    /// The names of the repositories whose git dependencies are managed, e.g. `polkadot-sdk`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    match_repos: Option<Vec<String>>,
+    /// Directories that should be skipped, relative to the configuration file.
+    exclude: Vec<PathBuf>,
+    /// Names of directories that should be skipped wherever they appear, e.g. `fixtures`.
+    exclude_dirs: Vec<String>,
+    /// The `git` url dependencies should be rewritten to by `update`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    git: Option<String>,
+    /// The `branch` `update` uses if no version is given on the command line.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    branch: Option<String>,
+    /// The patch target used by `patch`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    patch_target: Option<String>,
+    /// The output format of reporting subcommands.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<OutputFormat>,
+    /// How `workspacify` writes the paths of dependencies.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path_style: Option<PathStyle>,
+    /// The commands run after the modified files are written.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hooks: Option<Hooks>,
+    /// Ask for confirmation before modifying more files, `0` never asks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    confirm_threshold: Option<usize>,
+    /// How `--open-pr` opens pull requests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pull_request: Option<PullRequestConfig>,
+    /// Self-hosted forges diener talks to, besides github.com and gitlab.com.
+    forges: Vec<ForgeConfig>,
+    /// The proxy and CA bundle of the network operations.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    network: Option<NetworkConfig>,
+    /// How long the on-disk caches keep their entries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache: Option<CacheConfig>,
+}
+
+impl Config {
+    /// Load the user configuration and the first `.diener.toml` found in `start` or any of
+    /// its parents.
+    ///
+    /// Values of the project configuration override the ones of the user configuration.
+    pub(crate) fn load(start: &Path) -> Result<Self> {
+        let user = user_config_path()
+            .filter(|p| p.is_file())
+            .map(|p| Self::from_file(&p))
+            .transpose()?
+            .unwrap_or_default();
+
+        let project = start
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE))
+            .find(|p| p.is_file())
+            .map(|p| Self::from_file(&p))
+            .transpose()?
+            .unwrap_or_default();
+
+        Ok(user.merge(project))
+    }
+
+    /// Parse the configuration file at `path`.
+    fn from_file(path: &Path) -> Result<Self> {
+        log::debug!("Loading configuration from {}", path.display());
+
+        let content = fs::read_to_string(path)
+            .with_context(|| anyhow!("Failed to read configuration at {}", path.display()))?;
+        let mut config: Self = toml_edit::de::from_str(&content)
+            .with_context(|| anyhow!("Failed to parse configuration at {}", path.display()))
+            .failure(Failure::Parse)?;
+
+        let dir = path.parent().expect("Configuration is a file; qed");
+        config.exclude = config.exclude.iter().map(|p| dir.join(p)).collect();
+        if let Some(network) = &mut config.network {
+            network.relative_to(dir);
+        }
+        config.files = vec![path.to_path_buf()];
+
+        Ok(config)
+    }
+
+    /// Merge `other` into `self`, the values of `other` take precedence.
+    fn merge(mut self, other: Self) -> Self {
+        self.exclude.extend(other.exclude);
+        self.exclude_dirs.extend(other.exclude_dirs);
+        // The project forges come first, so they take precedence for the same host.
+        let forges = other.forges.into_iter().chain(self.forges).collect();
+
+        Self {
+            files: self.files.into_iter().chain(other.files).collect(),
+            match_repos: other.match_repos.or(self.match_repos),
+            exclude: self.exclude,
+            exclude_dirs: self.exclude_dirs,
+            git: other.git.or(self.git),
+            branch: other.branch.or(self.branch),
+            patch_target: other.patch_target.or(self.patch_target),
+            format: other.format.or(self.format),
+            path_style: other.path_style.or(self.path_style),
+            hooks: other.hooks.or(self.hooks),
+            confirm_threshold: other.confirm_threshold.or(self.confirm_threshold),
+            pull_request: other.pull_request.or(self.pull_request),
+            forges,
+            network: other.network.or(self.network),
+            cache: other.cache.or(self.cache),
+        }
+    }
+
+    /// The configuration files the values were loaded from, the user configuration first.
+    pub(crate) fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Returns the configuration with the defaults of all values that have one filled in.
+    pub(crate) fn with_defaults(&self) -> Self {
+        Self {
+            match_repos: Some(self.match_repos()),
+            confirm_threshold: Some(self.confirm_threshold()),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the configuration with the credentials it contains removed, for printing.
+    pub(crate) fn redacted(&self) -> Self {
+        Self {
+            network: self.network.as_ref().map(NetworkConfig::redacted),
+            ..self.clone()
+        }
+    }
+
+    /// The names of the repositories whose git dependencies are managed.
+    pub(crate) fn match_repos(&self) -> Vec<String> {
+        self.match_repos
+            .clone()
+            .unwrap_or_else(|| vec![DEFAULT_REPOSITORY.into()])
+    }
+
+    /// The directories that should be skipped, besides the defaults and the given `names`.
+    pub(crate) fn exclusions(&self, names: impl IntoIterator<Item = String>) -> Exclusions {
+        Exclusions::new(
+            self.exclude_dirs.iter().cloned().chain(names),
+            self.exclude.iter().cloned(),
+        )
+    }
+
+    /// The `git` url dependencies should be rewritten to.
+    pub(crate) fn git(&self) -> Option<&str> {
+        self.git.as_deref()
+    }
+
+    /// The `branch` `update` uses if no version is given on the command line.
+    pub(crate) fn branch(&self) -> Option<&str> {
+        self.branch.as_deref()
+    }
+
+    /// The patch target used by `patch`.
+    pub(crate) fn patch_target(&self) -> Option<&str> {
+        self.patch_target.as_deref()
+    }
+
+    /// The output format of reporting subcommands.
+    pub(crate) fn format(&self) -> Option<OutputFormat> {
+        self.format
+    }
+
+    /// How `workspacify` writes the paths of dependencies.
+    pub(crate) fn path_style(&self) -> Option<PathStyle> {
+        self.path_style
+    }
+
+    /// The commands run after the modified files are written.
+    pub(crate) fn hooks(&self) -> Option<&Hooks> {
+        self.hooks.as_ref()
+    }
+
+    /// The number of files that are modified without asking for confirmation, `0` never asks.
+    pub(crate) fn confirm_threshold(&self) -> usize {
+        self.confirm_threshold.unwrap_or(DEFAULT_CONFIRM_THRESHOLD)
+    }
+
+    /// How `--open-pr` opens pull requests.
+    pub(crate) fn pull_request(&self) -> Option<&PullRequestConfig> {
+        self.pull_request.as_ref()
+    }
+
+    /// Self-hosted forges diener talks to, besides github.com and gitlab.com.
+    pub(crate) fn forges(&self) -> &[ForgeConfig] {
+        &self.forges
+    }
+
+    /// The proxy and CA bundle of the network operations.
+    pub(crate) fn network(&self) -> NetworkConfig {
+        self.network.clone().unwrap_or_default()
+    }
+
+    /// How long the on-disk caches keep their entries.
+    pub(crate) fn cache(&self) -> CacheConfig {
+        self.cache.clone().unwrap_or_default()
+    }
+}
+
+/// Returns the path of the user configuration, `~/.config/diener/config.toml`.
+fn user_config_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .map(|dir| dir.join("diener").join("config.toml"))
+}
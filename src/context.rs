@@ -0,0 +1,151 @@
+use crate::{
+    changes::Changes,
+    config::Config,
+    failure::{Classify, Failure},
+    filesystem::{normalize, Fs},
+    metadata::MetadataCache,
+    network,
+    timings::{self, Phase},
+    update,
+    walk::Exclusions,
+};
+use anyhow::{anyhow, ensure, Error, Result};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// The state shared by all subcommands of one diener invocation.
+pub(crate) struct RunContext {
+    /// The directory relative paths are resolved against, the working directory of the CLI or
+    /// the root of the [`Engine`](crate::Engine).
+    pub(crate) root: PathBuf,
+    /// The configuration loaded from `.diener.toml` and the user configuration.
+    pub(crate) config: Config,
+    /// All modifications of the filesystem, applied or printed at the end of the run.
+    pub(crate) changes: Changes,
+    /// The directories that are skipped when searching for manifests.
+    pub(crate) exclusions: Exclusions,
+    /// Whether diffs should be colored.
+    pub(crate) color: bool,
+    /// The files that failed to process with `--keep-going`.
+    ///
+    /// The changes of all other files are still applied, but diener exits with an error.
+    pub(crate) failures: Vec<(PathBuf, Error)>,
+    /// Never access the network, see `--offline`.
+    pub(crate) offline: bool,
+    /// Only resolve the dependencies for this target triple, see `--filter-platform`.
+    pub(crate) filter_platform: Option<String>,
+    /// The results of `cargo metadata`, see [`RunContext::metadata`].
+    pub(crate) metadata: MetadataCache,
+    /// The manifests given by `--manifest-list`, which are processed instead of searching for
+    /// manifests.
+    pub(crate) manifest_list: Option<Vec<PathBuf>>,
+    /// Don't modify anything, see `--dry-run`.
+    ///
+    /// The changes of the manifests are printed as diff, this is for the subcommands that
+    /// modify something else.
+    pub(crate) dry_run: bool,
+}
+
+impl RunContext {
+    /// A context for the paths below `root` on `fs` with the defaults of all options, as used
+    /// by the [`Engine`].
+    ///
+    /// [`Engine`]: crate::Engine
+    pub(crate) fn new(root: PathBuf, config: Config, fs: Arc<dyn Fs>) -> Self {
+        Self {
+            root,
+            exclusions: config.exclusions(Vec::new()),
+            config,
+            changes: Changes::new(fs),
+            color: false,
+            failures: Vec::new(),
+            offline: false,
+            filter_platform: None,
+            metadata: MetadataCache::new(false),
+            manifest_list: None,
+            dry_run: false,
+        }
+    }
+
+    /// Fail if running with `--offline`, as `operation` requires network access.
+    pub(crate) fn ensure_online(&self, operation: &str) -> Result<()> {
+        if self.offline {
+            return Err(anyhow!(
+                "{} requires network access, which is disabled by `--offline`.",
+                operation
+            )
+            .failure(Failure::Network));
+        }
+
+        Ok(())
+    }
+
+    /// Returns `path` resolved against the [`root`](Self::root), without `.` and `..`.
+    pub(crate) fn resolve(&self, path: &Path) -> PathBuf {
+        normalize(&self.root.join(path))
+    }
+
+    /// Returns the directory a subcommand works on, `path` or the root.
+    pub(crate) fn workspace_dir(&self, path: Option<&Path>) -> Result<PathBuf> {
+        let dir = match path {
+            Some(path) => path.to_path_buf(),
+            None => self.root.clone(),
+        };
+        ensure!(
+            self.changes.fs().is_dir(&dir),
+            "Path '{}' is not a directory.",
+            dir.display()
+        );
+        Ok(dir)
+    }
+
+    /// Returns the manifests a subcommand processes, all `Cargo.toml` files below the `--path`
+    /// arguments `paths` or the ones given by `--manifest-list`.
+    pub(crate) fn manifests(&self, paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        match &self.manifest_list {
+            Some(_) if !paths.is_empty() => Err(anyhow!(
+                "`--manifest-list` can not be combined with `--path`."
+            )
+            .failure(Failure::Usage)),
+            Some(manifests) => Ok(manifests.clone()),
+            None => {
+                let dirs = update::search_dirs(self.changes.fs(), paths)?;
+                Ok(update::manifests_in(
+                    self.changes.fs(),
+                    &dirs,
+                    &self.exclusions,
+                ))
+            }
+        }
+    }
+
+    /// Returns the `cargo metadata` of the workspace at `dir`.
+    ///
+    /// The result is cached, so every workspace is only inspected once per run. Passes
+    /// `--offline` and `--filter-platform` to cargo when running with them.
+    pub(crate) fn metadata(&mut self, dir: &Path) -> Result<cargo_metadata::Metadata> {
+        let mut options = if self.offline {
+            vec!["--offline"]
+        } else {
+            vec![]
+        };
+        if let Some(platform) = &self.filter_platform {
+            options.extend(["--filter-platform", platform]);
+        }
+
+        let mut command = cargo_metadata::MetadataCommand::new();
+        for (var, value) in network::env() {
+            command.env(var, value);
+        }
+        command
+            .current_dir(dir)
+            .other_options(options.iter().map(ToString::to_string).collect::<Vec<_>>());
+
+        timings::measure(Phase::Metadata, Some(dir), || {
+            self.metadata
+                .get(dir, &options, command, &self.changes.caches())
+        })
+    }
+}
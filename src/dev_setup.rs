@@ -0,0 +1,262 @@
+use crate::{
+    config::POLKADOT_SDK_GIT,
+    context::RunContext,
+    editor,
+    filesystem::Fs,
+    git::{Checkout, GitRef},
+    manifest::{dependency_package, dependency_tables, dependency_tables_mut, manifest_path},
+    walk::Exclusions,
+    workspacify::{manifest_iter, package_name},
+};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+use toml_edit::{Document, Item, TableLike};
+
+/// `dev-setup` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct DevSetup {
+    /// The path to the workspace root directory.
+    ///
+    /// Uses the working directory if none is supplied.
+    #[structopt(long)]
+    path: Option<PathBuf>,
+
+    /// The `branch` that the dependencies should use.
+    #[structopt(long, conflicts_with_all = &[ "rev", "tag", "local" ])]
+    branch: Option<String>,
+
+    /// The `rev` that the dependencies should use.
+    #[structopt(long, conflicts_with_all = &[ "branch", "tag", "local" ])]
+    rev: Option<String>,
+
+    /// The `tag` that the dependencies should use.
+    #[structopt(long, conflicts_with_all = &[ "rev", "branch", "local" ])]
+    tag: Option<String>,
+
+    /// Point the dependencies to the crates of the Polkadot SDK checkout at the given path.
+    #[structopt(long, conflicts_with_all = &[ "rev", "branch", "tag", "git" ])]
+    local: Option<PathBuf>,
+
+    /// The url of the Polkadot SDK repository.
+    ///
+    /// Defaults to the `git` url of the configuration or the official `polkadot-sdk` repository.
+    #[structopt(long)]
+    git: Option<String>,
+
+    /// Also add a `[patch.crates-io]` entry for every switched crate to the workspace root.
+    ///
+    /// This makes crates.io crates that depend on the Polkadot SDK use the same copy.
+    #[structopt(long)]
+    patch: bool,
+}
+
+/// Where the dependencies are switched to.
+enum Target {
+    Git {
+        url: String,
+        reference: GitRef,
+    },
+    /// The checkout with the directory of every crate by name.
+    Local(BTreeMap<String, PathBuf>),
+}
+
+impl Target {
+    /// Switch the dependency `dep` on `package` declared in `manifest` to the target.
+    fn switch(&self, dep: &mut Item, manifest: &Path, package: &str) {
+        match self {
+            Self::Git { url, reference } => editor::to_git_dep(dep, url, reference_key(reference)),
+            Self::Local(crates) => editor::to_path_dep(dep, &Self::path(crates, manifest, package)),
+        }
+    }
+
+    /// The keys of a patch of `package` declared in `manifest`.
+    fn keys(&self, manifest: &Path, package: &str) -> Vec<(&'static str, String)> {
+        match self {
+            Self::Git { url, reference } => [("git", url.as_str())]
+                .into_iter()
+                .chain(reference_key(reference))
+                .map(|(k, v)| (k, v.to_string()))
+                .collect(),
+            Self::Local(crates) => {
+                vec![(
+                    "path",
+                    manifest_path(&Self::path(crates, manifest, package)),
+                )]
+            }
+        }
+    }
+
+    /// The path of the crate `package` of the checkout relative to `manifest`.
+    fn path(crates: &BTreeMap<String, PathBuf>, manifest: &Path, package: &str) -> PathBuf {
+        manifest
+            .parent()
+            .zip(crates.get(package))
+            .and_then(|(dir, krate)| pathdiff::diff_paths(krate, dir))
+            .unwrap_or_default()
+    }
+}
+
+/// Returns the dependency key and value of `reference`, `None` for the default branch.
+fn reference_key(reference: &GitRef) -> Option<(&'static str, &str)> {
+    match reference {
+        GitRef::Branch(branch) => Some(("branch", branch)),
+        GitRef::Tag(tag) => Some(("tag", tag)),
+        GitRef::Rev(rev) => Some(("rev", rev)),
+        GitRef::DefaultBranch => None,
+    }
+}
+
+impl DevSetup {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        let workspace = ctx.workspace_dir(self.path.as_deref())?;
+        // Paths to the local checkout are relative to the canonical manifest paths.
+        let workspace = workspace
+            .canonicalize()
+            .with_context(|| anyhow!("Failed to resolve {}", workspace.display()))?;
+        let target = self.target(ctx)?;
+        let packages = match &target {
+            Target::Git { url, reference } => {
+                ctx.ensure_online("Fetching the manifests of the Polkadot SDK")?;
+                let checkout = Checkout::manifests_only(url, reference)?;
+                crates(ctx.changes.fs(), checkout.path())?
+                    .into_keys()
+                    .collect()
+            }
+            Target::Local(crates) => crates.keys().cloned().collect::<BTreeSet<_>>(),
+        };
+
+        let mut switched = BTreeSet::new();
+        for manifest in manifest_iter(ctx.changes.fs(), &workspace, &ctx.exclusions) {
+            let mut doc = ctx.changes.read_toml(&manifest)?;
+
+            // `(table, key, package)` of every crates.io dependency on the Polkadot SDK.
+            let deps = dependency_tables(&doc)
+                .into_iter()
+                .flat_map(|(table, deps)| {
+                    deps.iter()
+                        .filter_map(|(key, dep)| {
+                            let package = match dep.as_table_like() {
+                                _ if dep.is_str() => key,
+                                Some(dep) if is_registry_dependency(dep) => {
+                                    dependency_package(key, dep)
+                                }
+                                _ => return None,
+                            };
+                            Some((table.clone(), key.to_string(), package.to_string()))
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .filter(|(_, _, package)| packages.contains(package))
+                .collect::<Vec<_>>();
+            if deps.is_empty() {
+                continue;
+            }
+
+            for (table, key, package) in deps {
+                let Some(dep) = dependency_tables_mut(&mut doc)
+                    .find(|(name, _)| *name == table)
+                    .and_then(|(_, deps)| deps.get_mut(&key))
+                else {
+                    continue;
+                };
+                target.switch(dep, &manifest, &package);
+                log::info!("{}: Switched `{}`.", manifest.display(), key);
+                switched.insert(package);
+            }
+            ctx.changes.write(&manifest, doc.to_string());
+        }
+
+        if self.patch && !switched.is_empty() {
+            let root = workspace.join("Cargo.toml");
+            let mut doc = ctx.changes.read_toml(&root)?;
+            add_patches(&mut doc, &root, &switched, &target)?;
+            ctx.changes.write(&root, doc.to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Returns where the dependencies should be switched to.
+    fn target(&self, ctx: &RunContext) -> Result<Target> {
+        if let Some(local) = &self.local {
+            let crates = crates(ctx.changes.fs(), local)?;
+            ensure!(
+                !crates.is_empty(),
+                "No crates found in {}.",
+                local.display()
+            );
+            return Ok(Target::Local(crates));
+        }
+
+        let reference = if let Some(branch) = &self.branch {
+            GitRef::Branch(branch.clone())
+        } else if let Some(rev) = &self.rev {
+            GitRef::Rev(rev.clone())
+        } else if let Some(tag) = &self.tag {
+            GitRef::Tag(tag.clone())
+        } else {
+            bail!("You need to pass `--branch`, `--tag`, `--rev` or `--local`.");
+        };
+        let url = self
+            .git
+            .clone()
+            .or_else(|| ctx.config.git().map(Into::into))
+            .unwrap_or_else(|| POLKADOT_SDK_GIT.into());
+
+        Ok(Target::Git { url, reference })
+    }
+}
+
+/// Returns `true` if `dep` is a crates.io dependency with a version requirement.
+fn is_registry_dependency(dep: &dyn TableLike) -> bool {
+    dep.contains_key("version")
+        && !["git", "path", "registry", "workspace"]
+            .iter()
+            .any(|k| dep.contains_key(k))
+}
+
+/// Returns the directory of every crate in the checkout at `dir` by name.
+fn crates(fs: &dyn Fs, dir: &Path) -> Result<BTreeMap<String, PathBuf>> {
+    let dir = dir
+        .canonicalize()
+        .with_context(|| anyhow!("Failed to resolve {}", dir.display()))?;
+
+    let mut crates = BTreeMap::new();
+    for manifest in manifest_iter(fs, &dir, &Exclusions::default()) {
+        match package_name(fs, &manifest) {
+            Ok(Some(name)) => {
+                let krate = manifest.parent().map(Path::to_path_buf).unwrap_or_default();
+                crates.insert(name, krate);
+            }
+            Ok(None) => {}
+            Err(e) => log::debug!("Skipping {}: {:?}", manifest.display(), e),
+        }
+    }
+    Ok(crates)
+}
+
+/// Add a `[patch.crates-io]` entry to `doc` at `root` for every package in `packages`.
+///
+/// Existing patches are kept.
+fn add_patches(
+    doc: &mut Document,
+    root: &Path,
+    packages: &BTreeSet<String>,
+    target: &Target,
+) -> Result<()> {
+    let crates_io = editor::patch_table(doc, "crates-io")?;
+    for package in packages {
+        if crates_io.contains_key(package) {
+            continue;
+        }
+        let patch = editor::patch_entry(crates_io, package)?;
+        editor::set_patch_source(patch, target.keys(root, package));
+        log::info!("{}: Patched `{}`.", root.display(), package);
+    }
+    Ok(())
+}
@@ -0,0 +1,255 @@
+use anyhow::{bail, Error};
+use std::{
+    fmt::Write,
+    io::{self, IsTerminal},
+    path::Path,
+    str::FromStr,
+};
+
+/// Number of unchanged lines shown around every change.
+const CONTEXT: usize = 3;
+
+pub(crate) const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+pub(crate) const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+
+/// When diffs should be colored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ColorChoice {
+    /// Only if stdout is a terminal and `NO_COLOR` isn't set.
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Returns `true` if the output should be colored.
+    pub(crate) fn enabled(self) -> bool {
+        match self {
+            Self::Auto => std::env::var_os("NO_COLOR").is_none() && io::stdout().is_terminal(),
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => bail!("Unknown color choice `{s}`, expected `auto`, `always` or `never`."),
+        }
+    }
+}
+
+/// A single step of a line based diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    /// The line at the given index is the same in the old and the new text.
+    Equal(usize, usize),
+    /// The line at the given index of the old text was removed.
+    Delete(usize),
+    /// The line at the given index of the new text was added.
+    Insert(usize),
+}
+
+/// Calculate the diff operations that transform `old` into `new`.
+///
+/// Uses the longest common subsequence of both inputs, after stripping the common prefix and
+/// suffix. Manifests are small enough for the quadratic table.
+fn diff_ops(old: &[&str], new: &[&str]) -> Vec<Op> {
+    let prefix = old.iter().zip(new).take_while(|(o, n)| o == n).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(o, n)| o == n)
+        .count();
+
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut lcs = vec![vec![0u32; new_mid.len() + 1]; old_mid.len() + 1];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lcs[i][j] = if old_mid[i] == new_mid[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = (0..prefix).map(|i| Op::Equal(i, i)).collect::<Vec<_>>();
+
+    let (mut i, mut j) = (0, 0);
+    while i < old_mid.len() || j < new_mid.len() {
+        if i < old_mid.len() && j < new_mid.len() && old_mid[i] == new_mid[j] {
+            ops.push(Op::Equal(prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if i < old_mid.len() && (j == new_mid.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(Op::Delete(prefix + i));
+            i += 1;
+        } else {
+            ops.push(Op::Insert(prefix + j));
+            j += 1;
+        }
+    }
+
+    ops.extend((0..suffix).map(|k| Op::Equal(old.len() - suffix + k, new.len() - suffix + k)));
+    ops
+}
+
+/// Returns the `(first, last)` lines of `old` that are touched by every change, starting at 1.
+///
+/// A pure insertion touches the line it is inserted after.
+pub(crate) fn changed_lines(old: &str, new: &str) -> Vec<(usize, usize)> {
+    let old_lines = old.lines().collect::<Vec<_>>();
+    let new_lines = new.lines().collect::<Vec<_>>();
+
+    let mut ranges = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+    let mut old_pos = 0;
+    for op in diff_ops(&old_lines, &new_lines) {
+        match op {
+            Op::Equal(..) => {
+                ranges.extend(current.take());
+                old_pos += 1;
+            }
+            Op::Delete(_) => {
+                old_pos += 1;
+                let (first, _) = current.unwrap_or((old_pos, old_pos));
+                current = Some((first, old_pos));
+            }
+            Op::Insert(_) => {
+                current = current.or(Some((old_pos.max(1), old_pos.max(1))));
+            }
+        }
+    }
+    ranges.extend(current);
+
+    ranges
+}
+
+/// Render a unified diff between `old` and `new` for the file at `path`.
+///
+/// Returns an empty string if both are equal.
+pub(crate) fn unified_diff(path: &Path, old: &str, new: &str) -> String {
+    let old_lines = old.lines().collect::<Vec<_>>();
+    let new_lines = new.lines().collect::<Vec<_>>();
+    let ops = diff_ops(&old_lines, &new_lines);
+
+    let changes = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, Op::Equal(..)))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+
+    let mut out = String::new();
+    if changes.is_empty() {
+        return out;
+    }
+
+    let _ = writeln!(out, "--- {}", path.display());
+    let _ = writeln!(out, "+++ {}", path.display());
+
+    // The position in the old and the new text before the op at the given index.
+    let mut positions = Vec::with_capacity(ops.len());
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for op in &ops {
+        positions.push((old_pos, new_pos));
+        match op {
+            Op::Equal(..) => {
+                old_pos += 1;
+                new_pos += 1;
+            }
+            Op::Delete(_) => old_pos += 1,
+            Op::Insert(_) => new_pos += 1,
+        }
+    }
+
+    let mut current = 0;
+    while current < changes.len() {
+        let mut last = current;
+        while last + 1 < changes.len() && changes[last + 1] - changes[last] <= 2 * CONTEXT + 1 {
+            last += 1;
+        }
+
+        let start = changes[current].saturating_sub(CONTEXT);
+        let end = (changes[last] + CONTEXT).min(ops.len() - 1);
+        let hunk = &ops[start..=end];
+
+        let old_len = hunk
+            .iter()
+            .filter(|op| !matches!(op, Op::Insert(_)))
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|op| !matches!(op, Op::Delete(_)))
+            .count();
+        let (old_start, new_start) = positions[start];
+
+        let _ = writeln!(
+            out,
+            "@@ -{},{} +{},{} @@",
+            old_start + usize::from(old_len > 0),
+            old_len,
+            new_start + usize::from(new_len > 0),
+            new_len
+        );
+
+        for op in hunk {
+            let _ = match op {
+                Op::Equal(o, _) => writeln!(out, " {}", old_lines[*o]),
+                Op::Delete(o) => writeln!(out, "-{}", old_lines[*o]),
+                Op::Insert(n) => writeln!(out, "+{}", new_lines[*n]),
+            };
+        }
+
+        current = last + 1;
+    }
+
+    out
+}
+
+/// Color the output of [`unified_diff`] for the terminal.
+///
+/// Removed lines are red and added lines green, the key of a changed `key = value` line is
+/// highlighted.
+pub(crate) fn colorize(diff: &str) -> String {
+    let mut out = String::with_capacity(diff.len() * 2);
+
+    for line in diff.lines() {
+        if line.starts_with("--- ") || line.starts_with("+++ ") {
+            let _ = writeln!(out, "{BOLD}{line}{RESET}");
+        } else if line.starts_with("@@") {
+            let _ = writeln!(out, "{CYAN}{line}{RESET}");
+        } else if let Some(color) = line.chars().next().and_then(|c| match c {
+            '-' => Some(RED),
+            '+' => Some(GREEN),
+            _ => None,
+        }) {
+            let (sign, rest) = line.split_at(1);
+            match rest.split_once('=') {
+                Some((key, value)) if !key.trim_start().starts_with('[') => {
+                    let _ = writeln!(out, "{color}{sign}{BOLD}{key}{RESET}{color}={value}{RESET}");
+                }
+                _ => {
+                    let _ = writeln!(out, "{color}{line}{RESET}");
+                }
+            }
+        } else {
+            let _ = writeln!(out, "{line}");
+        }
+    }
+
+    out
+}
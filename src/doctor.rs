@@ -0,0 +1,356 @@
+use crate::{
+    changes,
+    context::RunContext,
+    format::OutputFormat,
+    git::GitRef,
+    manifest::{dependency_tables, manifest_path},
+    update::{self, is_managed_dependency, RepoMatcher},
+};
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    env::current_dir,
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+use toml_edit::Document;
+
+/// `doctor` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct Doctor {
+    /// The path where Diener should search for `Cargo.toml` files.
+    ///
+    /// Can be passed multiple times and may be a glob pattern like `runtimes/*`.
+    /// If not given, the current directory will be taken.
+    #[structopt(long)]
+    path: Vec<PathBuf>,
+
+    /// The output format, `text`, `json` or `github`.
+    ///
+    /// `github` emits GitHub Actions annotations. Defaults to the `format` of the configuration
+    /// or `text`.
+    #[structopt(long)]
+    format: Option<OutputFormat>,
+}
+
+/// How bad a problem found by `doctor` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Severity {
+    /// Cargo fails on the tree.
+    Error,
+    /// The tree works, but likely not as intended.
+    Warning,
+}
+
+impl Severity {
+    /// The label of the severity, also the GitHub Actions annotation command.
+    fn label(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        }
+    }
+}
+
+/// A problem found by `doctor`.
+#[derive(Debug, Serialize)]
+struct Diagnostic {
+    /// The ID of the check that found the problem.
+    check: &'static str,
+    severity: Severity,
+    /// The manifest the problem is in.
+    manifest: PathBuf,
+    message: String,
+    /// The diener command that fixes the problem, if there is one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fix: Option<String>,
+}
+
+impl Doctor {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &RunContext) -> Result<()> {
+        let format = self
+            .format
+            .or(ctx.config.format())
+            .unwrap_or(OutputFormat::Text);
+        let dirs = update::search_dirs(ctx.changes.fs(), &self.path)?;
+        let root = dirs.first().cloned().unwrap_or_default();
+        let repos: RepoMatcher = ctx.config.match_repos().into();
+
+        let mut diagnostics = Vec::new();
+        let mut docs = Vec::new();
+        for manifest in ctx.manifests(&self.path)? {
+            match changes::parse_toml(&manifest, &ctx.changes.read(&manifest)?) {
+                Ok(doc) => docs.push((manifest, doc)),
+                Err(e) => diagnostics.push(Diagnostic {
+                    check: "parse-error",
+                    severity: Severity::Error,
+                    manifest,
+                    message: format!("{e:#}"),
+                    fix: None,
+                }),
+            }
+        }
+
+        diagnostics.extend(duplicate_packages(&docs));
+        diagnostics.extend(missing_members(&docs));
+        diagnostics.extend(mixed_pins(&root, &docs, &repos));
+        diagnostics.extend(stale_patches(ctx, &docs));
+        diagnostics.sort_by(|a, b| (a.severity, &a.manifest).cmp(&(b.severity, &b.manifest)));
+
+        print_diagnostics(&diagnostics, format)?;
+
+        let errors = diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .count();
+        if errors > 0 {
+            bail!(
+                "Found {} error(s) and {} warning(s).",
+                errors,
+                diagnostics.len() - errors
+            );
+        }
+        log::info!("Found {} warning(s).", diagnostics.len());
+        Ok(())
+    }
+}
+
+/// Returns the name of the package of `doc`, `None` for virtual manifests.
+fn package_name(doc: &Document) -> Option<&str> {
+    doc.get("package")?.get("name")?.as_str()
+}
+
+/// Returns the workspace root closest to `manifest`, besides `manifest` itself.
+fn workspace_of<'a>(
+    docs: &'a [(PathBuf, Document)],
+    manifest: &Path,
+) -> Option<&'a (PathBuf, Document)> {
+    docs.iter()
+        .filter(|(root, doc)| root != manifest && doc.contains_key("workspace"))
+        .filter(|(root, _)| root.parent().is_some_and(|dir| manifest.starts_with(dir)))
+        .max_by_key(|(root, _)| root.components().count())
+}
+
+/// Packages with the same name, which cargo rejects in the same workspace.
+fn duplicate_packages(docs: &[(PathBuf, Document)]) -> Vec<Diagnostic> {
+    let mut packages = BTreeMap::<_, Vec<_>>::new();
+    for (manifest, doc) in docs {
+        if let Some(name) = package_name(doc) {
+            packages.entry(name).or_default().push(manifest);
+        }
+    }
+
+    packages
+        .into_iter()
+        .filter(|(_, manifests)| manifests.len() > 1)
+        .map(|(name, manifests)| {
+            let root = workspace_of(docs, manifests[0])
+                .and_then(|(root, _)| root.parent())
+                .unwrap_or(Path::new("."));
+            Diagnostic {
+                check: "duplicate-package",
+                severity: Severity::Error,
+                manifest: manifests[0].clone(),
+                message: format!(
+                    "The package `{name}` is also declared in {}.",
+                    manifests[1..]
+                        .iter()
+                        .map(|m| m.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                fix: Some(format!(
+                    "diener workspacify --path {} --dedupe-strategy suffix-dir",
+                    root.display()
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Packages below a workspace root that are neither in its `members` nor in its `exclude`.
+fn missing_members(docs: &[(PathBuf, Document)]) -> Vec<Diagnostic> {
+    let patterns = |doc: &Document, key: &str| {
+        doc.get("workspace")
+            .and_then(|w| w.get(key))
+            .and_then(|m| m.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|m| m.as_str())
+            .filter_map(|m| glob::Pattern::new(m.trim_end_matches('/')).ok())
+            .collect::<Vec<_>>()
+    };
+
+    docs.iter()
+        .filter(|(_, doc)| package_name(doc).is_some() && !doc.contains_key("workspace"))
+        .filter(|(_, doc)| {
+            doc.get("package")
+                .and_then(|p| p.get("workspace"))
+                .is_none()
+        })
+        .filter_map(|(manifest, doc)| {
+            let (workspace, workspace_doc) = workspace_of(docs, manifest)?;
+            let root = workspace.parent()?;
+            let member = manifest_path(manifest.parent()?.strip_prefix(root).ok()?);
+            let listed = |key| {
+                patterns(workspace_doc, key)
+                    .iter()
+                    .any(|p| p.matches(&member))
+            };
+            if listed("members") || listed("exclude") {
+                return None;
+            }
+
+            Some(Diagnostic {
+                check: "missing-member",
+                severity: Severity::Warning,
+                manifest: manifest.clone(),
+                message: format!(
+                    "The package `{}` is not a member of the workspace at {}.",
+                    package_name(doc).unwrap_or_default(),
+                    workspace.display()
+                ),
+                fix: Some(format!(
+                    "diener workspacify --path {} --add-missing-only",
+                    root.display()
+                )),
+            })
+        })
+        .collect()
+}
+
+/// Polkadot SDK repositories that are used with more than one branch, tag or rev.
+fn mixed_pins(root: &Path, docs: &[(PathBuf, Document)], repos: &RepoMatcher) -> Vec<Diagnostic> {
+    let mut pins = BTreeMap::<_, BTreeMap<_, usize>>::new();
+    for (_, doc) in docs {
+        for (_, deps) in dependency_tables(doc) {
+            let deps = deps.iter().filter_map(|(_, dep)| dep.as_table_like());
+            for dep in deps.filter(|dep| is_managed_dependency(*dep, repos)) {
+                let url = dep.get("git").and_then(|g| g.as_str()).unwrap_or_default();
+                *pins
+                    .entry(url.to_string())
+                    .or_default()
+                    .entry(GitRef::from_dependency(dep))
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    pins.into_iter()
+        .filter(|(_, references)| references.len() > 1)
+        .map(|(url, references)| {
+            let (common, _) = references
+                .iter()
+                .max_by_key(|(_, count)| **count)
+                .expect("There are multiple references; qed");
+            let flag = match common {
+                GitRef::Branch(branch) => Some(format!("--branch {branch}")),
+                GitRef::Tag(tag) => Some(format!("--tag {tag}")),
+                GitRef::Rev(rev) => Some(format!("--rev {rev}")),
+                GitRef::DefaultBranch => None,
+            };
+            Diagnostic {
+                check: "mixed-pins",
+                severity: Severity::Warning,
+                manifest: root.join("Cargo.toml"),
+                message: format!(
+                    "{url} is used with {} different references: {}.",
+                    references.len(),
+                    references
+                        .iter()
+                        .map(|(reference, count)| format!("`{}` ({count}x)", reference.name()))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                fix: flag.map(|flag| format!("diener update --path {} {flag}", root.display())),
+            }
+        })
+        .collect()
+}
+
+/// `[patch]` entries whose `path` doesn't contain a manifest.
+fn stale_patches(ctx: &RunContext, docs: &[(PathBuf, Document)]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for (manifest, doc) in docs {
+        let Some(sources) = doc.get("patch").and_then(|p| p.as_table_like()) else {
+            continue;
+        };
+        let dir = manifest.parent().unwrap_or(Path::new("."));
+
+        for (source, patches) in sources.iter() {
+            let Some(patches) = patches.as_table_like() else {
+                continue;
+            };
+            for (name, patch) in patches.iter() {
+                let Some(path) = patch.get("path").and_then(|p| p.as_str()) else {
+                    continue;
+                };
+                if ctx.changes.exists(&dir.join(path).join("Cargo.toml")) {
+                    continue;
+                }
+
+                diagnostics.push(Diagnostic {
+                    check: "stale-patch",
+                    severity: Severity::Error,
+                    manifest: manifest.clone(),
+                    // `patch --prune` only removes the patches of a checkout that exists.
+                    message: format!(
+                        "The patch of `{name}` in `[patch.{source}]` points to `{path}`, which \
+                         doesn't contain a manifest. Remove the entry or point it to a checkout."
+                    ),
+                    fix: None,
+                });
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// Print `diagnostics` in the given `format`.
+fn print_diagnostics(diagnostics: &[Diagnostic], format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            for diagnostic in diagnostics {
+                println!(
+                    "{}[{}]: {}: {}",
+                    diagnostic.severity.label(),
+                    diagnostic.check,
+                    diagnostic.manifest.display(),
+                    diagnostic.message
+                );
+                if let Some(fix) = &diagnostic.fix {
+                    println!("  fix: {fix}");
+                }
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(diagnostics)?),
+        OutputFormat::Github => {
+            let cwd = current_dir().unwrap_or_default();
+            for diagnostic in diagnostics {
+                let file = diagnostic
+                    .manifest
+                    .strip_prefix(&cwd)
+                    .unwrap_or(&diagnostic.manifest);
+                let fix = diagnostic
+                    .fix
+                    .as_ref()
+                    .map(|fix| format!(" Fix with `{fix}`."))
+                    .unwrap_or_default();
+                println!(
+                    "::{} file={}::{}{} ({})",
+                    diagnostic.severity.label(),
+                    manifest_path(file),
+                    diagnostic.message.replace('\n', "%0A"),
+                    fix,
+                    diagnostic.check
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,408 @@
+//! Editing manifests while keeping their formatting.
+//!
+//! [`ManifestEditor`] offers the edits diener does on manifests as typed operations. Every
+//! operation only touches the keys it is about: the order of the other keys, their spacing and
+//! the comments in the manifest are kept. The subcommands do their edits through the same
+//! functions, so they all format the manifests alike.
+
+use crate::{
+    changes,
+    engine::{Error, Reference},
+    manifest::{self, dependency_tables_mut},
+};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+use toml_edit::{Document, InlineTable, Item, Table, TableLike, Value};
+
+/// The keys of a dependency that select the commit of a git dependency.
+pub(crate) const REFERENCE_KEYS: &[&str] = &["branch", "tag", "rev"];
+
+/// The keys of a dependency that declare where it comes from, replaced by [`to_path_dep`].
+const SOURCE_KEYS: &[&str] = &["git", "branch", "tag", "rev", "version", "registry"];
+
+/// The keys of a dependency that declare where it comes from, replaced by [`to_git_dep`].
+const GIT_SOURCE_KEYS: &[&str] = &["git", "branch", "tag", "rev", "path", "version", "registry"];
+
+/// The keys of a dependency that declare where it comes from, replaced by [`to_version_dep`].
+const VERSION_SOURCE_KEYS: &[&str] = &["git", "branch", "tag", "rev", "path"];
+
+/// The keys of a patch that declare where it comes from, replaced by [`set_patch_source`].
+const PATCH_SOURCE_KEYS: &[&str] = &["path", "git", "branch", "tag", "rev"];
+
+/// Where a patch added by [`ManifestEditor::add_patch_entry`] points to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchSource {
+    /// The crate in the directory, written as is.
+    Path(PathBuf),
+    /// The crate in the git repository at `url`.
+    Git { url: String, reference: Reference },
+}
+
+/// A parsed manifest with typed, formatting preserving edits.
+///
+/// Dependencies are addressed by their table, like `dependencies`, `workspace.dependencies` or
+/// `target.'cfg(unix)'.dev-dependencies`, and their key in it:
+///
+/// ```
+/// use diener::{ManifestEditor, Reference};
+///
+/// let mut manifest = ManifestEditor::parse(
+///     "[dependencies]\nsp-core = { git = \"https://github.com/paritytech/polkadot-sdk\", branch = \"master\" } # core\n",
+/// )?;
+/// manifest.set_reference("dependencies", "sp-core", &Reference::Tag("v1.0.0".into()))?;
+/// assert_eq!(
+///     manifest.to_string(),
+///     "[dependencies]\nsp-core = { git = \"https://github.com/paritytech/polkadot-sdk\", tag = \"v1.0.0\" } # core\n",
+/// );
+/// # Ok::<_, diener::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ManifestEditor {
+    doc: Document,
+}
+
+impl ManifestEditor {
+    /// Parse the manifest `content`.
+    pub fn parse(content: &str) -> Result<Self, Error> {
+        changes::parse_toml(Path::new("Cargo.toml"), content)
+            .map(|doc| Self { doc })
+            .map_err(Error::Failed)
+    }
+
+    /// Returns the table and key of every dependency of the manifest.
+    pub fn dependencies(&self) -> Vec<(String, String)> {
+        manifest::dependency_tables(&self.doc)
+            .into_iter()
+            .flat_map(|(table, deps)| {
+                deps.iter()
+                    .map(move |(key, _)| (table.clone(), key.to_string()))
+            })
+            .collect()
+    }
+
+    /// Set the `git` url of the dependency `key` in `table`.
+    pub fn set_git(&mut self, table: &str, key: &str, url: &str) -> Result<(), Error> {
+        set_git(self.dependency(table, key)?, url);
+        Ok(())
+    }
+
+    /// Point the dependency `key` in `table` to the git `reference`, replacing its current one.
+    pub fn set_reference(
+        &mut self,
+        table: &str,
+        key: &str,
+        reference: &Reference,
+    ) -> Result<(), Error> {
+        let (name, value) = reference_key(reference);
+        set_reference(self.dependency(table, key)?, name, value);
+        Ok(())
+    }
+
+    /// Point the dependency `key` in `table` to the `branch`, see
+    /// [`ManifestEditor::set_reference`].
+    pub fn set_branch(&mut self, table: &str, key: &str, branch: &str) -> Result<(), Error> {
+        self.set_reference(table, key, &Reference::Branch(branch.into()))
+    }
+
+    /// Turn the dependency `key` in `table` into a dependency on the crate at `path`.
+    ///
+    /// The `git`, `version` and `registry` of the dependency are removed, the `path` takes the
+    /// place of the first of them.
+    pub fn to_path_dep(&mut self, table: &str, key: &str, path: &Path) -> Result<(), Error> {
+        to_path_dep(self.dependency(table, key)?, path);
+        Ok(())
+    }
+
+    /// Turn the dependency `key` in `table` into a dependency on the git repository at `url`,
+    /// using `reference`.
+    ///
+    /// The `path`, `version` and `registry` of the dependency are removed, the `git` url takes
+    /// the place of the first of them.
+    pub fn to_git_dep(
+        &mut self,
+        table: &str,
+        key: &str,
+        url: &str,
+        reference: &Reference,
+    ) -> Result<(), Error> {
+        let (name, value) = reference_key(reference);
+        to_git_dep(self.dependency(table, key)?, url, Some((name, value)));
+        Ok(())
+    }
+
+    /// Turn the dependency `key` in `table` into a crates.io dependency on `version`.
+    ///
+    /// The `git` and `path` of the dependency are removed, the `version` takes the place of the
+    /// first of them.
+    pub fn to_version_dep(&mut self, table: &str, key: &str, version: &str) -> Result<(), Error> {
+        to_version_dep(self.dependency(table, key)?, version);
+        Ok(())
+    }
+
+    /// Set `name` of the dependency `key` in `table` to `value`, like `default-features = false`.
+    pub fn set_key(
+        &mut self,
+        table: &str,
+        key: &str,
+        name: &str,
+        value: impl Into<Value>,
+    ) -> Result<(), Error> {
+        set_key(self.dependency(table, key)?, name, value);
+        Ok(())
+    }
+
+    /// Remove `name` from the dependency `key` in `table`.
+    pub fn remove_key(&mut self, table: &str, key: &str, name: &str) -> Result<(), Error> {
+        remove_key(self.dependency(table, key)?, name);
+        Ok(())
+    }
+
+    /// Depend on the crate `package` under the name `key` in `table`, by setting `package`.
+    pub fn set_package(&mut self, table: &str, key: &str, package: &str) -> Result<(), Error> {
+        set_package(self.dependency(table, key)?, package);
+        Ok(())
+    }
+
+    /// Rename the dependency `key` in `table` to `new_key`, keeping its position and formatting.
+    ///
+    /// References to the dependency, like in `[features]`, are not changed.
+    pub fn rename_dependency(
+        &mut self,
+        table: &str,
+        key: &str,
+        new_key: &str,
+    ) -> Result<(), Error> {
+        let deps = dependency_tables_mut(&mut self.doc)
+            .find(|(name, _)| name == table)
+            .map(|(_, deps)| deps)
+            .filter(|deps| deps.contains_key(key))
+            .ok_or_else(|| {
+                Error::InvalidOperation(format!("No dependency `{key}` in `[{table}]`."))
+            })?;
+        rename_dependency(deps, key, new_key);
+        Ok(())
+    }
+
+    /// Patch the crate `name` of the patch `target`, like `crates-io` or a git url, to `source`.
+    ///
+    /// The `[patch]` tables are created if they don't exist yet, an existing patch of the crate
+    /// is pointed to `source`.
+    pub fn add_patch_entry(
+        &mut self,
+        target: &str,
+        name: &str,
+        source: &PatchSource,
+    ) -> Result<(), Error> {
+        let keys = match source {
+            PatchSource::Path(path) => vec![("path", manifest::manifest_path(path))],
+            PatchSource::Git { url, reference } => {
+                let (name, value) = reference_key(reference);
+                vec![("git", url.clone()), (name, value.into())]
+            }
+        };
+        let table = patch_table(&mut self.doc, target).map_err(Error::Failed)?;
+        let patch = patch_entry(table, name).map_err(Error::Failed)?;
+        set_patch_source(patch, keys);
+        Ok(())
+    }
+
+    /// Returns the dependency `key` in `table`.
+    fn dependency(&mut self, table: &str, key: &str) -> Result<&mut Item, Error> {
+        let dep = dependency_tables_mut(&mut self.doc)
+            .find(|(name, _)| name == table)
+            .and_then(|(_, deps)| deps.get_mut(key))
+            .ok_or_else(|| {
+                Error::InvalidOperation(format!("No dependency `{key}` in `[{table}]`."))
+            })?;
+        manifest::expand_version_dependency(dep);
+        Ok(dep)
+    }
+}
+
+impl fmt::Display for ManifestEditor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.doc)
+    }
+}
+
+/// Returns the dependency key and value of `reference`, like `("tag", "v1.0.0")`.
+fn reference_key(reference: &Reference) -> (&'static str, &str) {
+    match reference {
+        Reference::Branch(branch) => ("branch", branch),
+        Reference::Tag(tag) => ("tag", tag),
+        Reference::Rev(rev) => ("rev", rev),
+    }
+}
+
+/// Replace `value` by `new`, keeping the decor of `value`.
+pub(crate) fn set_value(value: &mut Value, new: impl Into<Value>) {
+    let decor = value.decor().clone();
+    *value = new.into();
+    *value.decor_mut() = decor;
+}
+
+/// Set `key` of the dependency `dep` to `value`.
+///
+/// Dependencies declared as version, like `foo = "1.0"`, are turned into an inline table first.
+pub(crate) fn set_key(dep: &mut Item, key: &str, value: impl Into<Value>) {
+    manifest::expand_version_dependency(dep);
+    manifest::set_dependency_key(dep, key, value);
+}
+
+/// Remove `key` from the dependency `dep`.
+pub(crate) fn remove_key(dep: &mut Item, key: &str) {
+    manifest::remove_dependency_key(dep, key);
+}
+
+/// Set the `package` of the dependency `dep`, an existing `package` keeps its place.
+pub(crate) fn set_package(dep: &mut Item, package: &str) {
+    set_key(dep, "package", package);
+}
+
+/// Rename the dependency `key` in the dependency table `deps` to `new_key`.
+pub(crate) fn rename_dependency(deps: &mut Table, key: &str, new_key: &str) {
+    manifest::rename_key(deps, key, new_key);
+}
+
+/// Set the `git` url of the dependency `dep`, an existing url keeps its place.
+pub(crate) fn set_git(dep: &mut Item, url: &str) {
+    manifest::set_dependency_key(dep, "git", url);
+}
+
+/// Set the git reference of the dependency `dep` to `key = value`, with `key` one of
+/// [`REFERENCE_KEYS`].
+///
+/// The reference takes the place of the current one.
+pub(crate) fn set_reference(dep: &mut Item, key: &str, value: &str) {
+    manifest::replace_dependency_keys(dep, REFERENCE_KEYS, [(key, Value::from(value))]);
+}
+
+/// Turn the dependency `dep` into a dependency on the crate at `path`.
+pub(crate) fn to_path_dep(dep: &mut Item, path: &Path) {
+    manifest::expand_version_dependency(dep);
+    manifest::replace_dependency_keys(
+        dep,
+        SOURCE_KEYS,
+        [("path", Value::from(manifest::manifest_path(path)))],
+    );
+}
+
+/// Turn the dependency `dep` into a dependency on the git repository at `url`.
+///
+/// `reference` is the key and value of the git reference, like `("tag", "v1.0.0")`, without one
+/// the default branch is used.
+pub(crate) fn to_git_dep(dep: &mut Item, url: &str, reference: Option<(&str, &str)>) {
+    manifest::expand_version_dependency(dep);
+    manifest::replace_dependency_keys(
+        dep,
+        GIT_SOURCE_KEYS,
+        [("git", Value::from(url))]
+            .into_iter()
+            .chain(reference.map(|(k, v)| (k, Value::from(v)))),
+    );
+}
+
+/// Turn the dependency `dep` into a crates.io dependency on `version`.
+pub(crate) fn to_version_dep(dep: &mut Item, version: &str) {
+    manifest::replace_dependency_keys(
+        dep,
+        VERSION_SOURCE_KEYS,
+        [("version", Value::from(version))],
+    );
+}
+
+/// Replace all keys of the dependency `dep` by `entries`.
+///
+/// The dependency keeps its form, a `[dependencies.name]` table stays a table with its comments,
+/// all other dependencies become an inline table with the decor of the old value.
+pub(crate) fn replace_dependency(
+    dep: &mut Item,
+    entries: impl IntoIterator<Item = (String, Value)>,
+) {
+    if let Some(table) = dep.as_table_mut() {
+        let mut new = Table::new();
+        *new.decor_mut() = table.decor().clone();
+        new.set_position(table.position().unwrap_or_default());
+        entries.into_iter().for_each(|(k, v)| {
+            new.insert(&k, Item::Value(v.decorated(" ", "")));
+        });
+        *table = new;
+    } else {
+        let decor = dep.as_value().map(|v| v.decor().clone());
+        let mut value = Value::InlineTable(inline_dependency(entries));
+        if let Some(decor) = decor {
+            *value.decor_mut() = decor;
+        }
+        *dep = Item::Value(value);
+    }
+}
+
+/// Add the dependency `key` declared by `entries` as inline table to the dependency table `deps`.
+pub(crate) fn add_dependency(
+    deps: &mut dyn TableLike,
+    key: &str,
+    entries: impl IntoIterator<Item = (String, Value)>,
+) {
+    deps.insert(
+        key,
+        Item::Value(Value::InlineTable(inline_dependency(entries))),
+    );
+}
+
+/// Returns an inline table of `entries` in the default format, `{ key = value, .. }`.
+fn inline_dependency(entries: impl IntoIterator<Item = (String, Value)>) -> InlineTable {
+    let mut dep = InlineTable::new();
+    entries.into_iter().for_each(|(k, v)| {
+        dep.insert(&k, v);
+    });
+    dep.fmt();
+    dep
+}
+
+/// Returns the `[patch.<target>]` table of `doc`, created if it doesn't exist.
+pub(crate) fn patch_table<'a>(
+    doc: &'a mut Document,
+    target: &str,
+) -> anyhow::Result<&'a mut Table> {
+    let patch = doc
+        .as_table_mut()
+        .entry("patch")
+        .or_insert(Item::Table(Default::default()))
+        .as_table_mut()
+        .ok_or_else(|| anyhow::anyhow!("Patch table isn't a toml table!"))?;
+    patch.set_implicit(true);
+
+    patch
+        .entry(target)
+        .or_insert(Item::Table(Default::default()))
+        .as_table_mut()
+        .ok_or_else(|| anyhow::anyhow!("Patch target table isn't a toml table!"))
+}
+
+/// Returns the patch of the crate `name` in the patch `table`, created as empty inline table if
+/// it doesn't exist.
+pub(crate) fn patch_entry<'a>(table: &'a mut Table, name: &str) -> anyhow::Result<&'a mut Item> {
+    let patch = table
+        .entry(name)
+        .or_insert(Item::Value(Value::InlineTable(Default::default())));
+    if !patch.is_inline_table() {
+        anyhow::bail!("Patch entry for `{}` isn't an inline table!", name);
+    }
+    Ok(patch)
+}
+
+/// Point the patch `patch` to the source given by `keys`, like `[("path", "..")]`.
+///
+/// The new keys take the place of the current source of the patch.
+pub(crate) fn set_patch_source<'a>(
+    patch: &mut Item,
+    keys: impl IntoIterator<Item = (&'a str, String)>,
+) {
+    manifest::replace_dependency_keys(
+        patch,
+        PATCH_SOURCE_KEYS,
+        keys.into_iter().map(|(k, v)| (k, Value::from(v))),
+    );
+}
@@ -0,0 +1,398 @@
+//! Embedding diener into other Rust programs.
+//!
+//! The [`Engine`] runs typed [`Operation`]s and returns the resulting [`ChangeSet`] instead of
+//! writing to the filesystem. It never prints or exits, errors are returned as [`Error`].
+
+use crate::{
+    changes::Changes,
+    cli::SubCommands,
+    config::Config,
+    context::RunContext,
+    filesystem::{Fs, RealFs},
+    update::Update,
+};
+use serde::Deserialize;
+use std::{
+    ffi::OsString,
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use structopt::StructOpt;
+
+/// The git reference dependencies are switched to.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Reference {
+    Branch(String),
+    Tag(String),
+    Rev(String),
+}
+
+impl Reference {
+    fn args(&self) -> [OsString; 2] {
+        let (flag, value) = match self {
+            Self::Branch(branch) => ("--branch", branch),
+            Self::Tag(tag) => ("--tag", tag),
+            Self::Rev(rev) => ("--rev", rev),
+        };
+        [flag.into(), value.into()]
+    }
+}
+
+/// An operation the [`Engine`] can run.
+///
+/// Relative paths are relative to the root of the engine, paths that are not given default to
+/// the root. Every operation corresponds to the subcommand of the same name.
+///
+/// As JSON, e.g. for `diener serve`, the operation is given by `command`, like
+/// `{ "command": "update", "reference": { "tag": "v1" } }`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(
+    tag = "command",
+    rename_all = "kebab-case",
+    rename_all_fields = "kebab-case"
+)]
+#[non_exhaustive]
+pub enum Operation {
+    /// Switch the Polkadot SDK git dependencies to `reference`, see `diener update`.
+    Update {
+        /// The directories to search for manifests.
+        #[serde(default)]
+        paths: Vec<PathBuf>,
+        reference: Reference,
+        /// The `git` url the dependencies are rewritten to.
+        git: Option<String>,
+    },
+    /// Create or fix up the workspace, see `diener workspacify`.
+    Workspacify { path: Option<PathBuf> },
+    /// Rename the crate `old` to `new`, see `diener rename-crate`.
+    RenameCrate {
+        path: Option<PathBuf>,
+        old: String,
+        new: String,
+        /// Rename the dependencies named `old` instead of adding `package = "new"`.
+        #[serde(default)]
+        rename_key: bool,
+    },
+    /// Set or remove keys of the dependencies on `packages`, see `diener set`.
+    Set {
+        /// The directories to search for manifests.
+        #[serde(default)]
+        paths: Vec<PathBuf>,
+        /// Glob patterns of the package names to change, e.g. `sp-*`.
+        packages: Vec<String>,
+        /// The keys to set with their values as TOML, e.g. `("default-features", "false")`.
+        #[serde(default)]
+        assignments: Vec<(String, String)>,
+        /// The keys to remove.
+        #[serde(default)]
+        remove: Vec<String>,
+    },
+    /// Convert the git and path dependencies to crates.io dependencies, see
+    /// `diener release-prep`.
+    ReleasePrep {
+        path: Option<PathBuf>,
+        /// The TOML file mapping the crates to their versions.
+        versions: Option<PathBuf>,
+    },
+    /// Switch the crates.io dependencies on the Polkadot SDK to git, see `diener dev-setup`.
+    DevSetup {
+        path: Option<PathBuf>,
+        reference: Reference,
+        /// The url of the Polkadot SDK repository.
+        git: Option<String>,
+        /// Also add `[patch.crates-io]` entries to the workspace root.
+        #[serde(default)]
+        patch: bool,
+    },
+    /// Patch the crates of the workspace at `crates_to_patch` into the workspace at `path`, see
+    /// `diener patch`.
+    Patch {
+        path: Option<PathBuf>,
+        crates_to_patch: PathBuf,
+        /// The patch target, e.g. `crates-io` or a git url.
+        target: Option<String>,
+    },
+}
+
+impl Operation {
+    /// Returns the subcommand, with all paths resolved against `root`.
+    fn subcommand(&self, root: &Path) -> Result<SubCommands, Error> {
+        let path = |path: &Option<PathBuf>| -> [OsString; 2] {
+            let path = path.as_deref().map_or(root.to_path_buf(), |p| root.join(p));
+            ["--path".into(), path.into()]
+        };
+        let paths = |paths: &[PathBuf]| -> Vec<OsString> {
+            match paths {
+                [] => path(&None).into(),
+                paths => paths.iter().flat_map(|p| path(&Some(p.clone()))).collect(),
+            }
+        };
+
+        let mut args: Vec<OsString> = vec!["diener".into()];
+        match self {
+            // Parsing the command line of `update` would take the defaults from the environment.
+            Self::Update {
+                paths: dirs,
+                reference,
+                git,
+            } => {
+                let dirs = match &dirs[..] {
+                    [] => vec![root.to_path_buf()],
+                    dirs => dirs.iter().map(|d| root.join(d)).collect(),
+                };
+                return Ok(SubCommands::Update(Update::new(
+                    dirs,
+                    reference,
+                    git.clone(),
+                )));
+            }
+            Self::Workspacify { path: dir } => {
+                args.push("workspacify".into());
+                args.extend(path(dir));
+            }
+            Self::RenameCrate {
+                path: dir,
+                old,
+                new,
+                rename_key,
+            } => {
+                args.push("rename-crate".into());
+                args.extend(path(dir));
+                if *rename_key {
+                    args.push("--rename-key".into());
+                }
+                args.extend([old.into(), new.into()]);
+            }
+            Self::Set {
+                paths: dirs,
+                packages,
+                assignments,
+                remove,
+            } => {
+                args.push("set".into());
+                args.extend(paths(dirs));
+                args.extend(packages.iter().flat_map(|p| ["--match".into(), p.into()]));
+                args.extend(remove.iter().flat_map(|k| ["--remove".into(), k.into()]));
+                args.extend(assignments.iter().map(|(k, v)| format!("{k}={v}").into()));
+            }
+            Self::ReleasePrep {
+                path: dir,
+                versions,
+            } => {
+                args.push("release-prep".into());
+                args.extend(path(dir));
+                args.extend(
+                    versions
+                        .iter()
+                        .flat_map(|v| ["--versions".into(), root.join(v).into()]),
+                );
+            }
+            Self::DevSetup {
+                path: dir,
+                reference,
+                git,
+                patch,
+            } => {
+                args.push("dev-setup".into());
+                args.extend(path(dir));
+                args.extend(reference.args());
+                args.extend(git.iter().flat_map(|g| ["--git".into(), g.into()]));
+                if *patch {
+                    args.push("--patch".into());
+                }
+            }
+            Self::Patch {
+                path: dir,
+                crates_to_patch,
+                target,
+            } => {
+                args.push("patch".into());
+                args.extend(path(dir));
+                args.extend([
+                    "--crates-to-patch".into(),
+                    root.join(crates_to_patch).into(),
+                ]);
+                args.extend(target.iter().flat_map(|t| ["--target".into(), t.into()]));
+            }
+        }
+        SubCommands::from_iter_safe(args).map_err(Error::invalid)
+    }
+}
+
+/// An error returned by the [`Engine`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The operation is invalid, e.g. it contains an invalid glob pattern.
+    InvalidOperation(String),
+    /// Loading the configuration or running the operation failed, the error is the
+    /// [`source`](std::error::Error::source).
+    Failed(anyhow::Error),
+    /// Applying a [`ChangeSet`] failed, no file was modified. The error is the
+    /// [`source`](std::error::Error::source).
+    Apply(anyhow::Error),
+}
+
+impl Error {
+    /// The error of a command line that clap rejected.
+    pub(crate) fn invalid(error: structopt::clap::Error) -> Self {
+        Self::InvalidOperation(error.message.trim_start_matches("error: ").into())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidOperation(message) => write!(f, "Invalid operation: {message}"),
+            Self::Failed(_) => write!(f, "The operation failed"),
+            Self::Apply(_) => write!(f, "Applying the changes failed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidOperation(_) => None,
+            Self::Failed(error) | Self::Apply(error) => Some(error.as_ref()),
+        }
+    }
+}
+
+/// The modifications of the filesystem done by an [`Operation`].
+///
+/// Nothing is written until [`ChangeSet::apply`] is called, which writes to the filesystem of
+/// the [`Engine`].
+pub struct ChangeSet(Changes);
+
+impl ChangeSet {
+    /// Returns `true` if the operation doesn't modify anything.
+    pub fn is_empty(&self) -> bool {
+        self.0.touched_paths().is_empty()
+    }
+
+    /// Returns `(path, original content, new content)` of all modified files.
+    ///
+    /// The original content is `None` for new files.
+    pub fn files(&self) -> impl Iterator<Item = (&Path, Option<&str>, &str)> {
+        self.0.modified()
+    }
+
+    /// Returns all directory moves as `(from, to)`, done before any file is written.
+    pub fn moves(&self) -> &[(PathBuf, PathBuf)] {
+        self.0.moves()
+    }
+
+    /// Returns a unified diff of all modifications.
+    pub fn diff(&self) -> String {
+        self.0.diff(false)
+    }
+
+    /// Write all modifications to the filesystem.
+    ///
+    /// Either all modifications are applied or none.
+    pub fn apply(self) -> Result<(), Error> {
+        self.0.apply().map_err(Error::Apply)
+    }
+}
+
+/// Runs [`Operation`]s on the manifests below a root directory.
+///
+/// The configuration is loaded from the `.diener.toml` files above the root and the results of
+/// `cargo metadata` are kept between the operations.
+pub struct Engine {
+    root: PathBuf,
+    ctx: RunContext,
+}
+
+impl Engine {
+    /// Create an engine for the manifests below `root` on the real filesystem.
+    pub fn new(root: impl Into<PathBuf>) -> Result<Self, Error> {
+        let root = root.into();
+        let config = Config::load(&root).map_err(Error::Failed)?;
+        Ok(Self::with_config(root, config, Arc::new(RealFs)))
+    }
+
+    /// Create an engine for the manifests below `root` on `fs`, e.g. a [`MemoryFs`](crate::MemoryFs).
+    ///
+    /// No configuration is loaded. The operations that need the real filesystem, like the ones
+    /// running `cargo metadata` or resolving paths of other crates, fail on other filesystems.
+    pub fn with_fs(root: impl Into<PathBuf>, fs: Arc<dyn Fs>) -> Self {
+        Self::with_config(root.into(), Config::default(), fs)
+    }
+
+    fn with_config(root: PathBuf, config: Config, fs: Arc<dyn Fs>) -> Self {
+        Self {
+            ctx: RunContext::new(root.clone(), config, fs),
+            root,
+        }
+    }
+
+    /// Never access the network, operations that require it fail.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.ctx.offline = offline;
+        self
+    }
+
+    /// Only consider the dependencies built for the target triple `platform`, like
+    /// `--filter-platform`.
+    pub fn filter_platform(mut self, platform: Option<String>) -> Self {
+        self.ctx.filter_platform = platform;
+        self
+    }
+
+    /// Read and write at most `jobs` files at the same time, like `--jobs`.
+    ///
+    /// Defaults to the number of CPUs.
+    pub fn jobs(mut self, jobs: std::num::NonZeroUsize) -> Self {
+        self.ctx.changes.set_jobs(jobs.get());
+        self
+    }
+
+    /// Keep at most `max` read manifests in memory until they are modified, like
+    /// `--max-buffered`.
+    ///
+    /// Defaults to 256.
+    pub fn max_buffered(mut self, max: usize) -> Self {
+        self.ctx.changes.set_max_buffered(max);
+        self
+    }
+
+    /// Run `operation` and return its modifications without applying them.
+    pub fn run(&mut self, operation: Operation) -> Result<ChangeSet, Error> {
+        run(&mut self.ctx, &self.root, &operation)
+    }
+}
+
+/// Run `operation` with the paths relative to `root` and return the modifications it staged in
+/// `ctx`.
+pub(crate) fn run(
+    ctx: &mut RunContext,
+    root: &Path,
+    operation: &Operation,
+) -> Result<ChangeSet, Error> {
+    let subcommand = operation.subcommand(root)?;
+    let res = subcommand.run(ctx);
+    take_changes(ctx, res)
+}
+
+/// Take the modifications staged in `ctx`, failing if `res` or processing any file failed.
+pub(crate) fn take_changes(
+    ctx: &mut RunContext,
+    res: anyhow::Result<()>,
+) -> Result<ChangeSet, Error> {
+    let scratch = ctx.changes.scratch();
+    let changes = std::mem::replace(&mut ctx.changes, scratch);
+    let failures = std::mem::take(&mut ctx.failures);
+    res.map_err(Error::Failed)?;
+
+    if let Some((path, error)) = failures.into_iter().next() {
+        return Err(Error::Failed(
+            error.context(format!("Failed to process {}", path.display())),
+        ));
+    }
+
+    Ok(ChangeSet(changes))
+}
@@ -0,0 +1,245 @@
+use crate::{
+    config::Config,
+    context::RunContext,
+    format::OutputFormat,
+    http, incremental, metadata,
+    network::{self, Network},
+    update, walk,
+};
+use anyhow::{ensure, Context, Result};
+use serde::Serialize;
+use std::{collections::BTreeMap, env::current_dir, path::PathBuf};
+use structopt::StructOpt;
+
+/// The environment variables that change the behavior of diener.
+const VARIABLES: &[&str] = &[
+    "DIENER_BRANCH",
+    "DIENER_GIT",
+    "DIENER_PATH",
+    "DIENER_MATCH_REPO",
+    "GITHUB_API_URL",
+    "HTTPS_PROXY",
+    "https_proxy",
+    "NO_PROXY",
+    "no_proxy",
+    "XDG_CONFIG_HOME",
+    "XDG_CACHE_HOME",
+    "CARGO",
+    "NO_COLOR",
+    "RUST_LOG",
+];
+
+/// The environment variables containing tokens, their values are never printed.
+const TOKEN_VARIABLES: &[&str] = &["GITHUB_TOKEN", "GH_TOKEN", "GITLAB_TOKEN"];
+
+/// `env` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct Env {
+    /// The path where Diener should search for workspace roots.
+    ///
+    /// Can be passed multiple times and may be a glob pattern like `runtimes/*`.
+    /// If not given, the current directory will be taken.
+    #[structopt(long)]
+    path: Vec<PathBuf>,
+
+    /// The output format, `text` or `json`.
+    ///
+    /// Defaults to the `format` of the configuration or `text`.
+    #[structopt(long)]
+    format: Option<OutputFormat>,
+}
+
+/// Everything that influences a diener run.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Environment {
+    /// The configuration files that were loaded, the user configuration first.
+    config_files: Vec<PathBuf>,
+    /// The merged configuration, including the defaults and without credentials.
+    config: Config,
+    /// Never access the network, see `--offline`.
+    offline: bool,
+    /// The proxy and CA bundle, the credentials of the proxy are given as `***`.
+    network: Network,
+    /// The number of files read in parallel, see `--jobs`.
+    jobs: usize,
+    /// The directory names that are skipped, including `--exclude-dir`.
+    exclude_dirs: Vec<String>,
+    /// The manifests given by `--manifest-list`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    manifest_list: Option<Vec<PathBuf>>,
+    workspace_roots: Vec<PathBuf>,
+    caches: Vec<Cache>,
+    /// The set environment variables that diener reads, tokens and the credentials of proxies
+    /// are given as `***`.
+    variables: BTreeMap<String, String>,
+}
+
+/// A directory diener caches results in.
+#[derive(Debug, Serialize)]
+struct Cache {
+    /// What is cached, e.g. `API responses`.
+    name: &'static str,
+    path: PathBuf,
+    exists: bool,
+}
+
+impl Cache {
+    fn new(name: &'static str, path: PathBuf) -> Self {
+        Self {
+            name,
+            exists: path.is_dir(),
+            path,
+        }
+    }
+}
+
+impl Env {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &RunContext) -> Result<()> {
+        let format = self
+            .format
+            .or(ctx.config.format())
+            .unwrap_or(OutputFormat::Text);
+        ensure!(
+            format != OutputFormat::Github,
+            "`env` doesn't support the `github` format."
+        );
+
+        let mut workspace_roots = Vec::new();
+        for dir in update::search_dirs(ctx.changes.fs(), &self.path)? {
+            for root in walk::workspace_roots(ctx.changes.fs(), &dir, &ctx.exclusions) {
+                if !workspace_roots.contains(&root) {
+                    workspace_roots.push(root);
+                }
+            }
+        }
+
+        let working_dir = current_dir().with_context(|| "Working directory is invalid.")?;
+        let mut caches = Vec::new();
+        caches.extend(http::cache_dir().map(|dir| Cache::new("API responses", dir)));
+        caches.push(Cache::new(
+            "incremental hashes",
+            working_dir.join(incremental::CACHE_DIR),
+        ));
+        if ctx.metadata.persists() {
+            caches.extend(
+                workspace_roots
+                    .iter()
+                    .map(|root| Cache::new("cargo metadata", root.join(metadata::CACHE_DIR))),
+            );
+        }
+
+        let tokens = TOKEN_VARIABLES
+            .iter()
+            .copied()
+            .chain(ctx.config.forges().iter().map(|f| f.token_env()))
+            .collect::<Vec<_>>();
+        let variables = VARIABLES
+            .iter()
+            .chain(&tokens)
+            .filter_map(|var| {
+                let value = std::env::var(var).ok()?;
+                let value = if tokens.contains(var) {
+                    "***".into()
+                } else if var.to_lowercase().ends_with("proxy") {
+                    network::redact(&value)
+                } else {
+                    value
+                };
+                Some((var.to_string(), value))
+            })
+            .collect();
+
+        let environment = Environment {
+            config_files: ctx.config.files().to_vec(),
+            config: ctx.config.with_defaults().redacted(),
+            offline: ctx.offline,
+            network: ctx.config.network().resolve()?.redacted(),
+            jobs: ctx.changes.jobs(),
+            exclude_dirs: ctx.exclusions.names().to_vec(),
+            manifest_list: ctx.manifest_list.clone(),
+            workspace_roots,
+            caches,
+            variables,
+        };
+        match format {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&environment)?),
+            OutputFormat::Text | OutputFormat::Github => print_text(&environment)?,
+        }
+
+        Ok(())
+    }
+}
+
+fn print_text(env: &Environment) -> Result<()> {
+    println!("Configuration files:");
+    if env.config_files.is_empty() {
+        println!("  none");
+    }
+    for file in &env.config_files {
+        println!("  {}", file.display());
+    }
+    println!();
+
+    println!("Configuration:");
+    let config = toml_edit::ser::to_string_pretty(&env.config)
+        .with_context(|| "Failed to print the configuration")?;
+    for line in config.lines() {
+        if line.is_empty() {
+            println!();
+        } else {
+            println!("  {line}");
+        }
+    }
+    println!();
+
+    println!(
+        "Network:       {}",
+        if env.offline { "offline" } else { "online" }
+    );
+    if let Some(proxy) = env.network.proxy() {
+        println!("Proxy:         {proxy}");
+    }
+    if let Some(no_proxy) = env.network.no_proxy() {
+        println!("No proxy:      {no_proxy}");
+    }
+    if let Some(ca) = env.network.ca_bundle() {
+        println!("CA bundle:     {}", ca.display());
+    }
+    println!("Jobs:          {}", env.jobs);
+    println!("Excluded dirs: {}", env.exclude_dirs.join(", "));
+    if let Some(manifests) = &env.manifest_list {
+        println!("Manifest list: {} manifest(s)", manifests.len());
+    }
+    println!();
+
+    println!("Workspace roots:");
+    if env.workspace_roots.is_empty() {
+        println!("  none");
+    }
+    for root in &env.workspace_roots {
+        println!("  {}", root.display());
+    }
+    println!();
+
+    println!("Caches:");
+    for cache in &env.caches {
+        println!(
+            "  {:<20} {}{}",
+            format!("{}:", cache.name),
+            cache.path.display(),
+            if cache.exists { "" } else { " (missing)" }
+        );
+    }
+
+    if !env.variables.is_empty() {
+        println!();
+        println!("Environment variables:");
+        for (var, value) in &env.variables {
+            println!("  {var}={value}");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use std::fmt;
+
+/// The class of a failure, which decides the exit code of diener.
+///
+/// Failures without a class exit with `1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Failure {
+    /// The command line is invalid.
+    Usage,
+    /// `--check` found files that are not up to date.
+    NotUpToDate,
+    /// Nothing was modified, only reported with `--fail-if-unchanged`.
+    NothingToDo,
+    /// A manifest or another input file could not be parsed.
+    Parse,
+    /// Accessing the network failed or is disabled by `--offline`.
+    Network,
+    /// Running `cargo` failed.
+    Cargo,
+}
+
+impl Failure {
+    /// The exit code of diener for this failure.
+    pub(crate) fn exit_code(self) -> u8 {
+        match self {
+            Self::Usage => 2,
+            Self::NotUpToDate => 3,
+            Self::NothingToDo => 4,
+            Self::Parse => 5,
+            Self::Network => 6,
+            Self::Cargo => 7,
+        }
+    }
+
+    /// Returns the class of `error`, the innermost one if there are multiple.
+    pub(crate) fn of(error: &anyhow::Error) -> Option<Self> {
+        error
+            .chain()
+            .filter_map(|e| e.downcast_ref::<Classified>())
+            .last()
+            .map(|c| Self::of(&c.error).unwrap_or(c.failure))
+    }
+}
+
+/// An error with its [`Failure`] class.
+///
+/// Shows up in the error chain like the wrapped error itself, so the wrapped error is not part
+/// of the chain. [`Failure::of`] looks into it for the classes of nested errors.
+#[derive(Debug)]
+struct Classified {
+    failure: Failure,
+    error: anyhow::Error,
+}
+
+impl fmt::Display for Classified {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl std::error::Error for Classified {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// Attach a [`Failure`] class to errors.
+pub(crate) trait Classify {
+    /// Classify the error as `failure`.
+    fn failure(self, failure: Failure) -> Self;
+}
+
+impl Classify for anyhow::Error {
+    fn failure(self, failure: Failure) -> Self {
+        anyhow::Error::new(Classified {
+            failure,
+            error: self,
+        })
+    }
+}
+
+impl<T> Classify for anyhow::Result<T> {
+    fn failure(self, failure: Failure) -> Self {
+        self.map_err(|e| e.failure(failure))
+    }
+}
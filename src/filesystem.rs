@@ -0,0 +1,262 @@
+//! The filesystem diener edits manifests on.
+//!
+//! All manifest reads, writes, directory walks and glob lookups of the editing core go through
+//! [`Fs`], so the core can run against the real filesystem ([`RealFs`]) or purely in memory
+//! ([`MemoryFs`]).
+
+use glob::{MatchOptions, Pattern};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+};
+use walkdir::WalkDir;
+
+/// Decides whether [`Fs::walk`] skips an entry, called with the path and whether it is a
+/// directory. Skipped directories are not descended into.
+pub type SkipFn = Box<dyn Fn(&Path, bool) -> bool>;
+
+/// The file access needed to edit manifests.
+pub trait Fs: Send + Sync {
+    /// Read the file at `path`.
+    fn read(&self, path: &Path) -> io::Result<String>;
+
+    /// Write `content` to the file at `path`, creating its parent directories.
+    fn write(&self, path: &Path, content: &str) -> io::Result<()>;
+
+    /// Move the file or directory `from` to `to`, creating the parent directories of `to`.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Remove the file at `path`.
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    /// Returns `true` if `path` is a file.
+    fn is_file(&self, path: &Path) -> bool;
+
+    /// Returns `true` if `path` is a directory.
+    fn is_dir(&self, path: &Path) -> bool;
+
+    /// Returns all files below `root` that are not skipped by `skip`.
+    ///
+    /// `root` itself is never skipped. Symbolic links are only followed if `follow_links` is
+    /// `true`.
+    fn walk(
+        &self,
+        root: &Path,
+        follow_links: bool,
+        skip: SkipFn,
+    ) -> Box<dyn Iterator<Item = PathBuf>>;
+
+    /// Returns all files and directories matching the glob `pattern`, like `runtimes/*`.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if `pattern` is invalid.
+    fn glob(&self, pattern: &str) -> io::Result<Vec<PathBuf>>;
+}
+
+/// Lexically normalize the given absolute path, resolving `.` and `..`.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                res.pop();
+            }
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Parse the glob `pattern` of [`Fs::glob`].
+fn pattern(pattern: &str) -> io::Result<Pattern> {
+    Pattern::new(pattern).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+/// The filesystem of the operating system.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RealFs;
+
+impl Fs for RealFs {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn write(&self, path: &Path, content: &str) -> io::Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        if let Some(parent) = to.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn walk(
+        &self,
+        root: &Path,
+        follow_links: bool,
+        skip: SkipFn,
+    ) -> Box<dyn Iterator<Item = PathBuf>> {
+        let files = WalkDir::new(root)
+            .follow_links(follow_links)
+            .into_iter()
+            .filter_entry(move |e| e.depth() == 0 || !skip(e.path(), e.file_type().is_dir()))
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .map(|e| e.into_path());
+        Box::new(files)
+    }
+
+    fn glob(&self, pattern: &str) -> io::Result<Vec<PathBuf>> {
+        let paths = glob::glob(pattern)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
+            .filter_map(|p| p.ok())
+            .collect();
+        Ok(paths)
+    }
+}
+
+/// A filesystem that only exists in memory, e.g. for tests or tooling without disk access.
+///
+/// Directories exist implicitly as the parents of the files.
+#[derive(Debug, Default)]
+pub struct MemoryFs {
+    files: Mutex<BTreeMap<PathBuf, String>>,
+}
+
+impl MemoryFs {
+    /// Create an empty filesystem.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the file at `path` with the given `content`.
+    pub fn with_file(self, path: impl Into<PathBuf>, content: impl Into<String>) -> Self {
+        self.lock().insert(path.into(), content.into());
+        self
+    }
+
+    /// Returns all files with their content.
+    pub fn files(&self) -> BTreeMap<PathBuf, String> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<PathBuf, String>> {
+        self.files.lock().expect("Never poisoned; qed")
+    }
+}
+
+impl Fs for MemoryFs {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.lock()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+
+    fn write(&self, path: &Path, content: &str) -> io::Result<()> {
+        self.lock().insert(path.into(), content.into());
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut files = self.lock();
+        let moved = files
+            .keys()
+            .filter(|p| p.starts_with(from))
+            .cloned()
+            .collect::<Vec<_>>();
+        if moved.is_empty() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+
+        for path in moved {
+            let content = files.remove(&path).unwrap_or_default();
+            let rest = path.strip_prefix(from).unwrap_or(&path);
+            files.insert(to.join(rest), content);
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.lock()
+            .remove(path)
+            .map(drop)
+            .ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.lock().contains_key(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.lock().keys().any(|p| p != path && p.starts_with(path))
+    }
+
+    fn walk(
+        &self,
+        root: &Path,
+        _follow_links: bool,
+        skip: SkipFn,
+    ) -> Box<dyn Iterator<Item = PathBuf>> {
+        let files = self
+            .lock()
+            .keys()
+            .filter(|p| {
+                let Ok(relative) = p.strip_prefix(root) else {
+                    return false;
+                };
+                // Every directory between `root` and the file, then the file itself.
+                let mut entry = root.to_path_buf();
+                let mut components = relative.components().peekable();
+                while let Some(component) = components.next() {
+                    entry.push(component);
+                    if skip(&entry, components.peek().is_some()) {
+                        return false;
+                    }
+                }
+                true
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        Box::new(files.into_iter())
+    }
+
+    fn glob(&self, pattern: &str) -> io::Result<Vec<PathBuf>> {
+        let pattern = self::pattern(pattern)?;
+        // Like `glob::glob`, `*` never matches a `/`.
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        let files = self.lock();
+        let paths = files
+            .keys()
+            .flat_map(|p| p.ancestors())
+            .filter(|p| !p.as_os_str().is_empty())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter(|p| pattern.matches_path_with(p, options))
+            .map(Path::to_path_buf)
+            .collect();
+        Ok(paths)
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{
+    context::RunContext,
+    failure::{Classify, Failure},
+    format::OutputFormat,
+    manifest::manifest_path,
+    workspacify::manifest_iter,
+};
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+use toml_edit::{Item, TableLike, Value};
+
+/// The repositories that were archived when they were merged into the Polkadot SDK.
+const ARCHIVED_REPOSITORIES: &[&str] = &[
+    "https://github.com/paritytech/substrate",
+    "https://github.com/paritytech/polkadot",
+    "https://github.com/paritytech/cumulus",
+];
+
+/// `fix-links` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct FixLinks {
+    /// The path to the workspace root directory.
+    ///
+    /// Uses the working directory if none is supplied.
+    #[structopt(long)]
+    path: Option<PathBuf>,
+
+    /// Don't modify any file, fail if any link is not up to date instead.
+    #[structopt(long)]
+    check: bool,
+
+    /// The output format of `--check`, `text`, `json` or `github`.
+    ///
+    /// `github` emits GitHub Actions annotations. Defaults to the `format` of the configuration
+    /// or `text`.
+    #[structopt(long, requires = "check")]
+    format: Option<OutputFormat>,
+
+    /// The template of the `repository` of every crate, e.g. `https://github.com/org/repo`.
+    ///
+    /// `{name}` is replaced by the name of the crate and `{path}` by its directory relative to
+    /// the workspace root.
+    #[structopt(long, value_name = "TEMPLATE")]
+    repository: Option<String>,
+
+    /// The template of the `homepage` of every crate, e.g.
+    /// `https://github.com/org/repo/tree/master/{path}`.
+    #[structopt(long, value_name = "TEMPLATE")]
+    homepage: Option<String>,
+
+    /// The template of the `documentation` of every crate, e.g. `https://docs.rs/{name}`.
+    #[structopt(long, value_name = "TEMPLATE")]
+    documentation: Option<String>,
+
+    /// A repository that was archived, links pointing into it are reported.
+    ///
+    /// Can be passed multiple times. Defaults to the repositories merged into the Polkadot
+    /// SDK: `substrate`, `polkadot` and `cumulus` of `paritytech`.
+    #[structopt(long, value_name = "URL")]
+    archived: Vec<String>,
+}
+
+/// A crate of the workspace whose links are rendered.
+struct Crate<'a> {
+    name: &'a str,
+    /// The directory of the crate relative to the workspace root, empty for the root crate.
+    path: &'a str,
+}
+
+impl FixLinks {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        let check = self.check.then(|| {
+            self.format
+                .or(ctx.config.format())
+                .unwrap_or(OutputFormat::Text)
+        });
+        let workspace = ctx.workspace_dir(self.path.as_deref())?;
+
+        let mut archived_links = 0;
+        for manifest in manifest_iter(ctx.changes.fs(), &workspace, &ctx.exclusions) {
+            let mut doc = ctx.changes.read_toml(&manifest)?;
+            let dir = crate_dir(&workspace, &manifest);
+
+            let mut modified = false;
+            if let Some(package) = doc.get_mut("package").and_then(Item::as_table_like_mut) {
+                let name = package
+                    .get("name")
+                    .and_then(Item::as_str)
+                    .unwrap_or_default()
+                    .to_string();
+                let krate = Crate {
+                    name: &name,
+                    path: &dir,
+                };
+                let (fixed, archived) = self.fix_fields(package, Some(&krate), &manifest);
+                modified |= fixed;
+                archived_links += archived;
+            }
+            // `[workspace.package]` has no crate to render the templates for, only the templates
+            // without placeholders are applied to it.
+            if let Some(package) = doc
+                .get_mut("workspace")
+                .and_then(|w| w.get_mut("package"))
+                .and_then(Item::as_table_like_mut)
+            {
+                let (fixed, archived) = self.fix_fields(package, None, &manifest);
+                modified |= fixed;
+                archived_links += archived;
+            }
+
+            if modified {
+                log::info!("Updated the links of {}.", manifest.display());
+                ctx.changes.write(&manifest, doc.to_string());
+            }
+        }
+
+        if let Some(format) = check {
+            ctx.changes.check(
+                format,
+                ctx.color,
+                "Package links are not up to date, run `diener fix-links`.",
+            )?;
+            if archived_links > 0 {
+                return Err(
+                    anyhow!("{} link(s) point to archived repositories.", archived_links)
+                        .failure(Failure::NotUpToDate),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Set the links in `package` to their templates rendered for `krate` and report the links
+    /// into archived repositories that are left.
+    ///
+    /// Without `krate`, only existing fields are set to templates without placeholders. Returns
+    /// whether any field was modified and the number of links into archived repositories.
+    fn fix_fields(
+        &self,
+        package: &mut dyn TableLike,
+        krate: Option<&Crate>,
+        manifest: &Path,
+    ) -> (bool, usize) {
+        let mut modified = false;
+        let mut archived = 0;
+        for (key, template) in [
+            ("repository", &self.repository),
+            ("homepage", &self.homepage),
+            ("documentation", &self.documentation),
+        ] {
+            if package
+                .get(key)
+                .and_then(|i| i.get("workspace"))
+                .and_then(Item::as_bool)
+                == Some(true)
+            {
+                log::debug!("Skipping `{}`, it is inherited.", key);
+                continue;
+            }
+
+            let link = template.as_deref().and_then(|template| match krate {
+                Some(krate) => Some(render(template, krate)),
+                None if !template.contains(['{', '}']) => Some(template.to_string()),
+                None => None,
+            });
+            match (package.get_mut(key).and_then(Item::as_value_mut), link) {
+                (Some(value), Some(link)) if value.as_str() != Some(&link) => {
+                    let decor = value.decor().clone();
+                    *value = Value::from(link);
+                    *value.decor_mut() = decor;
+                    modified = true;
+                }
+                (None, Some(link)) if krate.is_some() => {
+                    package.insert(key, Item::Value(Value::from(link)));
+                    modified = true;
+                }
+                (Some(value), _) => {
+                    if let Some(repo) = value.as_str().and_then(|v| self.archived_repository(v)) {
+                        log::warn!(
+                            "{}: `{}` points to the archived repository {}.",
+                            manifest.display(),
+                            key,
+                            repo
+                        );
+                        archived += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        (modified, archived)
+    }
+
+    /// Returns the archived repository `link` points into, if any.
+    fn archived_repository(&self, link: &str) -> Option<&str> {
+        let archived = if self.archived.is_empty() {
+            ARCHIVED_REPOSITORIES.to_vec()
+        } else {
+            self.archived.iter().map(String::as_str).collect()
+        };
+        let link = normalize(link);
+        archived.into_iter().find(|repo| {
+            let repo = normalize(repo);
+            link == repo
+                || link.starts_with(&format!("{repo}/"))
+                || link.starts_with(&format!("{repo}#"))
+        })
+    }
+}
+
+/// Returns the directory of the crate of `manifest` relative to `workspace`, as written to
+/// manifests.
+fn crate_dir(workspace: &Path, manifest: &Path) -> String {
+    let dir = manifest.parent().unwrap_or(manifest);
+    manifest_path(dir.strip_prefix(workspace).unwrap_or(dir))
+}
+
+/// Render the link `template` for `krate`.
+///
+/// A `/` in front of an empty `{path}` is dropped, so the root crate doesn't get a trailing `/`.
+fn render(template: &str, krate: &Crate) -> String {
+    let template = if krate.path.is_empty() {
+        template.replace("/{path}", "{path}")
+    } else {
+        template.to_string()
+    };
+    template
+        .replace("{name}", krate.name)
+        .replace("{path}", krate.path)
+}
+
+/// Returns `url` in lower case without `.git` and trailing `/`, for comparisons.
+fn normalize(url: &str) -> String {
+    url.trim_end_matches('/')
+        .trim_end_matches(".git")
+        .to_lowercase()
+}
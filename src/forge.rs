@@ -0,0 +1,136 @@
+use crate::{changes::StagedCaches, github::GitHub, gitlab::GitLab};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// The API a forge speaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ForgeKind {
+    /// GitHub Enterprise Server.
+    Github,
+    /// A self-hosted GitLab.
+    Gitlab,
+}
+
+/// A self-hosted forge, configured in `[[forges]]`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct ForgeConfig {
+    /// The host of the repository urls, e.g. `gitlab.parity.io`.
+    host: String,
+    kind: ForgeKind,
+    /// The url of the API, `https://<host>/api/v4` for GitLab and `https://<host>/api/v3` for
+    /// GitHub by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api: Option<String>,
+    /// The environment variable containing the token, `GITLAB_TOKEN` or `GITHUB_TOKEN` by
+    /// default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_env: Option<String>,
+}
+
+impl ForgeConfig {
+    /// The environment variable containing the token.
+    pub(crate) fn token_env(&self) -> &str {
+        self.token_env.as_deref().unwrap_or(match self.kind {
+            ForgeKind::Github => "GITHUB_TOKEN",
+            ForgeKind::Gitlab => "GITLAB_TOKEN",
+        })
+    }
+
+    fn forge(&self, caches: StagedCaches) -> Box<dyn Forge> {
+        match self.kind {
+            ForgeKind::Github => Box::new(GitHub::new(
+                self.api
+                    .clone()
+                    .unwrap_or_else(|| format!("https://{}/api/v3", self.host)),
+                self.token_env(),
+                caches,
+            )),
+            ForgeKind::Gitlab => Box::new(GitLab::new(
+                self.api
+                    .clone()
+                    .unwrap_or_else(|| format!("https://{}/api/v4", self.host)),
+                self.token_env(),
+                caches,
+            )),
+        }
+    }
+}
+
+/// The head of a pull request.
+pub(crate) struct PullRequest {
+    /// The branch of the pull request.
+    pub(crate) branch: String,
+    /// The commit the branch points to.
+    pub(crate) sha: String,
+    /// The url of the repository containing the branch, a fork for pull requests from forks.
+    ///
+    /// `None` if the repository was deleted.
+    pub(crate) repository: Option<String>,
+}
+
+/// A pull request to open with [`Forge::create_pull_request`].
+pub(crate) struct NewPullRequest<'a> {
+    /// The branch with the changes.
+    pub(crate) head: &'a str,
+    /// The branch the changes should be merged into.
+    pub(crate) base: &'a str,
+    pub(crate) title: &'a str,
+    pub(crate) body: &'a str,
+    pub(crate) labels: &'a [String],
+    /// The users, or `org/team`s where supported, whose review is requested.
+    pub(crate) reviewers: &'a [String],
+}
+
+/// A service hosting git repositories, like GitHub or GitLab.
+///
+/// Repositories are given by their path on the forge, e.g. `paritytech/polkadot-sdk`. Pull
+/// requests are merge requests on GitLab.
+pub(crate) trait Forge {
+    /// The name of the forge for messages, e.g. `GitHub`.
+    fn name(&self) -> &'static str;
+
+    /// Returns the commit `reference` of the repository `repo` points to.
+    fn commit_sha(&self, repo: &str, reference: &str) -> Result<String>;
+
+    /// Returns the pull request `number` of the repository `repo`.
+    fn pull_request(&self, repo: &str, number: u64) -> Result<PullRequest>;
+
+    /// Open the pull request `pr` in the repository `repo`, returning its number and url.
+    fn create_pull_request(&self, repo: &str, pr: &NewPullRequest) -> Result<(u64, String)>;
+}
+
+/// Returns the forge hosting the repository at `url` and the path of the repository there.
+///
+/// `github.com` and `gitlab.com` are always known, other hosts need to be configured in
+/// `forges`. The API responses are cached in `caches`. Returns `None` for repositories on
+/// unknown hosts.
+pub(crate) fn for_url(
+    url: &str,
+    forges: &[ForgeConfig],
+    caches: StagedCaches,
+) -> Option<(Box<dyn Forge>, String)> {
+    let url = git_url_parse::GitUrl::parse(url).ok()?;
+    let host = url.host?;
+    let repo = url
+        .path
+        .trim_matches('/')
+        .trim_end_matches(".git")
+        .to_string();
+    if repo.is_empty() {
+        return None;
+    }
+
+    let forge: Box<dyn Forge> = match forges.iter().find(|f| f.host == host) {
+        Some(config) => config.forge(caches),
+        None if host == "github.com" => Box::new(GitHub::from_env(caches)),
+        None if host == "gitlab.com" => Box::new(GitLab::new(
+            "https://gitlab.com/api/v4".into(),
+            "GITLAB_TOKEN",
+            caches,
+        )),
+        None => return None,
+    };
+    Some((forge, repo))
+}
@@ -0,0 +1,28 @@
+use anyhow::{bail, Error};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// The format of the output of reporting subcommands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum OutputFormat {
+    /// Human readable text.
+    Text,
+    /// JSON, for consumption by other tools.
+    Json,
+    /// GitHub Actions annotations, only supported by `--check`.
+    Github,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "github" => Ok(Self::Github),
+            _ => bail!("Unknown format `{s}`, expected `text`, `json` or `github`."),
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use crate::{
+    failure::{Classify, Failure},
+    network,
+    timings::{self, Phase},
+};
+use anyhow::{anyhow, bail, Context, Result};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The local branch [`Checkout::branch_history`] fetches the remote branch into.
+const BRANCH_HISTORY: &str = "diener-branch";
+
+/// A git reference as used in `Cargo.toml`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum GitRef {
+    Branch(String),
+    Tag(String),
+    Rev(String),
+    /// No reference given, the default branch of the repository.
+    DefaultBranch,
+}
+
+impl GitRef {
+    /// Parse the reference from the `branch`, `tag` and `rev` keys of a dependency.
+    pub(crate) fn from_dependency(dep: &dyn toml_edit::TableLike) -> Self {
+        let get = |k| dep.get(k).and_then(|v| v.as_str()).map(ToString::to_string);
+
+        if let Some(branch) = get("branch") {
+            Self::Branch(branch)
+        } else if let Some(tag) = get("tag") {
+            Self::Tag(tag)
+        } else if let Some(rev) = get("rev") {
+            Self::Rev(rev)
+        } else {
+            Self::DefaultBranch
+        }
+    }
+
+    /// The name of the reference, `HEAD` for the default branch.
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            Self::Branch(name) | Self::Tag(name) | Self::Rev(name) => name,
+            Self::DefaultBranch => "HEAD",
+        }
+    }
+
+    /// The reference in a form that `git fetch` understands.
+    fn refspec(&self) -> String {
+        match self {
+            Self::Branch(branch) => format!("refs/heads/{branch}"),
+            Self::Tag(tag) => format!("refs/tags/{tag}"),
+            Self::Rev(rev) => rev.clone(),
+            Self::DefaultBranch => "HEAD".into(),
+        }
+    }
+}
+
+impl std::fmt::Display for GitRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Branch(branch) => write!(f, "branch = {branch}"),
+            Self::Tag(tag) => write!(f, "tag = {tag}"),
+            Self::Rev(rev) => write!(f, "rev = {rev}"),
+            Self::DefaultBranch => write!(f, "default branch"),
+        }
+    }
+}
+
+/// Returns a short identifier of the repository at `url`, e.g. `paritytech/polkadot-sdk`.
+///
+/// Falls back to the url itself if it can not be parsed.
+pub(crate) fn repository_id(url: &str) -> String {
+    git_url_parse::GitUrl::parse(url)
+        .map(|u| u.fullname)
+        .unwrap_or_else(|_| url.trim_end_matches(".git").to_string())
+}
+
+/// Returns the commit the branch `branch` of the repository at `url` currently points to.
+pub(crate) fn branch_head(url: &str, branch: &str) -> Result<String> {
+    remote_ref(url, &format!("refs/heads/{branch}"))?
+        .ok_or_else(|| anyhow!("Branch `{}` does not exist in {}", branch, url))
+}
+
+/// Returns the commit the default branch of the repository at `url` currently points to.
+pub(crate) fn default_head(url: &str) -> Result<String> {
+    remote_ref(url, "HEAD")?.ok_or_else(|| anyhow!("{} has no default branch", url))
+}
+
+/// Returns the `revs` that are not an ancestor of the branch `branch` of the repository at
+/// `url`, e.g. commits of pull request branches that were never merged.
+pub(crate) fn revs_not_on_branch<'a>(
+    url: &str,
+    branch: &str,
+    revs: impl IntoIterator<Item = &'a str>,
+) -> Result<Vec<&'a str>> {
+    let history = Checkout::branch_history(url, branch)?;
+    Ok(revs
+        .into_iter()
+        .filter(|rev| !history.contains(rev))
+        .collect())
+}
+
+/// Returns the commit `reference` of the repository at `url` points to, `None` if it doesn't
+/// exist.
+fn remote_ref(url: &str, reference: &str) -> Result<Option<String>> {
+    let refs = timings::measure(Phase::Network, None, || {
+        run(&std::env::temp_dir(), &["ls-remote", url, reference])
+    })
+    .with_context(|| anyhow!("Failed to query {}", url))
+    .failure(Failure::Network)?;
+
+    Ok(refs.split_whitespace().next().map(ToString::to_string))
+}
+
+/// Run `git` with the given `args` in `dir` and return its stdout.
+pub(crate) fn run(dir: &Path, args: &[&str]) -> Result<String> {
+    log::debug!("Running `git {}` in {}", args.join(" "), dir.display());
+
+    let output = Command::new("git")
+        .envs(network::env())
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .with_context(|| "Failed to execute `git`, is it installed?")?;
+
+    if !output.status.success() {
+        bail!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Create the branch `branch` in the repository containing `paths` and commit all `paths` on it.
+///
+/// Only the given `paths` are staged, other modifications of the working tree are left alone.
+/// Returns the root of the repository, `None` if nothing was committed.
+pub(crate) fn commit_on_branch(
+    paths: &[PathBuf],
+    branch: &str,
+    message: &str,
+) -> Result<Option<PathBuf>> {
+    let Some(first) = paths.first() else {
+        log::info!("Nothing changed, not creating a commit.");
+        return Ok(None);
+    };
+    let dir = first
+        .ancestors()
+        .skip(1)
+        .find(|d| d.is_dir())
+        .ok_or_else(|| anyhow!("{} has no existing parent directory", first.display()))?;
+    let root = PathBuf::from(run(dir, &["rev-parse", "--show-toplevel"])?.trim());
+
+    let paths = paths
+        .iter()
+        .map(|p| p.canonicalize().unwrap_or_else(|_| dir.join(p)))
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+
+    run(&root, &["checkout", "-q", "-b", branch])?;
+    let mut add = vec!["add", "-A", "--"];
+    add.extend(paths.iter().map(String::as_str));
+    run(&root, &add)?;
+    run(&root, &["commit", "-q", "-m", message])?;
+
+    log::info!("Committed {} path(s) on branch `{}`.", paths.len(), branch);
+    Ok(Some(root))
+}
+
+/// A temporary checkout that is removed again on drop.
+pub(crate) struct Checkout {
+    dir: PathBuf,
+}
+
+impl Checkout {
+    /// Fetch `reference` of the repository at `url` into a new temporary directory.
+    ///
+    /// Only the `Cargo.toml` files are checked out and the history is not fetched, which
+    /// keeps this fast even for big repositories like the Polkadot SDK.
+    pub(crate) fn manifests_only(url: &str, reference: &GitRef) -> Result<Self> {
+        let checkout = Self::temp()?;
+
+        log::info!("Fetching `Cargo.toml` files of {} ({}).", url, reference);
+
+        let refspec = reference.refspec();
+        run(&checkout.dir, &["init", "-q"])?;
+        run(&checkout.dir, &["remote", "add", "origin", url])?;
+        run(
+            &checkout.dir,
+            &["sparse-checkout", "set", "--no-cone", "/**/Cargo.toml"],
+        )?;
+        timings::measure(Phase::Network, None, || {
+            run(
+                &checkout.dir,
+                &[
+                    "fetch",
+                    "-q",
+                    "--depth",
+                    "1",
+                    "--filter=blob:none",
+                    "origin",
+                    &refspec,
+                ],
+            )
+        })
+        .with_context(|| anyhow!("Failed to fetch {} ({})", url, reference))
+        .failure(Failure::Network)?;
+        run(&checkout.dir, &["checkout", "-q", "FETCH_HEAD"])?;
+
+        Ok(checkout)
+    }
+
+    /// Fetch the history of `rev` of the repository at `url` into a new temporary directory.
+    ///
+    /// Only the commits are fetched, without any trees or files.
+    pub(crate) fn history(url: &str, rev: &str) -> Result<Self> {
+        let checkout = Self::temp()?;
+
+        log::info!("Fetching the history of {} ({}).", url, rev);
+
+        run(&checkout.dir, &["init", "-q", "--bare"])?;
+        run(&checkout.dir, &["remote", "add", "origin", url])?;
+        timings::measure(Phase::Network, None, || {
+            run(
+                &checkout.dir,
+                &["fetch", "-q", "--filter=tree:0", "origin", rev],
+            )
+            .or_else(|e| {
+                // Not every server allows fetching commits that are not the tip of a branch.
+                log::debug!("Failed to fetch {}, fetching all branches: {:?}", rev, e);
+                run(
+                    &checkout.dir,
+                    &[
+                        "fetch",
+                        "-q",
+                        "--filter=tree:0",
+                        "origin",
+                        "+refs/heads/*:refs/remotes/origin/*",
+                    ],
+                )
+            })
+        })
+        .with_context(|| anyhow!("Failed to fetch {} ({})", url, rev))
+        .failure(Failure::Network)?;
+
+        Ok(checkout)
+    }
+
+    /// Fetch the history of the branch `branch` of the repository at `url` into a new temporary
+    /// directory.
+    ///
+    /// Only the commits are fetched, without any trees or files.
+    pub(crate) fn branch_history(url: &str, branch: &str) -> Result<Self> {
+        let checkout = Self::temp()?;
+
+        log::info!("Fetching the history of {} (branch = {}).", url, branch);
+
+        run(&checkout.dir, &["init", "-q", "--bare"])?;
+        run(&checkout.dir, &["remote", "add", "origin", url])?;
+        timings::measure(Phase::Network, None, || {
+            run(
+                &checkout.dir,
+                &[
+                    "fetch",
+                    "-q",
+                    "--filter=tree:0",
+                    "origin",
+                    &format!("+refs/heads/{branch}:refs/heads/{BRANCH_HISTORY}"),
+                ],
+            )
+        })
+        .with_context(|| anyhow!("Failed to fetch branch `{}` of {}", branch, url))
+        .failure(Failure::Network)?;
+
+        Ok(checkout)
+    }
+
+    /// Returns `true` if `rev` is an ancestor of the branch fetched by
+    /// [`branch_history`](Self::branch_history).
+    ///
+    /// The whole history of the branch is fetched, a `rev` that doesn't exist in the checkout
+    /// is not on the branch.
+    fn contains(&self, rev: &str) -> bool {
+        run(&self.dir, &["cat-file", "-e", &format!("{rev}^{{commit}}")]).is_ok()
+            && run(
+                &self.dir,
+                &["merge-base", "--is-ancestor", rev, BRANCH_HISTORY],
+            )
+            .is_ok()
+    }
+
+    /// Returns the first-parent commits after `good` up to and including `bad`, oldest first.
+    ///
+    /// Fails if `good` is not an ancestor of `bad`.
+    pub(crate) fn commits_between(&self, good: &str, bad: &str) -> Result<Vec<String>> {
+        run(&self.dir, &["merge-base", "--is-ancestor", good, bad])
+            .with_context(|| anyhow!("`{}` is not an ancestor of `{}`", good, bad))?;
+
+        let commits = run(
+            &self.dir,
+            &[
+                "rev-list",
+                "--first-parent",
+                "--reverse",
+                &format!("{good}..{bad}"),
+            ],
+        )?;
+        Ok(commits.lines().map(ToString::to_string).collect())
+    }
+
+    /// Create a new empty temporary directory.
+    fn temp() -> Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = std::env::temp_dir().join(format!(
+            "diener-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir)
+            .with_context(|| anyhow!("Failed to create {}", dir.display()))?;
+        Ok(Self { dir })
+    }
+
+    /// The directory of the checkout.
+    pub(crate) fn path(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for Checkout {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.dir) {
+            log::warn!("Failed to remove {}: {}", self.dir.display(), e);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{
+    changes::StagedCaches,
+    forge::{Forge, NewPullRequest, PullRequest},
+    http::{self, Client},
+};
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+
+/// A minimal client of the GitHub REST API, see [`Client`].
+///
+/// Authenticates with `GITHUB_TOKEN` or `GH_TOKEN` if set.
+pub(crate) struct GitHub {
+    client: Client,
+}
+
+impl GitHub {
+    /// Create a new client of github.com, configured by the environment.
+    ///
+    /// The responses are cached in `caches`.
+    pub(crate) fn from_env(caches: StagedCaches) -> Self {
+        Self::with_token(
+            std::env::var("GITHUB_API_URL").unwrap_or_else(|_| "https://api.github.com".into()),
+            &["GITHUB_TOKEN", "GH_TOKEN"],
+            caches,
+        )
+    }
+
+    /// Create a new client of the GitHub Enterprise API at `api`, authenticated with the token in
+    /// the environment variable `token_env`.
+    pub(crate) fn new(api: String, token_env: &str, caches: StagedCaches) -> Self {
+        Self::with_token(api, &[token_env], caches)
+    }
+
+    fn with_token(api: String, token_env: &[&str], caches: StagedCaches) -> Self {
+        let headers = vec![
+            "Accept: application/vnd.github+json".into(),
+            "X-GitHub-Api-Version: 2022-11-28".into(),
+        ];
+        Self {
+            client: Client::new(
+                "GitHub",
+                api,
+                headers,
+                http::env_token(token_env),
+                http::describe_env(token_env),
+                caches,
+            ),
+        }
+    }
+}
+
+impl Forge for GitHub {
+    fn name(&self) -> &'static str {
+        "GitHub"
+    }
+
+    fn commit_sha(&self, repo: &str, reference: &str) -> Result<String> {
+        let commit = self
+            .client
+            .get(&format!("/repos/{repo}/commits/{reference}"))?;
+
+        commit
+            .get("sha")
+            .and_then(|s| s.as_str())
+            .map(ToString::to_string)
+            .ok_or_else(|| anyhow!("GitHub returned no commit for `{}` in {}", reference, repo))
+    }
+
+    fn pull_request(&self, repo: &str, number: u64) -> Result<PullRequest> {
+        let pr = self.client.get(&format!("/repos/{repo}/pulls/{number}"))?;
+        let head = &pr["head"];
+
+        let field = |value: &Value, name: &str| {
+            value
+                .as_str()
+                .map(ToString::to_string)
+                .ok_or_else(|| anyhow!("GitHub returned no `{}` for #{} in {}", name, number, repo))
+        };
+        Ok(PullRequest {
+            branch: field(&head["ref"], "head.ref")?,
+            sha: field(&head["sha"], "head.sha")?,
+            repository: head["repo"]["clone_url"]
+                .as_str()
+                .map(|url| url.trim_end_matches(".git").to_string()),
+        })
+    }
+
+    fn create_pull_request(&self, repo: &str, pr: &NewPullRequest) -> Result<(u64, String)> {
+        let created = self.client.post(
+            &format!("/repos/{repo}/pulls"),
+            &json!({ "title": pr.title, "head": pr.head, "base": pr.base, "body": pr.body }),
+        )?;
+        let number = created["number"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("GitHub returned no number for the pull request in {}", repo))?;
+
+        if !pr.labels.is_empty() {
+            self.client.post(
+                &format!("/repos/{repo}/issues/{number}/labels"),
+                &json!({ "labels": pr.labels }),
+            )?;
+        }
+        if !pr.reviewers.is_empty() {
+            // Reviewers like `org/team` are teams, all others users.
+            let (teams, users): (Vec<_>, Vec<_>) =
+                pr.reviewers.iter().partition(|r| r.contains('/'));
+            let teams = teams
+                .iter()
+                .filter_map(|t| t.split_once('/').map(|(_, team)| team))
+                .collect::<Vec<_>>();
+            self.client.post(
+                &format!("/repos/{repo}/pulls/{number}/requested_reviewers"),
+                &json!({ "reviewers": users, "team_reviewers": teams }),
+            )?;
+        }
+
+        let url = created["html_url"].as_str().unwrap_or_default().to_string();
+        Ok((number, url))
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{
+    changes::StagedCaches,
+    forge::{Forge, NewPullRequest, PullRequest},
+    http::{self, Client},
+};
+use anyhow::{anyhow, bail, Result};
+use serde_json::{json, Value};
+
+/// A minimal client of the GitLab REST API, of gitlab.com or a self-hosted instance, see
+/// [`Client`].
+pub(crate) struct GitLab {
+    client: Client,
+}
+
+impl GitLab {
+    /// Create a new client of the API at `api`, e.g. `https://gitlab.com/api/v4`, authenticated
+    /// with the token in the environment variable `token_env`. The responses are cached in
+    /// `caches`.
+    pub(crate) fn new(api: String, token_env: &str, caches: StagedCaches) -> Self {
+        Self {
+            client: Client::new(
+                "GitLab",
+                api,
+                Vec::new(),
+                http::env_token(&[token_env]),
+                http::describe_env(&[token_env]),
+                caches,
+            ),
+        }
+    }
+
+    /// Returns the id of the user `username`.
+    fn user_id(&self, username: &str) -> Result<u64> {
+        let users = self
+            .client
+            .get(&format!("/users?username={}", encode(username)))?;
+        users[0]["id"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("GitLab has no user `{}`", username))
+    }
+}
+
+impl Forge for GitLab {
+    fn name(&self) -> &'static str {
+        "GitLab"
+    }
+
+    fn commit_sha(&self, repo: &str, reference: &str) -> Result<String> {
+        let commit = self.client.get(&format!(
+            "/projects/{}/repository/commits/{}",
+            encode(repo),
+            encode(reference)
+        ))?;
+
+        commit
+            .get("id")
+            .and_then(|s| s.as_str())
+            .map(ToString::to_string)
+            .ok_or_else(|| anyhow!("GitLab returned no commit for `{}` in {}", reference, repo))
+    }
+
+    fn pull_request(&self, repo: &str, number: u64) -> Result<PullRequest> {
+        let mr = self.client.get(&format!(
+            "/projects/{}/merge_requests/{number}",
+            encode(repo)
+        ))?;
+
+        let field = |value: &Value, name: &str| {
+            value
+                .as_str()
+                .map(ToString::to_string)
+                .ok_or_else(|| anyhow!("GitLab returned no `{}` for !{} in {}", name, number, repo))
+        };
+        // The source project is `null` if it was deleted.
+        let repository = match mr["source_project_id"].as_u64() {
+            Some(id) => self.client.get(&format!("/projects/{id}"))?["http_url_to_repo"]
+                .as_str()
+                .map(|url| url.trim_end_matches(".git").to_string()),
+            None => None,
+        };
+        Ok(PullRequest {
+            branch: field(&mr["source_branch"], "source_branch")?,
+            sha: field(&mr["sha"], "sha")?,
+            repository,
+        })
+    }
+
+    fn create_pull_request(&self, repo: &str, pr: &NewPullRequest) -> Result<(u64, String)> {
+        let reviewers = pr
+            .reviewers
+            .iter()
+            .map(|reviewer| {
+                if reviewer.contains('/') {
+                    bail!("GitLab doesn't support groups as reviewers, `{reviewer}` is one.");
+                }
+                self.user_id(reviewer)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let created = self.client.post(
+            &format!("/projects/{}/merge_requests", encode(repo)),
+            &json!({
+                "source_branch": pr.head,
+                "target_branch": pr.base,
+                "title": pr.title,
+                "description": pr.body,
+                "labels": pr.labels.join(","),
+                "reviewer_ids": reviewers,
+            }),
+        )?;
+
+        let number = created["iid"].as_u64().ok_or_else(|| {
+            anyhow!(
+                "GitLab returned no number for the merge request in {}",
+                repo
+            )
+        })?;
+        let url = created["web_url"].as_str().unwrap_or_default().to_string();
+        Ok((number, url))
+    }
+}
+
+/// Percent-encode `s` for a path segment or query value, e.g. the project `group/project`.
+fn encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
@@ -0,0 +1,80 @@
+use anyhow::{anyhow, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+/// Commands run after diener wrote the modified files, configured in `[hooks]`.
+///
+/// The commands are run by the shell in the working directory, in the order they are given.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct Hooks {
+    /// Run once for every modified file, which is given in `DIENER_FILE`.
+    after_file: Vec<String>,
+    /// Run once after all files are written.
+    ///
+    /// The modified files are given one per line in `DIENER_CHANGED_FILES` and on stdin.
+    after_run: Vec<String>,
+}
+
+impl Hooks {
+    /// Run the hooks for the modified `files`, does nothing if no file was modified.
+    pub(crate) fn run(&self, files: &[PathBuf]) -> Result<()> {
+        if files.is_empty() {
+            return Ok(());
+        }
+
+        for file in files {
+            for hook in &self.after_file {
+                run(hook, &[("DIENER_FILE", file.display().to_string())], "")?;
+            }
+        }
+
+        let list = files
+            .iter()
+            .map(|f| format!("{}\n", f.display()))
+            .collect::<String>();
+        for hook in &self.after_run {
+            run(hook, &[("DIENER_CHANGED_FILES", list.clone())], &list)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Run `hook` with the shell, passing `env` and writing `stdin` to it.
+fn run(hook: &str, env: &[(&str, String)], stdin: &str) -> Result<()> {
+    log::info!("Running hook `{}`.", hook);
+
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C");
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c");
+        command
+    };
+    let mut child = command
+        .arg(hook)
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| anyhow!("Failed to execute hook `{}`", hook))?;
+
+    // Hooks that don't read stdin may exit before everything is written.
+    let _ = child
+        .stdin
+        .take()
+        .expect("stdin is piped; qed")
+        .write_all(stdin.as_bytes());
+    let status = child
+        .wait()
+        .with_context(|| anyhow!("Failed to wait for hook `{}`", hook))?;
+    ensure!(status.success(), "Hook `{}` failed with {}.", hook, status);
+
+    Ok(())
+}
@@ -0,0 +1,406 @@
+use crate::{
+    changes::StagedCaches,
+    failure::{Classify, Failure},
+    network,
+    timings::{self, Phase},
+};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// How often a request is retried when it is rate limited or the server fails.
+const MAX_RETRIES: u32 = 3;
+
+/// The longest time diener waits for a rate limit to reset.
+const MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// A cached response of an API.
+#[derive(Serialize, Deserialize)]
+struct CachedResponse {
+    etag: String,
+    body: String,
+}
+
+/// The response of a single request.
+struct Response {
+    status: u32,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Response {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns how long to wait before retrying, if the request was rate limited.
+    fn rate_limit_wait(&self) -> Option<Duration> {
+        if let Some(retry_after) = self.header("retry-after").and_then(|r| r.parse().ok()) {
+            return Some(Duration::from_secs(retry_after));
+        }
+
+        if self.header("x-ratelimit-remaining") == Some("0") {
+            let reset = self.header("x-ratelimit-reset")?.parse::<u64>().ok()?;
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+            return Some(Duration::from_secs(reset.saturating_sub(now) + 1));
+        }
+
+        None
+    }
+}
+
+/// A minimal client of a JSON REST API like the one of GitHub, the requests are run by `curl`.
+///
+/// Waits for the rate limit to reset if it is hit and caches the responses of `GET` requests on
+/// disk, revalidating them with their `ETag`. Revalidated responses don't count against the rate
+/// limit.
+pub(crate) struct Client {
+    /// The name of the API for messages, e.g. `GitHub`.
+    name: &'static str,
+    api: String,
+    /// The headers sent with every request, including the authentication if there is a token.
+    headers: Vec<String>,
+    authenticated: bool,
+    /// Where the token is read from, for messages, e.g. `GITHUB_TOKEN`.
+    token_source: String,
+    cache_dir: Option<PathBuf>,
+    /// Where the responses are cached, they are only written if the changes are applied.
+    caches: StagedCaches,
+}
+
+impl Client {
+    /// Create a client of the API at `api`, authenticated with `token` as bearer token.
+    ///
+    /// `token_source` tells where the token comes from, for messages if it is missing. The
+    /// responses are cached in `caches`.
+    pub(crate) fn new(
+        name: &'static str,
+        api: String,
+        mut headers: Vec<String>,
+        token: Option<String>,
+        token_source: String,
+        caches: StagedCaches,
+    ) -> Self {
+        if token.is_none() {
+            log::debug!(
+                "No {} set, using the {} API anonymously.",
+                token_source,
+                name
+            );
+        }
+        let authenticated = token.is_some();
+        headers.extend(token.map(|token| format!("Authorization: Bearer {token}")));
+
+        let cache_dir = cache_dir().map(|dir| dir.join(name.to_lowercase()));
+
+        Self {
+            name,
+            api,
+            headers,
+            authenticated,
+            token_source,
+            cache_dir,
+            caches,
+        }
+    }
+
+    /// `GET` the given API `path` and return the parsed JSON response.
+    pub(crate) fn get(&self, path: &str) -> Result<serde_json::Value> {
+        self.fetch(path).failure(Failure::Network)
+    }
+
+    /// `POST` `body` to the given API `path` and return the parsed JSON response.
+    ///
+    /// Unlike [`Client::get`] the request is never retried, to not create anything twice.
+    pub(crate) fn post(&self, path: &str, body: &serde_json::Value) -> Result<serde_json::Value> {
+        self.send(path, body).failure(Failure::Network)
+    }
+
+    /// Request `path`, see [`Client::get`].
+    fn fetch(&self, path: &str) -> Result<serde_json::Value> {
+        let url = format!("{}{}", self.api, path);
+        let cache_file = self.cache_dir.as_ref().map(|dir| {
+            let mut hasher = DefaultHasher::new();
+            url.hash(&mut hasher);
+            dir.join(format!("{:016x}.json", hasher.finish()))
+        });
+        let cached = cache_file
+            .as_ref()
+            .and_then(|f| fs::read(f).ok())
+            .and_then(|c| serde_json::from_slice::<CachedResponse>(&c).ok());
+
+        let mut retries = 0;
+        let response = loop {
+            let etag = cached.as_ref().map(|c| c.etag.as_str());
+            let response = timings::measure(Phase::Network, None, || {
+                self.request("GET", &url, etag, None)
+            })?;
+
+            let wait = match response.status {
+                403 | 429 => response.rate_limit_wait(),
+                500..=599 => Some(Duration::from_secs(1 << retries)),
+                _ => None,
+            };
+            match wait {
+                Some(wait) if retries < MAX_RETRIES && wait <= MAX_WAIT => {
+                    log::warn!(
+                        "{} request to {} failed with {}, retrying in {}s.",
+                        self.name,
+                        url,
+                        response.status,
+                        wait.as_secs()
+                    );
+                    thread::sleep(wait);
+                    retries += 1;
+                }
+                _ if matches!(response.status, 403 | 429)
+                    && response.rate_limit_wait().is_some() =>
+                {
+                    bail!(
+                        "{} rate limit exceeded for {}, set {} to raise it.",
+                        self.name,
+                        url,
+                        self.token_source
+                    );
+                }
+                _ => break response,
+            }
+        };
+
+        let body = match (response.status, cached) {
+            (304, Some(cached)) => {
+                log::debug!("Using cached {} response for {}", self.name, url);
+                cached.body
+            }
+            (200..=299, _) => {
+                if let (Some(file), Some(etag)) = (&cache_file, response.header("etag")) {
+                    let cached = CachedResponse {
+                        etag: etag.into(),
+                        body: response.body.clone(),
+                    };
+                    match serde_json::to_string(&cached) {
+                        Ok(content) => self.caches.write(file, content),
+                        Err(e) => log::debug!(
+                            "Failed to cache {} response at {}: {:?}",
+                            self.name,
+                            file.display(),
+                            e
+                        ),
+                    }
+                }
+                response.body
+            }
+            (status, _) => bail!(
+                "{} request to {} failed with {}: {}",
+                self.name,
+                url,
+                status,
+                response.body.trim()
+            ),
+        };
+
+        serde_json::from_str(&body).with_context(|| anyhow!("Invalid response from {}", url))
+    }
+
+    /// Send `body` to `path`, see [`Client::post`].
+    fn send(&self, path: &str, body: &serde_json::Value) -> Result<serde_json::Value> {
+        let url = format!("{}{}", self.api, path);
+        if !self.authenticated {
+            bail!("Set {} to send requests to {}.", self.token_source, url);
+        }
+
+        let body = body.to_string();
+        let response = timings::measure(Phase::Network, None, || {
+            self.request("POST", &url, None, Some(&body))
+        })?;
+        if !(200..=299).contains(&response.status) {
+            bail!(
+                "{} request to {} failed with {}: {}",
+                self.name,
+                url,
+                response.status,
+                response.body.trim()
+            );
+        }
+
+        serde_json::from_str(&response.body)
+            .with_context(|| anyhow!("Invalid response from {}", url))
+    }
+
+    /// Run a single request with `curl`, sending `body` as JSON if given.
+    fn request(
+        &self,
+        method: &str,
+        url: &str,
+        etag: Option<&str>,
+        body: Option<&str>,
+    ) -> Result<Response> {
+        log::debug!("{} {}", method, url);
+
+        // The headers and the body are passed as config on stdin, the command line of `curl` is
+        // visible to all users and the headers contain the token.
+        let mut config = String::new();
+        let headers = self
+            .headers
+            .iter()
+            .cloned()
+            .chain(etag.map(|etag| format!("If-None-Match: {etag}")))
+            .chain(body.map(|_| "Content-Type: application/json".to_string()));
+        for header in headers {
+            config.push_str(&curl_config("header", &header));
+        }
+        if let Some(body) = body {
+            config.push_str(&curl_config("data-binary", body));
+        }
+
+        let mut command = Command::new("curl");
+        command.envs(network::env()).args([
+            "-sS",
+            "-L",
+            "-i",
+            "-X",
+            method,
+            "-A",
+            concat!("diener/", env!("CARGO_PKG_VERSION")),
+            "-K",
+            "-",
+        ]);
+
+        let mut child = command
+            .arg(url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| "Failed to execute `curl`, is it installed?")?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped; qed")
+            .write_all(config.as_bytes())
+            .with_context(|| "Failed to write the request to `curl`")?;
+        let output = child
+            .wait_with_output()
+            .with_context(|| "Failed to wait for `curl`")?;
+        if !output.status.success() {
+            bail!(
+                "`curl {}` failed: {}",
+                url,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        parse_response(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// Returns the directory the API responses are cached in, `~/.cache/diener`.
+///
+/// Every API has its own directory below it.
+pub(crate) fn cache_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))
+        .map(|dir| dir.join("diener"))
+}
+
+/// Returns the token in the first of the environment variables `vars` that is set.
+pub(crate) fn env_token(vars: &[&str]) -> Option<String> {
+    vars.iter()
+        .find_map(|v| std::env::var(v).ok())
+        .filter(|t| !t.is_empty())
+}
+
+/// Describe the environment variables `vars` for messages, e.g. `` `GITHUB_TOKEN` or `GH_TOKEN` ``.
+pub(crate) fn describe_env(vars: &[&str]) -> String {
+    vars.iter()
+        .map(|v| format!("`{v}`"))
+        .collect::<Vec<_>>()
+        .join(" or ")
+}
+
+/// Returns the line of a `curl` config file that sets `option` to `value`.
+///
+/// The value is always quoted, a `data-binary` value starting with `@` would still be read from
+/// the file of that name.
+fn curl_config(option: &str, value: &str) -> String {
+    let mut line = format!("{option} = \"");
+    for c in value.chars() {
+        match c {
+            '\\' => line.push_str("\\\\"),
+            '"' => line.push_str("\\\""),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\t' => line.push_str("\\t"),
+            c => line.push(c),
+        }
+    }
+    line.push_str("\"\n");
+    line
+}
+
+/// Parse the output of `curl -i`.
+///
+/// With `-L` the output contains the headers of every redirect and large bodies may be preceded by
+/// a `100 Continue`, only the last response is relevant.
+fn parse_response(output: &str) -> Result<Response> {
+    let mut rest = output;
+    loop {
+        let (head, body) = rest
+            .split_once("\r\n\r\n")
+            .ok_or_else(|| anyhow!("Invalid HTTP response"))?;
+
+        let mut lines = head.lines();
+        let status = lines
+            .next()
+            .and_then(|l| l.split_whitespace().nth(1))
+            .and_then(|s| s.parse::<u32>().ok())
+            .ok_or_else(|| anyhow!("Invalid HTTP status line"))?;
+
+        let interim = (100..200).contains(&status) || (300..400).contains(&status) && status != 304;
+        if interim && body.starts_with("HTTP/") {
+            rest = body;
+            continue;
+        }
+
+        let headers = lines
+            .filter_map(|l| l.split_once(':'))
+            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+            .collect();
+
+        return Ok(Response {
+            status,
+            headers,
+            body: body.into(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curl_config_quotes_values() {
+        assert_eq!(
+            curl_config("header", "Authorization: Bearer ghp_abc"),
+            "header = \"Authorization: Bearer ghp_abc\"\n"
+        );
+        assert_eq!(
+            curl_config("data-binary", "{\"body\":\"a\\\\b\nc\"}"),
+            "data-binary = \"{\\\"body\\\":\\\"a\\\\\\\\b\\nc\\\"}\"\n"
+        );
+    }
+}
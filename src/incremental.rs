@@ -0,0 +1,61 @@
+use crate::{changes::StagedCaches, filesystem::Fs, plan::sha256};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// The directory, relative to the working directory, the hashes are persisted to.
+pub(crate) const CACHE_DIR: &str = ".diener/cache";
+
+/// The hashes of the files that were up to date after they were processed, e.g. for
+/// `update --incremental`.
+///
+/// The hashes are stored per set of operation parameters. A file whose content still has the
+/// recorded hash was left in exactly this state by a run with the same parameters, so processing
+/// it again would not change it.
+pub(crate) struct IncrementalCache {
+    file: PathBuf,
+    hashes: BTreeMap<PathBuf, String>,
+}
+
+impl IncrementalCache {
+    /// Load the hashes recorded below `dir` for the operation `name` with `params`.
+    ///
+    /// `params` must contain everything that changes the result of processing a file.
+    pub(crate) fn load(fs: &dyn Fs, dir: &Path, name: &str, params: &str) -> Self {
+        let key = &sha256(&format!("{name}\n{}\n{params}", env!("CARGO_PKG_VERSION")))[..16];
+        let file = dir.join(CACHE_DIR).join(format!("{name}-{key}.json"));
+        let hashes = fs
+            .read(&file)
+            .ok()
+            .and_then(|c| serde_json::from_str(&c).ok())
+            .unwrap_or_default();
+
+        Self { file, hashes }
+    }
+
+    /// Returns `true` if `content` of the file at `path` is the one recorded for it.
+    pub(crate) fn is_current(&self, path: &Path, content: &str) -> bool {
+        self.hashes
+            .get(path)
+            .is_some_and(|hash| *hash == sha256(content))
+    }
+
+    /// Record `content` as the processed content of the file at `path`.
+    pub(crate) fn record(&mut self, path: &Path, content: &str) {
+        self.hashes.insert(path.to_path_buf(), sha256(content));
+    }
+
+    /// Persist the recorded hashes in `caches`, so they are only written if the changes are
+    /// applied. Failing to do so only logs a warning.
+    pub(crate) fn store(&self, caches: &StagedCaches) {
+        match serde_json::to_string(&self.hashes) {
+            Ok(hashes) => caches.write(&self.file, hashes),
+            Err(e) => log::warn!(
+                "Failed to store the hashes at {}: {:?}",
+                self.file.display(),
+                e
+            ),
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{
+    config::{Config, CONFIG_FILE, DEFAULT_REPOSITORY},
+    context::RunContext,
+    failure::{Classify, Failure},
+    git::GitRef,
+    manifest::dependency_tables,
+    walk,
+    workspacify::manifest_iter,
+};
+use anyhow::{anyhow, ensure, Context, Result};
+use git_url_parse::GitUrl;
+use std::{
+    collections::BTreeMap,
+    io::{self, IsTerminal},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+use toml_edit::{value, Array, Document};
+
+/// The repositories the Polkadot SDK is developed in, including the ones merged into it.
+const SDK_REPOSITORIES: &[&str] = &["polkadot-sdk", "substrate", "polkadot", "cumulus"];
+
+/// `init` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct Init {
+    /// The directory that is inspected and gets the `.diener.toml`.
+    ///
+    /// Uses the working directory if none is supplied.
+    #[structopt(long)]
+    path: Option<PathBuf>,
+
+    /// Don't ask any questions, write the values detected in the tree.
+    #[structopt(long)]
+    defaults: bool,
+
+    /// Replace an existing `.diener.toml`.
+    #[structopt(long)]
+    force: bool,
+}
+
+/// The git dependencies on one repository found in the tree.
+#[derive(Default)]
+struct Repository {
+    dependencies: usize,
+    /// The references of the dependencies with the number of dependencies using them.
+    references: BTreeMap<GitRef, usize>,
+}
+
+impl Init {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        let dir = ctx.workspace_dir(self.path.as_deref())?;
+        let path = dir.join(CONFIG_FILE);
+        if ctx.changes.exists(&path) && !self.force {
+            return Err(anyhow!(
+                "{} already exists, pass `--force` to replace it.",
+                path.display()
+            )
+            .failure(Failure::Usage));
+        }
+        if !self.defaults && !io::stdin().is_terminal() {
+            return Err(anyhow!(
+                "`init` asks questions on the terminal, pass `--defaults` to write the detected \
+                 values without asking."
+            )
+            .failure(Failure::Usage));
+        }
+
+        let manifests = manifest_iter(ctx.changes.fs(), &dir, &ctx.exclusions).collect::<Vec<_>>();
+        let roots = walk::workspace_roots(ctx.changes.fs(), &dir, &ctx.exclusions);
+        let mut repositories = BTreeMap::<String, Repository>::new();
+        for manifest in &manifests {
+            let doc = ctx.changes.read_toml(manifest)?;
+            for (_, deps) in dependency_tables(&doc) {
+                for (_, dep) in deps.iter() {
+                    let Some(dep) = dep.as_table_like() else {
+                        continue;
+                    };
+                    let Some(name) = dep
+                        .get("git")
+                        .and_then(|g| g.as_str())
+                        .and_then(|g| GitUrl::parse(g).ok())
+                        .map(|g| g.name)
+                    else {
+                        continue;
+                    };
+                    let repository = repositories.entry(name).or_default();
+                    repository.dependencies += 1;
+                    *repository
+                        .references
+                        .entry(GitRef::from_dependency(dep))
+                        .or_default() += 1;
+                }
+            }
+        }
+
+        eprintln!(
+            "Found {} manifest(s) and {} workspace root(s) below {}.",
+            manifests.len(),
+            roots.len(),
+            dir.display()
+        );
+        for (name, repository) in &repositories {
+            eprintln!(
+                "  {} git dependencies on `{}`, using {}.",
+                repository.dependencies,
+                name,
+                repository
+                    .references
+                    .keys()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        // The SDK repositories the tree depends on, the most used one first.
+        let mut detected = repositories
+            .iter()
+            .filter(|(name, _)| SDK_REPOSITORIES.contains(&name.as_str()))
+            .collect::<Vec<_>>();
+        detected.sort_by_key(|(_, repository)| std::cmp::Reverse(repository.dependencies));
+        let match_repos = if detected.is_empty() {
+            DEFAULT_REPOSITORY.to_string()
+        } else {
+            detected
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        // The most used branch, if the dependencies mostly follow one.
+        let branch = detected
+            .first()
+            .and_then(|(_, repository)| {
+                repository
+                    .references
+                    .iter()
+                    .max_by_key(|(_, count)| **count)
+            })
+            .and_then(|(reference, _)| match reference {
+                GitRef::Branch(branch) => Some(branch.clone()),
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        let answer = |question: &str, default: String| {
+            if self.defaults {
+                Ok(default)
+            } else {
+                ask(question, default)
+            }
+        };
+        let match_repos = list(&answer(
+            "Repositories whose git dependencies diener manages, separated by `,`",
+            match_repos,
+        )?);
+        ensure!(
+            !match_repos.is_empty(),
+            "At least one repository needs to be managed."
+        );
+        let git = answer(
+            "The `git` url `update` rewrites the dependencies to, `-` keeps their urls",
+            String::new(),
+        )?;
+        let branch = answer(
+            "The branch `update` uses if no version is given, `-` for none",
+            branch,
+        )?;
+        let exclude = list(&answer(
+            &format!(
+                "Directories diener skips, relative to {} and separated by `,`",
+                dir.display()
+            ),
+            String::new(),
+        )?);
+        for excluded in &exclude {
+            ensure!(
+                ctx.changes.fs().is_dir(&dir.join(excluded)),
+                "Directory `{}` doesn't exist in {}.",
+                excluded,
+                dir.display()
+            );
+        }
+
+        let mut doc = Document::new();
+        doc["match-repos"] = value(match_repos.iter().collect::<Array>());
+        if !git.is_empty() && git != "-" {
+            doc["git"] = value(git);
+        }
+        if !branch.is_empty() && branch != "-" {
+            doc["branch"] = value(branch);
+        }
+        if !exclude.is_empty() {
+            doc["exclude"] = value(exclude.iter().collect::<Array>());
+        }
+        let content = format!(
+            "# Generated by `diener init`, `diener schema config` describes all values.\n{doc}"
+        );
+        toml_edit::de::from_str::<Config>(&content)
+            .with_context(|| "The generated configuration is invalid")?;
+
+        log::info!("Writing the configuration to {}.", path.display());
+        ctx.changes.write(&path, content);
+
+        Ok(())
+    }
+}
+
+/// Ask `question` on the terminal, an empty answer takes `default`.
+fn ask(question: &str, default: String) -> Result<String> {
+    if default.is_empty() {
+        eprint!("{question}: ");
+    } else {
+        eprint!("{question} [{default}]: ");
+    }
+
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .context("Failed to read the answer.")?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() {
+        default
+    } else {
+        answer.into()
+    })
+}
+
+/// Split the `,` separated `answer` into its entries.
+fn list(answer: &str) -> Vec<String> {
+    answer
+        .split(',')
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .map(Into::into)
+        .collect()
+}
@@ -0,0 +1,995 @@
+/*!
+
+diener - dependency diener is a tool for easily changing [Polkadot SDK](https://github.com/paritytech/polkadot) dependency versions
+
+[![](https://docs.rs/diener/badge.svg)](https://docs.rs/diener/) [![](https://img.shields.io/crates/v/diener.svg)](https://crates.io/crates/diener) [![](https://img.shields.io/crates/d/diener.png)](https://crates.io/crates/diener)
+
+* [Usage](#usage)
+* [License](#license)
+
+## Usage
+
+### Update
+
+The `update` subcommand changes all `Cargo.toml` files in a given folder to use
+a specific branch/path/commit/tag.
+
+Change all Polkadot SDK dependencies in a folder to a different branch:
+
+```sh
+diener update --branch diener-branch
+```
+
+Diener also supports `tag` and `rev` as arguments.
+
+Related repositories that must move together can be updated in one run, each
+to its own version:
+
+```sh
+diener update --repo polkadot-sdk=branch:stable2407 --repo frontier=rev:abc123
+```
+
+`--path` can be passed multiple times and accepts glob patterns, e.g.
+`--path 'runtime-*'`.
+Directories and manifests matching `--exclude-path`, e.g.
+`--exclude-path 'tests/fixtures'`, are never rewritten.
+
+With `--dev-dependencies-only` only the `[dev-dependencies]` are rewritten, e.g.
+to point test-only crates to a branch with extra test helpers.
+
+With `--to-workspace-dep` the updated dependencies are moved into
+`[workspace.dependencies]` of their workspace and the members inherit them with
+`workspace = true`, so the branch lives in exactly one place going forward.
+
+`--report-skipped` lists the dependencies that look like they belong to the
+Polkadot SDK, but were not updated, and why, e.g. because they are `path`
+dependencies.
+
+`--ensure-consistent` fails before changing anything if the dependencies of a
+repository already point to different branches, tags or revs, e.g. after a half
+finished previous update. `--ensure-consistent=warn` only prints the conflicts.
+
+`--verify-rev-on-branch <BRANCH>` fails if a rev written by `--rev` or by a
+`--repo` with `rev:<rev>` is not an ancestor of the given branch of its
+repository. This guards against pinning commits of pull request branches that
+never get merged:
+
+```sh
+diener update --rev 4a5b6c7 --verify-rev-on-branch master
+```
+
+`--normalize` additionally rewrites every updated dependency into a canonical
+key order and spacing, so repeated runs always produce identical manifests.
+
+Manifests that don't contain the name of any updated repository are skipped
+without parsing them, which speeds up big monorepos. `--no-prefilter` parses
+every manifest anyway.
+
+When diener is run many times on an almost unchanged tree, `--incremental`
+skips the manifests that didn't change since a previous run with the same
+options. The hashes of the processed manifests are stored under `.diener/cache`
+in the working directory, but only if the changes are applied, not with
+`--dry-run`, `--check` or `--output-dir`.
+
+Even multi-gigabyte trees don't need to fit into memory: `update` finds the
+manifests while it processes them, buffers at most `--jobs` of them and drops
+every manifest once it is processed. Only the modified manifests are kept until
+they are written. Of the manifests that are read but not modified yet, at most
+`--max-buffered` (256 by default) are kept in memory, so modifying them later
+doesn't read them again.
+
+By default the dependencies on repositories named `polkadot-sdk` are updated.
+Forks under other organizations or self-hosted mirrors can be matched by their
+`git` url instead:
+
+```sh
+diener update --branch diener-branch --match-url-regex 'gitlab\.example\.com/[^/]+/polkadot-sdk'
+```
+
+In CI the flags can also be given as environment variables: `DIENER_BRANCH`,
+`DIENER_GIT`, `DIENER_PATH` and `DIENER_MATCH_REPO` (comma separated). Flags
+passed on the command line take precedence.
+
+Instead of searching a whole folder, the manifests and folders to update can be
+read from a file or stdin:
+
+```sh
+git diff --name-only | diener update --branch diener-branch --paths-from -
+```
+
+Follow-up runs in big repositories, e.g. after a rebase, can be limited to the
+manifests that changed since a git ref with `--changed-since`. This includes the
+uncommitted and untracked manifests, deleted ones are ignored:
+
+```sh
+diener update --branch diener-branch --changed-since origin/master
+```
+
+### Patch
+
+The `patch` subcommand adds a patch section for each crate in a given cargo workspace
+to the workspace `Cargo.toml` file in some other cargo workspace.
+
+Patch all git dependencies to be build from a given path:
+
+```sh
+diener patch --crates-to-patch ../path/to/polkadot-sdk/checkout
+```
+
+This subcommand can be compared to `.cargo/config` without using a deprecated
+feature of Cargo ;)
+
+When re-running `patch` after crates were removed or renamed in the checkout,
+`--prune` removes the patches of crates that don't exist there anymore.
+
+To only patch some of the crates, pass a regex with `--filter-regex`:
+
+```sh
+diener patch --crates-to-patch ../path/to/polkadot-sdk/checkout --filter-regex '^(sp|frame)-'
+```
+
+`--prune` still considers all crates of the checkout, so patches of crates that
+are filtered out are kept.
+
+To review what a re-run against an already patched workspace would change, pass
+`--diff`. It only prints the patches that would be added, changed or removed,
+without modifying anything.
+
+Patches that point to a branch with `--point-to-git-branch` follow the branch as
+it moves, which makes bisecting build breakage painful. With `--pin` the head of
+the branch is resolved once and written as `rev`. The branch of every patch is
+recorded in `.diener/pins.json` next to the manifest, so it's known what to
+refresh from later. Patches that are re-pointed without `--pin` or pruned are
+removed from the file again.
+
+When a project uses some crates from crates.io and others from git, a single
+patch target doesn't fit. `--auto-target` derives the targets from the resolved
+dependencies of the project instead. Every crate is patched in
+`[patch.crates-io]` and/or `[patch."<git-url>"]`, depending on where the project
+takes it from. Crates taken from a fork are patched in a table for the fork,
+because a patch for the upstream repository would silently not apply to them.
+
+In a partially vendored setup the project already uses some of the crates via
+`path`. Patching them as well makes cargo fail with `points to the same source`.
+`--skip-path-deps` leaves these crates alone.
+
+The patches are added to the root `Cargo.toml` of the workspace at `--path`,
+which may be a virtual manifest. In layouts without a workspace, where every
+package is its own root, `--member <name>` adds them to the manifest of the given
+package instead. diener warns when the chosen manifest isn't the root of its
+workspace, because cargo ignores patches there.
+
+Cargo silently ignores a patch whose version doesn't match the version
+requirement of the dependency. `--warn-version-mismatch` compares the version of
+every patched crate against the requirements of the project and warns about the
+patches that can never apply.
+
+`cargo metadata` can take a long time on big workspaces. With `--cache-metadata`
+its result is stored under `.diener/cache` in the workspace and reused until a
+manifest or the `Cargo.lock` changes.
+
+By default `cargo metadata` resolves the dependencies of all platforms, so a
+crate only used by the native build still counts as used for a runtime that is
+only built for wasm. `--filter-platform <triple>` is passed to `cargo metadata`
+to only consider the dependencies of the given target:
+
+```sh
+diener patch --crates-to-patch ../polkadot-sdk --auto-target --filter-platform wasm32-unknown-unknown
+```
+
+### Workspacify
+
+The `workspacify` subcommand adds all crates found in a directory to the members
+of the workspace there and points all dependencies between them to their paths:
+
+```sh
+diener workspacify --path ../my-workspace
+```
+
+Rewriting every manifest creates big diffs. With `--add-missing-only` only the
+crates that are not members yet are added and only their manifests are
+rewritten, everything else is left untouched.
+
+`cargo publish` can't publish crates whose dev-dependencies with a `version`
+form a cycle with their other dependencies. `--break-dev-cycles` finds these
+cycles and removes the `version` of the dev-dependencies that close them, which
+makes cargo ignore them when publishing.
+
+`--path-style` controls how the paths of the dependencies are written, to follow
+the conventions of the repository: `relative` writes the shortest path like
+`../foo` (the default), `dot` starts paths that stay in the directory with `./`
+and `from-root` always goes up to the workspace root, e.g. `../../crates/foo`.
+
+Crates with the same name, e.g. copied example crates, make `workspacify` fail.
+`--dedupe-strategy` resolves them instead. The copy closest to the workspace root
+is kept. `suffix-dir` renames the other copies by appending the names of their
+parent directories and updates their dependents with `package = ...`. `skip`
+leaves the other copies out of the workspace. `interactive` asks which copy to
+keep and skips the others.
+
+With `--inherit-deps` every in-tree dependency is declared once in
+`[workspace.dependencies]` with its path and the members inherit it with
+`workspace = true`. Member specific keys like `features` and `optional` stay in
+the members.
+
+A monorepo may host several independent workspaces. `--all-workspaces` finds
+every workspace root below `--path` and workspacifies each of them separately,
+the crates below a nested root belong to the nested workspace. `update` accepts
+`--all-workspaces` as well, to report the modified manifests per workspace.
+Both print a combined report of all workspaces at the end.
+
+Other tools can reuse the crates diener found instead of walking the tree again.
+With `--export-packages` the name, manifest and version of every crate is
+written to `.diener/packages.json` in the workspace:
+
+```json
+{
+  "sp-core": { "manifest": "substrate/primitives/core/Cargo.toml", "version": "21.0.0" }
+}
+```
+
+### Set metadata
+
+The `set-metadata` subcommand sets package metadata fields uniformly across all
+crates in a given folder, keeping the formatting of the manifests:
+
+```sh
+diener set-metadata license=Apache-2.0 repository=https://github.com/org/repo edition=2021
+```
+
+Fields inherited with `key.workspace = true` are left untouched, fields that are
+already present in `[workspace.package]` are updated there instead. The values
+of `authors`, `keywords` and `categories` are split at `,`. With `--missing-only`
+only the missing fields are added and existing values are kept.
+
+### Fix links
+
+The `fix-links` subcommand rewrites the `repository`, `homepage` and
+`documentation` of all crates in a given folder from templates. `{name}` is
+replaced by the name of the crate and `{path}` by its directory relative to the
+workspace root:
+
+```sh
+diener fix-links --repository https://github.com/paritytech/polkadot-sdk.git \
+  --homepage 'https://github.com/paritytech/polkadot-sdk/tree/master/{path}' \
+  --documentation 'https://docs.rs/{name}'
+```
+
+Fields inherited with `key.workspace = true` are left untouched, in
+`[workspace.package]` only the templates without placeholders are applied. Links
+into archived repositories that are not fixed by a template are reported, by
+default the `substrate`, `polkadot` and `cumulus` repositories that were merged
+into the Polkadot SDK. Other repositories are given with `--archived <URL>`.
+With `--check` diener fails if any link is not up to date or points to an
+archived repository.
+
+### Lint
+
+The `lint` subcommand checks all manifests of a workspace against a set of
+lints. Every lint has an ID and a default level, list them with `--list`:
+
+```sh
+diener lint --path ../my-workspace --deny wildcard-version
+```
+
+The following lints are available:
+
+- `deprecated-replace`: entries of the deprecated `[replace]` table. The fix
+  moves them to the `[patch]` table of the replaced source and warns about
+  entries of a version, unlike `[replace]` a patch is only used by dependents
+  whose version requirement the patched crate matches.
+- `duplicate-dependency`: packages a member declares itself with a different
+  source, version or `default-features` than `[workspace.dependencies]`, and
+  packages declared twice in one dependency table under different aliases.
+- `inconsistent-package`: members whose `rust-version` or `edition` differs
+  from `[workspace.package]` or from the value most members use. The fix makes
+  them inherit the field from `[workspace.package]`.
+- `missing-std-feature`: crates that disable the default features of SDK
+  dependencies, but have no `std` feature. The fix adds a `std` feature that
+  forwards to all of them, it is enabled by default if other SDK dependencies
+  still enable their default features.
+- `path-dependency`: publishable crates with `path` dependencies without
+  `version`, which `cargo publish` rejects. The fix adds the version of the
+  dependency, with `--path-dependency-fix no-publish` it sets `publish = false`
+  instead.
+- `rev-not-on-branch`: SDK dependencies pinned to a `rev` that is not an
+  ancestor of the branch given by `--verify-rev-on-branch`. Finds nothing
+  without the flag, as it fetches the history of the branch.
+- `sdk-default-features`: SDK dependencies of crates with a `std` feature that
+  enable their default features. The fix sets `default-features = false` and
+  adds the `/std` forward to the `std` feature.
+- `unknown-feature-dependency`: feature entries like `foo/std` or `dep:foo`
+  where `foo` is not a dependency of the crate.
+- `unpinned-git`: git dependencies without `branch`, `tag` or `rev`, which
+  follow the default branch. The fix pins them to the commit the default branch
+  currently points to. Manifests whose repositories can't be queried are
+  reported as failed, the others are still fixed.
+- `wildcard-version`: dependencies that accept any version (`*`).
+
+`--allow` disables a lint, `--warn` reports its findings and `--deny` also makes
+diener fail. `--fix` applies the auto-fixes of all lints that have one. Findings
+are printed as text, as JSON with `--format json` or as GitHub Actions
+annotations with `--format github`.
+
+To adopt lints in an existing workspace, record the current findings in a
+baseline file. The first run with `--baseline` writes all findings to the file,
+later runs only report findings that are not in it. `--update-baseline`
+rewrites the file with the current findings:
+
+```sh
+diener lint --baseline lint-baseline.json --deny path-dependency
+```
+
+### Bisect
+
+The `bisect` subcommand finds the first Polkadot SDK commit that breaks your
+project. Given a good and a bad rev, it points all Polkadot SDK dependencies to
+the commit in the middle, runs the given command and continues with the half
+that contains the breaking commit:
+
+```sh
+diener bisect --good 1a2b3c --bad 4d5e6f -- cargo check
+```
+
+A non-zero exit code of the command marks a commit as bad. Only the first-parent
+history between both revs is searched. The manifests and the `Cargo.lock` files
+are restored at the end and the first bad commit is printed. As the command needs
+the rewritten manifests on disk, `bisect` can't run with `--dry-run`.
+
+### Snapshot and restore
+
+Before experimenting with several diener operations, store the content of all
+`Cargo.toml` files of the workspace in a snapshot under `.diener/snapshots` and
+bring them back later:
+
+```sh
+diener snapshot --name before-upgrade
+diener update --branch stable2407
+diener restore before-upgrade
+```
+
+Without `--name` the snapshot is named after the current unix timestamp.
+Manifests that were created after the snapshot was taken are left untouched by
+`restore`.
+
+### Companion
+
+Downstream projects that need to build against an unmerged Polkadot SDK change,
+a so called companion, can point their dependencies to the branch of the pull
+request:
+
+```sh
+diener companion 1234
+```
+
+The pull request can also be given as `#1234` or as its url, the head branch is
+queried from the API of the forge hosting the repository, see
+[Forges](#forges). GitLab merge requests are also given by their url or as
+`!1234`. Pull requests from forks switch the `git` url of the dependencies to
+the fork. Instead of a pull request also a branch of the
+Polkadot SDK can be given. With `--rev` the dependencies are pinned to the
+current head commit instead of the branch.
+
+### Release prep
+
+Before publishing a workspace to crates.io, its Polkadot SDK git dependencies and
+its `path` dependencies outside of the workspace need to be replaced by
+crates.io versions:
+
+```sh
+diener release-prep --versions versions.toml
+```
+
+The version of a git dependency is taken from the `Cargo.toml` of the crate at
+the pinned `tag`. Dependencies pinned to a branch or a rev, or all others that
+should use a different version, need to be listed in a TOML file passed to
+`--versions`, like `sp-core = "34.0.0"`. All `[patch]` sections are removed.
+Afterwards every dependency of a publishable crate, except dev-dependencies, must
+have a version requirement. If any dependency can not be converted or is left
+without a version requirement, nothing is written.
+
+### Dev setup
+
+The inverse of `release-prep` switches the crates.io dependencies on Polkadot SDK
+crates back to git:
+
+```sh
+diener dev-setup --branch master
+```
+
+Instead of `--branch`, also `--tag` or `--rev` can be given, the `git` url is
+taken from `--git` or the configuration. With `--local ../polkadot-sdk` the
+dependencies point to the crates of a local checkout via `path` instead. Which
+crates are part of the Polkadot SDK is decided by the crates in the repository
+or checkout. `--patch` also adds a `[patch.crates-io]` entry for every switched
+crate to the workspace root, so that crates.io crates depending on the Polkadot
+SDK use the same copy.
+
+### Where
+
+To debug why cargo resolves an unexpected copy of a crate, `where` shows every
+manifest that depends on or patches the crate, the table and the source of the
+declaration:
+
+```sh
+diener where sp-core
+```
+
+The source is a git repository with its branch, tag or rev, a path, a registry
+version or `[workspace.dependencies]` for inherited dependencies. Pass
+`--format json` for machine readable output.
+
+### Tree
+
+`cargo tree` prints every dependency, which makes it hard to spot conflicting
+Polkadot SDK pins. `tree` prints the resolved dependency tree of every workspace
+member restricted to the crates of the Polkadot SDK, crates in between are
+skipped. Every crate is annotated with its git pin and crates whose
+dependencies come from different pins are highlighted:
+
+```sh
+diener tree
+```
+
+### Verify tags
+
+Before signing off a release branch, `verify-tags` checks that all Polkadot SDK
+dependencies use tags of the same release family:
+
+```sh
+diener verify-tags --family 'polkadot-stable2407*'
+```
+
+Every dependency with a tag outside of the family or without a tag is reported
+with its manifest. Without `--family` the family of the most used tag is
+expected, where `polkadot-stable2407-2` is part of the `polkadot-stable2407`
+family.
+
+### Set
+
+For one-off edits across the tree, `set` sets or removes arbitrary keys of all
+dependencies matching a filter:
+
+```sh
+diener set --match 'sp-*' default-features=false
+diener set --match-url polkadot-sdk --remove tag
+```
+
+`--match` selects dependencies by the glob pattern of their package name,
+`--match-url` git dependencies by a regex on their url and `--table` the
+dependency tables by a glob pattern, like `dev-dependencies`. At least one
+filter is required, all given filters must match. The values are parsed as TOML,
+like `false` or `["std"]`, anything else is written as string. Dependencies
+inherited with `workspace = true` only get `features` and `optional`, the other
+keys are skipped with a warning and need to be set in `[workspace.dependencies]`,
+e.g. with `--table workspace.dependencies`. `--check` fails instead if any
+dependency isn't up to date.
+
+### Serve
+
+Editor extensions and bots can keep diener running with `serve --stdio`. It
+reads one JSON request per line from stdin and writes one JSON response per
+line to stdout:
+
+```sh
+diener serve --stdio
+{"id": 1, "command": "update", "reference": {"tag": "polkadot-stable2407"}}
+{"id": 2, "command": "lint", "manifest": "runtime/Cargo.toml"}
+{"id": 3, "command": "shutdown"}
+```
+
+The commands are `update`, `workspacify`, `rename-crate`, `set`,
+`release-prep`, `dev-setup`, `patch`, `lint` and `shutdown`. Their fields are
+the ones of the `Operation`s of the embedding API, in kebab-case. Every response
+has the `id` of its request, `ok`, the modified `files` with their `original`
+and new `content`, and the `error` or the lint `findings`. Nothing is written
+unless the request sets `"apply": true`. The results of `cargo metadata` are
+kept between the requests.
+
+### Sbom
+
+`sbom` prints an inventory of the workspace members and the Polkadot SDK git
+pins they depend on, with their repository and branch, tag or rev. The format
+is CycloneDX 1.5 JSON (`--format cyclonedx`, the default) or SPDX 2.3 JSON
+(`--format spdx-json`):
+
+```sh
+diener sbom --format spdx-json > sbom.spdx.json
+```
+
+Dependencies inherited from `[workspace.dependencies]` are attributed to the
+members using them.
+
+### Compare lock
+
+`compare-lock` compares the Polkadot SDK crates of two `Cargo.lock` files, the
+git packages of the SDK repositories and the crates.io releases whose name looks
+like an SDK crate. It lists the crates that were added, removed or are locked at
+other versions or revs as Markdown tables for release notes, or as JSON with
+`--format json`. With `--git` the arguments are git refs and the `Cargo.lock` of
+the working directory or `--path` at these refs is compared:
+
+```sh
+diener compare-lock old/Cargo.lock Cargo.lock
+diener compare-lock --git polkadot-v1.0.0 HEAD --format json
+```
+
+### Doctor
+
+`doctor` runs a set of checks on the tree without modifying anything and prints
+every problem with its severity and, where there is one, the diener command to
+fix it:
+
+- `parse-error`: A manifest fails to parse.
+- `duplicate-package`: Multiple manifests declare a package with the same name.
+- `missing-member`: A crate below a workspace root is neither in its
+  `workspace.members` nor in its `workspace.exclude`.
+- `mixed-pins`: A Polkadot SDK repository is used with different branches, tags
+  or revs.
+- `stale-patch`: A `[patch]` entry points to a path without a manifest. There is
+  no command to fix it, the entry has to be removed or pointed to a checkout.
+
+```sh
+diener doctor --path ../my-project
+```
+
+It fails if any problem has the severity `error`. `--format json` and
+`--format github` print the problems as JSON or GitHub Actions annotations.
+
+### Schema
+
+`schema` prints the JSON Schema of the [configuration](#configuration) or of the
+plans written by `--plan-out`, for editors to validate and complete the files
+and for CI to reject invalid ones before diener runs:
+
+```sh
+diener schema config > diener.schema.json
+diener schema plan
+```
+
+Editors with TOML schema support, like Taplo, pick the schema up from a
+comment at the top of `.diener.toml`:
+
+```toml
+#:schema ./diener.schema.json
+```
+
+### Env
+
+`env` prints everything that influences a run: the loaded configuration files,
+the merged configuration including the defaults and the global flags, the
+network mode, the workspace roots, the cache directories and the environment
+variables diener reads. Tokens are never printed, only whether they are set.
+Compare the output of a CI job with a local run to find out why they differ:
+
+```sh
+diener env --offline --exclude-dir fixtures
+diener env --format json
+```
+
+### Cache
+
+`cache` manages the caches diener keeps on disk: the responses of the forge
+APIs, the results of `cargo metadata` (see `--cache-metadata`), the hashes of
+`update --incremental` and the temporary git checkouts. The checkouts are
+removed at the end of every run, only the ones left behind by killed runs are
+cached. The checkouts of diener runs that are still running are never removed. `cache info` prints the location, the number of entries and the size of
+every cache, `cache clear` removes all entries and `cache gc` only the ones
+older than the TTL. `--cache` restricts all of them to the given caches:
+
+```sh
+diener cache info
+diener cache clear --cache metadata --cache api
+diener cache gc --ttl 7d
+```
+
+The TTL of `cache gc` defaults to 30 days and can be configured:
+
+```toml
+[cache]
+ttl = "14d"
+```
+
+### Tui
+
+The `tui` subcommand lists all Polkadot SDK dependencies found in a given folder
+in a table. Select the crates you want to change, choose a branch/tag/rev or the
+path to a local checkout, preview the diff and apply it:
+
+```sh
+diener tui --path ../my-project
+```
+
+### Manifest list
+
+Build systems that already know the affected manifests can pass them with
+`--manifest-list`, one `Cargo.toml` path per line and `-` for stdin. All
+subcommands that search for manifests process exactly these manifests instead,
+no directory is searched and the exclusions don't apply:
+
+```sh
+diener update --branch diener-branch --manifest-list affected-manifests.txt
+```
+
+### Dry run
+
+Every subcommand that modifies files accepts `--dry-run`. Nothing is written,
+instead a diff of all changes that would be made is printed:
+
+```sh
+diener update --branch diener-branch --dry-run
+```
+
+The diff is colored when printed to a terminal, use `--color always|never` to
+override this.
+
+To review the changes before applying them, write them to a plan with
+`--plan-out` and apply it later. `apply-plan` refuses to run if any of the
+files changed in between:
+
+```sh
+diener update --branch diener-branch --plan-out plan.json
+diener apply-plan plan.json
+```
+
+`--output-dir <dir>` leaves the source tree untouched and writes the modified
+files to the same path relative to the working directory below `<dir>`
+instead. A build system can overlay the directory on the source tree, e.g. to
+test an update against several branches at the same time:
+
+```sh
+diener update --branch stable2407 --output-dir ../overlays/stable2407
+```
+
+Relative `path` dependencies are written unchanged, so they are only valid once
+overlaid. Subcommands that move directories, like `move-crate`, are not
+supported.
+
+Before modifying more than 100 files diener lists some of them and asks for
+confirmation, e.g. in case a mistyped `--path` points to a whole home
+directory. The number is configured by `confirm-threshold` in the
+configuration, `0` never asks. Without a terminal diener fails instead, pass
+`--yes` to skip the confirmation in automation:
+
+```sh
+diener update --branch stable2407 --yes
+```
+
+### Commit
+
+With `--commit` diener creates a new branch and commits all files it modified,
+other modifications of the working tree are not staged:
+
+```sh
+diener update --branch stable2407 --commit --branch-name bump-sdk
+```
+
+The commit message defaults to the diener command line and can be changed with
+`--message`.
+
+With `--open-pr` the branch is pushed to `origin` and a pull request is opened
+on the forge hosting it, targeting the branch that was checked out before. On
+GitLab this is a merge request. The title is the first line of the commit
+message and the body the summary of `--markdown-summary`. This needs a token
+with access to the repository: in `GITHUB_TOKEN` or `GH_TOKEN` for GitHub, in
+`GITLAB_TOKEN` for GitLab and in the `token-env` of self-hosted instances, see
+[Forges](#forges). Labels and reviewers are added with `--pr-label` and
+`--pr-reviewer` or configured in `[pull-request]`, reviewers like `org/team` are
+teams:
+
+```sh
+diener update --tag polkadot-stable2409 --commit --open-pr --pr-label A-dependencies
+```
+
+```toml
+[pull-request]
+labels = ["A-dependencies"]
+reviewers = ["alice", "paritytech/sdk-node"]
+# The remote the branch is pushed to.
+remote = "origin"
+```
+
+### Check
+
+`update`, `workspacify` and `set-metadata` accept `--check` to verify in CI that
+all manifests are up to date. Nothing is written, instead every manifest that
+would change is reported and diener exits with an error. `--format github`
+reports them as GitHub Actions annotations, `--format json` as JSON:
+
+```sh
+diener update --branch stable2407 --check --format github
+```
+
+### Timings
+
+`--timings` prints the time spent walking the directories, parsing manifests,
+running `cargo metadata`, accessing the network and writing files, followed by
+the slowest files, to stderr. Pass `--timings-format json` for a JSON report:
+
+```sh
+diener update --branch stable2407 --timings
+```
+
+`update` reads the manifests and every subcommand writes the modified files in
+batches of `--jobs` files at the same time, which defaults to the number of
+CPUs. Lower values go easy on network filesystems and constrained CI runners:
+
+```sh
+diener update --branch stable2407 --jobs 2
+```
+
+### Markdown summary
+
+`--markdown-summary` writes the modifications of the run as Markdown, ready to
+paste into the description of a pull request. The dependencies whose source
+changed are grouped by repository and by their old and new source, like
+"34 crates moved from branch `master` to tag `polkadot-stable2409`", followed by
+the list of modified files. It is written with `--dry-run` as well:
+
+```sh
+diener update --tag polkadot-stable2409 --markdown-summary summary.md
+```
+
+### Operation log
+
+`--log-file` appends one JSON record per action to the given file, independent
+of the log level, e.g. for audits of release tooling. Every record has the
+`time` and the `action`: `run-started` with the command line, `file-opened`,
+`file-processing`, `entry-rewritten` with the old and new version of the
+dependency, `patch-added`, `file-written`, `file-moved`, `error` and
+`run-finished`:
+
+```sh
+diener update --branch stable2407 --log-file ops.jsonl
+```
+
+### Exit codes
+
+diener exits with a code per class of failure, so scripts can branch on the
+result without parsing stderr:
+
+- `0`: Success.
+- `1`: Any other failure.
+- `2`: The command line is invalid.
+- `3`: `--check` found manifests that are not up to date.
+- `4`: Nothing was modified, only with `--fail-if-unchanged`.
+- `5`: A manifest or another input file could not be parsed.
+- `6`: Accessing the network failed or is disabled by `--offline`.
+- `7`: Running `cargo` failed.
+
+```sh
+diener update --branch stable2407 --fail-if-unchanged
+```
+
+Manifests that fail to parse are reported with their path, the line and column
+of the error and the offending line. With `--keep-going` all of them are reported
+and the exit code is the one of their class if all files failed the same way:
+
+```text
+Failed to parse manifest at runtime/Cargo.toml:3:14: expected newline, `#`
+  |
+3 | version = 0.1.0"
+  |              ^
+```
+
+### Configuration
+
+Defaults for the command line flags can be stored in a `.diener.toml` in the
+working directory or any of its parents and in `~/.config/diener/config.toml`.
+Values of the project configuration take precedence over the user configuration,
+command line flags take precedence over both:
+
+```toml
+# The repositories whose git dependencies are managed (default: `polkadot-sdk`).
+match-repos = ["polkadot-sdk"]
+# Directories that are skipped, relative to the configuration file.
+exclude = ["tests/fixtures"]
+# Names of directories that are skipped wherever they appear. `target`,
+# `node_modules`, `vendor` and hidden directories are always skipped.
+exclude-dirs = ["templates"]
+# The `git` url `update` rewrites the dependencies to.
+git = "https://github.com/paritytech/polkadot-sdk"
+# The `branch` `update` uses if no version is given and `DIENER_BRANCH` isn't set.
+branch = "master"
+# The patch target used by `patch`.
+patch-target = "https://github.com/paritytech/polkadot-sdk"
+# The output format of reporting subcommands, `text` or `json` (`github` for `--check`).
+format = "text"
+# How `workspacify` writes the paths of dependencies, `relative`, `dot` or `from-root`.
+path-style = "relative"
+# Ask for confirmation before modifying more files (default: 100, `0` never asks).
+confirm-threshold = 100
+```
+
+`init` writes a `.diener.toml` for the working directory or `--path`. It
+inspects the tree first, prints the git dependencies it found and suggests the
+Polkadot SDK repositories they use and the branch most of them follow. It asks
+for the `match-repos`, the `git` url, the `branch` and the `exclude`
+directories, `--defaults` writes the suggestions without asking. An existing
+configuration is only replaced with `--force`:
+
+```sh
+diener init
+diener init --defaults --dry-run
+```
+
+### Hooks
+
+Commands configured in the `[hooks]` table of the configuration are run by the
+shell after diener wrote the modified files, e.g. to format them. Every
+`after-file` hook is run once per modified file, which is given in
+`DIENER_FILE`. The `after-run` hooks are run once at the end, with all modified
+files given one per line in `DIENER_CHANGED_FILES` and on stdin:
+
+```toml
+[hooks]
+after-file = ['taplo fmt "$DIENER_FILE"']
+after-run = ["cargo metadata -q --format-version 1 > /dev/null"]
+```
+
+Hooks are not run with `--dry-run`, `--plan-out` or `--no-hooks`, or when
+nothing was modified. diener fails if a hook fails. With `--commit` the hooks
+run before the files are committed, so e.g. the formatted manifests are committed.
+
+### Forges
+
+Repositories on github.com and gitlab.com are supported by `companion`,
+`check-versions` and `--open-pr` out of the box, authenticated with
+the token in `GITHUB_TOKEN` (or `GH_TOKEN`) and `GITLAB_TOKEN`. Self-hosted
+GitHub Enterprise and GitLab instances are configured in `[[forges]]` of the
+configuration, matched by the host of the repository url:
+
+```toml
+[[forges]]
+host = "gitlab.parity.io"
+kind = "gitlab"
+# The API, `https://<host>/api/v4` for GitLab and `https://<host>/api/v3` for GitHub by default.
+api = "https://gitlab.parity.io/api/v4"
+# The environment variable containing the token.
+token-env = "PARITY_GITLAB_TOKEN"
+```
+
+Reviewers of GitLab merge requests need to be users, groups are not supported.
+
+### Network
+
+All network operations, the forge APIs, `git ls-remote` and fetches and the
+`cargo` invocations, use the proxy in `HTTPS_PROXY` and skip it for the hosts in
+`NO_PROXY`. Behind a proxy that intercepts TLS, the CA bundle to verify the
+servers with is given in `[network]` of the configuration, which can also hold
+a proxy for environments that don't set the variables:
+
+```toml
+[network]
+# Used if `HTTPS_PROXY` isn't set.
+proxy = "http://proxy.example.com:3128"
+# Used if `NO_PROXY` isn't set.
+no-proxy = ["localhost", ".example.com"]
+# PEM certificates, relative to the configuration file.
+ca-bundle = "certs/corporate-ca.pem"
+```
+
+`diener env` prints the proxy and CA bundle in use.
+
+### Embedding
+
+diener can also be used as a library. `Engine` runs typed operations on the
+manifests below a directory and returns the resulting `ChangeSet` instead of
+writing anything. It never prints or exits, failures are returned as
+`diener::Error`:
+
+```no_run
+# fn main() -> Result<(), diener::Error> {
+use diener::{Engine, Operation, Reference};
+
+let mut engine = Engine::new("path/to/workspace")?;
+let changes = engine.run(Operation::Update {
+    paths: Vec::new(),
+    reference: Reference::Branch("stable2407".into()),
+    git: None,
+})?;
+println!("{}", changes.diff());
+changes.apply()?;
+# Ok(())
+# }
+```
+
+The configuration is loaded from the `.diener.toml` files above the directory.
+Diagnostics are still emitted through the `log` crate.
+
+All file access goes through the `Fs` trait. `Engine::with_fs` runs the
+operations on another filesystem, like the in-memory `MemoryFs`, which is handy
+for tests and for tooling without disk access. Operations that run
+`cargo metadata` or resolve the paths of other crates still need the real
+filesystem.
+
+Single manifests can be edited with `ManifestEditor`. Its operations, like
+`set_git`, `set_branch`, `to_path_dep`, `to_version_dep`, `set_key`,
+`rename_dependency` or `add_patch_entry`, only touch the keys they are about and
+keep the order of the other keys, their spacing and the comments. The
+subcommands edit the manifests with the same operations:
+
+```no_run
+# fn main() -> Result<(), Box<dyn std::error::Error>> {
+use diener::ManifestEditor;
+
+let mut manifest = ManifestEditor::parse(&std::fs::read_to_string("Cargo.toml")?)?;
+manifest.set_branch("dependencies", "sp-core", "stable2407")?;
+manifest.to_path_dep("dev-dependencies", "sp-io", "../polkadot-sdk/substrate/primitives/io".as_ref())?;
+std::fs::write("Cargo.toml", manifest.to_string())?;
+# Ok(())
+# }
+```
+
+## License
+
+Licensed under either of
+
+ * [Apache License, Version 2.0](http://www.apache.org/licenses/LICENSE-2.0)
+
+ * [MIT license](http://opensource.org/licenses/MIT)
+
+at your option.
+*/
+
+mod bisect;
+mod cache;
+mod changes;
+mod check_versions;
+#[doc(hidden)]
+pub mod cli;
+mod companion;
+mod compare_lock;
+mod config;
+mod context;
+mod dev_setup;
+mod diff;
+mod doctor;
+pub mod editor;
+pub mod engine;
+mod env;
+mod failure;
+pub mod filesystem;
+mod fix_links;
+mod forge;
+mod format;
+mod git;
+mod github;
+mod gitlab;
+mod hooks;
+mod http;
+mod incremental;
+mod init;
+mod lint;
+mod manifest;
+mod metadata;
+mod move_crate;
+mod network;
+mod oplog;
+mod patch;
+mod plan;
+mod pull_request;
+mod release_prep;
+mod rename_crate;
+mod sbom;
+mod schema;
+mod serve;
+mod set;
+mod set_metadata;
+mod snapshot;
+mod stats;
+mod summary;
+mod timings;
+mod tree;
+mod tui;
+mod update;
+mod verify;
+mod verify_tags;
+mod walk;
+mod where_declared;
+mod workspace_deps;
+mod workspacify;
+
+pub use editor::{ManifestEditor, PatchSource};
+pub use engine::{ChangeSet, Engine, Error, Operation, Reference};
+pub use filesystem::{Fs, MemoryFs, RealFs};
@@ -0,0 +1,510 @@
+use crate::{
+    context::RunContext,
+    failure::{Classify, Failure},
+    format::OutputFormat,
+    manifest::{dependency_tables, manifest_path},
+    update::{is_managed_dependency, RepoMatcher},
+    workspacify::manifest_iter,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env::current_dir,
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+use toml_edit::{Document, Item, TableLike};
+
+mod deprecated_replace;
+mod duplicate_dependency;
+mod inconsistent_package;
+mod missing_std_feature;
+mod path_dependency;
+mod rev_not_on_branch;
+mod sdk_default_features;
+mod unknown_feature_dependency;
+mod unpinned_git;
+mod wildcard_version;
+
+/// How findings of a lint are treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Level {
+    /// The lint isn't run.
+    Allow,
+    /// Findings are reported.
+    Warn,
+    /// Findings are reported and make diener fail.
+    Deny,
+}
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Allow => "allow",
+            Self::Warn => "warn",
+            Self::Deny => "deny",
+        }
+    }
+
+    /// The label of findings with this level, also the GitHub Actions annotation command.
+    fn label(self) -> &'static str {
+        match self {
+            Self::Allow | Self::Warn => "warning",
+            Self::Deny => "error",
+        }
+    }
+}
+
+/// A problem found by a lint.
+pub(crate) struct Finding {
+    /// The manifest the problem is in.
+    pub(crate) manifest: PathBuf,
+    /// What is wrong, e.g. "`serde` in `[dependencies]` uses a wildcard version.".
+    pub(crate) message: String,
+}
+
+/// The findings of all lints with the id and the level of the lint reporting them.
+pub(crate) type Findings = Vec<(&'static str, Level, Finding)>;
+
+/// All manifests of the workspace, a lint is run on all of them at once.
+pub(crate) struct Manifests {
+    /// The workspace root directory.
+    pub(crate) root: PathBuf,
+    /// The parsed manifests by their path.
+    pub(crate) docs: BTreeMap<PathBuf, Document>,
+    /// The git repositories of the Polkadot SDK.
+    pub(crate) repos: RepoMatcher,
+}
+
+/// An SDK dependency of a crate that ends up in its normal build.
+///
+/// Dev and build dependencies are not included.
+pub(crate) struct SdkDependency<'a> {
+    /// The dependency table, e.g. `dependencies` or `target.'cfg(unix)'.dependencies`.
+    pub(crate) table: String,
+    pub(crate) key: &'a str,
+    /// The dependency, or the entry of `[workspace.dependencies]` it inherits.
+    pub(crate) source: &'a dyn TableLike,
+    /// The dependency is declared with `workspace = true`.
+    pub(crate) inherited: bool,
+    pub(crate) optional: bool,
+}
+
+impl SdkDependency<'_> {
+    /// Returns `true` if the default features of the dependency are enabled.
+    pub(crate) fn default_features(&self) -> bool {
+        ["default-features", "default_features"]
+            .iter()
+            .filter_map(|k| self.source.get(k))
+            .all(|v| v.as_bool() != Some(false))
+    }
+
+    /// Returns the entry of the `std` feature that forwards to the dependency.
+    pub(crate) fn std_forward(&self) -> String {
+        if self.optional {
+            format!("{}?/std", self.key)
+        } else {
+            format!("{}/std", self.key)
+        }
+    }
+}
+
+impl Manifests {
+    /// Returns the SDK dependencies of the crate in `doc`.
+    pub(crate) fn sdk_dependencies<'a>(&'a self, doc: &'a Document) -> Vec<SdkDependency<'a>> {
+        let tables = dependency_tables(doc).into_iter().filter(|(table, _)| {
+            table == "dependencies"
+                || (table.starts_with("target.") && table.ends_with(".dependencies"))
+        });
+
+        let mut dependencies = Vec::new();
+        for (table, deps) in tables {
+            for (key, dep) in deps.iter() {
+                let Some(dep) = dep.as_table_like() else {
+                    continue;
+                };
+
+                let inherited = dep.get("workspace").and_then(|w| w.as_bool()) == Some(true);
+                let source = if inherited {
+                    let Some(source) = self.workspace_dependency(key).and_then(Item::as_table_like)
+                    else {
+                        continue;
+                    };
+                    source
+                } else {
+                    dep
+                };
+
+                if is_managed_dependency(source, &self.repos) {
+                    dependencies.push(SdkDependency {
+                        table: table.clone(),
+                        key,
+                        source,
+                        inherited,
+                        optional: dep.get("optional").and_then(|o| o.as_bool()) == Some(true),
+                    });
+                }
+            }
+        }
+        dependencies
+    }
+
+    /// Returns the entry of `[workspace.dependencies]` that a dependency declared as `key` with
+    /// `workspace = true` inherits.
+    pub(crate) fn workspace_dependency(&self, key: &str) -> Option<&Item> {
+        self.docs
+            .get(&self.root.join("Cargo.toml"))?
+            .get("workspace")?
+            .get("dependencies")?
+            .get(key)
+    }
+}
+
+/// A manifest lint.
+pub(crate) trait ManifestLint {
+    /// The ID used by `--allow`, `--warn` and `--deny`, e.g. `wildcard-version`.
+    fn id(&self) -> &'static str;
+
+    /// A short description of what the lint detects.
+    fn description(&self) -> &'static str;
+
+    /// The level of the lint if not set on the command line.
+    fn default_level(&self) -> Level {
+        Level::Warn
+    }
+
+    /// Returns all problems in `manifests`.
+    fn check(&self, manifests: &Manifests) -> Vec<Finding>;
+
+    /// Fix all problems in `manifests`.
+    ///
+    /// Returns `false` if the lint has no auto-fix.
+    fn fix(&self, _manifests: &mut Manifests, _ctx: &mut RunContext) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+/// `lint` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct Lint {
+    /// The path to the workspace root directory.
+    ///
+    /// Uses the working directory if none is supplied.
+    #[structopt(long)]
+    path: Option<PathBuf>,
+
+    /// Don't run the given lint.
+    ///
+    /// Can be passed multiple times.
+    #[structopt(long, value_name = "lint")]
+    allow: Vec<String>,
+
+    /// Report the findings of the given lint.
+    ///
+    /// Can be passed multiple times, overrides `--allow`.
+    #[structopt(long, value_name = "lint")]
+    warn: Vec<String>,
+
+    /// Report the findings of the given lint and fail if there are any.
+    ///
+    /// Can be passed multiple times, overrides `--allow` and `--warn`.
+    #[structopt(long, value_name = "lint")]
+    deny: Vec<String>,
+
+    /// Apply the auto-fixes of all lints that are run.
+    ///
+    /// Only the findings that can not be fixed are reported.
+    #[structopt(long)]
+    fix: bool,
+
+    /// List all lints with their default level and exit.
+    #[structopt(long)]
+    list: bool,
+
+    /// The output format, `text`, `json` or `github`.
+    ///
+    /// `github` emits GitHub Actions annotations. Defaults to the `format` of the configuration
+    /// or `text`.
+    #[structopt(long)]
+    format: Option<OutputFormat>,
+
+    /// How `--fix` fixes path dependencies of publishable crates, `version` or `no-publish`.
+    ///
+    /// `version` adds the version of the dependency, `no-publish` sets `publish = false` in the
+    /// crate instead. Defaults to `version`.
+    #[structopt(long)]
+    path_dependency_fix: Option<path_dependency::Fix>,
+
+    /// Report the revs of SDK dependencies that are not an ancestor of the given branch.
+    ///
+    /// Enables the `rev-not-on-branch` lint, which fetches the history of the branch of every
+    /// pinned repository.
+    #[structopt(long, value_name = "BRANCH")]
+    verify_rev_on_branch: Option<String>,
+
+    /// Suppress the findings recorded in the given baseline file, only new findings are reported.
+    ///
+    /// The path is relative to the workspace root. If the file doesn't exist yet, all current
+    /// findings are recorded in it.
+    #[structopt(long)]
+    baseline: Option<PathBuf>,
+
+    /// Record all current findings in the `--baseline` file, replacing the old ones.
+    #[structopt(long, requires = "baseline")]
+    update_baseline: bool,
+}
+
+/// A finding recorded in the `--baseline` file.
+#[derive(PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct BaselineEntry {
+    lint: String,
+    /// The manifest relative to the workspace root, so the baseline can be shared.
+    manifest: String,
+    message: String,
+}
+
+impl BaselineEntry {
+    fn new(root: &Path, lint: &str, finding: &Finding) -> Self {
+        let manifest = finding
+            .manifest
+            .strip_prefix(root)
+            .unwrap_or(&finding.manifest);
+        Self {
+            lint: lint.into(),
+            manifest: manifest_path(manifest),
+            message: finding.message.clone(),
+        }
+    }
+}
+
+/// A finding together with the lint that reported it, as printed by `--format json`.
+#[derive(Serialize)]
+pub(crate) struct Report<'a> {
+    lint: &'static str,
+    level: Level,
+    manifest: &'a Path,
+    message: &'a str,
+}
+
+impl Lint {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        let lints = self.lints();
+        if self.list {
+            for lint in &lints {
+                println!(
+                    "{:<24} {:<6} {}",
+                    lint.id(),
+                    lint.default_level().name(),
+                    lint.description()
+                );
+            }
+            return Ok(());
+        }
+
+        let format = self
+            .format
+            .or(ctx.config.format())
+            .unwrap_or(OutputFormat::Text);
+        let Some(findings) = self.findings(ctx)? else {
+            return Ok(());
+        };
+
+        print_findings(&findings, format)?;
+
+        let denied = findings
+            .iter()
+            .filter(|(_, level, _)| *level == Level::Deny)
+            .count();
+        if denied > 0 {
+            bail!("Found {} denied lint finding(s).", denied);
+        }
+        log::info!("Found {} lint finding(s).", findings.len());
+        Ok(())
+    }
+
+    /// Run the lints and stage their fixes, returning the findings that are not in the baseline.
+    ///
+    /// Returns `None` if the findings were recorded in a new or updated baseline instead.
+    pub(crate) fn findings(&self, ctx: &mut RunContext) -> Result<Option<Findings>> {
+        let lints = self.lints();
+        let levels = self.levels(&lints)?;
+        if self.verify_rev_on_branch.is_some() && levels["rev-not-on-branch"] != Level::Allow {
+            ctx.ensure_online("`--verify-rev-on-branch`")?;
+        }
+        let root = ctx.workspace_dir(self.path.as_deref())?;
+
+        let mut docs = BTreeMap::new();
+        for manifest in manifest_iter(ctx.changes.fs(), &root, &ctx.exclusions) {
+            let doc = ctx.changes.read_toml(&manifest)?;
+            docs.insert(manifest, doc);
+        }
+        let originals = docs
+            .iter()
+            .map(|(p, d)| (p.clone(), d.to_string()))
+            .collect::<BTreeMap<_, _>>();
+        let mut manifests = Manifests {
+            root,
+            docs,
+            repos: ctx.config.match_repos().into(),
+        };
+
+        let mut findings = Vec::new();
+        for lint in &lints {
+            let level = levels[lint.id()];
+            if level == Level::Allow {
+                continue;
+            }
+
+            if self.fix && lint.fix(&mut manifests, ctx)? {
+                log::debug!("Applied the fixes of `{}`.", lint.id());
+            }
+            findings.extend(
+                lint.check(&manifests)
+                    .into_iter()
+                    .map(|f| (lint.id(), level, f)),
+            );
+        }
+
+        for (path, doc) in &manifests.docs {
+            let content = doc.to_string();
+            if originals.get(path) != Some(&content) {
+                ctx.changes.write(path, content);
+            }
+        }
+
+        if let Some(baseline) = &self.baseline {
+            let path = manifests.root.join(baseline);
+            let entries = findings
+                .iter()
+                .map(|(lint, _, finding)| BaselineEntry::new(&manifests.root, lint, finding))
+                .collect::<Vec<_>>();
+
+            if self.update_baseline || !ctx.changes.exists(&path) {
+                ctx.changes.write(
+                    &path,
+                    format!("{}\n", serde_json::to_string_pretty(&entries)?),
+                );
+                log::info!(
+                    "Recorded {} lint finding(s) in {}.",
+                    entries.len(),
+                    path.display()
+                );
+                return Ok(None);
+            }
+
+            let known = serde_json::from_str::<Vec<BaselineEntry>>(&ctx.changes.read(&path)?)
+                .with_context(|| anyhow!("Failed to parse baseline {}", path.display()))
+                .failure(Failure::Parse)?
+                .into_iter()
+                .collect::<HashSet<_>>();
+            let before = findings.len();
+            findings = findings
+                .into_iter()
+                .zip(entries)
+                .filter(|(_, entry)| !known.contains(entry))
+                .map(|(finding, _)| finding)
+                .collect();
+            log::info!(
+                "Suppressed {} lint finding(s) of the baseline.",
+                before - findings.len()
+            );
+        }
+
+        Ok(Some(findings))
+    }
+
+    /// Returns all lints known to diener.
+    fn lints(&self) -> Vec<Box<dyn ManifestLint>> {
+        vec![
+            Box::new(deprecated_replace::DeprecatedReplace),
+            Box::new(duplicate_dependency::DuplicateDependency),
+            Box::new(inconsistent_package::InconsistentPackage),
+            Box::new(missing_std_feature::MissingStdFeature),
+            Box::new(path_dependency::PathDependency {
+                fix: self.path_dependency_fix.unwrap_or_default(),
+            }),
+            Box::new(rev_not_on_branch::RevNotOnBranch {
+                branch: self.verify_rev_on_branch.clone(),
+            }),
+            Box::new(sdk_default_features::SdkDefaultFeatures),
+            Box::new(unknown_feature_dependency::UnknownFeatureDependency),
+            Box::new(unpinned_git::UnpinnedGit),
+            Box::new(wildcard_version::WildcardVersion),
+        ]
+    }
+
+    /// Returns the level of every lint, taking `--allow`, `--warn` and `--deny` into account.
+    fn levels(&self, lints: &[Box<dyn ManifestLint>]) -> Result<HashMap<&'static str, Level>> {
+        let mut levels = lints
+            .iter()
+            .map(|l| (l.id(), l.default_level()))
+            .collect::<HashMap<_, _>>();
+
+        for (ids, level) in [
+            (&self.allow, Level::Allow),
+            (&self.warn, Level::Warn),
+            (&self.deny, Level::Deny),
+        ] {
+            for id in ids {
+                let Some(entry) = levels.get_mut(id.as_str()) else {
+                    bail!("Unknown lint `{id}`, run `diener lint --list` to see all lints.");
+                };
+                *entry = level;
+            }
+        }
+
+        Ok(levels)
+    }
+}
+
+/// Returns the JSON reports of `findings`.
+pub(crate) fn reports<'a>(findings: &'a [(&'static str, Level, Finding)]) -> Vec<Report<'a>> {
+    findings
+        .iter()
+        .map(|(lint, level, finding)| Report {
+            lint,
+            level: *level,
+            manifest: &finding.manifest,
+            message: &finding.message,
+        })
+        .collect()
+}
+
+/// Print `findings` in the given `format`.
+fn print_findings(findings: &[(&'static str, Level, Finding)], format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            for (lint, level, finding) in findings {
+                println!(
+                    "{}[{}]: {}: {}",
+                    level.label(),
+                    lint,
+                    finding.manifest.display(),
+                    finding.message
+                );
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&reports(findings))?),
+        OutputFormat::Github => {
+            let cwd = current_dir().unwrap_or_default();
+            for (lint, level, finding) in findings {
+                let file = finding
+                    .manifest
+                    .strip_prefix(&cwd)
+                    .unwrap_or(&finding.manifest);
+                println!(
+                    "::{} file={}::{} ({})",
+                    level.label(),
+                    manifest_path(file),
+                    finding.message,
+                    lint
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,235 @@
+use super::{Finding, ManifestLint, Manifests};
+use crate::{context::RunContext, editor};
+use anyhow::Result;
+use toml_edit::{Decor, Document, Item, Value};
+
+/// The urls of the crates.io index, which is the `crates-io` patch target.
+const CRATES_IO_INDEXES: &[&str] = &[
+    "https://github.com/rust-lang/crates.io-index",
+    "https://index.crates.io/",
+];
+
+/// Entries of the deprecated `[replace]` table, which `[patch]` superseded.
+pub(crate) struct DeprecatedReplace;
+
+impl ManifestLint for DeprecatedReplace {
+    fn id(&self) -> &'static str {
+        "deprecated-replace"
+    }
+
+    fn description(&self) -> &'static str {
+        "Entries of the deprecated `[replace]` table."
+    }
+
+    fn check(&self, manifests: &Manifests) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for (manifest, doc) in &manifests.docs {
+            for spec in replaced_specs(doc) {
+                let message = match migration(doc, &spec) {
+                    Ok(Migration { target, .. }) => format!(
+                        "`{spec}` uses the deprecated `[replace]`, use `[patch.{target}]` instead."
+                    ),
+                    Err(reason) => format!(
+                        "`{spec}` uses the deprecated `[replace]` and can not be moved to \
+                         `[patch]` automatically: {reason}."
+                    ),
+                };
+                findings.push(Finding {
+                    manifest: manifest.clone(),
+                    message,
+                });
+            }
+        }
+        findings
+    }
+
+    /// Moves the entries to `[patch]`, the table of the replaced source.
+    fn fix(&self, manifests: &mut Manifests, _: &mut RunContext) -> Result<bool> {
+        for (manifest, doc) in manifests.docs.iter_mut() {
+            // The comments above `[replace]` and its position go to the first `[patch]` table
+            // the entries are moved to, or to the first moved entry if the table exists.
+            let mut replace_header = doc
+                .get("replace")
+                .and_then(Item::as_table)
+                .map(|r| (r.decor().clone(), r.position()));
+            for spec in replaced_specs(doc) {
+                let Ok(migration) = migration(doc, &spec) else {
+                    continue;
+                };
+                let replace = doc["replace"]
+                    .as_table_mut()
+                    .expect("`replaced_specs` only returns entries of tables; qed");
+                // The key of a `[replace."spec"]` table is part of its header, only the comments
+                // above the header fit an entry.
+                let mut key_decor = match &replace[spec.as_str()] {
+                    Item::Table(table) => comments(table.decor()).map(|c| Decor::new(c, " ")),
+                    _ => replace.key_decor(&spec).cloned(),
+                };
+                let Some(entry) = replace.remove(&spec) else {
+                    continue;
+                };
+                let mut entry = match entry {
+                    Item::Table(table) => {
+                        let mut entry = table.into_inline_table();
+                        entry.fmt();
+                        Value::InlineTable(entry).decorated(" ", "")
+                    }
+                    entry => entry.into_value().expect("Checked by `migration`; qed"),
+                };
+                if entry.decor().prefix().is_none() {
+                    entry.decor_mut().set_prefix(" ");
+                }
+
+                let exists = doc
+                    .get("patch")
+                    .and_then(|p| p.get(&migration.target))
+                    .is_some();
+                let table = editor::patch_table(doc, &migration.target)?;
+                match replace_header.take() {
+                    Some((decor, position)) if !exists => {
+                        *table.decor_mut() = decor;
+                        if let Some(position) = position {
+                            table.set_position(position);
+                        }
+                    }
+                    Some((decor, _)) => {
+                        if let Some(comments) = comments(&decor) {
+                            let key = key_decor.get_or_insert_with(|| Decor::new("", " "));
+                            let prefix = key.prefix().and_then(|p| p.as_str()).unwrap_or_default();
+                            key.set_prefix(format!("{comments}{prefix}"));
+                        }
+                    }
+                    None => {}
+                }
+                table.insert(&migration.name, Item::Value(entry));
+                if let (Some(decor), Some(new)) = (key_decor, table.key_decor_mut(&migration.name))
+                {
+                    *new = decor;
+                }
+                log::info!(
+                    "{}: Moved `{}` from `[replace]` to `[patch.{}]`.",
+                    manifest.display(),
+                    spec,
+                    migration.target
+                );
+                if let Some(version) = migration.version {
+                    log::warn!(
+                        "{}: `[replace]` forced `{}` {} on all dependents, `[patch]` is only used \
+                         by the dependents whose version requirement the patched crate matches.",
+                        manifest.display(),
+                        migration.name,
+                        version
+                    );
+                }
+            }
+
+            if doc
+                .get("replace")
+                .and_then(Item::as_table)
+                .is_some_and(|r| r.is_empty())
+            {
+                doc.remove("replace");
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Returns the comment lines of the prefix of `decor`, without the blank lines before them.
+fn comments(decor: &Decor) -> Option<String> {
+    let prefix = decor.prefix()?.as_str()?;
+    prefix.contains('#').then(|| {
+        prefix
+            .trim_start_matches(['\n', '\r', ' ', '\t'])
+            .to_string()
+    })
+}
+
+/// How a `[replace]` entry is moved to `[patch]`.
+struct Migration {
+    /// The patch target, `crates-io` or the url of the replaced source.
+    target: String,
+    /// The name of the replaced package.
+    name: String,
+    /// The replaced version, if the entry is restricted to one.
+    version: Option<String>,
+}
+
+/// Returns the package ID specs of all entries of `[replace]` in `doc`.
+fn replaced_specs(doc: &Document) -> Vec<String> {
+    doc.get("replace")
+        .and_then(Item::as_table)
+        .map(|r| r.iter().map(|(spec, _)| spec.to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// Returns how the `[replace]` entry `spec` of `doc` is moved to `[patch]`, or why it can't be.
+fn migration(doc: &Document, spec: &str) -> Result<Migration, String> {
+    let migration = parse_spec(spec).ok_or("the package ID spec is not understood")?;
+    let entry = &doc["replace"][spec];
+    let Some(source) = entry.as_table_like() else {
+        return Err("the replacement is not a table".into());
+    };
+    if source.get("git").and_then(|g| g.as_str()) == Some(migration.target.as_str()) {
+        return Err("`[patch]` can't point to the source it patches".into());
+    }
+    if doc
+        .get("patch")
+        .and_then(|p| p.get(&migration.target))
+        .and_then(|t| t.get(&migration.name))
+        .is_some()
+    {
+        return Err(format!(
+            "`[patch.{}]` already patches `{}`",
+            migration.target, migration.name
+        ));
+    }
+    Ok(migration)
+}
+
+/// Parses a package ID spec like `foo:1.0.0`, `foo@1.0.0` or
+/// `https://github.com/org/repo#foo@1.0.0`.
+fn parse_spec(spec: &str) -> Option<Migration> {
+    let (source, package) = match spec.rsplit_once('#') {
+        Some((source, package)) => (Some(source), package),
+        None if spec.contains("://") => (Some(spec), ""),
+        None => (None, spec),
+    };
+    let (name, version) = match package.split_once([':', '@']) {
+        Some((name, version)) => (name, Some(version)),
+        // Specs with a source may only give the version, the name is the one of the repository.
+        None if package.starts_with(|c: char| c.is_ascii_digit()) => ("", Some(package)),
+        None => (package, None),
+    };
+
+    let source = source.map(|s| {
+        let s = s
+            .trim_start_matches("git+")
+            .trim_start_matches("registry+")
+            .trim_start_matches("sparse+");
+        s.split('?').next().unwrap_or(s)
+    });
+    let name = match (name, source) {
+        ("", Some(source)) => source
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()?
+            .trim_end_matches(".git"),
+        (name, _) => name,
+    };
+    if name.is_empty() {
+        return None;
+    }
+
+    let target = match source {
+        None => "crates-io",
+        Some(url) if CRATES_IO_INDEXES.contains(&url) => "crates-io",
+        Some(url) => url,
+    };
+    Some(Migration {
+        target: target.into(),
+        name: name.into(),
+        version: version.map(Into::into),
+    })
+}
@@ -0,0 +1,150 @@
+use super::{Finding, ManifestLint, Manifests};
+use crate::{
+    manifest::{dependency_package, dependency_tables},
+    workspace_deps::workspace_relative,
+};
+use std::{collections::BTreeMap, path::Path};
+use toml_edit::Item;
+
+/// The keys of a dependency that must match between a member and `[workspace.dependencies]`.
+const COMPARED_KEYS: &[&str] = &[
+    "git",
+    "branch",
+    "tag",
+    "rev",
+    "path",
+    "registry",
+    "version",
+    "default-features",
+];
+
+/// Packages that are declared more than once in conflicting ways.
+///
+/// Reports members that declare a package of `[workspace.dependencies]` themselves with a
+/// different source, version or `default-features`, and dependency tables that declare the same
+/// package under different aliases.
+pub(crate) struct DuplicateDependency;
+
+impl ManifestLint for DuplicateDependency {
+    fn id(&self) -> &'static str {
+        "duplicate-dependency"
+    }
+
+    fn description(&self) -> &'static str {
+        "Packages declared both by a member and in `[workspace.dependencies]` with conflicting \
+         specifications, or twice under different aliases."
+    }
+
+    fn check(&self, manifests: &Manifests) -> Vec<Finding> {
+        let root_manifest = manifests.root.join("Cargo.toml");
+        // The entries of `[workspace.dependencies]` by package name.
+        let workspace = manifests
+            .docs
+            .get(&root_manifest)
+            .and_then(|doc| doc.get("workspace")?.get("dependencies")?.as_table())
+            .map(|deps| {
+                deps.iter()
+                    .map(|(key, dep)| {
+                        (
+                            package(key, dep),
+                            (key, spec(&root_manifest, dep, &manifests.root)),
+                        )
+                    })
+                    .collect::<BTreeMap<_, _>>()
+            })
+            .unwrap_or_default();
+
+        let mut findings = Vec::new();
+        for (manifest, doc) in &manifests.docs {
+            for (table, deps) in dependency_tables(doc) {
+                let mut by_package = BTreeMap::<&str, Vec<&str>>::new();
+                for (key, dep) in deps.iter() {
+                    by_package.entry(package(key, dep)).or_default().push(key);
+
+                    let inherited = dep.get("workspace").and_then(|w| w.as_bool()) == Some(true);
+                    if table == "workspace.dependencies" || inherited {
+                        continue;
+                    }
+                    let Some((workspace_key, workspace_spec)) = workspace.get(package(key, dep))
+                    else {
+                        continue;
+                    };
+
+                    let spec = spec(manifest, dep, &manifests.root);
+                    let conflicts = COMPARED_KEYS
+                        .iter()
+                        .filter(|k| spec.get(*k) != workspace_spec.get(*k))
+                        .map(|k| {
+                            let show = |v: Option<&String>| {
+                                v.map_or_else(|| "unset".into(), |v| format!("`{v}`"))
+                            };
+                            format!(
+                                "`{k}` {} vs {}",
+                                show(spec.get(*k)),
+                                show(workspace_spec.get(*k))
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    if !conflicts.is_empty() {
+                        findings.push(Finding {
+                            manifest: manifest.clone(),
+                            message: format!(
+                                "`{key}` in `[{table}]` conflicts with `{workspace_key}` in \
+                                 `[workspace.dependencies]`: {}.",
+                                conflicts.join(", ")
+                            ),
+                        });
+                    }
+                }
+
+                for (package, keys) in by_package.into_iter().filter(|(_, k)| k.len() > 1) {
+                    findings.push(Finding {
+                        manifest: manifest.clone(),
+                        message: format!(
+                            "`{package}` is declared {} times in `[{table}]` as {}.",
+                            keys.len(),
+                            keys.iter()
+                                .map(|k| format!("`{k}`"))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                    });
+                }
+            }
+        }
+        findings
+    }
+}
+
+/// Returns the name of the package the dependency `dep` declared as `key` refers to.
+fn package<'a>(key: &'a str, dep: &'a Item) -> &'a str {
+    dep.as_table_like()
+        .map_or(key, |dep| dependency_package(key, dep))
+}
+
+/// Returns the values of the [`COMPARED_KEYS`] of the dependency `dep` of `manifest`.
+///
+/// Paths are made relative to the workspace `root`, so they can be compared between manifests.
+fn spec(manifest: &Path, dep: &Item, root: &Path) -> BTreeMap<&'static str, String> {
+    let Some(table) = dep.as_table_like() else {
+        return dep
+            .as_str()
+            .map(|version| BTreeMap::from([("version", version.to_string())]))
+            .unwrap_or_default();
+    };
+
+    COMPARED_KEYS
+        .iter()
+        .filter_map(|k| {
+            let value = table.get(k)?;
+            let value = match (*k, value.as_str()) {
+                ("path", Some(path)) => {
+                    workspace_relative(manifest, path, root).unwrap_or_else(|| path.into())
+                }
+                (_, Some(value)) => value.to_string(),
+                (_, None) => value.to_string().trim().to_string(),
+            };
+            Some((*k, value))
+        })
+        .collect()
+}
@@ -0,0 +1,196 @@
+use super::{Finding, ManifestLint, Manifests};
+use crate::context::RunContext;
+use anyhow::{anyhow, Result};
+use std::{collections::BTreeMap, path::PathBuf};
+use toml_edit::{value, Document, Item, Table, Value};
+
+/// The fields of `[package]` that should be the same for all members.
+const FIELDS: &[&str] = &["edition", "rust-version"];
+
+/// Members whose `edition` or `rust-version` differs from the rest of the workspace.
+///
+/// The expected value is the one of `[workspace.package]`, or the one most members use.
+pub(crate) struct InconsistentPackage;
+
+/// A member reported by [`InconsistentPackage`].
+struct Offender {
+    manifest: PathBuf,
+    field: &'static str,
+    /// The value of the member, `None` if it doesn't set the field.
+    value: Option<String>,
+    expected: String,
+    /// The expected value is the one of `[workspace.package]`.
+    from_workspace: bool,
+}
+
+impl ManifestLint for InconsistentPackage {
+    fn id(&self) -> &'static str {
+        "inconsistent-package"
+    }
+
+    fn description(&self) -> &'static str {
+        "Members whose `edition` or `rust-version` differs from the rest of the workspace."
+    }
+
+    fn check(&self, manifests: &Manifests) -> Vec<Finding> {
+        offenders(manifests)
+            .into_iter()
+            .map(|o| {
+                let source = if o.from_workspace {
+                    "`[workspace.package]`"
+                } else {
+                    "most members"
+                };
+                let message = match o.value {
+                    Some(value) => format!(
+                        "`{}` is `{}`, but {} use `{}`.",
+                        o.field, value, source, o.expected
+                    ),
+                    None => format!(
+                        "`{}` is not set, but {} use `{}`.",
+                        o.field, source, o.expected
+                    ),
+                };
+                Finding {
+                    manifest: o.manifest,
+                    message,
+                }
+            })
+            .collect()
+    }
+
+    /// Makes the members inherit the field from `[workspace.package]`.
+    ///
+    /// If `[workspace.package]` doesn't set the field yet, it is added with the value most
+    /// members use.
+    fn fix(&self, manifests: &mut Manifests, _ctx: &mut RunContext) -> Result<bool> {
+        let offenders = offenders(manifests);
+        let root = manifests.root.join("Cargo.toml");
+
+        for offender in &offenders {
+            if offender.from_workspace {
+                continue;
+            }
+            let doc = manifests
+                .docs
+                .get_mut(&root)
+                .ok_or_else(|| anyhow!("There is no workspace manifest at {}", root.display()))?;
+            let package = doc
+                .get_mut("workspace")
+                .and_then(Item::as_table_like_mut)
+                .ok_or_else(|| anyhow!("{} has no `[workspace]`", root.display()))?
+                .entry("package")
+                .or_insert(Item::Table(Table::new()))
+                .as_table_like_mut()
+                .ok_or_else(|| anyhow!("`workspace.package` is not a table"))?;
+            if !package.contains_key(offender.field) {
+                package.insert(offender.field, value(offender.expected.as_str()));
+                log::info!(
+                    "Added `{}` `{}` to `[workspace.package]`.",
+                    offender.field,
+                    offender.expected
+                );
+            }
+        }
+
+        for offender in offenders {
+            let Some(package) = manifests
+                .docs
+                .get_mut(&offender.manifest)
+                .and_then(|doc| doc.get_mut("package"))
+                .and_then(Item::as_table_like_mut)
+            else {
+                continue;
+            };
+
+            // Keep a comment behind the old value.
+            let suffix = package
+                .get(offender.field)
+                .and_then(Item::as_value)
+                .and_then(|v| v.decor().suffix()?.as_str().map(ToString::to_string));
+            let mut inherit = Table::new();
+            inherit.set_dotted(true);
+            inherit.insert(
+                "workspace",
+                Item::Value(Value::from(true).decorated(" ", suffix.unwrap_or_default())),
+            );
+            package.insert(offender.field, Item::Table(inherit));
+            log::info!(
+                "{}: `{}` is now inherited from the workspace.",
+                offender.manifest.display(),
+                offender.field
+            );
+        }
+
+        Ok(true)
+    }
+}
+
+/// Returns all members with an unexpected value of one of the [`FIELDS`].
+fn offenders(manifests: &Manifests) -> Vec<Offender> {
+    let workspace = manifests
+        .docs
+        .get(&manifests.root.join("Cargo.toml"))
+        .and_then(|doc| doc.get("workspace"))
+        .and_then(|w| w.get("package"));
+
+    let mut offenders = Vec::new();
+    for field in FIELDS {
+        // The value of every member, `None` if it inherits the field.
+        let values = manifests
+            .docs
+            .iter()
+            .filter_map(|(path, doc)| Some((path, field_value(doc, field)?)))
+            .collect::<Vec<_>>();
+
+        let (expected, from_workspace) = match workspace
+            .and_then(|w| w.get(field))
+            .and_then(|v| v.as_str())
+        {
+            Some(expected) => (expected.to_string(), true),
+            None => {
+                let mut counts = BTreeMap::<&str, usize>::new();
+                for value in values.iter().filter_map(|(_, v)| v.as_deref()) {
+                    *counts.entry(value).or_default() += 1;
+                }
+                // Ties are resolved in favour of the newer value.
+                let Some((expected, _)) = counts.into_iter().max_by_key(|(_, count)| *count) else {
+                    continue;
+                };
+                (expected.to_string(), false)
+            }
+        };
+
+        for (path, value) in values {
+            match value {
+                Some(value) if value == expected => {}
+                // Nothing to compare against without `[workspace.package]`.
+                None if !from_workspace => {}
+                value => offenders.push(Offender {
+                    manifest: path.clone(),
+                    field,
+                    value,
+                    expected: expected.clone(),
+                    from_workspace,
+                }),
+            }
+        }
+    }
+    offenders
+}
+
+/// Returns the value of `field` of the crate in `doc`.
+///
+/// Returns `None` if `doc` has no `[package]` or the field is inherited from the workspace,
+/// `Some(None)` if the field isn't set.
+fn field_value(doc: &Document, field: &str) -> Option<Option<String>> {
+    let value = doc.get("package")?.get(field);
+    if value
+        .and_then(|v| v.get("workspace"))
+        .and_then(|w| w.as_bool())
+        == Some(true)
+    {
+        return None;
+    }
+    Some(value.and_then(|v| v.as_str()).map(ToString::to_string))
+}
@@ -0,0 +1,231 @@
+use super::{Finding, ManifestLint, Manifests};
+use crate::{context::RunContext, manifest::push_formatted_like_last};
+use anyhow::{anyhow, Result};
+use toml_edit::{value, Array, Document, Formatted, Item, Table, Value};
+
+/// Crates that disable the default features of SDK dependencies, but have no `std` feature.
+///
+/// Such a crate can't enable `std` of its dependencies, so it can never be built correctly for
+/// `std`.
+pub(crate) struct MissingStdFeature;
+
+impl ManifestLint for MissingStdFeature {
+    fn id(&self) -> &'static str {
+        "missing-std-feature"
+    }
+
+    fn description(&self) -> &'static str {
+        "Crates with `no_std` SDK dependencies, but without a `std` feature."
+    }
+
+    fn check(&self, manifests: &Manifests) -> Vec<Finding> {
+        manifests
+            .docs
+            .iter()
+            .filter_map(|(manifest, doc)| {
+                let forwards = std_forwards(manifests, doc);
+                let (first, rest) = forwards.split_first()?;
+                let names = rest
+                    .iter()
+                    .fold(format!("`{}`", first.0), |names, (key, _)| {
+                        format!("{names}, `{key}`")
+                    });
+                Some(Finding {
+                    manifest: manifest.clone(),
+                    message: format!(
+                        "The crate has no `std` feature, but disables the default features of \
+                         {names}."
+                    ),
+                })
+            })
+            .collect()
+    }
+
+    /// Adds a `std` feature that forwards to all SDK dependencies without default features.
+    ///
+    /// If the crate got `std` from the default features of other SDK dependencies, `std` is
+    /// enabled by default, so it stays a `std` crate once `sdk-default-features` disables them.
+    fn fix(&self, manifests: &mut Manifests, _ctx: &mut RunContext) -> Result<bool> {
+        let fixes = manifests
+            .docs
+            .iter()
+            .map(|(path, doc)| {
+                let is_std = manifests
+                    .sdk_dependencies(doc)
+                    .iter()
+                    .any(|d| d.default_features());
+                (path.clone(), std_forwards(manifests, doc), is_std)
+            })
+            .filter(|(_, forwards, _)| !forwards.is_empty())
+            .collect::<Vec<_>>();
+
+        for (path, forwards, is_std) in fixes {
+            let doc = manifests
+                .docs
+                .get_mut(&path)
+                .expect("Fixes are collected from `docs`; qed");
+
+            let mut std = forwards
+                .into_iter()
+                .map(|(_, forward)| {
+                    let mut formatted = Formatted::new(forward);
+                    formatted.decor_mut().set_prefix("\n\t");
+                    Value::String(formatted)
+                })
+                .collect::<Array>();
+            std.set_trailing("\n");
+            std.set_trailing_comma(true);
+
+            let features = doc
+                .entry("features")
+                .or_insert(Item::Table(Table::new()))
+                .as_table_like_mut()
+                .ok_or_else(|| anyhow!("`features` in {} is not a table", path.display()))?;
+            if is_std {
+                match features.get_mut("default").and_then(Item::as_array_mut) {
+                    Some(default) => {
+                        if !default.iter().any(|f| f.as_str() == Some("std")) {
+                            push_formatted_like_last(default, "std");
+                        }
+                    }
+                    None => {
+                        features.insert("default", value(Array::from_iter(["std"])));
+                    }
+                }
+            }
+            features.insert("std", value(std));
+            log::info!("{}: Added a `std` feature.", path.display());
+        }
+
+        Ok(true)
+    }
+}
+
+/// Returns the key and the `std` forward of all SDK dependencies of the crate in `doc` that
+/// disable their default features.
+///
+/// Returns nothing if the crate already has a `std` feature.
+fn std_forwards(manifests: &Manifests, doc: &Document) -> Vec<(String, String)> {
+    if doc.get("package").is_none() || doc.get("features").and_then(|f| f.get("std")).is_some() {
+        return Vec::new();
+    }
+
+    let mut forwards = manifests
+        .sdk_dependencies(doc)
+        .into_iter()
+        .filter(|d| !d.default_features())
+        .map(|d| (d.key.to_string(), d.std_forward()))
+        .collect::<Vec<_>>();
+    forwards.sort();
+    forwards.dedup();
+    forwards
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, filesystem::MemoryFs, lint::sdk_default_features};
+    use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
+
+    /// The manifests of a workspace consisting of `manifest` only.
+    fn manifests(manifest: &str) -> Manifests {
+        Manifests {
+            root: "/ws".into(),
+            docs: BTreeMap::from([("/ws/Cargo.toml".into(), manifest.parse().unwrap())]),
+            repos: Config::default().match_repos().into(),
+        }
+    }
+
+    /// Apply the fixes of `missing-std-feature` and `sdk-default-features` to `manifest`, in the
+    /// order `lint --fix` applies them.
+    fn fix(manifest: &str) -> String {
+        let path = PathBuf::from("/ws/Cargo.toml");
+        let mut manifests = manifests(manifest);
+        let mut ctx = RunContext::new("/ws".into(), Config::default(), Arc::new(MemoryFs::new()));
+        MissingStdFeature.fix(&mut manifests, &mut ctx).unwrap();
+        sdk_default_features::SdkDefaultFeatures
+            .fix(&mut manifests, &mut ctx)
+            .unwrap();
+        manifests.docs[&path].to_string()
+    }
+
+    #[test]
+    fn crates_without_std_feature_are_reported() {
+        let manifest = r#"[package]
+name = "pallet"
+
+[dependencies]
+sp-core = { git = "https://github.com/paritytech/polkadot-sdk", default-features = false }
+sp-io = { git = "https://github.com/paritytech/polkadot-sdk", default-features = false }
+serde = { version = "1", default-features = false }
+"#;
+        let findings = MissingStdFeature.check(&manifests(manifest));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            findings[0].message,
+            "The crate has no `std` feature, but disables the default features of `sp-core`, \
+             `sp-io`."
+        );
+
+        let manifest = format!("{manifest}\n[features]\nstd = [\"sp-core/std\"]\n");
+        assert!(MissingStdFeature.check(&manifests(&manifest)).is_empty());
+    }
+
+    #[test]
+    fn std_stays_default_with_sdk_default_features() {
+        let manifest = r#"[package]
+name = "pallet"
+
+[dependencies]
+sp-core = { git = "https://github.com/paritytech/polkadot-sdk", default-features = false }
+sp-io = { git = "https://github.com/paritytech/polkadot-sdk" }
+"#;
+        assert_eq!(
+            fix(manifest),
+            r#"[package]
+name = "pallet"
+
+[dependencies]
+sp-core = { git = "https://github.com/paritytech/polkadot-sdk", default-features = false }
+sp-io = { git = "https://github.com/paritytech/polkadot-sdk", default-features = false }
+
+[features]
+default = ["std"]
+std = [
+	"sp-core/std",
+	"sp-io/std",
+]
+"#
+        );
+    }
+
+    #[test]
+    fn std_is_added_to_existing_default() {
+        let manifest = r#"[package]
+name = "pallet"
+
+[dependencies]
+sp-core = { git = "https://github.com/paritytech/polkadot-sdk", default-features = false }
+sp-io = { git = "https://github.com/paritytech/polkadot-sdk" }
+
+[features]
+default = ["runtime-benchmarks"]
+runtime-benchmarks = []
+"#;
+        let fixed = fix(manifest);
+        assert!(fixed.contains(r#"default = ["runtime-benchmarks", "std"]"#));
+    }
+
+    #[test]
+    fn no_std_crate_stays_no_std() {
+        let manifest = r#"[package]
+name = "primitives"
+
+[dependencies]
+sp-core = { git = "https://github.com/paritytech/polkadot-sdk", default-features = false }
+"#;
+        let fixed = fix(manifest);
+        assert!(!fixed.contains("default ="));
+        assert!(fixed.contains("std = [\n\t\"sp-core/std\",\n]"));
+    }
+}
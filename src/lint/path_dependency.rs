@@ -0,0 +1,263 @@
+use super::{Finding, ManifestLint, Manifests};
+use crate::{
+    context::RunContext,
+    manifest::{dependency_tables, dependency_tables_mut, set_dependency_key},
+};
+use anyhow::{bail, Result};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use toml_edit::{Document, Item, Value};
+
+/// How `--fix` fixes path dependencies of publishable crates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum Fix {
+    /// Add the version of the dependency.
+    #[default]
+    Version,
+    /// Set `publish = false` in the crate.
+    NoPublish,
+}
+
+impl FromStr for Fix {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "version" => Ok(Self::Version),
+            "no-publish" => Ok(Self::NoPublish),
+            _ => bail!("Unknown path dependency fix `{s}`, expected `version` or `no-publish`."),
+        }
+    }
+}
+
+/// Publishable crates with `path` dependencies without `version`, `cargo publish` rejects them.
+///
+/// Dev-dependencies are ignored, `cargo publish` removes them if they have no `version`.
+pub(crate) struct PathDependency {
+    pub(crate) fix: Fix,
+}
+
+/// A dependency reported by [`PathDependency`].
+struct Offender {
+    manifest: PathBuf,
+    table: String,
+    key: String,
+    /// The dependency is declared with `workspace = true`, the `path` is in
+    /// `[workspace.dependencies]`.
+    inherited: bool,
+    /// The manifest of the dependency, `None` if it isn't part of the workspace.
+    dependency: Option<PathBuf>,
+}
+
+impl ManifestLint for PathDependency {
+    fn id(&self) -> &'static str {
+        "path-dependency"
+    }
+
+    fn description(&self) -> &'static str {
+        "Publishable crates with `path` dependencies without `version`."
+    }
+
+    fn check(&self, manifests: &Manifests) -> Vec<Finding> {
+        offenders(manifests)
+            .into_iter()
+            .map(|o| {
+                let source = if o.inherited {
+                    " through `[workspace.dependencies]`"
+                } else {
+                    ""
+                };
+                Finding {
+                    manifest: o.manifest,
+                    message: format!(
+                        "`{}` in `[{}]` is a path dependency without `version`{}, `cargo \
+                         publish` will fail.",
+                        o.key, o.table, source
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    /// Adds the version of the dependency or sets `publish = false`, see [`Fix`].
+    fn fix(&self, manifests: &mut Manifests, _ctx: &mut RunContext) -> Result<bool> {
+        let root = manifests.root.join("Cargo.toml");
+        for offender in offenders(manifests) {
+            if self.fix == Fix::NoPublish {
+                let Some(package) = manifests
+                    .docs
+                    .get_mut(&offender.manifest)
+                    .and_then(|doc| doc.get_mut("package"))
+                    .and_then(Item::as_table_like_mut)
+                else {
+                    continue;
+                };
+                match package.get_mut("publish").and_then(Item::as_value_mut) {
+                    Some(publish) => {
+                        let decor = publish.decor().clone();
+                        *publish = Value::from(false);
+                        *publish.decor_mut() = decor;
+                    }
+                    None => {
+                        package.insert("publish", toml_edit::value(false));
+                    }
+                }
+                log::info!("{}: Set `publish = false`.", offender.manifest.display());
+                continue;
+            }
+
+            let Some(version) = offender
+                .dependency
+                .as_ref()
+                .and_then(|d| package_version(manifests, d))
+            else {
+                log::warn!(
+                    "{}: Can not add a version to `{}`, the version of the dependency is \
+                     unknown.",
+                    offender.manifest.display(),
+                    offender.key
+                );
+                continue;
+            };
+            let (manifest, table) = if offender.inherited {
+                (&root, "workspace.dependencies")
+            } else {
+                (&offender.manifest, offender.table.as_str())
+            };
+            let Some(dep) = manifests
+                .docs
+                .get_mut(manifest)
+                .and_then(|doc| dependency_tables_mut(doc).find(|(name, _)| name == table))
+                .and_then(|(_, deps)| deps.get_mut(&offender.key))
+            else {
+                continue;
+            };
+            set_dependency_key(dep, "version", version.as_str());
+            log::info!(
+                "{}: Added version `{}` to `{}`.",
+                manifest.display(),
+                version,
+                offender.key
+            );
+        }
+
+        Ok(true)
+    }
+}
+
+/// Returns all path dependencies without `version` of publishable crates.
+fn offenders(manifests: &Manifests) -> Vec<Offender> {
+    let canonical = manifests
+        .docs
+        .keys()
+        .filter_map(|p| Some((p.canonicalize().ok()?, p.clone())))
+        .collect::<HashMap<_, _>>();
+    let root = manifests.root.join("Cargo.toml");
+    let workspace_deps = manifests
+        .docs
+        .get(&root)
+        .and_then(|doc| doc.get("workspace")?.get("dependencies"));
+
+    let mut offenders = Vec::new();
+    for (manifest, doc) in &manifests.docs {
+        if !publishable(manifests, doc) {
+            continue;
+        }
+
+        let tables = dependency_tables(doc).into_iter().filter(|(table, _)| {
+            !table.ends_with("dev-dependencies") && table != "workspace.dependencies"
+        });
+        for (table, deps) in tables {
+            for (key, dep) in deps.iter() {
+                let inherited = dep.get("workspace").and_then(|w| w.as_bool()) == Some(true);
+                let (dep, base) = if inherited {
+                    match workspace_deps.and_then(|deps| deps.get(key)) {
+                        Some(dep) => (dep, &root),
+                        None => continue,
+                    }
+                } else {
+                    (dep, manifest)
+                };
+                let Some(path) = dep
+                    .get("path")
+                    .and_then(|p| p.as_str())
+                    .filter(|_| dep.get("version").is_none())
+                else {
+                    continue;
+                };
+
+                offenders.push(Offender {
+                    manifest: manifest.clone(),
+                    table: table.clone(),
+                    key: key.to_string(),
+                    inherited,
+                    dependency: dependency_manifest(base, path)
+                        .and_then(|d| canonical.get(&d).cloned()),
+                });
+            }
+        }
+    }
+    offenders
+}
+
+/// Returns the canonical path of the manifest of the path dependency `path` of `manifest`.
+fn dependency_manifest(manifest: &Path, path: &str) -> Option<PathBuf> {
+    manifest
+        .parent()?
+        .join(path)
+        .join("Cargo.toml")
+        .canonicalize()
+        .ok()
+}
+
+/// Returns `true` if the crate in `doc` can be published.
+fn publishable(manifests: &Manifests, doc: &Document) -> bool {
+    let Some(package) = doc.get("package") else {
+        return false;
+    };
+
+    let mut publish = package.get("publish");
+    if publish
+        .and_then(|p| p.get("workspace"))
+        .and_then(|w| w.as_bool())
+        == Some(true)
+    {
+        publish = manifests
+            .docs
+            .get(&manifests.root.join("Cargo.toml"))
+            .and_then(|doc| doc.get("workspace")?.get("package")?.get("publish"));
+    }
+
+    match publish {
+        None => true,
+        Some(publish) => {
+            publish.as_bool().unwrap_or(true)
+                && publish
+                    .as_array()
+                    .is_none_or(|registries| !registries.is_empty())
+        }
+    }
+}
+
+/// Returns the version of the crate at `manifest`, resolving `version.workspace = true`.
+fn package_version(manifests: &Manifests, manifest: &Path) -> Option<String> {
+    let version = manifests
+        .docs
+        .get(manifest)?
+        .get("package")?
+        .get("version")?;
+    if version.get("workspace").and_then(|w| w.as_bool()) == Some(true) {
+        return manifests
+            .docs
+            .get(&manifests.root.join("Cargo.toml"))?
+            .get("workspace")?
+            .get("package")?
+            .get("version")?
+            .as_str()
+            .map(ToString::to_string);
+    }
+    version.as_str().map(ToString::to_string)
+}
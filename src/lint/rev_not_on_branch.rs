@@ -0,0 +1,77 @@
+use super::{Finding, ManifestLint, Manifests};
+use crate::{git, manifest::dependency_tables, update::is_managed_dependency};
+use std::collections::{BTreeMap, BTreeSet};
+use toml_edit::Item;
+
+/// Pinned revs of SDK dependencies that are not on the branch given by
+/// `--verify-rev-on-branch`.
+pub(crate) struct RevNotOnBranch {
+    /// The branch all revs must be on, the lint finds nothing without it.
+    pub(crate) branch: Option<String>,
+}
+
+impl ManifestLint for RevNotOnBranch {
+    fn id(&self) -> &'static str {
+        "rev-not-on-branch"
+    }
+
+    fn description(&self) -> &'static str {
+        "Revs of SDK dependencies that are not on the `--verify-rev-on-branch` branch."
+    }
+
+    fn check(&self, manifests: &Manifests) -> Vec<Finding> {
+        let Some(branch) = &self.branch else {
+            return Vec::new();
+        };
+
+        // Every repository is fetched once, for all dependencies pinned to any of its revs.
+        let mut pins = BTreeMap::<&str, Vec<(&_, String, &str)>>::new();
+        for (manifest, doc) in &manifests.docs {
+            for (table, deps) in dependency_tables(doc) {
+                for (key, dep) in deps.iter() {
+                    let Some(dep) = dep
+                        .as_table_like()
+                        .filter(|dep| is_managed_dependency(*dep, &manifests.repos))
+                    else {
+                        continue;
+                    };
+                    let get = |k| dep.get(k).and_then(Item::as_str);
+                    if let (Some(git), Some(rev)) = (get("git"), get("rev")) {
+                        pins.entry(git).or_default().push((
+                            manifest,
+                            format!("`{key}` in `[{table}]`"),
+                            rev,
+                        ));
+                    }
+                }
+            }
+        }
+
+        let mut findings = Vec::new();
+        for (url, pins) in pins {
+            let revs = pins.iter().map(|(_, _, rev)| *rev).collect::<BTreeSet<_>>();
+            match git::revs_not_on_branch(url, branch, revs) {
+                Ok(off_branch) => findings.extend(
+                    pins.iter()
+                        .filter(|(_, _, rev)| off_branch.contains(rev))
+                        .map(|(manifest, dependency, rev)| Finding {
+                            manifest: manifest.to_path_buf(),
+                            message: format!(
+                                "{dependency} is pinned to `{rev}`, which is not on branch \
+                                 `{branch}` of {url}."
+                            ),
+                        }),
+                ),
+                // A repository that can't be fetched is reported instead of passing silently.
+                Err(e) => findings.extend(pins.iter().map(|(manifest, dependency, rev)| Finding {
+                    manifest: manifest.to_path_buf(),
+                    message: format!(
+                        "{dependency} is pinned to `{rev}`, which could not be verified to be on \
+                         branch `{branch}`: {e:#}."
+                    ),
+                })),
+            }
+        }
+        findings
+    }
+}
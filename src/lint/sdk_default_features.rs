@@ -0,0 +1,120 @@
+use super::{Finding, ManifestLint, Manifests, SdkDependency};
+use crate::{
+    context::RunContext,
+    manifest::{dependency_tables_mut, push_formatted_like_last, set_dependency_key},
+};
+use anyhow::Result;
+use toml_edit::{Document, Item};
+
+/// SDK dependencies of crates with a `std` feature that enable their default features.
+///
+/// The default features of SDK crates include `std`, so the crate can't be built for `no_std`
+/// anymore.
+pub(crate) struct SdkDefaultFeatures;
+
+impl ManifestLint for SdkDefaultFeatures {
+    fn id(&self) -> &'static str {
+        "sdk-default-features"
+    }
+
+    fn description(&self) -> &'static str {
+        "SDK dependencies of crates with a `std` feature that enable their default features."
+    }
+
+    fn check(&self, manifests: &Manifests) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for (manifest, doc) in &manifests.docs {
+            for offender in offenders(manifests, doc) {
+                let source = if offender.inherited {
+                    " through `[workspace.dependencies]`"
+                } else {
+                    ""
+                };
+                findings.push(Finding {
+                    manifest: manifest.clone(),
+                    message: format!(
+                        "`{}` in `[{}]` enables its default features{}, but the crate has a \
+                         `std` feature.",
+                        offender.key, offender.table, source
+                    ),
+                });
+            }
+        }
+        findings
+    }
+
+    /// Sets `default-features = false` and forwards the `std` feature to the dependency.
+    ///
+    /// Dependencies inheriting the default features from `[workspace.dependencies]` are not
+    /// fixed, as changing the workspace entry affects all other members as well.
+    fn fix(&self, manifests: &mut Manifests, _ctx: &mut RunContext) -> Result<bool> {
+        // `(manifest, table, key, std forward)` of every dependency to fix.
+        let offenders = manifests
+            .docs
+            .iter()
+            .flat_map(|(path, doc)| {
+                offenders(manifests, doc)
+                    .into_iter()
+                    .filter(|o| !o.inherited)
+                    .map(|o| {
+                        (
+                            path.clone(),
+                            o.table.clone(),
+                            o.key.to_string(),
+                            o.std_forward(),
+                        )
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        for (path, table, key, forward) in offenders {
+            let doc = manifests
+                .docs
+                .get_mut(&path)
+                .expect("Offenders are collected from `docs`; qed");
+
+            let Some(dep) = dependency_tables_mut(doc)
+                .find(|(name, _)| *name == table)
+                .and_then(|(_, deps)| deps.get_mut(&key))
+            else {
+                continue;
+            };
+            if let Some(table) = dep.as_table_like_mut() {
+                table.remove("default_features");
+            }
+            set_dependency_key(dep, "default-features", false);
+
+            if let Some(std) = doc
+                .get_mut("features")
+                .and_then(|f| f.get_mut("std"))
+                .and_then(Item::as_array_mut)
+            {
+                if !std.iter().any(|f| f.as_str() == Some(forward.as_str())) {
+                    push_formatted_like_last(std, forward.as_str());
+                }
+            }
+            log::info!(
+                "{}: Disabled the default features of `{}`.",
+                path.display(),
+                key
+            );
+        }
+
+        Ok(true)
+    }
+}
+
+/// Returns the SDK dependencies of the crate in `doc` that enable their default features.
+///
+/// Returns nothing if the crate has no `std` feature.
+fn offenders<'a>(manifests: &'a Manifests, doc: &'a Document) -> Vec<SdkDependency<'a>> {
+    if doc.get("package").is_none() || doc.get("features").and_then(|f| f.get("std")).is_none() {
+        return Vec::new();
+    }
+
+    manifests
+        .sdk_dependencies(doc)
+        .into_iter()
+        .filter(SdkDependency::default_features)
+        .collect()
+}
@@ -0,0 +1,63 @@
+use super::{Finding, ManifestLint, Manifests};
+use crate::manifest::dependency_tables;
+use std::collections::HashSet;
+
+/// Feature entries like `foo/std` or `dep:foo` that refer to a dependency that doesn't exist.
+///
+/// These are usually left behind when a dependency is removed.
+pub(crate) struct UnknownFeatureDependency;
+
+impl ManifestLint for UnknownFeatureDependency {
+    fn id(&self) -> &'static str {
+        "unknown-feature-dependency"
+    }
+
+    fn description(&self) -> &'static str {
+        "Feature entries like `foo/std` or `dep:foo` where `foo` is not a dependency."
+    }
+
+    fn check(&self, manifests: &Manifests) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for (manifest, doc) in &manifests.docs {
+            let Some(features) = doc.get("features").and_then(|f| f.as_table_like()) else {
+                continue;
+            };
+            let dependencies = dependency_tables(doc)
+                .into_iter()
+                .filter(|(table, _)| table != "workspace.dependencies")
+                .flat_map(|(_, deps)| deps.iter().map(|(key, _)| key))
+                .collect::<HashSet<_>>();
+
+            for (feature, entries) in features.iter() {
+                let entries = entries.as_array().into_iter().flat_map(|a| a.iter());
+                for entry in entries.filter_map(|e| e.as_str()) {
+                    let Some(dependency) = feature_dependency(entry) else {
+                        continue;
+                    };
+                    if !dependencies.contains(dependency) {
+                        findings.push(Finding {
+                            manifest: manifest.clone(),
+                            message: format!(
+                                "Feature `{feature}` refers to `{entry}`, but `{dependency}` is \
+                                 not a dependency."
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+        findings
+    }
+}
+
+/// Returns the dependency the feature entry refers to, `None` if it refers to a feature.
+///
+/// Supports `dep:foo`, `foo/bar` and `foo?/bar`.
+fn feature_dependency(entry: &str) -> Option<&str> {
+    if let Some(dependency) = entry.strip_prefix("dep:") {
+        return Some(dependency);
+    }
+
+    let (dependency, _) = entry.split_once('/')?;
+    Some(dependency.strip_suffix('?').unwrap_or(dependency))
+}
@@ -0,0 +1,109 @@
+use super::{Finding, ManifestLint, Manifests};
+use crate::{
+    context::RunContext,
+    git,
+    manifest::{dependency_tables, dependency_tables_mut, set_dependency_key},
+};
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use toml_edit::TableLike;
+
+/// The keys that pin a git dependency.
+const PIN_KEYS: &[&str] = &["branch", "tag", "rev"];
+
+/// Git dependencies without `branch`, `tag` or `rev`, which follow the default branch.
+pub(crate) struct UnpinnedGit;
+
+impl ManifestLint for UnpinnedGit {
+    fn id(&self) -> &'static str {
+        "unpinned-git"
+    }
+
+    fn description(&self) -> &'static str {
+        "Git dependencies without `branch`, `tag` or `rev`."
+    }
+
+    fn check(&self, manifests: &Manifests) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for (manifest, doc) in &manifests.docs {
+            for (table, deps) in dependency_tables(doc) {
+                for (key, dep) in deps.iter() {
+                    let Some(git) = dep.as_table_like().and_then(unpinned) else {
+                        continue;
+                    };
+                    findings.push(Finding {
+                        manifest: manifest.clone(),
+                        message: format!(
+                            "`{key}` in `[{table}]` follows the default branch of {git}."
+                        ),
+                    });
+                }
+            }
+        }
+        findings
+    }
+
+    /// Pins the dependencies to the commit the default branch currently points to.
+    ///
+    /// Manifests with a dependency whose default branch can't be resolved are recorded as failed,
+    /// the other dependencies are still pinned.
+    fn fix(&self, manifests: &mut Manifests, ctx: &mut RunContext) -> Result<bool> {
+        // The head of every repository, `None` if resolving it failed.
+        let mut heads = HashMap::<String, Option<String>>::new();
+        for (manifest, doc) in manifests.docs.iter_mut() {
+            let mut failed = false;
+            for (_, deps) in dependency_tables_mut(doc) {
+                for (key, dep) in deps.iter_mut() {
+                    let Some(git) = dep.as_table_like().and_then(unpinned).map(String::from) else {
+                        continue;
+                    };
+
+                    let rev = match heads.get(&git) {
+                        Some(rev) => rev.clone(),
+                        None => {
+                            ctx.ensure_online("Pinning git dependencies")?;
+                            let rev = git::default_head(&git)
+                                .with_context(|| {
+                                    format!(
+                                        "Failed to pin `{}` in {}",
+                                        key.get(),
+                                        manifest.display()
+                                    )
+                                })
+                                .map_err(|e| {
+                                    ctx.failures.push((manifest.clone(), e));
+                                    failed = true;
+                                })
+                                .ok();
+                            heads.entry(git.clone()).or_insert(rev).clone()
+                        }
+                    };
+                    let Some(rev) = rev else {
+                        if !failed {
+                            let error = anyhow!(
+                                "Failed to pin `{}` in {}, the default branch of {} could not be \
+                                 resolved",
+                                key.get(),
+                                manifest.display(),
+                                git
+                            );
+                            ctx.failures.push((manifest.clone(), error));
+                            failed = true;
+                        }
+                        continue;
+                    };
+                    set_dependency_key(dep, "rev", rev.as_str());
+                    log::info!("{}: Pinned `{}` to {}.", manifest.display(), key.get(), rev);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Returns the `git` url of `dep` if it is a git dependency that isn't pinned.
+fn unpinned(dep: &dyn TableLike) -> Option<&str> {
+    let git = dep.get("git")?.as_str()?;
+    (!PIN_KEYS.iter().any(|k| dep.contains_key(k))).then_some(git)
+}
@@ -0,0 +1,35 @@
+use super::{Finding, ManifestLint, Manifests};
+use crate::manifest::dependency_tables;
+
+/// Dependencies with `version = "*"`, which `cargo publish` rejects.
+pub(crate) struct WildcardVersion;
+
+impl ManifestLint for WildcardVersion {
+    fn id(&self) -> &'static str {
+        "wildcard-version"
+    }
+
+    fn description(&self) -> &'static str {
+        "Dependencies that accept any version (`*`)."
+    }
+
+    fn check(&self, manifests: &Manifests) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for (manifest, doc) in &manifests.docs {
+            for (table, deps) in dependency_tables(doc) {
+                for (name, dep) in deps.iter() {
+                    let version = dep
+                        .as_str()
+                        .or_else(|| dep.get("version").and_then(|v| v.as_str()));
+                    if version.map(str::trim) == Some("*") {
+                        findings.push(Finding {
+                            manifest: manifest.clone(),
+                            message: format!("`{name}` in `[{table}]` accepts any version."),
+                        });
+                    }
+                }
+            }
+        }
+        findings
+    }
+}
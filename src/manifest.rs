@@ -0,0 +1,580 @@
+use std::path::Path;
+use toml_edit::{Array, Decor, Document, InlineTable, Item, Table, TableLike, Value};
+
+/// Returns `path` in the format written to manifests.
+///
+/// Manifests are shared between platforms, so the path always uses `/` as separator, even if
+/// it was created on Windows. The `\\?\` prefix of verbatim Windows paths is removed, verbatim
+/// UNC paths (`\\?\UNC\server\share`) are written as `//server/share`.
+pub(crate) fn manifest_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    let path = match path.strip_prefix(r"\\?\UNC\") {
+        Some(unc) => format!(r"\\{unc}"),
+        None => path.strip_prefix(r"\\?\").unwrap_or(&path).to_string(),
+    };
+    path.replace('\\', "/")
+}
+
+/// Returns the dependency declared by `item` if it is an inline table (`foo = { .. }`) or uses
+/// dotted keys (`foo.git = ".."`).
+///
+/// Dependencies declared as `[dependencies.foo]` table are not returned.
+pub(crate) fn dependency_mut(item: &mut Item) -> Option<&mut dyn TableLike> {
+    match item {
+        Item::Value(Value::InlineTable(dep)) => Some(dep),
+        Item::Table(dep) if dep.is_dotted() => Some(dep),
+        _ => None,
+    }
+}
+
+/// Returns the name of the package the dependency `dep` declared as `key` refers to.
+///
+/// This is the `package` of renamed dependencies (`alias = { package = "name" }`) and `key`
+/// for all others.
+pub(crate) fn dependency_package<'a>(key: &'a str, dep: &'a dyn TableLike) -> &'a str {
+    dep.get("package").and_then(|p| p.as_str()).unwrap_or(key)
+}
+
+/// Returns all dependency tables of the given manifest together with their name.
+///
+/// This includes `[dependencies]`, `[dev-dependencies]`, `[build-dependencies]`, their
+/// `[target.'cfg(..)'.*]` counterparts and `[workspace.dependencies]`.
+pub(crate) fn dependency_tables_mut(
+    doc: &mut Document,
+) -> impl Iterator<Item = (String, &mut Table)> {
+    doc.as_table_mut().iter_mut().flat_map(|(key, item)| {
+        let tables: Vec<(String, &mut Table)> = match key.get() {
+            "target" => item
+                .as_table_mut()
+                .into_iter()
+                .flat_map(|targets| targets.iter_mut())
+                .filter_map(|(target, t)| t.as_table_mut().map(|t| (target.to_string(), t)))
+                .flat_map(|(target, t)| {
+                    t.iter_mut()
+                        .filter(|(k, _)| k.contains("dependencies"))
+                        .filter_map(move |(k, deps)| {
+                            deps.as_table_mut()
+                                .map(|deps| (format!("target.{target}.{}", k.get()), deps))
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect(),
+            "workspace" => item
+                .as_table_mut()
+                .and_then(|ws| ws.get_mut("dependencies"))
+                .and_then(|deps| deps.as_table_mut())
+                .map(|deps| ("workspace.dependencies".to_string(), deps))
+                .into_iter()
+                .collect(),
+            k if k.contains("dependencies") => item
+                .as_table_mut()
+                .map(|deps| (k.to_string(), deps))
+                .into_iter()
+                .collect(),
+            _ => Vec::new(),
+        };
+        tables
+    })
+}
+
+/// Returns all dependency tables of the given manifest together with their name.
+///
+/// The read-only counterpart of [`dependency_tables_mut`].
+pub(crate) fn dependency_tables(doc: &Document) -> Vec<(String, &Table)> {
+    let mut tables = Vec::new();
+    for (key, item) in doc.iter() {
+        match key {
+            "target" => {
+                let targets = item.as_table().into_iter().flat_map(|targets| {
+                    // Use the key as written, e.g. `'cfg(unix)'`, like `dependency_tables_mut`.
+                    targets
+                        .iter()
+                        .filter_map(|(target, t)| Some((targets.get_key_value(target)?.0, t)))
+                });
+                for (target, t) in targets {
+                    for (k, deps) in t.as_table().into_iter().flat_map(|t| t.iter()) {
+                        if let Some(deps) = deps.as_table().filter(|_| k.contains("dependencies")) {
+                            tables.push((format!("target.{target}.{k}"), deps));
+                        }
+                    }
+                }
+            }
+            "workspace" => {
+                if let Some(deps) = item.get("dependencies").and_then(Item::as_table) {
+                    tables.push(("workspace.dependencies".to_string(), deps));
+                }
+            }
+            k if k.contains("dependencies") => {
+                if let Some(deps) = item.as_table() {
+                    tables.push((k.to_string(), deps));
+                }
+            }
+            _ => {}
+        }
+    }
+    tables
+}
+
+/// Returns all `[patch.*]` tables of the given manifest together with the patch target.
+pub(crate) fn patch_tables_mut(doc: &mut Document) -> impl Iterator<Item = (String, &mut Table)> {
+    doc.get_mut("patch")
+        .and_then(|p| p.as_table_mut())
+        .into_iter()
+        .flat_map(|p| p.iter_mut())
+        .filter_map(|(target, t)| t.as_table_mut().map(|t| (target.to_string(), t)))
+}
+
+/// Rename `old` to `new` in `table` while keeping the position and formatting of the entry.
+pub(crate) fn rename_key(table: &mut Table, old: &str, new: &str) {
+    let order = table.iter().map(|(k, _)| k.to_string()).collect::<Vec<_>>();
+    let decor = table.key_decor(old).cloned();
+    let Some(item) = table.remove(old) else {
+        return;
+    };
+
+    table.insert(new, item);
+    if let (Some(decor), Some(new_decor)) = (decor, table.key_decor_mut(new)) {
+        *new_decor = decor;
+    }
+
+    let index = |k: &str| {
+        let k = if k == new { old } else { k };
+        order.iter().position(|o| o == k).unwrap_or(usize::MAX)
+    };
+    table.sort_values_by(|k0, _, k1, _| index(k0.get()).cmp(&index(k1.get())));
+}
+
+/// Append `value` to `array`, formatted like its last element.
+///
+/// A comment behind the last element is part of the trailing whitespace of the array, it stays
+/// behind that element. Comments in front of the last element are not copied.
+pub(crate) fn push_formatted_like_last(array: &mut Array, value: impl Into<Value>) {
+    let mut value = value.into();
+    if let Some(decor) = array.iter().last().map(|v| v.decor().clone()) {
+        let trailing = array.trailing().as_str().unwrap_or_default().to_string();
+        let (comment, trailing) = trailing.split_at(trailing.rfind('\n').unwrap_or(0));
+
+        let prefix = decor
+            .prefix()
+            .and_then(|p| p.as_str())
+            .map(|p| p.rfind('\n').map_or(p, |i| &p[i..]))
+            .filter(|p| !p.is_empty())
+            .unwrap_or(" ");
+        value.decor_mut().set_prefix(format!("{comment}{prefix}"));
+        if let Some(suffix) = decor.suffix() {
+            value.decor_mut().set_suffix(suffix.clone());
+        }
+        array.set_trailing(trailing);
+    }
+    array.push_formatted(value);
+}
+
+/// Set `key` of the dependency `dep` to `value`.
+///
+/// The formatting of an existing value is kept, new keys are appended. Dependencies that are not
+/// tables are left untouched.
+pub(crate) fn set_dependency_key(dep: &mut Item, key: &str, value: impl Into<Value>) {
+    let value = value.into();
+    if let Some(existing) = dep.get_mut(key).and_then(Item::as_value_mut) {
+        let decor = existing.decor().clone();
+        *existing = value;
+        *existing.decor_mut() = decor;
+        return;
+    }
+
+    match dep {
+        Item::Value(Value::InlineTable(dep)) => {
+            // The space before the closing brace belongs to the last value, keep it at the end.
+            let trailing = dep.iter_mut().last().and_then(|(_, v)| {
+                let trailing = v.decor().suffix()?.as_str()?.to_string();
+                v.decor_mut().set_suffix("");
+                Some(trailing)
+            });
+            dep.insert(
+                key,
+                value.decorated(" ", trailing.unwrap_or_else(|| " ".into())),
+            );
+        }
+        dep => {
+            if let Some(dep) = dep.as_table_like_mut() {
+                dep.insert(key, Item::Value(value));
+            }
+        }
+    }
+}
+
+/// Turn the dependency `dep` declared as version, like `foo = "1.0"`, into an inline table,
+/// `foo = { version = "1.0" }`, so that other keys can be set.
+pub(crate) fn expand_version_dependency(dep: &mut Item) {
+    if let Some(version) = dep.as_value().filter(|v| v.is_str()).cloned() {
+        // The comment after the version belongs to the line, keep it after the table.
+        let suffix = version.decor().suffix().cloned();
+        let mut inline = InlineTable::new();
+        inline.insert("version", version.decorated(" ", " "));
+        let mut inline = Value::InlineTable(inline).decorated(" ", "");
+        if let Some(suffix) = suffix {
+            inline.decor_mut().set_suffix(suffix);
+        }
+        *dep = Item::Value(inline);
+    }
+}
+
+/// Remove `key` from the dependency `dep`.
+///
+/// The space before the closing brace of inline tables is moved to the new last value.
+pub(crate) fn remove_dependency_key(dep: &mut Item, key: &str) {
+    match dep {
+        Item::Value(Value::InlineTable(dep)) => {
+            let is_last = dep.iter().last().is_some_and(|(k, _)| k == key);
+            let Some(removed) = dep.remove(key) else {
+                return;
+            };
+            let trailing = removed.decor().suffix().cloned();
+            if let (true, Some(trailing), Some((_, last))) =
+                (is_last, trailing, dep.iter_mut().last())
+            {
+                last.decor_mut().set_suffix(trailing);
+            }
+        }
+        dep => {
+            if let Some(dep) = dep.as_table_like_mut() {
+                dep.remove(key);
+            }
+        }
+    }
+}
+
+/// Replace the `old` keys of the dependency `dep` by the `new` ones.
+///
+/// The `new` keys take the position of the first of the `old` or `new` keys in `dep`, the
+/// formatting of existing values is kept. Dependencies that are not tables are left untouched.
+pub(crate) fn replace_dependency_keys<'a>(
+    dep: &mut Item,
+    old: &[&str],
+    new: impl IntoIterator<Item = (&'a str, Value)>,
+) {
+    let Some(order) = dep
+        .as_table_like()
+        .map(|d| d.iter().map(|(k, _)| k.to_string()).collect::<Vec<_>>())
+    else {
+        return;
+    };
+    let new = new.into_iter().collect::<Vec<_>>();
+    let is_replaced = |k: &str| old.contains(&k) || new.iter().any(|(n, _)| *n == k);
+    let position = order
+        .iter()
+        .position(|k| is_replaced(k))
+        .unwrap_or(usize::MAX);
+
+    // The first added key takes the spacing of the first removed one, so compact tables like
+    // `{git="..",rev=".."}` stay compact. In tables and with dotted keys every key is on its own
+    // line, the added key also takes the comments around the removed one.
+    let removed = old.iter().filter(|k| !new.iter().any(|(n, _)| n == *k));
+    let template = removed.clone().find_map(|k| {
+        let (_, value) = dep.as_table_like()?.get_key_value(k)?;
+        let decor = value.as_value()?.decor().clone();
+        Some((*k, dep.as_table_like()?.key_decor(k)?.clone(), decor))
+    });
+    let added = new
+        .iter()
+        .map(|(k, _)| *k)
+        .find(|k| !order.iter().any(|o| o == k));
+    // In inline tables the suffix is the space before the `,` or, for the last value, before the
+    // closing brace. They are restored once the keys are in their final order.
+    let suffixes = match &*dep {
+        Item::Value(Value::InlineTable(dep)) => dep
+            .iter()
+            .map(|(k, v)| {
+                let suffix = v
+                    .decor()
+                    .suffix()
+                    .and_then(|s| s.as_str())
+                    .unwrap_or_default();
+                (k.to_string(), suffix.to_string())
+            })
+            .collect::<Vec<_>>(),
+        _ => Vec::new(),
+    };
+
+    for key in removed {
+        remove_dependency_key(dep, key);
+    }
+    let keys = new.iter().map(|(k, _)| *k).collect::<Vec<_>>();
+    for (key, value) in new {
+        set_dependency_key(dep, key, value);
+    }
+    let template_key = template.as_ref().map(|(k, _, _)| *k);
+    if let (Some((_, key_decor, decor)), Some(added), Some(dep)) =
+        (template, added, dep.as_table_like_mut())
+    {
+        if let Some(existing) = dep.key_decor_mut(added) {
+            *existing = key_decor;
+        }
+        if let Some(value) = dep.get_mut(added).and_then(Item::as_value_mut) {
+            *value.decor_mut() = decor;
+        }
+    }
+
+    let index = |k: &str| match keys.iter().position(|n| *n == k) {
+        Some(i) => (position, i),
+        None => (order.iter().position(|o| o == k).unwrap_or(usize::MAX), 0),
+    };
+    match dep {
+        Item::Value(Value::InlineTable(dep)) => {
+            dep.sort_values_by(|k0, _, k1, _| index(k0.get()).cmp(&index(k1.get())));
+
+            // Returns the suffix `key` had when not being the last value.
+            let (trailing, inner) = suffixes
+                .split_last()
+                .map_or((" ", &[][..]), |(last, inner)| (last.1.as_str(), inner));
+            let inner_suffix = |key: &str| {
+                let key = if Some(key) == added {
+                    template_key.unwrap_or(key)
+                } else {
+                    key
+                };
+                inner
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map_or("", |(_, s)| s.as_str())
+            };
+            let len = dep.len();
+            for (i, (key, value)) in dep.iter_mut().enumerate() {
+                let suffix = if i + 1 == len {
+                    trailing
+                } else {
+                    inner_suffix(key.get())
+                };
+                value.decor_mut().set_suffix(suffix);
+            }
+        }
+        Item::Table(dep) => {
+            dep.sort_values_by(|k0, _, k1, _| index(k0.get()).cmp(&index(k1.get())))
+        }
+        _ => {}
+    }
+}
+
+/// Move the comments and blank lines above dependencies declared with dotted keys to the end of
+/// the line before them.
+///
+/// `toml_edit` repeats the decor of the first key of `foo.git = ".."` on every line of `foo`, so a
+/// comment above a dependency with multiple dotted keys would be duplicated on every write.
+/// Printing the document is unchanged by this. Comments above the first entry of the root table
+/// are left alone, there is no line before them.
+pub(crate) fn keep_dotted_comments(doc: &mut Document) {
+    keep_dotted_comments_in(doc.as_table_mut());
+}
+
+fn keep_dotted_comments_in(table: &mut Table) {
+    let keys = table.iter().map(|(k, _)| k.to_string()).collect::<Vec<_>>();
+    // The last key of the body whose line is printed before the current one.
+    let mut previous = None::<String>;
+    for key in keys {
+        match table.get_mut(&key) {
+            Some(Item::Table(t)) if !t.is_dotted() => {
+                keep_dotted_comments_in(t);
+                continue;
+            }
+            Some(Item::ArrayOfTables(tables)) => {
+                tables.iter_mut().for_each(keep_dotted_comments_in);
+                continue;
+            }
+            Some(Item::Table(_)) => {}
+            _ => {
+                previous = Some(key);
+                continue;
+            }
+        }
+
+        let lines = table
+            .key_decor(&key)
+            .and_then(|d| d.prefix())
+            .and_then(|p| p.as_str())
+            .and_then(|p| {
+                p.rfind('\n')
+                    .map(|i| (p[..i].to_string(), p[i + 1..].to_string()))
+            });
+        if let Some((lines, indent)) = lines {
+            let holder = match &previous {
+                Some(previous) => table.get_mut(previous).and_then(last_line_decor),
+                None if !table.is_implicit() && table.position().is_some() => {
+                    Some(table.decor_mut())
+                }
+                None => None,
+            };
+            if let Some(holder) = holder {
+                let suffix = holder
+                    .suffix()
+                    .and_then(|s| s.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                holder.set_suffix(format!("{suffix}\n{lines}"));
+                if let Some(decor) = table.key_decor_mut(&key) {
+                    decor.set_prefix(indent);
+                }
+            }
+        }
+        previous = Some(key);
+    }
+}
+
+/// Returns the decor of the value printed last by the entry `item` of a table body.
+fn last_line_decor(item: &mut Item) -> Option<&mut Decor> {
+    match item {
+        Item::Value(value) => Some(value.decor_mut()),
+        Item::Table(table) if table.is_dotted() => {
+            let (_, last) = table.iter_mut().last()?;
+            last_line_decor(last)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::changes::parse_toml;
+    use toml_edit::value;
+
+    /// Replace the `branch` of `sp-core` in `manifest` by a `tag`, like `update --tag` does.
+    fn branch_to_tag(manifest: &str) -> String {
+        let mut doc = parse_toml(Path::new("Cargo.toml"), manifest).unwrap();
+        let dep = &mut doc["dependencies"]["sp-core"];
+        replace_dependency_keys(dep, &["branch", "tag", "rev"], [("tag", "v1".into())]);
+        doc.to_string()
+    }
+
+    #[test]
+    fn manifest_paths_use_forward_slashes() {
+        for (path, expected) in [
+            ("../polkadot-sdk/substrate", "../polkadot-sdk/substrate"),
+            (r"..\polkadot-sdk\substrate", "../polkadot-sdk/substrate"),
+            (
+                r"..\polkadot-sdk/substrate\frame",
+                "../polkadot-sdk/substrate/frame",
+            ),
+            (r"C:\src\polkadot-sdk", "C:/src/polkadot-sdk"),
+            (r"\\?\C:\src\polkadot-sdk", "C:/src/polkadot-sdk"),
+            (
+                r"\\server\share\polkadot-sdk",
+                "//server/share/polkadot-sdk",
+            ),
+            (
+                r"\\?\UNC\server\share\polkadot-sdk",
+                "//server/share/polkadot-sdk",
+            ),
+        ] {
+            assert_eq!(manifest_path(Path::new(path)), expected, "{path}");
+        }
+    }
+
+    #[test]
+    fn dotted_dependency_keeps_comments() {
+        let manifest = r#"[dependencies]
+serde = "1"
+# The primitives.
+sp-core.git = "https://github.com/paritytech/polkadot-sdk"
+sp-core.branch = "master" # Follows the SDK.
+log = "0.4"
+"#;
+        assert_eq!(
+            branch_to_tag(manifest),
+            r#"[dependencies]
+serde = "1"
+# The primitives.
+sp-core.git = "https://github.com/paritytech/polkadot-sdk"
+sp-core.tag = "v1" # Follows the SDK.
+log = "0.4"
+"#
+        );
+    }
+
+    #[test]
+    fn dotted_dependency_first_in_table_keeps_comments() {
+        let manifest = r#"[dependencies] # All of them.
+
+# The primitives.
+sp-core.git = "https://github.com/paritytech/polkadot-sdk"
+sp-core.branch = "master"
+"#;
+        assert_eq!(
+            branch_to_tag(manifest),
+            r#"[dependencies] # All of them.
+
+# The primitives.
+sp-core.git = "https://github.com/paritytech/polkadot-sdk"
+sp-core.tag = "v1"
+"#
+        );
+    }
+
+    #[test]
+    fn table_dependency_keeps_comments() {
+        let manifest = r#"[dependencies.sp-core]
+git = "https://github.com/paritytech/polkadot-sdk"
+# Moves with the SDK.
+branch = "master" # For now.
+default-features = false
+"#;
+        assert_eq!(
+            branch_to_tag(manifest),
+            r#"[dependencies.sp-core]
+git = "https://github.com/paritytech/polkadot-sdk"
+# Moves with the SDK.
+tag = "v1" # For now.
+default-features = false
+"#
+        );
+    }
+
+    #[test]
+    fn inline_dependency_keeps_spacing() {
+        let manifest = r#"[dependencies]
+sp-core = {git="https://github.com/paritytech/polkadot-sdk",branch="master"} # Pinned.
+"#;
+        assert_eq!(
+            branch_to_tag(manifest),
+            r#"[dependencies]
+sp-core = {git="https://github.com/paritytech/polkadot-sdk",tag="v1"} # Pinned.
+"#
+        );
+    }
+
+    #[test]
+    fn dotted_comments_are_not_duplicated() {
+        let manifest = r#"[dependencies]
+serde = "1"
+
+# The primitives.
+sp-core.git = "https://github.com/paritytech/polkadot-sdk"
+sp-core.branch = "master"
+# The runtime.
+frame-support.git = "https://github.com/paritytech/polkadot-sdk"
+frame-support.branch = "master"
+"#;
+        let mut doc = parse_toml(Path::new("Cargo.toml"), manifest).unwrap();
+        assert_eq!(doc.to_string(), manifest);
+
+        doc["dependencies"]["sp-core"]["default-features"] = value(false);
+        let printed = doc.to_string();
+        assert_eq!(printed.matches("# The primitives.").count(), 1);
+        assert_eq!(printed.matches("# The runtime.").count(), 1);
+    }
+
+    #[test]
+    fn version_dependency_keeps_comment() {
+        let mut doc = parse_toml(
+            Path::new("Cargo.toml"),
+            "[dependencies]\nlog = \"0.4\" # Logs.\n",
+        )
+        .unwrap();
+        let dep = &mut doc["dependencies"]["log"];
+        expand_version_dependency(dep);
+        set_dependency_key(dep, "default-features", false);
+        assert_eq!(
+            doc.to_string(),
+            "[dependencies]\nlog = { version = \"0.4\", default-features = false } # Logs.\n"
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{
+    changes::StagedCaches,
+    failure::{Classify, Failure},
+    filesystem::RealFs,
+    walk::{self, Exclusions},
+};
+use anyhow::{anyhow, Context, Result};
+use cargo_metadata::{Metadata, MetadataCommand};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+/// The directory, relative to the workspace root, the cache is persisted to.
+pub(crate) const CACHE_DIR: &str = ".diener/cache";
+
+/// A cache of `cargo metadata` results.
+///
+/// The results are keyed by the workspace root and the modification times of all manifests
+/// and the `Cargo.lock`, so any change to the workspace invalidates them. With `persist` the
+/// results are also stored under `.diener/cache` in the workspace, to speed up later runs.
+pub(crate) struct MetadataCache {
+    entries: HashMap<u64, Metadata>,
+    persist: bool,
+}
+
+impl MetadataCache {
+    /// Create a new cache, `persist` enables the cache on disk.
+    pub(crate) fn new(persist: bool) -> Self {
+        Self {
+            entries: HashMap::new(),
+            persist,
+        }
+    }
+
+    /// Returns `true` if the results are stored on disk, see `--cache-metadata`.
+    pub(crate) fn persists(&self) -> bool {
+        self.persist
+    }
+
+    /// Returns the metadata of the workspace at `dir`, running `command` if it isn't cached.
+    ///
+    /// `options` are the options of `command` that change its result. The results are persisted
+    /// in `caches`, so they are only written if the changes are applied.
+    pub(crate) fn get(
+        &mut self,
+        dir: &Path,
+        options: &[&str],
+        command: MetadataCommand,
+        caches: &StagedCaches,
+    ) -> Result<Metadata> {
+        let root = workspace_root(dir);
+        let key = cache_key(&root, options);
+
+        if let Some(metadata) = self.entries.get(&key) {
+            log::debug!("Using cached metadata of {}", root.display());
+            return Ok(metadata.clone());
+        }
+
+        let cache_file = root
+            .join(CACHE_DIR)
+            .join(format!("metadata-{key:016x}.json"));
+        if self.persist {
+            if let Some(metadata) = fs::read(&cache_file)
+                .ok()
+                .and_then(|c| serde_json::from_slice::<Metadata>(&c).ok())
+            {
+                log::debug!("Using metadata cached at {}", cache_file.display());
+                self.entries.insert(key, metadata.clone());
+                return Ok(metadata);
+            }
+        }
+
+        let metadata = command
+            .exec()
+            .with_context(|| anyhow!("Failed to get cargo metadata for {}", dir.display()))
+            .failure(Failure::Cargo)?;
+
+        if self.persist {
+            match serde_json::to_string(&metadata) {
+                Ok(content) => caches.write(&cache_file, content),
+                Err(e) => log::warn!(
+                    "Failed to cache metadata at {}: {:?}",
+                    cache_file.display(),
+                    e
+                ),
+            }
+        }
+
+        self.entries.insert(key, metadata.clone());
+        Ok(metadata)
+    }
+}
+
+/// Returns the root of the workspace `dir` belongs to.
+///
+/// This is the closest directory with a `Cargo.toml` containing a `[workspace]`, or `dir`
+/// itself if there is none. Like cargo, a nested workspace is its own root.
+fn workspace_root(dir: &Path) -> PathBuf {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+
+    dir.ancestors()
+        .filter(|d| {
+            fs::read_to_string(d.join("Cargo.toml"))
+                .is_ok_and(|c| c.lines().any(|l| l.trim() == "[workspace]"))
+        })
+        .map(Path::to_path_buf)
+        .next()
+        .unwrap_or(dir)
+}
+
+/// Hash the workspace `root`, the modification times of all its manifests and `options`.
+fn cache_key(root: &Path, options: &[&str]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    root.hash(&mut hasher);
+    options.hash(&mut hasher);
+
+    let lock_file = root.join("Cargo.lock");
+    for file in walk::manifests(&RealFs, root, false, &Exclusions::default()).chain([lock_file]) {
+        file.hash(&mut hasher);
+        fs::metadata(&file)
+            .and_then(|m| m.modified())
+            .ok()
+            .hash(&mut hasher);
+    }
+
+    hasher.finish()
+}
@@ -0,0 +1,193 @@
+use crate::{
+    context::RunContext,
+    filesystem::normalize,
+    manifest::{dependency_tables_mut, manifest_path, patch_tables_mut},
+    workspacify::manifest_iter,
+};
+use anyhow::{anyhow, bail, ensure, Result};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+use toml_edit::{Document, Table, Value};
+
+/// `move-crate` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct MoveCrate {
+    /// The path to the workspace root directory.
+    ///
+    /// Uses the working directory if none is supplied.
+    #[structopt(long)]
+    path: Option<PathBuf>,
+
+    /// The current directory of the crate.
+    from: PathBuf,
+
+    /// The new directory of the crate. Must not exist yet.
+    to: PathBuf,
+}
+
+impl MoveCrate {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        let workspace = ctx.resolve(&ctx.workspace_dir(self.path.as_deref())?);
+
+        let from = ctx.resolve(&self.from);
+        let to = ctx.resolve(&self.to);
+        ensure!(
+            from.join("Cargo.toml").is_file(),
+            "'{}' does not contain a `Cargo.toml`.",
+            from.display()
+        );
+        ensure!(!to.exists(), "'{}' already exists.", to.display());
+        ensure!(
+            from.starts_with(&workspace) && from != workspace,
+            "'{}' is not a crate inside the workspace '{}'.",
+            from.display(),
+            workspace.display()
+        );
+        ensure!(
+            to.starts_with(&workspace),
+            "'{}' is not inside the workspace '{}'.",
+            to.display(),
+            workspace.display()
+        );
+        if to.starts_with(&from) {
+            bail!("Can not move '{}' into itself.", from.display());
+        }
+
+        let remap = |path: &Path| match path.strip_prefix(&from) {
+            Ok(rest) => to.join(rest),
+            Err(_) => path.to_path_buf(),
+        };
+
+        let mut docs = BTreeMap::<PathBuf, (Document, String)>::new();
+        for manifest in manifest_iter(ctx.changes.fs(), &workspace, &ctx.exclusions) {
+            let doc = ctx.changes.read_toml(&manifest)?;
+            let original = doc.to_string();
+            docs.insert(normalize(&manifest), (doc, original));
+        }
+
+        for (manifest, (doc, _)) in docs.iter_mut() {
+            let old_dir = manifest.parent().expect("Manifests are files; qed");
+            let new_dir = remap(old_dir);
+
+            if manifest.parent() == Some(workspace.as_path()) {
+                update_members(doc, &workspace, &remap)?;
+            }
+
+            for (_, table) in patch_tables_mut(doc) {
+                update_paths(table, old_dir, &new_dir, &remap)?;
+            }
+            for (_, table) in dependency_tables_mut(doc) {
+                update_paths(table, old_dir, &new_dir, &remap)?;
+            }
+        }
+
+        ctx.changes.rename(&from, &to);
+
+        for (manifest, (doc, original)) in docs {
+            let content = doc.to_string();
+            if content != original {
+                let manifest = remap(&manifest);
+                log::info!("Updating: {}", manifest.display());
+                ctx.changes.write(manifest, content);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns `target` relative to `dir` in the format used in manifests.
+fn relative(target: &Path, dir: &Path) -> Result<String> {
+    let relpath = pathdiff::diff_paths(target, dir).ok_or_else(|| {
+        anyhow!(
+            "Cannot make {} relative to {}",
+            target.display(),
+            dir.display()
+        )
+    })?;
+
+    if relpath.as_os_str().is_empty() {
+        Ok(".".into())
+    } else {
+        Ok(manifest_path(&relpath))
+    }
+}
+
+/// Replace the string `value` with `new`, keeping its formatting.
+fn set_str(value: &mut Value, new: String) {
+    let decor = value.decor().clone();
+    *value = Value::from(new);
+    *value.decor_mut() = decor;
+}
+
+/// Update all `path` dependencies in `table` of a manifest that moves from `old_dir` to `new_dir`.
+fn update_paths(
+    table: &mut Table,
+    old_dir: &Path,
+    new_dir: &Path,
+    remap: &impl Fn(&Path) -> PathBuf,
+) -> Result<()> {
+    for (_, dep) in table.iter_mut() {
+        let Some(path) = dep
+            .as_table_like_mut()
+            .and_then(|d| d.get_mut("path"))
+            .and_then(|p| p.as_value_mut())
+        else {
+            continue;
+        };
+        let Some(current) = path.as_str() else {
+            continue;
+        };
+
+        let target = remap(&normalize(&old_dir.join(current)));
+        let new = relative(&target, new_dir)?;
+        if new != current {
+            set_str(path, new);
+        }
+    }
+
+    Ok(())
+}
+
+/// Update `workspace.members`, `workspace.default-members` and `workspace.exclude`.
+fn update_members(
+    doc: &mut Document,
+    workspace: &Path,
+    remap: &impl Fn(&Path) -> PathBuf,
+) -> Result<()> {
+    let Some(ws) = doc.get_mut("workspace").and_then(|w| w.as_table_mut()) else {
+        return Ok(());
+    };
+
+    for key in ["members", "default-members", "exclude"] {
+        let Some(entries) = ws.get_mut(key).and_then(|m| m.as_array_mut()) else {
+            continue;
+        };
+
+        for entry in entries.iter_mut() {
+            let Some(current) = entry.as_str() else {
+                continue;
+            };
+            if current.contains('*') {
+                log::warn!(
+                    "`workspace.{}` contains the pattern `{}`, make sure it still matches the moved crate.",
+                    key,
+                    current
+                );
+                continue;
+            }
+
+            let target = remap(&normalize(&workspace.join(current)));
+            let new = relative(&target, workspace)?;
+            if new != current {
+                set_str(entry, new);
+            }
+        }
+    }
+
+    Ok(())
+}
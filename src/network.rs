@@ -0,0 +1,156 @@
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// The proxy and CA bundle used by `curl`, `git` and `cargo`, `None` if not configured.
+///
+/// This is global like the operation log, so every command that touches the network is
+/// configured the same way without passing the settings around.
+static NETWORK: Mutex<Option<Network>> = Mutex::new(None);
+
+/// How the network is reached, configured in `[network]`.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct NetworkConfig {
+    /// The proxy of all requests, used if `HTTPS_PROXY` isn't set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proxy: Option<String>,
+    /// The hosts that are reached without the proxy, used if `NO_PROXY` isn't set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    no_proxy: Option<Vec<String>>,
+    /// A file with the PEM certificates the servers are verified with, relative to the
+    /// configuration file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ca_bundle: Option<PathBuf>,
+}
+
+impl NetworkConfig {
+    /// Returns the configuration with the credentials of the proxy removed, for printing.
+    pub(crate) fn redacted(&self) -> Self {
+        Self {
+            proxy: self.proxy.as_deref().map(redact),
+            ..self.clone()
+        }
+    }
+
+    /// Make the `ca-bundle` relative to `dir`, the directory of the configuration file.
+    pub(crate) fn relative_to(&mut self, dir: &Path) {
+        self.ca_bundle = self.ca_bundle.as_ref().map(|ca| dir.join(ca));
+    }
+
+    /// Returns the settings that are used, the environment takes precedence over the
+    /// configuration.
+    pub(crate) fn resolve(&self) -> Result<Network> {
+        let network = Network {
+            proxy: env_var(&["HTTPS_PROXY", "https_proxy"]).or_else(|| self.proxy.clone()),
+            no_proxy: env_var(&["NO_PROXY", "no_proxy"])
+                .or_else(|| self.no_proxy.as_ref().map(|hosts| hosts.join(","))),
+            ca_bundle: self.ca_bundle.clone(),
+        };
+        if let Some(ca) = &network.ca_bundle {
+            ensure!(
+                ca.is_file(),
+                "The CA bundle {} of `[network]` doesn't exist.",
+                ca.display()
+            );
+        }
+        Ok(network)
+    }
+}
+
+/// The proxy and CA bundle of the network operations.
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Network {
+    proxy: Option<String>,
+    /// The hosts that are reached without the proxy, separated by `,`.
+    no_proxy: Option<String>,
+    ca_bundle: Option<PathBuf>,
+}
+
+impl Network {
+    /// Returns the settings with the credentials of the proxy removed, for printing.
+    pub(crate) fn redacted(&self) -> Self {
+        Self {
+            proxy: self.proxy.as_deref().map(redact),
+            ..self.clone()
+        }
+    }
+
+    /// The proxy, `None` if the network is reached directly.
+    pub(crate) fn proxy(&self) -> Option<&str> {
+        self.proxy.as_deref()
+    }
+
+    /// The hosts that are reached without the proxy, separated by `,`.
+    pub(crate) fn no_proxy(&self) -> Option<&str> {
+        self.no_proxy.as_deref()
+    }
+
+    /// The CA bundle the servers are verified with, `None` for the system certificates.
+    pub(crate) fn ca_bundle(&self) -> Option<&Path> {
+        self.ca_bundle.as_deref()
+    }
+}
+
+/// Use `network` for all following network operations.
+pub(crate) fn enable(network: Network) {
+    if let Some(proxy) = &network.proxy {
+        log::debug!("Using the proxy {}.", redact(proxy));
+    }
+    *NETWORK.lock().expect("Never poisoned; qed") = Some(network);
+}
+
+/// Returns the environment variables that make `curl`, `git` and `cargo` use the proxy and CA
+/// bundle.
+///
+/// All of them read the proxy from the same variables, the CA bundle has a variable per tool.
+pub(crate) fn env() -> Vec<(&'static str, String)> {
+    let network = NETWORK.lock().expect("Never poisoned; qed");
+    let Some(network) = network.as_ref() else {
+        return Vec::new();
+    };
+
+    let mut vars = Vec::new();
+    if let Some(proxy) = &network.proxy {
+        vars.extend([
+            ("HTTPS_PROXY", proxy.clone()),
+            ("https_proxy", proxy.clone()),
+        ]);
+    }
+    if let Some(no_proxy) = &network.no_proxy {
+        vars.extend([
+            ("NO_PROXY", no_proxy.clone()),
+            ("no_proxy", no_proxy.clone()),
+        ]);
+    }
+    if let Some(ca) = &network.ca_bundle {
+        let ca = ca.display().to_string();
+        vars.extend([
+            ("CURL_CA_BUNDLE", ca.clone()),
+            ("GIT_SSL_CAINFO", ca.clone()),
+            ("CARGO_HTTP_CAINFO", ca),
+        ]);
+    }
+    vars
+}
+
+/// Returns the value of the first of the environment variables `vars` that is set.
+fn env_var(vars: &[&str]) -> Option<String> {
+    vars.iter()
+        .find_map(|v| std::env::var(v).ok())
+        .filter(|v| !v.is_empty())
+}
+
+/// Replace the credentials in the proxy url `url` by `***`.
+pub(crate) fn redact(url: &str) -> String {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+    match rest.rsplit_once('@') {
+        Some((_, host)) if scheme.is_empty() => format!("***@{host}"),
+        Some((_, host)) => format!("{scheme}://***@{host}"),
+        None => url.to_string(),
+    }
+}
@@ -0,0 +1,50 @@
+use crate::sbom::rfc3339;
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The file the records are appended to, `None` if `--log-file` isn't given.
+///
+/// This is global like the timings, so the actions can be recorded where they happen.
+static LOG: Mutex<Option<File>> = Mutex::new(None);
+
+/// Start appending the records to the file at `path`, which is created if it doesn't exist.
+pub(crate) fn enable(path: &Path) -> Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| anyhow!("Failed to open the log file at {}", path.display()))?;
+    *LOG.lock().expect("Never poisoned; qed") = Some(file);
+    Ok(())
+}
+
+/// Record `action` with its `details`, which must be a JSON object.
+///
+/// Every record is written as one line with the time and the `action`, and flushed right away,
+/// so the log survives a crash. Does nothing if the log isn't enabled.
+pub(crate) fn record(action: &str, details: Value) {
+    let mut log = LOG.lock().expect("Never poisoned; qed");
+    let Some(file) = log.as_mut() else {
+        return;
+    };
+
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let mut record = json!({ "time": rfc3339(time), "action": action });
+    if let (Some(record), Value::Object(details)) = (record.as_object_mut(), details) {
+        record.extend(details);
+    }
+
+    if let Err(e) = writeln!(file, "{record}").and_then(|_| file.flush()) {
+        log::warn!("Failed to write to the log file: {:?}", e);
+    }
+}
@@ -1,12 +1,17 @@
+use crate::{
+    changes::Changes, config::POLKADOT_SDK_GIT, context::RunContext, diff, editor, git,
+    manifest::manifest_path, oplog,
+};
 use anyhow::{anyhow, bail, Context, Error, Result};
+use regex::Regex;
+use serde_json::json;
 use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     env::current_dir,
-    fs,
     path::{Path, PathBuf},
-    str::FromStr,
 };
 use structopt::StructOpt;
-use toml_edit::{Document, Item, Value};
+use toml_edit::{Document, Table};
 
 enum PatchTarget {
     Crates,
@@ -21,7 +26,13 @@ enum PointTo {
     /// Point to the git branch.
     GitBranch { repository: String, branch: String },
     /// Point to the git commit.
-    GitCommit { repository: String, commit: String },
+    ///
+    /// `branch` is the branch whose head was resolved to `commit` by `--pin`.
+    GitCommit {
+        repository: String,
+        commit: String,
+        branch: Option<String>,
+    },
 }
 
 impl PointTo {
@@ -34,7 +45,11 @@ impl PointTo {
             if let Some(branch) = point_to_git_branch {
                 Ok(Self::GitBranch { repository, branch })
             } else if let Some(commit) = point_to_git_commit {
-                Ok(Self::GitCommit { repository, commit })
+                Ok(Self::GitCommit {
+                    repository,
+                    commit,
+                    branch: None,
+                })
             } else {
                 bail!("`--point-to-git-branch` or `--point-to-git-commit` are required when `--point-to-git` is passed!");
             }
@@ -42,8 +57,34 @@ impl PointTo {
             Ok(Self::Path)
         }
     }
+
+    /// Resolve the head of the branch to point to, so the patches don't float.
+    fn pin(self, ctx: &RunContext) -> Result<Self> {
+        let Self::GitBranch { repository, branch } = self else {
+            return Ok(self);
+        };
+
+        ctx.ensure_online("Pinning `--point-to-git-branch`")?;
+        let commit = git::branch_head(&repository, &branch)?;
+        log::info!(
+            "Pinning branch `{}` of {} to {}.",
+            branch,
+            repository,
+            commit
+        );
+
+        Ok(Self::GitCommit {
+            repository,
+            commit,
+            branch: Some(branch),
+        })
+    }
 }
 
+/// The file, next to the patched manifest, that records the branch every pinned patch was
+/// resolved from.
+const PINS_FILE: &str = ".diener/pins.json";
+
 impl PatchTarget {
     /// Returns the patch target in a toml compatible format.
     fn as_str(&self) -> &str {
@@ -69,6 +110,14 @@ pub struct Patch {
     #[structopt(long)]
     path: Option<PathBuf>,
 
+    /// Add the patches to the manifest of the package with the given name, instead of the
+    /// workspace `Cargo.toml` file.
+    ///
+    /// The package is searched in the workspace at `--path` and its path dependencies. This is
+    /// meant for layouts without a workspace, where every package is its own workspace root.
+    #[structopt(long)]
+    member: Option<String>,
+
     /// The workspace that should be scanned and added to the patch section.
     ///
     /// This will execute `cargo metadata` in the given workspace and add
@@ -101,8 +150,16 @@ pub struct Patch {
     )]
     point_to_git_commit: Option<String>,
 
+    /// Resolve the head of `--point-to-git-branch` and point the patches to this commit.
+    ///
+    /// The branch of every patch is recorded in `.diener/pins.json` next to the manifest, to know
+    /// what to refresh from.
+    #[structopt(long, requires = "point-to-git-branch")]
+    pin: bool,
+
     /// The patch target that should be used.
-    /// The default is the official `polkadot-sdk` repository.
+    /// The default is the `patch-target` of the configuration or the official `polkadot-sdk`
+    /// repository.
     ///
     /// The target is `[patch.TARGET]` in the final `Cargo.toml`.
     #[structopt(
@@ -117,12 +174,47 @@ pub struct Patch {
         conflicts_with_all = &[ "target" ]
     )]
     crates: bool,
+
+    /// Derive the patch targets from the sources the project consumes the crates from.
+    ///
+    /// A crate that is used from `crates.io` is patched in `[patch.crates-io]`, a crate that is
+    /// used from a git repository in `[patch."<git-url>"]`. Crates that are used from both get
+    /// a patch in both. Crates that aren't used at all are not patched.
+    #[structopt(
+        long,
+        conflicts_with_all = &[ "target", "crates" ]
+    )]
+    auto_target: bool,
+
+    /// Remove the patches that point to the workspace of `--crates-to-patch` or the repository
+    /// of `--point-to-git`, but whose crate doesn't exist there anymore.
+    #[structopt(long)]
+    prune: bool,
+
+    /// Only add patches for the crates whose name matches the given regex, e.g. `^(sp|frame)-`.
+    #[structopt(long)]
+    filter_regex: Option<Regex>,
+
+    /// Don't patch crates the project already uses via `path`.
+    ///
+    /// A patch would shadow them, which cargo rejects as `points to the same source`.
+    #[structopt(long)]
+    skip_path_deps: bool,
+
+    /// Warn about patched crates whose version doesn't match the version requirements of the
+    /// project, cargo never applies these patches.
+    #[structopt(long)]
+    warn_version_mismatch: bool,
+
+    /// Only print which patches would be added, changed or removed, without modifying anything.
+    #[structopt(long)]
+    diff: bool,
 }
 
 impl Patch {
     /// Run this subcommand.
-    pub fn run(self) -> Result<()> {
-        let patch_target = self.patch_target();
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        let patch_target = self.patch_target(ctx.config.patch_target());
         let path = self
             .path
             .map(|p| {
@@ -137,52 +229,173 @@ impl Patch {
             })?;
 
         // Get the path to the `Cargo.toml` where we need to add the patches
-        let cargo_toml_to_patch = workspace_root_package(&path)?;
+        let cargo_toml_to_patch = workspace_root_package(&path, self.member.as_deref(), ctx)?;
 
         let point_to = PointTo::from_cli(
             self.point_to_git,
             self.point_to_git_branch,
             self.point_to_git_commit,
         )?;
+        let point_to = if self.pin {
+            point_to.pin(ctx)?
+        } else {
+            point_to
+        };
+
+        let mut packages = workspace_packages(&self.crates_to_patch, ctx)?.collect::<Vec<_>>();
+        // All crates of the checkout are kept, even the ones that are skipped or moved to another
+        // target by `--auto-target` below.
+        let prune = if self.prune {
+            Some(Prune {
+                source: ctx.metadata(&self.crates_to_patch)?.workspace_root.into(),
+                names: packages.iter().map(|p| p.name.clone()).collect(),
+            })
+        } else {
+            None
+        };
+        if self.skip_path_deps {
+            let used = path_dependencies(&cargo_toml_to_patch, ctx)?;
+            packages.retain(|p| {
+                let used = used.contains(&p.name);
+                if used {
+                    log::info!("Not patching `{}`, it's already used via path.", p.name);
+                }
+                !used
+            });
+        }
+        if self.warn_version_mismatch {
+            warn_version_mismatches(
+                &cargo_toml_to_patch,
+                &packages,
+                self.filter_regex.as_ref(),
+                ctx,
+            )?;
+        }
+
+        let targets = if self.auto_target {
+            consumed_sources(&cargo_toml_to_patch, packages, &patch_target, ctx)?
+        } else {
+            vec![(patch_target, packages)]
+        };
+
+        // With `--diff` the modifications are only staged to compare them.
+        let mut scratch = ctx.changes.scratch();
+        let changes = if self.diff {
+            &mut scratch
+        } else {
+            &mut ctx.changes
+        };
+
+        for (patch_target, packages) in targets {
+            let before = patch_entries(&changes.read_toml(&cargo_toml_to_patch)?, &patch_target);
+            add_patches_for_packages(
+                &cargo_toml_to_patch,
+                &patch_target,
+                packages.into_iter(),
+                &point_to,
+                prune.as_ref(),
+                self.filter_regex.as_ref(),
+                changes,
+            )?;
 
-        add_patches_for_packages(
-            &cargo_toml_to_patch,
-            &patch_target,
-            workspace_packages(&self.crates_to_patch)?,
-            point_to,
-        )
+            if self.diff {
+                let after = patch_entries(&changes.read_toml(&cargo_toml_to_patch)?, &patch_target);
+                print_patch_diff(&patch_target, &before, &after, ctx.color);
+            }
+        }
+
+        Ok(())
     }
 
-    fn patch_target(&self) -> PatchTarget {
+    /// Returns the patch target, falling back to the `configured` one.
+    fn patch_target(&self, configured: Option<&str>) -> PatchTarget {
         if let Some(ref custom) = self.target {
             PatchTarget::Custom(custom.clone())
         } else if self.crates {
             PatchTarget::Crates
+        } else if let Some(configured) = configured {
+            PatchTarget::Custom(configured.into())
         } else {
-            PatchTarget::Git("https://github.com/paritytech/polkadot-sdk".into())
+            PatchTarget::Git(POLKADOT_SDK_GIT.into())
         }
     }
 }
 
-fn workspace_root_package(path: &Path) -> Result<PathBuf> {
+/// Returns the `Cargo.toml` at `path` that should get the patches.
+///
+/// This is the workspace root, which may be a virtual manifest, or the manifest of `member`.
+fn workspace_root_package(
+    path: &Path,
+    member: Option<&str>,
+    ctx: &mut RunContext,
+) -> Result<PathBuf> {
+    let dir = if path.ends_with("Cargo.toml") {
+        path.parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."))
+    } else {
+        path
+    };
+    if !dir.join("Cargo.toml").is_file() {
+        bail!("No `Cargo.toml` found in {}!", dir.display());
+    }
+
+    if let Some(member) = member {
+        let metadata = ctx.metadata(dir)?;
+        let package = metadata
+            .packages
+            .iter()
+            .find(|p| p.source.is_none() && p.name == member)
+            .ok_or_else(|| {
+                anyhow!(
+                    "`{}` is neither a member nor a path dependency of the workspace at {}!",
+                    member,
+                    metadata.workspace_root
+                )
+            })?;
+        let manifest = package.manifest_path.clone().into_std_path_buf();
+        warn_if_not_workspace_root(&manifest, ctx);
+        return Ok(manifest);
+    }
+
     if path.ends_with("Cargo.toml") {
+        warn_if_not_workspace_root(path, ctx);
         return Ok(path.into());
     }
 
-    let metadata = cargo_metadata::MetadataCommand::new()
-        .current_dir(path)
-        .exec()
-        .with_context(|| "Failed to get cargo metadata for workspace")?;
+    let metadata = ctx.metadata(dir)?;
 
     Ok(metadata.workspace_root.join("Cargo.toml").into())
 }
 
+/// Cargo ignores patches outside of the workspace root, warn if `manifest` isn't one.
+fn warn_if_not_workspace_root(manifest: &Path, ctx: &mut RunContext) {
+    let Some(dir) = manifest
+        .canonicalize()
+        .ok()
+        .and_then(|m| m.parent().map(Path::to_path_buf))
+    else {
+        return;
+    };
+
+    match ctx.metadata(&dir) {
+        Ok(metadata) if metadata.workspace_root != dir => log::warn!(
+            "{} is not the root of its workspace, cargo ignores the patches in it. \
+             The root of the workspace is {}.",
+            manifest.display(),
+            metadata.workspace_root
+        ),
+        Ok(_) => {}
+        Err(e) => log::debug!("Failed to find the workspace of {}: {:?}", dir.display(), e),
+    }
+}
+
 /// Returns all package names of the given `workspace`.
-fn workspace_packages(workspace: &Path) -> Result<impl Iterator<Item = cargo_metadata::Package>> {
-    let metadata = cargo_metadata::MetadataCommand::new()
-        .current_dir(workspace)
-        .exec()
-        .with_context(|| "Failed to get cargo metadata for workspace.")?;
+fn workspace_packages(
+    workspace: &Path,
+    ctx: &mut RunContext,
+) -> Result<impl Iterator<Item = cargo_metadata::Package>> {
+    let metadata = ctx.metadata(workspace)?;
 
     Ok(metadata
         .workspace_members
@@ -191,39 +404,176 @@ fn workspace_packages(workspace: &Path) -> Result<impl Iterator<Item = cargo_met
         .map(move |p| metadata[&p].clone()))
 }
 
+/// Returns the names of all packages the workspace of `cargo_toml` uses via `path`.
+///
+/// This includes the workspace members.
+fn path_dependencies(cargo_toml: &Path, ctx: &mut RunContext) -> Result<HashSet<String>> {
+    let metadata = ctx.metadata(cargo_toml.parent().unwrap_or_else(|| Path::new(".")))?;
+
+    Ok(metadata
+        .packages
+        .iter()
+        .filter(|p| p.source.is_none())
+        .map(|p| p.name.clone())
+        .collect())
+}
+
+/// Warn about every version requirement of the workspace of `cargo_toml` on one of `packages`
+/// that the version of the package doesn't match.
+fn warn_version_mismatches(
+    cargo_toml: &Path,
+    packages: &[cargo_metadata::Package],
+    filter: Option<&Regex>,
+    ctx: &mut RunContext,
+) -> Result<()> {
+    let metadata = ctx.metadata(cargo_toml.parent().unwrap_or_else(|| Path::new(".")))?;
+
+    let versions = packages
+        .iter()
+        .filter(|p| filter.is_none_or(|f| f.is_match(&p.name)))
+        .map(|p| (p.name.as_str(), &p.version))
+        .collect::<HashMap<_, _>>();
+
+    for package in &metadata.packages {
+        for dep in package.dependencies.iter().filter(|d| d.path.is_none()) {
+            let Some(version) = versions.get(dep.name.as_str()) else {
+                continue;
+            };
+
+            if !dep.req.matches(version) {
+                log::warn!(
+                    "`{}` requires `{} {}`, but the patch has version `{}`. \
+                     Cargo will not use the patch for it.",
+                    package.name,
+                    dep.name,
+                    dep.req,
+                    version
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Group `packages` by the patch target of the source `cargo_toml` consumes them from.
+///
+/// The sources are taken from the resolved dependency graph of the workspace of `cargo_toml`.
+/// Every repository gets its own patch target, so crates taken from a fork are patched there
+/// instead of in `upstream`, where the patch wouldn't apply.
+fn consumed_sources(
+    cargo_toml: &Path,
+    packages: Vec<cargo_metadata::Package>,
+    upstream: &PatchTarget,
+    ctx: &mut RunContext,
+) -> Result<Vec<(PatchTarget, Vec<cargo_metadata::Package>)>> {
+    // The url every repository is spelled with, the existing patch targets take precedence.
+    let mut urls = ctx
+        .changes
+        .read_toml(cargo_toml)?
+        .get("patch")
+        .and_then(|p| p.as_table_like())
+        .map(|p| {
+            p.iter()
+                .filter(|(target, _)| *target != "crates-io")
+                .map(|(url, _)| (git::repository_id(url), url.to_string()))
+                .collect::<HashMap<_, _>>()
+        })
+        .unwrap_or_default();
+    let upstream = git::repository_id(upstream.as_str());
+
+    let metadata = ctx.metadata(cargo_toml.parent().unwrap_or_else(|| Path::new(".")))?;
+
+    let mut targets = BTreeMap::<String, Vec<cargo_metadata::Package>>::new();
+    for package in packages {
+        let sources = metadata
+            .packages
+            .iter()
+            .filter(|p| p.name == package.name)
+            .filter_map(|p| p.source.as_ref())
+            .filter_map(|s| {
+                if s.is_crates_io() {
+                    return Some("crates-io".to_string());
+                }
+
+                let url = s.repr.strip_prefix("git+")?;
+                let url = url.split(['?', '#']).next().unwrap_or(url);
+                let id = git::repository_id(url);
+                if id != upstream {
+                    log::info!("`{}` is taken from {}.", package.name, url);
+                }
+                Some(urls.entry(id).or_insert_with(|| url.to_string()).clone())
+            })
+            .collect::<BTreeSet<_>>();
+
+        if sources.is_empty() {
+            log::debug!("`{}` is not used, not patching it.", package.name);
+        }
+        for source in sources {
+            targets.entry(source).or_default().push(package.clone());
+        }
+    }
+
+    if targets.is_empty() {
+        log::warn!("None of the crates is used by {}.", cargo_toml.display());
+    }
+
+    Ok(targets
+        .into_iter()
+        .map(|(source, packages)| {
+            let target = if source == "crates-io" {
+                PatchTarget::Crates
+            } else {
+                PatchTarget::Git(source)
+            };
+            (target, packages)
+        })
+        .collect())
+}
+
+/// The patches `--prune` removes.
+struct Prune {
+    /// The root of the workspace of the patched crates.
+    source: PathBuf,
+    /// All crates of the workspace at `source`, their patches are not stale.
+    names: HashSet<String>,
+}
+
+/// Add a patch for every package in `packages` to `cargo_toml`.
+///
+/// With `prune` stale patches are removed. With `filter` only the packages whose name matches
+/// are patched.
 fn add_patches_for_packages(
     cargo_toml: &Path,
     patch_target: &PatchTarget,
-    mut packages: impl Iterator<Item = cargo_metadata::Package>,
-    point_to: PointTo,
+    packages: impl Iterator<Item = cargo_metadata::Package>,
+    point_to: &PointTo,
+    prune: Option<&Prune>,
+    filter: Option<&Regex>,
+    changes: &mut Changes,
 ) -> Result<()> {
-    let content = fs::read_to_string(cargo_toml)
-        .with_context(|| anyhow!("Failed to read manifest at {}", cargo_toml.display()))?;
-    let mut doc = Document::from_str(&content).context("Failed to parse Cargo.toml")?;
+    let packages = packages.collect::<Vec<_>>();
+    let mut doc = changes.read_toml(cargo_toml)?;
 
-    let patch_table = doc
-        .as_table_mut()
-        .entry("patch")
-        .or_insert(Item::Table(Default::default()))
-        .as_table_mut()
-        .ok_or_else(|| anyhow!("Patch table isn't a toml table!"))?;
+    let patch_target_table = editor::patch_table(&mut doc, patch_target.as_str())?;
 
-    patch_table.set_implicit(true);
+    if let Some(prune) = prune {
+        prune_patches(patch_target_table, &prune.names, &prune.source, point_to);
+    }
 
-    let patch_target_table = patch_table
-        .entry(patch_target.as_str())
-        .or_insert(Item::Table(Default::default()))
-        .as_table_mut()
-        .ok_or_else(|| anyhow!("Patch target table isn't a toml table!"))?;
+    let mut packages = packages
+        .into_iter()
+        .filter(|p| filter.is_none_or(|f| f.is_match(&p.name)))
+        .peekable();
+    if filter.is_some() && packages.peek().is_none() {
+        log::warn!("No crate matches `--filter-regex`.");
+    }
 
+    let mut patched = Vec::new();
     packages.try_for_each(|mut p| {
         log::info!("Adding patch for `{}`.", p.name);
 
-        let patch = patch_target_table
-            .entry(&p.name)
-            .or_insert(Item::Value(Value::InlineTable(Default::default())))
-            .as_inline_table_mut()
-            .ok_or_else(|| anyhow!("Patch entry for `{}` isn't an inline table!", p.name))?;
+        let patch = editor::patch_entry(patch_target_table, &p.name)?;
 
         if p.manifest_path.ends_with("Cargo.toml") {
             p.manifest_path.pop();
@@ -231,26 +581,169 @@ fn add_patches_for_packages(
 
         let path: PathBuf = p.manifest_path.into();
 
-        match &point_to {
-            PointTo::Path => {
-                *patch.get_or_insert("path", "") =
-                    Value::from(path.display().to_string()).decorated(" ", " ");
-            }
+        let source = match point_to {
+            PointTo::Path => vec![("path", manifest_path(&path))],
             PointTo::GitBranch { repository, branch } => {
-                *patch.get_or_insert("git", "") =
-                    Value::from(repository.clone()).decorated(" ", " ");
-                *patch.get_or_insert("branch", "") =
-                    Value::from(branch.clone()).decorated(" ", " ");
-            }
-            PointTo::GitCommit { repository, commit } => {
-                *patch.get_or_insert("git", "") =
-                    Value::from(repository.clone()).decorated(" ", " ");
-                *patch.get_or_insert("rev", "") = Value::from(commit.clone()).decorated(" ", " ");
+                vec![("git", repository.clone()), ("branch", branch.clone())]
             }
-        }
+            PointTo::GitCommit {
+                repository, commit, ..
+            } => vec![("git", repository.clone()), ("rev", commit.clone())],
+        };
+        editor::set_patch_source(patch, source);
+        oplog::record(
+            "patch-added",
+            json!({ "path": cargo_toml, "crate": p.name, "entry": patch.to_string().trim() }),
+        );
+        patched.push(p.name);
         Ok::<_, Error>(())
     })?;
 
-    fs::write(cargo_toml, doc.to_string())
-        .with_context(|| anyhow!("Failed to write manifest to {}", cargo_toml.display()))
+    update_pins(
+        cargo_toml,
+        patch_target,
+        patch_target_table,
+        &patched,
+        point_to,
+        changes,
+    )?;
+    changes.write(cargo_toml, doc.to_string());
+    Ok(())
+}
+
+/// Record the branch of the `patched` crates in the [`PINS_FILE`] of `cargo_toml`, if they are
+/// pinned, and forget it otherwise.
+///
+/// Crates that are no longer patched in `table` are forgotten as well.
+fn update_pins(
+    cargo_toml: &Path,
+    patch_target: &PatchTarget,
+    table: &Table,
+    patched: &[String],
+    point_to: &PointTo,
+    changes: &mut Changes,
+) -> Result<()> {
+    let file = cargo_toml.with_file_name(PINS_FILE);
+    let mut pins: BTreeMap<String, BTreeMap<String, serde_json::Value>> = if changes.exists(&file) {
+        serde_json::from_str(&changes.read(&file)?)
+            .with_context(|| format!("Failed to parse {}", file.display()))?
+    } else {
+        BTreeMap::new()
+    };
+    let before = pins.clone();
+
+    let target = pins.entry(patch_target.as_str().to_string()).or_default();
+    target.retain(|name, _| table.contains_key(name));
+    for name in patched {
+        match point_to {
+            PointTo::GitCommit {
+                repository,
+                commit,
+                branch: Some(branch),
+            } => {
+                target.insert(
+                    name.clone(),
+                    json!({ "git": repository, "branch": branch, "rev": commit }),
+                );
+            }
+            _ => {
+                target.remove(name);
+            }
+        }
+    }
+    if target.is_empty() {
+        pins.remove(patch_target.as_str());
+    }
+
+    if pins != before {
+        changes.write(file, serde_json::to_string_pretty(&pins)? + "\n");
+    }
+    Ok(())
+}
+
+/// Returns every patch of `patch_target` in `doc`, rendered as inline table.
+fn patch_entries(doc: &Document, patch_target: &PatchTarget) -> BTreeMap<String, String> {
+    let Some(table) = doc
+        .get("patch")
+        .and_then(|p| p.get(patch_target.as_str()))
+        .and_then(|t| t.as_table_like())
+    else {
+        return BTreeMap::new();
+    };
+
+    table
+        .iter()
+        .filter_map(|(name, patch)| {
+            let mut patch = patch.clone().into_value().ok()?;
+            if let Some(patch) = patch.as_inline_table_mut() {
+                patch.fmt();
+            }
+            patch.decor_mut().clear();
+            Some((name.to_string(), patch.to_string()))
+        })
+        .collect()
+}
+
+/// Print the patches that differ between `before` and `after`.
+fn print_patch_diff(
+    patch_target: &PatchTarget,
+    before: &BTreeMap<String, String>,
+    after: &BTreeMap<String, String>,
+    color: bool,
+) {
+    let mut diff = String::new();
+    for name in before.keys().chain(after.keys()).collect::<BTreeSet<_>>() {
+        match (before.get(name), after.get(name)) {
+            (Some(old), Some(new)) if old == new => {}
+            (old, new) => {
+                if let Some(old) = old {
+                    diff.push_str(&format!("-{name} = {old}\n"));
+                }
+                if let Some(new) = new {
+                    diff.push_str(&format!("+{name} = {new}\n"));
+                }
+            }
+        }
+    }
+
+    if diff.is_empty() {
+        log::info!("The patches are up to date.");
+    } else {
+        let diff = format!("[patch.\"{}\"]\n{}", patch_target.as_str(), diff);
+        if color {
+            print!("{}", diff::colorize(&diff));
+        } else {
+            print!("{diff}");
+        }
+    }
+}
+
+/// Remove all patches from `table` that point to the same source as `point_to`, but whose
+/// package is not in `names`.
+///
+/// For path patches the source is the workspace at `source`, for git patches the repository.
+fn prune_patches(table: &mut Table, names: &HashSet<String>, source: &Path, point_to: &PointTo) {
+    let stale = table
+        .iter()
+        .filter(|(key, patch)| {
+            let Some(patch) = patch.as_table_like() else {
+                return false;
+            };
+            let get = |k| patch.get(k).and_then(|v| v.as_str());
+
+            let same_source = match point_to {
+                PointTo::Path => get("path").is_some_and(|p| Path::new(p).starts_with(source)),
+                PointTo::GitBranch { repository, .. } | PointTo::GitCommit { repository, .. } => {
+                    get("git") == Some(repository)
+                }
+            };
+            same_source && !names.contains(get("package").unwrap_or(key))
+        })
+        .map(|(key, _)| key.to_string())
+        .collect::<Vec<_>>();
+
+    for key in stale {
+        log::info!("Removing stale patch for `{}`.", key);
+        table.remove(&key);
+    }
 }
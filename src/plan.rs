@@ -0,0 +1,132 @@
+use crate::{
+    changes::Changes,
+    context::RunContext,
+    failure::{Classify, Failure},
+};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{fs, path::PathBuf};
+use structopt::StructOpt;
+
+/// The version of the plan format.
+pub(crate) const PLAN_VERSION: u32 = 1;
+
+/// A directory move of a [`Plan`].
+#[derive(Serialize, Deserialize)]
+struct Move {
+    from: PathBuf,
+    to: PathBuf,
+}
+
+/// A file modification of a [`Plan`].
+#[derive(Serialize, Deserialize)]
+struct FileEdit {
+    path: PathBuf,
+    /// The SHA-256 of the file when the plan was created, `None` if the file is created.
+    original_sha256: Option<String>,
+    content: String,
+}
+
+/// All modifications a diener run would make, written by `--plan-out`.
+///
+/// Changes of the format need to be reflected in the schema of `diener schema plan`.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Plan {
+    version: u32,
+    /// The diener command line that created the plan.
+    command: String,
+    moves: Vec<Move>,
+    files: Vec<FileEdit>,
+}
+
+impl Plan {
+    /// Create a plan of the given `changes`.
+    pub(crate) fn new(command: String, changes: &Changes) -> Self {
+        Self {
+            version: PLAN_VERSION,
+            command,
+            moves: changes
+                .moves()
+                .iter()
+                .map(|(from, to)| Move {
+                    from: from.clone(),
+                    to: to.clone(),
+                })
+                .collect(),
+            files: changes
+                .modified()
+                .map(|(path, original, content)| FileEdit {
+                    path: path.into(),
+                    original_sha256: original.map(sha256),
+                    content: content.into(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Stage all modifications of this plan in `changes`.
+    ///
+    /// Fails if any file changed since the plan was created.
+    fn stage(self, changes: &mut Changes) -> Result<()> {
+        for Move { from, to } in self.moves {
+            ensure!(from.is_dir(), "{} doesn't exist anymore.", from.display());
+            ensure!(!to.exists(), "{} already exists.", to.display());
+            changes.rename(from, to);
+        }
+
+        let mut drifted = Vec::new();
+        for edit in self.files {
+            let current = changes.read(&edit.path).ok();
+            if current.as_deref().map(sha256) != edit.original_sha256 {
+                drifted.push(edit.path.display().to_string());
+                continue;
+            }
+            changes.write(edit.path, edit.content);
+        }
+
+        if !drifted.is_empty() {
+            bail!(
+                "The following file(s) changed since the plan was created:\n  {}",
+                drifted.join("\n  ")
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the hex encoded SHA-256 of `content`.
+pub(crate) fn sha256(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// `apply-plan` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct ApplyPlan {
+    /// The plan written by `--plan-out`.
+    plan: PathBuf,
+}
+
+impl ApplyPlan {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        let content = fs::read_to_string(&self.plan)
+            .with_context(|| anyhow!("Failed to read plan at {}", self.plan.display()))?;
+        let plan: Plan = serde_json::from_str(&content)
+            .with_context(|| anyhow!("Failed to parse plan at {}", self.plan.display()))
+            .failure(Failure::Parse)?;
+        ensure!(
+            plan.version == PLAN_VERSION,
+            "Unsupported plan version {}, expected {}.",
+            plan.version,
+            PLAN_VERSION
+        );
+
+        log::info!("Applying the plan of `{}`.", plan.command);
+        plan.stage(&mut ctx.changes)
+    }
+}
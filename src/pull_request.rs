@@ -0,0 +1,92 @@
+use crate::{
+    changes::StagedCaches,
+    failure::{Classify, Failure},
+    forge::{self, ForgeConfig, NewPullRequest},
+    git,
+};
+use anyhow::{anyhow, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// The remote the branches of `--open-pr` are pushed to by default.
+const DEFAULT_REMOTE: &str = "origin";
+
+/// How `--open-pr` opens pull requests, configured in `[pull-request]`.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct PullRequestConfig {
+    /// The labels added to every pull request.
+    labels: Vec<String>,
+    /// The users or `org/team`s whose review is requested.
+    reviewers: Vec<String>,
+    /// The remote the branch is pushed to, `origin` by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remote: Option<String>,
+}
+
+impl PullRequestConfig {
+    /// Add the `labels` and `reviewers` given on the command line.
+    pub(crate) fn extend(&mut self, labels: Vec<String>, reviewers: Vec<String>) {
+        self.labels.extend(labels);
+        self.reviewers.extend(reviewers);
+    }
+
+    /// Push `branch` of the repository at `root` and open a pull request for it on the forge
+    /// hosting the remote, looked up in `forges`.
+    ///
+    /// The pull request targets the branch that was checked out before `branch`, its title is
+    /// the first line of the commit `message`. The API responses are cached in `caches`.
+    pub(crate) fn open(
+        &self,
+        forges: &[ForgeConfig],
+        caches: StagedCaches,
+        root: &Path,
+        branch: &str,
+        message: &str,
+        body: &str,
+    ) -> Result<()> {
+        let remote = self.remote.as_deref().unwrap_or(DEFAULT_REMOTE);
+        let url = git::run(root, &["remote", "get-url", remote])?;
+        let (forge, repo) = forge::for_url(url.trim(), forges, caches).ok_or_else(|| {
+            anyhow!(
+                "The remote `{}` is not hosted on a known forge, configure it in `[[forges]]`: {}",
+                remote,
+                url.trim()
+            )
+        })?;
+
+        let base = git::run(root, &["rev-parse", "--abbrev-ref", "@{-1}"])
+            .context("Failed to find the branch the pull request should target")?;
+        let base = base.trim();
+        ensure!(
+            !base.is_empty() && base != "HEAD",
+            "No branch was checked out before `{}`, the pull request needs a target branch.",
+            branch
+        );
+
+        git::run(root, &["push", "-q", "-u", remote, branch])
+            .with_context(|| anyhow!("Failed to push `{}` to `{}`", branch, remote))
+            .failure(Failure::Network)?;
+        log::info!("Pushed `{}` to `{}`.", branch, remote);
+
+        let (number, url) = forge.create_pull_request(
+            &repo,
+            &NewPullRequest {
+                head: branch,
+                base,
+                title: message.lines().next().unwrap_or(message),
+                body,
+                labels: &self.labels,
+                reviewers: &self.reviewers,
+            },
+        )?;
+        log::info!(
+            "Opened pull request {} on {}: {}",
+            number,
+            forge.name(),
+            url
+        );
+
+        Ok(())
+    }
+}
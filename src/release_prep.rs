@@ -0,0 +1,327 @@
+use crate::{
+    changes::Changes,
+    check_versions::package_versions,
+    context::RunContext,
+    editor,
+    failure::{Classify, Failure},
+    git::{Checkout, GitRef},
+    manifest::{dependency_package, dependency_tables, dependency_tables_mut},
+    update::{is_managed_dependency, RepoMatcher},
+    workspacify::manifest_iter,
+};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+use toml_edit::Document;
+
+/// `release-prep` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct ReleasePrep {
+    /// The path to the workspace root directory.
+    ///
+    /// Uses the working directory if none is supplied.
+    #[structopt(long)]
+    path: Option<PathBuf>,
+
+    /// A TOML file mapping package names to the crates.io version to use, e.g.
+    /// `sp-core = "34.0.0"`.
+    ///
+    /// The versions in the file take precedence over the versions at the pinned tag.
+    #[structopt(long)]
+    versions: Option<PathBuf>,
+}
+
+/// Where a dependency that is converted to a crates.io dependency comes from.
+enum Source {
+    /// A Polkadot SDK git dependency.
+    Git { url: String, reference: GitRef },
+    /// A `path` dependency outside of the workspace, with the manifest of the dependency.
+    Path(PathBuf),
+}
+
+/// A dependency that is converted to a crates.io dependency.
+struct Conversion {
+    manifest: PathBuf,
+    table: String,
+    key: String,
+    /// The name of the package.
+    package: String,
+    source: Source,
+}
+
+impl ReleasePrep {
+    /// Run this subcommand.
+    ///
+    /// Nothing is written if any dependency can not be converted or is left without a version
+    /// requirement.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        let workspace = ctx.workspace_dir(self.path.as_deref())?;
+        let workspace = workspace
+            .canonicalize()
+            .with_context(|| anyhow!("Failed to resolve {}", workspace.display()))?;
+        let mapping = match &self.versions {
+            Some(versions) => read_versions(&ctx.changes, versions)?,
+            None => HashMap::new(),
+        };
+        let repos: RepoMatcher = ctx.config.match_repos().into();
+
+        let mut docs = BTreeMap::new();
+        for manifest in manifest_iter(ctx.changes.fs(), &workspace, &ctx.exclusions) {
+            let doc = ctx.changes.read_toml(&manifest)?;
+            docs.insert(manifest, doc);
+        }
+
+        let conversions = docs
+            .iter()
+            .flat_map(|(manifest, doc)| conversions(&workspace, manifest, doc, &repos))
+            .collect::<Vec<_>>();
+
+        // The versions of the packages at every pinned tag that is needed.
+        let tags = conversions
+            .iter()
+            .filter(|c| !mapping.contains_key(&c.package))
+            .filter_map(|c| match &c.source {
+                Source::Git {
+                    url,
+                    reference: GitRef::Tag(tag),
+                } => Some((url.clone(), tag.clone())),
+                _ => None,
+            })
+            .collect::<BTreeSet<_>>();
+        if !tags.is_empty() {
+            ctx.ensure_online("Fetching the manifests of the pinned tags")?;
+        }
+        let mut tag_versions = HashMap::new();
+        for (url, tag) in tags {
+            let checkout = Checkout::manifests_only(&url, &GitRef::Tag(tag.clone()))?;
+            tag_versions.insert((url, tag), package_versions(&ctx.changes, checkout.path())?);
+        }
+
+        // `(manifest, table, key)` of the dependencies that could not be converted.
+        let mut failed = BTreeSet::new();
+        for conversion in conversions {
+            let version = match (mapping.get(&conversion.package), &conversion.source) {
+                (Some(version), _) => Ok(version.clone()),
+                (
+                    None,
+                    Source::Git {
+                        url,
+                        reference: GitRef::Tag(tag),
+                    },
+                ) => tag_versions
+                    .get(&(url.clone(), tag.clone()))
+                    .and_then(|versions| versions.get(&conversion.package))
+                    .map(ToString::to_string)
+                    .ok_or_else(|| format!("does not exist in {url} ({tag})")),
+                (None, Source::Git { reference, .. }) => Err(format!(
+                    "is not pinned to a tag but to {reference}, add it to `--versions`"
+                )),
+                (None, Source::Path(dependency)) => path_version(&ctx.changes, dependency)
+                    .ok_or_else(|| {
+                        format!(
+                            "has no version in {}, add it to `--versions`",
+                            dependency.display()
+                        )
+                    }),
+            };
+
+            let version = match version {
+                Ok(version) => version,
+                Err(reason) => {
+                    log::error!(
+                        "{}: `{}` in `[{}]` {}.",
+                        conversion.manifest.display(),
+                        conversion.key,
+                        conversion.table,
+                        reason
+                    );
+                    failed.insert((conversion.manifest, conversion.table, conversion.key));
+                    continue;
+                }
+            };
+
+            let Some(dep) = docs
+                .get_mut(&conversion.manifest)
+                .and_then(|doc| {
+                    dependency_tables_mut(doc).find(|(name, _)| *name == conversion.table)
+                })
+                .and_then(|(_, deps)| deps.get_mut(&conversion.key))
+            else {
+                continue;
+            };
+            editor::to_version_dep(dep, &version);
+            log::info!(
+                "{}: Switched `{}` to version `{}`.",
+                conversion.manifest.display(),
+                conversion.key,
+                version
+            );
+        }
+
+        for (manifest, doc) in docs.iter_mut() {
+            if doc.remove("patch").is_some() {
+                log::info!("{}: Removed the `[patch]` section.", manifest.display());
+            }
+        }
+
+        let problems = failed.len() + unversioned(&workspace, &docs, &failed);
+        ensure!(
+            problems == 0,
+            "Found {} problem(s), the workspace is not ready to be published.",
+            problems
+        );
+
+        for (manifest, doc) in docs {
+            ctx.changes.write(manifest, doc.to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Read the mapping of package names to versions at `path`.
+fn read_versions(changes: &Changes, path: &Path) -> Result<HashMap<String, String>> {
+    let content = changes.read(path)?;
+    let doc = content
+        .parse::<Document>()
+        .with_context(|| anyhow!("Failed to parse {}", path.display()))
+        .failure(Failure::Parse)?;
+
+    doc.iter()
+        .map(|(package, version)| match version.as_str() {
+            Some(version) => Ok((package.to_string(), version.to_string())),
+            None => bail!(
+                "The version of `{}` in {} is not a string.",
+                package,
+                path.display()
+            ),
+        })
+        .collect()
+}
+
+/// Returns the dependencies of the manifest `doc` at `manifest` that need to be converted.
+///
+/// These are the Polkadot SDK git dependencies and the `path` dependencies outside of the
+/// `workspace`. Dependencies inherited from `[workspace.dependencies]` are converted there.
+fn conversions(
+    workspace: &Path,
+    manifest: &Path,
+    doc: &Document,
+    repos: &RepoMatcher,
+) -> Vec<Conversion> {
+    let mut conversions = Vec::new();
+    for (table, deps) in dependency_tables(doc) {
+        for (key, dep) in deps.iter() {
+            let Some(dep) = dep.as_table_like() else {
+                continue;
+            };
+
+            let source = if is_managed_dependency(dep, repos) {
+                Source::Git {
+                    url: dep
+                        .get("git")
+                        .and_then(|g| g.as_str())
+                        .unwrap_or_default()
+                        .into(),
+                    reference: GitRef::from_dependency(dep),
+                }
+            } else if let Some(path) = dep.get("path").and_then(|p| p.as_str()) {
+                let dependency = manifest.parent().map(|dir| dir.join(path));
+                match dependency.and_then(|d| d.canonicalize().ok()) {
+                    Some(dependency) if !dependency.starts_with(workspace) => {
+                        Source::Path(dependency.join("Cargo.toml"))
+                    }
+                    _ => continue,
+                }
+            } else {
+                continue;
+            };
+
+            conversions.push(Conversion {
+                manifest: manifest.into(),
+                table: table.clone(),
+                key: key.into(),
+                package: dependency_package(key, dep).into(),
+                source,
+            });
+        }
+    }
+    conversions
+}
+
+/// Returns the version of the package at `manifest`, `None` if it is inherited or missing.
+fn path_version(changes: &Changes, manifest: &Path) -> Option<String> {
+    changes
+        .read_toml(manifest)
+        .ok()?
+        .get("package")?
+        .get("version")?
+        .as_str()
+        .map(ToString::to_string)
+}
+
+/// Report all dependencies of publishable crates without a version requirement.
+///
+/// Dev-dependencies are ignored, `cargo publish` removes them if they have no version. The
+/// `failed` conversions are already reported. Returns the number of reported dependencies.
+fn unversioned(
+    workspace: &Path,
+    docs: &BTreeMap<PathBuf, Document>,
+    failed: &BTreeSet<(PathBuf, String, String)>,
+) -> usize {
+    let root = docs.get(&workspace.join("Cargo.toml"));
+    let workspace_deps = root.and_then(|doc| doc.get("workspace")?.get("dependencies"));
+    let workspace_publish =
+        root.and_then(|doc| doc.get("workspace")?.get("package")?.get("publish"));
+
+    let mut unversioned = 0;
+    for (manifest, doc) in docs {
+        let Some(package) = doc.get("package") else {
+            continue;
+        };
+        let publish = match package.get("publish") {
+            Some(p) if p.get("workspace").and_then(|w| w.as_bool()) == Some(true) => {
+                workspace_publish
+            }
+            publish => publish,
+        };
+        if publish.is_some_and(|p| {
+            p.as_bool() == Some(false) || p.as_array().is_some_and(|a| a.is_empty())
+        }) {
+            continue;
+        }
+
+        let tables = dependency_tables(doc).into_iter().filter(|(table, _)| {
+            !table.ends_with("dev-dependencies") && table != "workspace.dependencies"
+        });
+        for (table, deps) in tables {
+            for (key, dep) in deps.iter() {
+                if failed.contains(&(manifest.clone(), table.clone(), key.to_string())) {
+                    continue;
+                }
+                let dep = match dep.get("workspace").and_then(|w| w.as_bool()) {
+                    Some(true) => match workspace_deps.and_then(|deps| deps.get(key)) {
+                        Some(dep) => dep,
+                        None => continue,
+                    },
+                    _ => dep,
+                };
+                if dep.is_str() || dep.get("version").is_some() {
+                    continue;
+                }
+
+                log::error!(
+                    "{}: `{}` in `[{}]` has no version requirement, `diener lint --fix` adds \
+                     it to path dependencies.",
+                    manifest.display(),
+                    key,
+                    table
+                );
+                unversioned += 1;
+            }
+        }
+    }
+    unversioned
+}
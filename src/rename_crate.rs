@@ -0,0 +1,295 @@
+use crate::{
+    changes::Changes,
+    context::RunContext,
+    editor,
+    filesystem::normalize,
+    manifest::{dependency_tables_mut, patch_tables_mut},
+    workspacify::manifest_iter,
+};
+use anyhow::{bail, Result};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+use toml_edit::{Document, Item, Table};
+
+/// `rename-crate` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct RenameCrate {
+    /// The path to the workspace root directory.
+    ///
+    /// Uses the working directory if none is supplied.
+    #[structopt(long)]
+    path: Option<PathBuf>,
+
+    /// Rename the dependency key in all dependents instead of adding `package = "<new>"`.
+    ///
+    /// The code of the dependents needs to use the new name then. By default they keep the old
+    /// key as alias, so they don't need to be changed.
+    #[structopt(long)]
+    rename_key: bool,
+
+    /// The current name of the crate.
+    old: String,
+
+    /// The new name of the crate.
+    new: String,
+}
+
+/// What should happen with a dependency entry that refers to the renamed crate.
+enum Action {
+    /// Keep the key and point `package` to the new name.
+    SetPackage,
+    /// Rename the dependency key.
+    RenameKey,
+}
+
+impl RenameCrate {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        let workspace = ctx.workspace_dir(self.path.as_deref())?;
+
+        let mut docs = BTreeMap::<PathBuf, Document>::new();
+        for manifest in manifest_iter(ctx.changes.fs(), &workspace, &ctx.exclusions) {
+            let doc = ctx.changes.read_toml(&manifest)?;
+            docs.insert(manifest, doc);
+        }
+
+        let package_name = |doc: &Document| {
+            doc.get("package")
+                .and_then(|p| p.get("name"))
+                .and_then(|n| n.as_str())
+                .map(ToString::to_string)
+        };
+
+        if let Some((path, _)) = docs
+            .iter()
+            .find(|(_, d)| package_name(d).as_deref() == Some(&self.new))
+        {
+            bail!(
+                "A crate named `{}` already exists at {}",
+                self.new,
+                path.display()
+            );
+        }
+
+        let crate_manifest = match docs
+            .iter()
+            .filter(|(_, d)| package_name(d).as_deref() == Some(&self.old))
+            .map(|(p, _)| p.clone())
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            [manifest] => manifest.clone(),
+            [] => bail!(
+                "No crate named `{}` found in {}",
+                self.old,
+                workspace.display()
+            ),
+            multiple => bail!(
+                "Found multiple crates named `{}`: {:#?}",
+                self.old,
+                multiple
+            ),
+        };
+        self.rename(&workspace, &crate_manifest, docs, &mut ctx.changes)
+    }
+
+    /// Rename the crate at `crate_manifest` and update all references to it in `docs`.
+    fn rename(
+        &self,
+        workspace: &Path,
+        crate_manifest: &Path,
+        mut docs: BTreeMap<PathBuf, Document>,
+        changes: &mut Changes,
+    ) -> Result<()> {
+        // The paths are only compared lexically, they may not exist on the real filesystem.
+        let crate_dir = normalize(crate_manifest.parent().expect("Manifests are files; qed"));
+
+        let originals = docs
+            .iter()
+            .map(|(p, d)| (p.clone(), d.to_string()))
+            .collect::<BTreeMap<_, _>>();
+
+        let crate_doc = docs
+            .get_mut(crate_manifest)
+            .expect("Crate manifest was found in `docs`; qed");
+        if let Some(name) = crate_doc["package"]["name"].as_value_mut() {
+            editor::set_value(name, self.new.as_str());
+        }
+        log::info!("Renamed `{}` to `{}`.", self.old, self.new);
+
+        // `[workspace.dependencies]` key that refers to the crate and if it is using
+        // the crate name as key.
+        let workspace_key = docs
+            .get(&workspace.join("Cargo.toml"))
+            .and_then(|d| d.get("workspace"))
+            .and_then(|w| w.get("dependencies"))
+            .and_then(|d| d.as_table())
+            .and_then(|deps| {
+                deps.iter().find_map(|(key, dep)| {
+                    self.action(workspace, key, dep, &crate_dir, None)
+                        .map(|a| (key.to_string(), matches!(a, Action::RenameKey)))
+                })
+            });
+
+        for (manifest, doc) in docs.iter_mut() {
+            let manifest_dir = manifest.parent().expect("Manifests are files; qed");
+            let mut key_renamed = false;
+
+            for (_, table) in patch_tables_mut(doc) {
+                key_renamed |= self.handle_table(table, manifest_dir, &crate_dir, None);
+            }
+
+            for (_, table) in dependency_tables_mut(doc) {
+                key_renamed |=
+                    self.handle_table(table, manifest_dir, &crate_dir, workspace_key.as_ref());
+            }
+
+            if key_renamed {
+                self.rename_features(doc);
+            }
+        }
+
+        for (path, doc) in docs {
+            let content = doc.to_string();
+            if originals[&path] != content {
+                log::info!("Updating: {}", path.display());
+                changes.write(path, content);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns what should happen with the dependency `key = dep`, if it refers to the crate.
+    ///
+    /// `workspace_key` is the `[workspace.dependencies]` key referring to the crate and whether
+    /// it is renamed.
+    fn action(
+        &self,
+        manifest_dir: &Path,
+        key: &str,
+        dep: &Item,
+        crate_dir: &Path,
+        workspace_key: Option<&(String, bool)>,
+    ) -> Option<Action> {
+        let dep = dep.as_table_like()?;
+
+        if dep.get("workspace").and_then(|w| w.as_bool()) == Some(true) {
+            return match workspace_key {
+                Some((ws_key, true)) if ws_key == key && self.rename_key => Some(Action::RenameKey),
+                _ => None,
+            };
+        }
+
+        let package = dep.get("package").and_then(|p| p.as_str());
+        if package.unwrap_or(key) != self.old {
+            return None;
+        }
+
+        let path = dep.get("path").and_then(|p| p.as_str())?;
+        if normalize(&manifest_dir.join(path)) != crate_dir {
+            return None;
+        }
+
+        if package.is_none() && self.rename_key {
+            Some(Action::RenameKey)
+        } else {
+            Some(Action::SetPackage)
+        }
+    }
+
+    /// Update all entries referring to the crate in the given dependency or patch table.
+    ///
+    /// Returns `true` if a dependency key was renamed.
+    fn handle_table(
+        &self,
+        table: &mut Table,
+        manifest_dir: &Path,
+        crate_dir: &Path,
+        workspace_key: Option<&(String, bool)>,
+    ) -> bool {
+        let actions = table
+            .iter()
+            .filter_map(|(key, dep)| {
+                self.action(manifest_dir, key, dep, crate_dir, workspace_key)
+                    .map(|a| (key.to_string(), a))
+            })
+            .collect::<Vec<_>>();
+
+        let mut key_renamed = false;
+        for (key, action) in actions {
+            match action {
+                Action::SetPackage => editor::set_package(&mut table[&key], &self.new),
+                Action::RenameKey => {
+                    editor::rename_dependency(table, &key, &self.new);
+                    key_renamed = true;
+                }
+            }
+        }
+
+        key_renamed
+    }
+
+    /// Update all references to the renamed dependency key in `[features]`.
+    fn rename_features(&self, doc: &mut Document) {
+        let Some(features) = doc.get_mut("features").and_then(|f| f.as_table_mut()) else {
+            return;
+        };
+        let has_explicit_feature = features.contains_key(&self.old);
+
+        for (_, enables) in features.iter_mut() {
+            let Some(enables) = enables.as_array_mut() else {
+                continue;
+            };
+
+            for value in enables.iter_mut() {
+                let Some(feature) = value.as_str() else {
+                    continue;
+                };
+
+                let renamed = if feature == format!("dep:{}", self.old) {
+                    format!("dep:{}", self.new)
+                } else if feature == self.old && !has_explicit_feature {
+                    self.new.clone()
+                } else if let Some(rest) = feature
+                    .strip_prefix(&self.old)
+                    .filter(|r| r.starts_with('/') || r.starts_with("?/"))
+                {
+                    format!("{}{}", self.new, rest)
+                } else {
+                    continue;
+                };
+
+                editor::set_value(value, renamed);
+            }
+        }
+    }
+}
+
+/// Rename the crate `old` at `crate_manifest` to `new`.
+///
+/// All dependents referring to it by path keep `old` as alias and get `package = "<new>"`.
+pub(crate) fn rename_copy(
+    workspace: &Path,
+    crate_manifest: &Path,
+    old: &str,
+    new: &str,
+    ctx: &mut RunContext,
+) -> Result<()> {
+    let mut docs = BTreeMap::new();
+    for manifest in manifest_iter(ctx.changes.fs(), workspace, &ctx.exclusions) {
+        let doc = ctx.changes.read_toml(&manifest)?;
+        docs.insert(manifest, doc);
+    }
+
+    let rename = RenameCrate {
+        path: None,
+        rename_key: false,
+        old: old.into(),
+        new: new.into(),
+    };
+    rename.rename(workspace, crate_manifest, docs, &mut ctx.changes)
+}
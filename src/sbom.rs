@@ -0,0 +1,403 @@
+use crate::{
+    context::RunContext,
+    git::GitRef,
+    manifest::{dependency_package, dependency_tables, manifest_path},
+    update::{self, is_managed_dependency, RepoMatcher},
+};
+use anyhow::{bail, Context, Error, Result};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use structopt::{
+    clap::{crate_name, crate_version},
+    StructOpt,
+};
+use toml_edit::{Document, Item, TableLike};
+
+/// `sbom` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct Sbom {
+    /// The path where Diener should search for `Cargo.toml` files.
+    ///
+    /// Can be passed multiple times and may be a glob pattern like `runtimes/*`.
+    /// If not given, the current directory will be taken.
+    #[structopt(long)]
+    path: Vec<PathBuf>,
+
+    /// The format of the inventory, `cyclonedx` (CycloneDX 1.5 JSON) or `spdx-json` (SPDX 2.3).
+    #[structopt(long, default_value = "cyclonedx")]
+    format: SbomFormat,
+}
+
+/// The format of the inventory written by `sbom`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SbomFormat {
+    CycloneDx,
+    SpdxJson,
+}
+
+impl FromStr for SbomFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cyclonedx" => Ok(Self::CycloneDx),
+            "spdx-json" => Ok(Self::SpdxJson),
+            _ => bail!("Unknown format `{s}`, expected `cyclonedx` or `spdx-json`."),
+        }
+    }
+}
+
+/// A Polkadot SDK package pinned to a git reference.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Pin {
+    name: String,
+    url: String,
+    reference: GitRef,
+}
+
+impl Pin {
+    /// The package url, e.g. `pkg:cargo/sp-core?vcs_url=git%2Bhttps://..%40stable2407`.
+    fn purl(&self) -> String {
+        format!(
+            "pkg:cargo/{}?vcs_url={}",
+            self.name,
+            percent_encode(&self.download_location())
+        )
+    }
+
+    /// The location in the format of SPDX, e.g. `git+https://..@stable2407`.
+    fn download_location(&self) -> String {
+        match self.reference {
+            GitRef::DefaultBranch => format!("git+{}", self.url),
+            _ => format!("git+{}@{}", self.url, self.reference.name()),
+        }
+    }
+
+    /// The kind of the reference with its name, e.g. `("tag", "stable2407")`.
+    fn reference_property(&self) -> Option<(&'static str, &str)> {
+        match &self.reference {
+            GitRef::Branch(branch) => Some(("branch", branch)),
+            GitRef::Tag(tag) => Some(("tag", tag)),
+            GitRef::Rev(rev) => Some(("rev", rev)),
+            GitRef::DefaultBranch => None,
+        }
+    }
+}
+
+/// A crate of the searched workspaces.
+#[derive(Debug)]
+struct Member {
+    name: String,
+    version: Option<String>,
+    /// The manifest relative to the first search directory.
+    manifest: String,
+    /// The pins the member depends on.
+    pins: BTreeSet<Pin>,
+}
+
+/// All members and pins found in the searched manifests.
+struct Inventory {
+    name: String,
+    members: Vec<Member>,
+    pins: BTreeSet<Pin>,
+}
+
+impl Sbom {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &RunContext) -> Result<()> {
+        let dirs = update::search_dirs(ctx.changes.fs(), &self.path)?;
+        let repos: RepoMatcher = ctx.config.match_repos().into();
+
+        let mut docs = Vec::new();
+        for manifest in ctx.manifests(&self.path)? {
+            let doc = ctx.changes.read_toml(&manifest)?;
+            docs.push((manifest, doc));
+        }
+
+        let root = dirs.first().cloned().unwrap_or_default();
+        let inventory = Inventory::new(&root, &docs, &repos);
+        log::info!(
+            "Found {} member(s) and {} Polkadot SDK pin(s).",
+            inventory.members.len(),
+            inventory.pins.len()
+        );
+
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .context("System time is before the unix epoch")?
+            .as_secs();
+        let sbom = match self.format {
+            SbomFormat::CycloneDx => inventory.cyclonedx(created),
+            SbomFormat::SpdxJson => inventory.spdx(created),
+        };
+        println!("{}", serde_json::to_string_pretty(&sbom)?);
+        Ok(())
+    }
+}
+
+impl Inventory {
+    fn new(root: &Path, docs: &[(PathBuf, Document)], repos: &RepoMatcher) -> Self {
+        let name = root
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "workspace".into());
+        let pin = |key: &str, dep: &dyn TableLike| {
+            is_managed_dependency(dep, repos).then(|| Pin {
+                name: dependency_package(key, dep).into(),
+                url: dep
+                    .get("git")
+                    .and_then(|g| g.as_str())
+                    .unwrap_or_default()
+                    .into(),
+                reference: GitRef::from_dependency(dep),
+            })
+        };
+
+        let mut pins = BTreeSet::new();
+        let mut members = Vec::new();
+        for (manifest, doc) in docs {
+            let workspace = workspace_of(docs, manifest);
+            let inherited = |key: &str| -> Option<&Item> {
+                workspace?.get("workspace")?.get("dependencies")?.get(key)
+            };
+
+            let mut member_pins = BTreeSet::new();
+            for (table, deps) in dependency_tables(doc) {
+                for (key, dep) in deps.iter() {
+                    let Some(dep) = dep.as_table_like() else {
+                        continue;
+                    };
+                    let pinned = if dep.get("workspace").and_then(|w| w.as_bool()) == Some(true) {
+                        inherited(key)
+                            .and_then(Item::as_table_like)
+                            .and_then(|dep| pin(key, dep))
+                    } else {
+                        pin(key, dep)
+                    };
+                    let Some(pinned) = pinned else {
+                        continue;
+                    };
+
+                    pins.insert(pinned.clone());
+                    if table != "workspace.dependencies" {
+                        member_pins.insert(pinned);
+                    }
+                }
+            }
+
+            let Some(package) = doc.get("package") else {
+                continue;
+            };
+            let Some(name) = package.get("name").and_then(|n| n.as_str()) else {
+                continue;
+            };
+            let version = match package.get("version") {
+                Some(version) if version.get("workspace").is_some() => workspace.and_then(|ws| {
+                    ws.get("workspace")?
+                        .get("package")?
+                        .get("version")?
+                        .as_str()
+                }),
+                Some(version) => version.as_str(),
+                None => None,
+            };
+            let relative = manifest.strip_prefix(root).unwrap_or(manifest);
+            members.push(Member {
+                name: name.into(),
+                version: version.map(Into::into),
+                manifest: manifest_path(relative),
+                pins: member_pins,
+            });
+        }
+
+        members.sort_by(|a, b| a.manifest.cmp(&b.manifest));
+        Self {
+            name,
+            members,
+            pins,
+        }
+    }
+
+    /// Returns the inventory as CycloneDX 1.5 JSON document.
+    fn cyclonedx(&self, created: u64) -> Value {
+        let members = self.members.iter().map(|member| {
+            let mut component = json!({
+                "type": "library",
+                "bom-ref": member.manifest,
+                "name": member.name,
+            });
+            if let Some(version) = &member.version {
+                component["version"] = version.as_str().into();
+                component["purl"] = format!("pkg:cargo/{}@{}", member.name, version).into();
+            }
+            component
+        });
+        let pins = self.pins.iter().map(|pin| {
+            let mut properties = vec![json!({ "name": "diener:repository", "value": pin.url })];
+            if let Some((kind, name)) = pin.reference_property() {
+                properties.push(json!({ "name": format!("diener:{kind}"), "value": name }));
+            }
+            json!({
+                "type": "library",
+                "bom-ref": pin.purl(),
+                "name": pin.name,
+                "version": pin.reference.name(),
+                "purl": pin.purl(),
+                "externalReferences": [{ "type": "vcs", "url": pin.url }],
+                "properties": properties,
+            })
+        });
+        let dependencies = self.members.iter().map(|member| {
+            json!({
+                "ref": member.manifest,
+                "dependsOn": member.pins.iter().map(Pin::purl).collect::<Vec<_>>(),
+            })
+        });
+
+        json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "version": 1,
+            "metadata": {
+                "timestamp": rfc3339(created),
+                "tools": {
+                    "components": [{
+                        "type": "application",
+                        "name": crate_name!(),
+                        "version": crate_version!(),
+                    }],
+                },
+                "component": { "type": "application", "name": self.name },
+            },
+            "components": members.chain(pins).collect::<Vec<_>>(),
+            "dependencies": dependencies.collect::<Vec<_>>(),
+        })
+    }
+
+    /// Returns the inventory as SPDX 2.3 JSON document.
+    fn spdx(&self, created: u64) -> Value {
+        let pin_ids = self
+            .pins
+            .iter()
+            .enumerate()
+            .map(|(i, pin)| (pin, format!("SPDXRef-Pin-{i}")))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut packages = Vec::new();
+        let mut relationships = Vec::new();
+        for (i, member) in self.members.iter().enumerate() {
+            let id = format!("SPDXRef-Member-{i}");
+            let mut package = json!({
+                "SPDXID": id,
+                "name": member.name,
+                "downloadLocation": "NOASSERTION",
+                "filesAnalyzed": false,
+                "sourceInfo": format!("Declared in {}", member.manifest),
+            });
+            if let Some(version) = &member.version {
+                package["versionInfo"] = version.as_str().into();
+                package["externalRefs"] = json!([{
+                    "referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl",
+                    "referenceLocator": format!("pkg:cargo/{}@{}", member.name, version),
+                }]);
+            }
+            packages.push(package);
+
+            relationships.push(json!({
+                "spdxElementId": "SPDXRef-DOCUMENT",
+                "relationshipType": "DESCRIBES",
+                "relatedSpdxElement": id,
+            }));
+            for pin in &member.pins {
+                relationships.push(json!({
+                    "spdxElementId": id,
+                    "relationshipType": "DEPENDS_ON",
+                    "relatedSpdxElement": pin_ids[pin],
+                }));
+            }
+        }
+        for (pin, id) in &pin_ids {
+            packages.push(json!({
+                "SPDXID": id,
+                "name": pin.name,
+                "versionInfo": pin.reference.name(),
+                "downloadLocation": pin.download_location(),
+                "filesAnalyzed": false,
+                "externalRefs": [{
+                    "referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl",
+                    "referenceLocator": pin.purl(),
+                }],
+            }));
+        }
+
+        json!({
+            "spdxVersion": "SPDX-2.3",
+            "dataLicense": "CC0-1.0",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "name": self.name,
+            "documentNamespace": format!(
+                "https://spdx.org/spdxdocs/{}-{}-{}",
+                crate_name!(),
+                percent_encode(&self.name),
+                created
+            ),
+            "creationInfo": {
+                "created": rfc3339(created),
+                "creators": [format!("Tool: {}-{}", crate_name!(), crate_version!())],
+            },
+            "packages": packages,
+            "relationships": relationships,
+        })
+    }
+}
+
+/// Returns the document of the closest workspace root containing `manifest`.
+fn workspace_of<'a>(docs: &'a [(PathBuf, Document)], manifest: &Path) -> Option<&'a Document> {
+    docs.iter()
+        .filter(|(_, doc)| doc.contains_key("workspace"))
+        .filter(|(root, _)| root.parent().is_some_and(|dir| manifest.starts_with(dir)))
+        .max_by_key(|(root, _)| root.components().count())
+        .map(|(_, doc)| doc)
+}
+
+/// Percent-encode all characters of `s` that are not allowed unencoded in a package url.
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b':' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Formats the unix timestamp `secs` as RFC 3339 date time in UTC, e.g. `2024-07-01T12:00:00Z`.
+pub(crate) fn rfc3339(secs: u64) -> String {
+    let (days, time) = (secs / 86400, secs % 86400);
+    // The civil date of the days since the epoch, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
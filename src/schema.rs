@@ -0,0 +1,226 @@
+use crate::context::RunContext;
+use anyhow::{bail, Error, Result};
+use serde_json::{json, Value};
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// The JSON Schema dialect of the printed schemas.
+const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// A file format of diener with a schema.
+#[derive(Debug, Clone, Copy)]
+enum SchemaFile {
+    /// `.diener.toml` and the user configuration.
+    Config,
+    /// The plans written by `--plan-out`.
+    Plan,
+}
+
+impl FromStr for SchemaFile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "config" => Ok(Self::Config),
+            "plan" => Ok(Self::Plan),
+            _ => bail!("Invalid schema `{s}`, expected `config` or `plan`."),
+        }
+    }
+}
+
+/// `schema` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct Schema {
+    /// The file to print the schema of, `config` for `.diener.toml` or `plan` for the plans of
+    /// `--plan-out`.
+    #[structopt(possible_values = &["config", "plan"])]
+    file: SchemaFile,
+}
+
+impl Schema {
+    /// Run this subcommand.
+    pub fn run(self, _: &RunContext) -> Result<()> {
+        let schema = match self.file {
+            SchemaFile::Config => config(),
+            SchemaFile::Plan => plan(),
+        };
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+        Ok(())
+    }
+}
+
+/// A list of strings described by `description`.
+fn strings(description: &str) -> Value {
+    json!({ "description": description, "type": "array", "items": { "type": "string" } })
+}
+
+/// The schema of the configuration, see [`Config`](crate::config::Config).
+fn config() -> Value {
+    json!({
+        "$schema": DIALECT,
+        "title": "diener configuration",
+        "description": "The configuration of diener in `.diener.toml` or `~/.config/diener/config.toml`. All values are defaults, the command line flags take precedence.",
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "match-repos": strings("The names of the repositories whose git dependencies are managed, `[\"polkadot-sdk\"]` by default."),
+            "exclude": strings("Directories that are skipped, relative to the configuration file."),
+            "exclude-dirs": strings("Names of directories that are skipped wherever they appear, e.g. `fixtures`."),
+            "git": {
+                "description": "The `git` url `update` rewrites the dependencies to.",
+                "type": "string"
+            },
+            "branch": {
+                "description": "The `branch` `update` uses if none of `--branch`, `--tag`, `--rev` and `--repo` is given and `DIENER_BRANCH` isn't set.",
+                "type": "string"
+            },
+            "patch-target": {
+                "description": "The patch target used by `patch`, e.g. `crates-io` or a git url.",
+                "type": "string"
+            },
+            "format": {
+                "description": "The output format of reporting subcommands, `github` is only supported by `--check`.",
+                "enum": ["text", "json", "github"]
+            },
+            "path-style": {
+                "description": "How `workspacify` writes the paths of dependencies.",
+                "enum": ["relative", "dot", "from-root"]
+            },
+            "hooks": {
+                "description": "The commands run by the shell after the modified files are written.",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "after-file": strings("Run once for every modified file, which is given in `DIENER_FILE`."),
+                    "after-run": strings("Run once after all files are written, with the modified files in `DIENER_CHANGED_FILES` and on stdin.")
+                }
+            },
+            "confirm-threshold": {
+                "description": "Ask for confirmation before modifying more files, `0` never asks.",
+                "type": "integer",
+                "minimum": 0
+            },
+            "pull-request": {
+                "description": "How `--open-pr` opens pull requests.",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "labels": strings("The labels added to every pull request."),
+                    "reviewers": strings("The users or `org/team`s whose review is requested."),
+                    "remote": {
+                        "description": "The remote the branch is pushed to, `origin` by default.",
+                        "type": "string"
+                    }
+                }
+            },
+            "forges": {
+                "description": "Self-hosted forges diener talks to, besides github.com and gitlab.com.",
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "required": ["host", "kind"],
+                    "properties": {
+                        "host": {
+                            "description": "The host of the repository urls, e.g. `gitlab.parity.io`.",
+                            "type": "string"
+                        },
+                        "kind": {
+                            "description": "The API the forge speaks.",
+                            "enum": ["github", "gitlab"]
+                        },
+                        "api": {
+                            "description": "The url of the API, `https://<host>/api/v4` for GitLab and `https://<host>/api/v3` for GitHub by default.",
+                            "type": "string"
+                        },
+                        "token-env": {
+                            "description": "The environment variable containing the token, `GITLAB_TOKEN` or `GITHUB_TOKEN` by default.",
+                            "type": "string"
+                        }
+                    }
+                }
+            },
+            "network": {
+                "description": "The proxy and CA bundle of `curl`, `git` and `cargo`, the environment takes precedence.",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "proxy": {
+                        "description": "The proxy of all requests, used if `HTTPS_PROXY` isn't set.",
+                        "type": "string"
+                    },
+                    "no-proxy": strings("The hosts that are reached without the proxy, used if `NO_PROXY` isn't set."),
+                    "ca-bundle": {
+                        "description": "A file with the PEM certificates the servers are verified with, relative to the configuration file.",
+                        "type": "string"
+                    }
+                }
+            },
+            "cache": {
+                "description": "How long the on-disk caches keep their entries.",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "ttl": {
+                        "description": "How old entries get before `cache gc` removes them, e.g. `7d` or `12h`, `30d` by default.",
+                        "type": "string",
+                        "pattern": "^[0-9]+[dhms]$"
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// The schema of the plans, see [`Plan`](crate::plan::Plan).
+fn plan() -> Value {
+    json!({
+        "$schema": DIALECT,
+        "title": "diener plan",
+        "description": "All modifications a diener run would make, written by `--plan-out` and applied by `apply-plan`.",
+        "type": "object",
+        "required": ["version", "command", "moves", "files"],
+        "properties": {
+            "version": {
+                "description": "The version of the plan format.",
+                "const": crate::plan::PLAN_VERSION
+            },
+            "command": {
+                "description": "The diener command line that created the plan.",
+                "type": "string"
+            },
+            "moves": {
+                "description": "The directory moves, done before any file is written.",
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["from", "to"],
+                    "properties": {
+                        "from": { "type": "string" },
+                        "to": { "type": "string" }
+                    }
+                }
+            },
+            "files": {
+                "description": "The modified and created files.",
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["path", "content"],
+                    "properties": {
+                        "path": { "type": "string" },
+                        "original_sha256": {
+                            "description": "The hex encoded SHA-256 of the file when the plan was created, `null` if the file is created.",
+                            "type": ["string", "null"],
+                            "pattern": "^[0-9a-f]{64}$"
+                        },
+                        "content": {
+                            "description": "The new content of the file.",
+                            "type": "string"
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
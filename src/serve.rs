@@ -0,0 +1,210 @@
+use crate::{
+    context::RunContext,
+    engine::{self, ChangeSet, Operation},
+    lint::{self, Findings, Lint},
+};
+use anyhow::{anyhow, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+
+/// `serve` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct Serve {
+    /// Read the requests from stdin and write the responses to stdout, one JSON object per line.
+    #[structopt(long)]
+    stdio: bool,
+
+    /// The directory relative paths of the requests are resolved against.
+    ///
+    /// Uses the working directory if none is supplied.
+    #[structopt(long)]
+    path: Option<PathBuf>,
+}
+
+/// A request besides the [`Operation`]s, which are given by their `command` as well.
+#[derive(Debug, Deserialize)]
+#[serde(
+    tag = "command",
+    rename_all = "kebab-case",
+    rename_all_fields = "kebab-case"
+)]
+enum Command {
+    /// Run the lints on the workspace at `path`.
+    Lint {
+        path: Option<PathBuf>,
+        /// Only report the findings in this manifest.
+        manifest: Option<PathBuf>,
+        /// Stage the auto-fixes of the lints.
+        #[serde(default)]
+        fix: bool,
+    },
+    /// Stop serving.
+    Shutdown,
+}
+
+/// The fields every request can have.
+#[derive(Debug, Default, Deserialize)]
+struct Envelope {
+    /// Copied into the response to match it with the request.
+    #[serde(default)]
+    id: Value,
+    /// Write the modifications to the filesystem, instead of only returning them.
+    #[serde(default)]
+    apply: bool,
+}
+
+/// A modified file of a response.
+#[derive(Serialize)]
+struct File<'a> {
+    path: &'a Path,
+    /// `None` for new files.
+    original: Option<&'a str>,
+    content: &'a str,
+}
+
+/// The response to a request.
+#[derive(Serialize)]
+struct Response<'a> {
+    id: &'a Value,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    files: Vec<File<'a>>,
+    moves: &'a [(PathBuf, PathBuf)],
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    findings: Vec<lint::Report<'a>>,
+    applied: bool,
+}
+
+impl Serve {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        ensure!(
+            self.stdio,
+            "`serve` only supports `--stdio`, pass it to serve on stdin and stdout."
+        );
+        let root = ctx.workspace_dir(self.path.as_deref())?;
+        log::info!("Serving requests on stdin.");
+
+        let mut stdout = io::stdout().lock();
+        for line in io::stdin().lock().lines() {
+            let line = line.with_context(|| "Failed to read the request from stdin.")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let request = serde_json::from_str::<Value>(&line);
+            let envelope = request
+                .as_ref()
+                .ok()
+                .and_then(|r| Envelope::deserialize(r).ok())
+                .unwrap_or_default();
+            let shutdown = request
+                .as_ref()
+                .is_ok_and(|r| r.get("command").and_then(Value::as_str) == Some("shutdown"));
+
+            let result = request
+                .map_err(|e| anyhow!("Invalid request: {e}"))
+                .and_then(|request| handle(ctx, &root, request));
+            let response = match result {
+                Ok((changes, findings)) => {
+                    let applied = envelope.apply && !changes.is_empty();
+                    let response = serde_json::to_string(&Response {
+                        id: &envelope.id,
+                        ok: true,
+                        error: None,
+                        files: files(&changes),
+                        moves: changes.moves(),
+                        findings: lint::reports(&findings),
+                        applied,
+                    })?;
+                    match applied.then(|| changes.apply()) {
+                        Some(Err(e)) => error_response(&envelope.id, &e.into())?,
+                        _ => response,
+                    }
+                }
+                Err(e) => error_response(&envelope.id, &e)?,
+            };
+            writeln!(stdout, "{response}")?;
+            stdout.flush()?;
+
+            if shutdown {
+                break;
+            }
+        }
+
+        log::info!("Stopped serving.");
+        Ok(())
+    }
+}
+
+/// Run the `request`, returning the staged modifications and the lint findings.
+fn handle(ctx: &mut RunContext, root: &Path, request: Value) -> Result<(ChangeSet, Findings)> {
+    let command = request
+        .get("command")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("Invalid request: missing `command`."))?;
+    let invalid = |e: serde_json::Error| anyhow!("Invalid `{command}` request: {e}");
+
+    if !matches!(command, "lint" | "shutdown") {
+        let operation = Operation::deserialize(&request).map_err(invalid)?;
+        return Ok((engine::run(ctx, root, &operation)?, Vec::new()));
+    }
+
+    match Command::deserialize(&request).map_err(invalid)? {
+        Command::Lint {
+            path,
+            manifest,
+            fix,
+        } => {
+            let path = path.map_or(root.to_path_buf(), |p| root.join(p));
+            let mut args = vec!["lint".into(), "--path".into(), path.into_os_string()];
+            if fix {
+                args.push("--fix".into());
+            }
+            let lint = Lint::from_iter_safe(args).map_err(engine::Error::invalid)?;
+
+            let (res, mut findings) = match lint.findings(ctx) {
+                Ok(findings) => (Ok(()), findings.unwrap_or_default()),
+                Err(e) => (Err(e), Vec::new()),
+            };
+            let changes = engine::take_changes(ctx, res)?;
+            if let Some(manifest) = manifest.map(|m| root.join(m)) {
+                findings.retain(|(_, _, finding)| finding.manifest == manifest);
+            }
+            Ok((changes, findings))
+        }
+        Command::Shutdown => Ok((engine::take_changes(ctx, Ok(()))?, Vec::new())),
+    }
+}
+
+/// Returns the modified files of `changes`.
+fn files(changes: &ChangeSet) -> Vec<File<'_>> {
+    changes
+        .files()
+        .map(|(path, original, content)| File {
+            path,
+            original,
+            content,
+        })
+        .collect()
+}
+
+/// Returns the response to a request that failed with `error`.
+fn error_response(id: &Value, error: &anyhow::Error) -> Result<String> {
+    let response = Response {
+        id,
+        ok: false,
+        error: Some(format!("{error:#}")),
+        files: Vec::new(),
+        moves: &[],
+        findings: Vec::new(),
+        applied: false,
+    };
+    Ok(serde_json::to_string(&response)?)
+}
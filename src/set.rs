@@ -0,0 +1,192 @@
+use crate::{
+    context::RunContext,
+    editor,
+    format::OutputFormat,
+    manifest::{dependency_package, dependency_tables_mut},
+};
+use anyhow::{bail, ensure, Result};
+use glob::Pattern;
+use regex::Regex;
+use std::{path::PathBuf, str::FromStr};
+use structopt::StructOpt;
+use toml_edit::{Item, Value};
+
+/// The keys a dependency inherited with `workspace = true` may set itself, all others are taken
+/// from `[workspace.dependencies]`.
+const INHERITED_DEPENDENCY_KEYS: &[&str] = &["features", "optional"];
+
+/// `set` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct Set {
+    /// The path where Diener should search for `Cargo.toml` files.
+    ///
+    /// Can be passed multiple times and may be a glob pattern like `runtimes/*`.
+    /// If not given, the current directory will be taken.
+    #[structopt(long, number_of_values = 1)]
+    path: Vec<PathBuf>,
+
+    /// Only change the dependencies whose package name matches the glob pattern, e.g. `sp-*`.
+    ///
+    /// Can be passed multiple times, a dependency needs to match one of them.
+    #[structopt(long = "match", number_of_values = 1)]
+    match_package: Vec<Pattern>,
+
+    /// Only change the git dependencies whose url matches the regex, e.g. `polkadot-sdk`.
+    #[structopt(long)]
+    match_url: Option<Regex>,
+
+    /// Only change the dependencies in the tables matching the glob pattern, e.g.
+    /// `dev-dependencies`.
+    ///
+    /// Target tables are named like `target.'cfg(unix)'.dependencies`.
+    #[structopt(long)]
+    table: Option<Pattern>,
+
+    /// Remove the given key from the dependencies, e.g. `--remove tag`.
+    ///
+    /// Can be passed multiple times.
+    #[structopt(long, number_of_values = 1)]
+    remove: Vec<String>,
+
+    /// Don't modify any file, fail if any manifest is not up to date instead.
+    #[structopt(long)]
+    check: bool,
+
+    /// The output format of `--check`, `text`, `json` or `github`.
+    ///
+    /// `github` emits GitHub Actions annotations. Defaults to the `format` of the configuration
+    /// or `text`.
+    #[structopt(long, requires = "check")]
+    format: Option<OutputFormat>,
+
+    /// The keys to set as `key=value`, e.g. `default-features=false`.
+    ///
+    /// The value is parsed as TOML value, like `false`, `["std"]` or `"1.0"`. Values that are no
+    /// valid TOML are written as strings.
+    ///
+    /// Dependencies inherited with `workspace = true` only get `features` and `optional`, other
+    /// keys need to be set in `[workspace.dependencies]`.
+    assignments: Vec<Assignment>,
+}
+
+/// A `key=value` given on the command line.
+#[derive(Debug)]
+struct Assignment {
+    key: String,
+    value: Value,
+}
+
+impl FromStr for Assignment {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((key, value)) = s.split_once('=') else {
+            bail!("Invalid assignment `{s}`, expected `key=value`.");
+        };
+        let key = key.trim();
+        ensure!(
+            !key.is_empty(),
+            "Invalid assignment `{s}`, the key is empty."
+        );
+
+        let value = value
+            .parse::<Value>()
+            .map(|v| v.decorated("", ""))
+            .unwrap_or_else(|_| Value::from(value));
+        Ok(Self {
+            key: key.into(),
+            value,
+        })
+    }
+}
+
+impl Set {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        ensure!(
+            !self.assignments.is_empty() || !self.remove.is_empty(),
+            "Nothing to do, pass `key=value` or `--remove`."
+        );
+        ensure!(
+            !self.match_package.is_empty() || self.match_url.is_some() || self.table.is_some(),
+            "Pass `--match`, `--match-url` or `--table` to select the dependencies to change."
+        );
+        let check = self.check.then(|| {
+            self.format
+                .or(ctx.config.format())
+                .unwrap_or(OutputFormat::Text)
+        });
+
+        for manifest in ctx.manifests(&self.path)? {
+            let mut doc = ctx.changes.read_toml(&manifest)?;
+
+            let mut matched = 0;
+            for (table, deps) in dependency_tables_mut(&mut doc) {
+                if self.table.as_ref().is_some_and(|t| !t.matches(&table)) {
+                    continue;
+                }
+                for (key, dep) in deps.iter_mut() {
+                    if !self.matches(key.get(), dep) {
+                        continue;
+                    }
+                    self.apply(dep, || format!("{}: `{}`", manifest.display(), key.get()));
+                    matched += 1;
+                }
+            }
+
+            if matched > 0 {
+                log::info!("{}: Matched {} dependencies.", manifest.display(), matched);
+                ctx.changes.write(&manifest, doc.to_string());
+            }
+        }
+
+        match check {
+            Some(format) => ctx.changes.check(
+                format,
+                ctx.color,
+                "Dependencies are not up to date, run `diener set`.",
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns `true` if the dependency `dep` declared as `key` should be changed.
+    fn matches(&self, key: &str, dep: &Item) -> bool {
+        let (package, git) = match dep.as_table_like() {
+            Some(dep) => (
+                dependency_package(key, dep),
+                dep.get("git").and_then(|g| g.as_str()),
+            ),
+            None => (key, None),
+        };
+
+        (self.match_package.is_empty() || self.match_package.iter().any(|p| p.matches(package)))
+            && self
+                .match_url
+                .as_ref()
+                .is_none_or(|regex| git.is_some_and(|git| regex.is_match(git)))
+    }
+
+    /// Remove and set the keys of `dep`, `name` describes it for messages.
+    ///
+    /// Keys that a dependency inherited from the workspace can't set are skipped.
+    fn apply(&self, dep: &mut Item, name: impl Fn() -> String) {
+        for key in &self.remove {
+            editor::remove_key(dep, key);
+        }
+
+        let inherited = dep.get("workspace").and_then(|w| w.as_bool()) == Some(true);
+        for assignment in &self.assignments {
+            if inherited && !INHERITED_DEPENDENCY_KEYS.contains(&assignment.key.as_str()) {
+                log::warn!(
+                    "{} is inherited from the workspace, not setting `{}`, set it in \
+                     `[workspace.dependencies]` with `--table workspace.dependencies`.",
+                    name(),
+                    assignment.key
+                );
+                continue;
+            }
+            editor::set_key(dep, &assignment.key, assignment.value.clone());
+        }
+    }
+}
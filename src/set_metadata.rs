@@ -0,0 +1,154 @@
+use crate::{context::RunContext, editor, format::OutputFormat, workspacify::manifest_iter};
+use anyhow::{bail, ensure, Result};
+use std::{path::PathBuf, str::FromStr};
+use structopt::StructOpt;
+use toml_edit::{Array, Item, TableLike, Value};
+
+/// Fields that are arrays of strings, their values are split at `,`.
+const ARRAY_FIELDS: &[&str] = &["authors", "keywords", "categories"];
+
+/// `set-metadata` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct SetMetadata {
+    /// The path to the workspace root directory.
+    ///
+    /// Uses the working directory if none is supplied.
+    #[structopt(long)]
+    path: Option<PathBuf>,
+
+    /// Don't modify any file, fail if any manifest is not up to date instead.
+    #[structopt(long)]
+    check: bool,
+
+    /// The output format of `--check`, `text`, `json` or `github`.
+    ///
+    /// `github` emits GitHub Actions annotations. Defaults to the `format` of the configuration
+    /// or `text`.
+    #[structopt(long, requires = "check")]
+    format: Option<OutputFormat>,
+
+    /// Only add the fields that are missing, existing values are kept.
+    #[structopt(long)]
+    missing_only: bool,
+
+    /// The fields to set as `key=value`, e.g. `license=Apache-2.0`.
+    ///
+    /// The values of `authors`, `keywords` and `categories` are split at `,`, `publish=true`
+    /// and `publish=false` are written as booleans. All other values are written as strings.
+    #[structopt(required = true)]
+    fields: Vec<Field>,
+}
+
+/// A `key=value` given on the command line.
+#[derive(Debug)]
+struct Field {
+    key: String,
+    value: Value,
+}
+
+impl FromStr for Field {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((key, value)) = s.split_once('=') else {
+            bail!("Invalid field `{s}`, expected `key=value`.");
+        };
+        let key = key.trim();
+        ensure!(!key.is_empty(), "Invalid field `{s}`, the key is empty.");
+        ensure!(key != "name", "`name` can not be set for all crates.");
+
+        let value = match (key, value) {
+            ("publish", "true") => Value::from(true),
+            ("publish", "false") => Value::from(false),
+            (key, value) if ARRAY_FIELDS.contains(&key) => Value::Array(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+                    .collect::<Array>(),
+            ),
+            (_, value) => Value::from(value),
+        };
+
+        Ok(Self {
+            key: key.into(),
+            value,
+        })
+    }
+}
+
+impl SetMetadata {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        let check = self.check.then(|| {
+            self.format
+                .or(ctx.config.format())
+                .unwrap_or(OutputFormat::Text)
+        });
+        let workspace = ctx.workspace_dir(self.path.as_deref())?;
+
+        for manifest in manifest_iter(ctx.changes.fs(), &workspace, &ctx.exclusions) {
+            let mut doc = ctx.changes.read_toml(&manifest)?;
+
+            let mut modified = false;
+            if let Some(package) = doc.get_mut("package").and_then(Item::as_table_like_mut) {
+                modified |= self.set_fields(package, true);
+            }
+            // Only the fields that are already in `[workspace.package]` are updated, the
+            // members decide what they inherit.
+            if let Some(package) = doc
+                .get_mut("workspace")
+                .and_then(|w| w.get_mut("package"))
+                .and_then(Item::as_table_like_mut)
+            {
+                modified |= self.set_fields(package, false);
+            }
+
+            if modified {
+                log::info!("Updated the metadata of {}.", manifest.display());
+                ctx.changes.write(&manifest, doc.to_string());
+            }
+        }
+
+        match check {
+            Some(format) => ctx.changes.check(
+                format,
+                ctx.color,
+                "Package metadata is not up to date, run `diener set-metadata`.",
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Set the fields in `package`, adding the missing ones if `insert` is `true`.
+    ///
+    /// Fields inherited with `key.workspace = true` are left untouched. Returns `true` if any
+    /// field was modified.
+    fn set_fields(&self, package: &mut dyn TableLike, insert: bool) -> bool {
+        let mut modified = false;
+        for field in &self.fields {
+            match package.get_mut(&field.key) {
+                Some(item) if item.get("workspace").and_then(|w| w.as_bool()) == Some(true) => {
+                    log::debug!("Skipping `{}`, it is inherited.", field.key);
+                }
+                Some(_) if self.missing_only => {}
+                Some(item) => {
+                    let Some(value) = item.as_value_mut() else {
+                        continue;
+                    };
+                    if value.to_string().trim() == field.value.to_string().trim() {
+                        continue;
+                    }
+                    editor::set_value(value, field.value.clone());
+                    modified = true;
+                }
+                None if insert => {
+                    package.insert(&field.key, Item::Value(field.value.clone()));
+                    modified = true;
+                }
+                None => {}
+            }
+        }
+        modified
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{
+    context::RunContext,
+    failure::{Classify, Failure},
+    manifest::manifest_path,
+    workspacify::manifest_iter,
+};
+use anyhow::{anyhow, ensure, Context, Result};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use structopt::StructOpt;
+
+/// The directory snapshots are stored in, relative to the workspace root.
+const SNAPSHOTS_DIR: &str = ".diener/snapshots";
+
+/// `snapshot` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct Snapshot {
+    /// The path to the workspace root directory.
+    ///
+    /// Uses the working directory if none is supplied.
+    #[structopt(long)]
+    path: Option<PathBuf>,
+
+    /// The name of the snapshot, defaults to the current unix timestamp.
+    ///
+    /// An existing snapshot with the same name is replaced.
+    #[structopt(long)]
+    name: Option<String>,
+}
+
+/// `restore` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct Restore {
+    /// The path to the workspace root directory.
+    ///
+    /// Uses the working directory if none is supplied.
+    #[structopt(long)]
+    path: Option<PathBuf>,
+
+    /// The name of the snapshot to restore.
+    name: String,
+}
+
+impl Snapshot {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        let workspace = ctx.workspace_dir(self.path.as_deref())?;
+        let name = match self.name {
+            Some(name) => name,
+            None => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .context("System time is before the unix epoch")?
+                .as_secs()
+                .to_string(),
+        };
+        let path = snapshot_path(&workspace, &name)?;
+
+        // The content of every manifest by its path relative to the workspace root.
+        let mut manifests = BTreeMap::new();
+        for manifest in manifest_iter(ctx.changes.fs(), &workspace, &ctx.exclusions) {
+            let content = ctx.changes.read(&manifest)?;
+            let relative = manifest.strip_prefix(&workspace).unwrap_or(&manifest);
+            manifests.insert(manifest_path(relative), content);
+        }
+
+        ctx.changes.write(
+            &path,
+            format!("{}\n", serde_json::to_string_pretty(&manifests)?),
+        );
+        log::info!(
+            "Stored {} manifest(s) in snapshot `{}`, restore them with `diener restore {}`.",
+            manifests.len(),
+            name,
+            name
+        );
+        Ok(())
+    }
+}
+
+impl Restore {
+    /// Run this subcommand.
+    ///
+    /// Manifests that were created after the snapshot was taken are left untouched.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        let workspace = ctx.workspace_dir(self.path.as_deref())?;
+        let path = snapshot_path(&workspace, &self.name)?;
+        ensure!(
+            ctx.changes.exists(&path),
+            "There is no snapshot `{}` at {}.",
+            self.name,
+            path.display()
+        );
+
+        let manifests = serde_json::from_str::<BTreeMap<String, String>>(&ctx.changes.read(&path)?)
+            .with_context(|| anyhow!("Failed to parse snapshot {}", path.display()))
+            .failure(Failure::Parse)?;
+        for (manifest, content) in &manifests {
+            ctx.changes
+                .write(workspace.join(manifest), content.as_str());
+        }
+
+        log::info!(
+            "Restored {} manifest(s) of snapshot `{}`.",
+            manifests.len(),
+            self.name
+        );
+        Ok(())
+    }
+}
+
+/// Returns the path of the snapshot `name` of the workspace at `workspace`.
+fn snapshot_path(workspace: &Path, name: &str) -> Result<PathBuf> {
+    ensure!(
+        !name.is_empty() && !name.contains(['/', '\\']) && name != "." && name != "..",
+        "Invalid snapshot name `{}`.",
+        name
+    );
+    Ok(workspace.join(SNAPSHOTS_DIR).join(format!("{name}.json")))
+}
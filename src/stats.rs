@@ -0,0 +1,167 @@
+use crate::{
+    changes,
+    context::RunContext,
+    format::OutputFormat,
+    git::{self, GitRef},
+    manifest::dependency_tables_mut,
+};
+use anyhow::{anyhow, ensure, Context, Result};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+/// `stats` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct Stats {
+    /// The path where Diener should search for `Cargo.toml` files.
+    ///
+    /// Can be passed multiple times and may be a glob pattern like `runtimes/*`.
+    /// If not given, the current directory will be taken.
+    #[structopt(long)]
+    path: Vec<PathBuf>,
+
+    /// The output format, `text` or `json`.
+    ///
+    /// Defaults to the `format` of the configuration or `text`.
+    #[structopt(long)]
+    format: Option<OutputFormat>,
+
+    /// The number of largest dependency tables to show.
+    #[structopt(long, default_value = "10")]
+    top: usize,
+}
+
+/// Number of dependencies per kind of source.
+#[derive(Debug, Default, Serialize)]
+struct DependencyCounts {
+    git: usize,
+    path: usize,
+    registry: usize,
+    workspace: usize,
+}
+
+/// A dependency table of a manifest.
+#[derive(Debug, Serialize)]
+struct DependencyTable {
+    manifest: PathBuf,
+    table: String,
+    len: usize,
+}
+
+/// The summary of a tree.
+#[derive(Debug, Default, Serialize)]
+struct Summary {
+    manifests: usize,
+    workspaces: usize,
+    crates: usize,
+    dependencies: DependencyCounts,
+    /// The distinct branches/tags/revs used per git repository.
+    git_references: BTreeMap<String, BTreeSet<String>>,
+    largest_dependency_tables: Vec<DependencyTable>,
+}
+
+impl Stats {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &RunContext) -> Result<()> {
+        let format = self
+            .format
+            .or(ctx.config.format())
+            .unwrap_or(OutputFormat::Text);
+        ensure!(
+            format != OutputFormat::Github,
+            "`stats` doesn't support the `github` format."
+        );
+
+        let mut summary = Summary::default();
+        let mut tables = Vec::new();
+
+        for manifest in ctx.manifests(&self.path)? {
+            let content = fs::read_to_string(&manifest)
+                .with_context(|| anyhow!("Failed to read manifest at {}", manifest.display()))?;
+            let mut doc = changes::parse_toml(&manifest, &content)?;
+
+            summary.manifests += 1;
+            summary.workspaces += usize::from(doc.contains_key("workspace"));
+            summary.crates += usize::from(doc.contains_key("package"));
+
+            for (name, table) in dependency_tables_mut(&mut doc) {
+                tables.push(DependencyTable {
+                    manifest: manifest.clone(),
+                    table: name,
+                    len: table.len(),
+                });
+
+                for (_, dep) in table.iter() {
+                    let Some(dep) = dep.as_table_like() else {
+                        summary.dependencies.registry += 1;
+                        continue;
+                    };
+
+                    if dep.contains_key("workspace") {
+                        summary.dependencies.workspace += 1;
+                    } else if let Some(url) = dep.get("git").and_then(|g| g.as_str()) {
+                        summary.dependencies.git += 1;
+                        summary
+                            .git_references
+                            .entry(git::repository_id(url))
+                            .or_default()
+                            .insert(GitRef::from_dependency(dep).to_string());
+                    } else if dep.contains_key("path") {
+                        summary.dependencies.path += 1;
+                    } else {
+                        summary.dependencies.registry += 1;
+                    }
+                }
+            }
+        }
+
+        tables.sort_by(|a, b| b.len.cmp(&a.len).then_with(|| a.manifest.cmp(&b.manifest)));
+        tables.truncate(self.top);
+        summary.largest_dependency_tables = tables;
+
+        match format {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&summary)?),
+            OutputFormat::Text | OutputFormat::Github => print_text(&summary),
+        }
+
+        Ok(())
+    }
+}
+
+fn print_text(summary: &Summary) {
+    println!("Manifests:  {}", summary.manifests);
+    println!("Workspaces: {}", summary.workspaces);
+    println!("Crates:     {}", summary.crates);
+    println!();
+    println!("Dependencies:");
+    println!("  git:       {}", summary.dependencies.git);
+    println!("  path:      {}", summary.dependencies.path);
+    println!("  registry:  {}", summary.dependencies.registry);
+    println!("  workspace: {}", summary.dependencies.workspace);
+
+    if !summary.git_references.is_empty() {
+        println!();
+        println!("Git references per repository:");
+        for (repository, references) in &summary.git_references {
+            println!("  {} ({}):", repository, references.len());
+            references.iter().for_each(|r| println!("    {r}"));
+        }
+    }
+
+    if !summary.largest_dependency_tables.is_empty() {
+        println!();
+        println!("Largest dependency tables:");
+        for table in &summary.largest_dependency_tables {
+            println!(
+                "  {:>4}  {} [{}]",
+                table.len,
+                table.manifest.display(),
+                table.table
+            );
+        }
+    }
+}
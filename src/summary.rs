@@ -0,0 +1,170 @@
+use crate::{
+    changes::Changes,
+    git::{self, GitRef},
+    manifest::{dependency_package, dependency_tables, manifest_path},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    path::Path,
+    str::FromStr,
+};
+use toml_edit::{Document, Item};
+
+/// Where a dependency is taken from.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Source {
+    Git { url: String, reference: GitRef },
+    Path,
+    Registry,
+    Workspace,
+}
+
+impl Source {
+    fn of(item: &Item) -> Self {
+        let Some(dep) = item.as_table_like() else {
+            return Self::Registry;
+        };
+        let get = |key| dep.get(key);
+
+        if let Some(url) = get("git").and_then(|g| g.as_str()) {
+            Self::Git {
+                url: url.into(),
+                reference: GitRef::from_dependency(dep),
+            }
+        } else if get("path").is_some() {
+            Self::Path
+        } else if get("workspace").and_then(|w| w.as_bool()) == Some(true) {
+            Self::Workspace
+        } else {
+            Self::Registry
+        }
+    }
+
+    fn url(&self) -> Option<&str> {
+        match self {
+            Self::Git { url, .. } => Some(url),
+            _ => None,
+        }
+    }
+
+    /// Describe the source for the summary of the repository `group`.
+    fn describe(&self, group: Option<&str>) -> String {
+        match self {
+            Self::Git { url, reference } => {
+                let reference = match reference {
+                    GitRef::Branch(branch) => format!("branch `{branch}`"),
+                    GitRef::Tag(tag) => format!("tag `{tag}`"),
+                    GitRef::Rev(rev) => format!("rev `{rev}`"),
+                    GitRef::DefaultBranch => "the default branch".into(),
+                };
+                if group == Some(url.as_str()) {
+                    reference
+                } else {
+                    format!("{reference} of `{}`", git::repository_id(url))
+                }
+            }
+            Self::Path => "a local path".into(),
+            Self::Registry => "crates.io".into(),
+            Self::Workspace => "the workspace dependencies".into(),
+        }
+    }
+}
+
+/// Render the modifications of `changes` as Markdown for a pull request description.
+///
+/// The dependencies whose source changed are grouped by repository and by their old and new
+/// source, e.g. "34 crates moved from branch `master` to tag `polkadot-stable2409`". Paths are
+/// shown relative to `root`.
+pub(crate) fn markdown(changes: &Changes, root: &Path, invocation: &str) -> String {
+    // Repository => (old source, new source) => dependency packages.
+    let mut groups = BTreeMap::<Option<String>, BTreeMap<_, BTreeSet<String>>>::new();
+    for (_, original, content) in changes.modified() {
+        let (Some(Ok(old)), Ok(new)) = (
+            original.map(Document::from_str),
+            Document::from_str(content),
+        ) else {
+            continue;
+        };
+        for (key, from, to) in moved_dependencies(&old, &new) {
+            let group = to.url().or(from.url()).map(Into::into);
+            groups
+                .entry(group)
+                .or_default()
+                .entry((from, to))
+                .or_default()
+                .insert(key);
+        }
+    }
+
+    let files = changes.modified_files().collect::<Vec<_>>();
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "## Dependency changes\n\nGenerated by `{invocation}`, {} file(s) modified.",
+        files.len()
+    );
+    if groups.is_empty() {
+        out.push_str("\nNo dependency changed its source.\n");
+    }
+
+    for (group, moves) in &groups {
+        match group {
+            Some(url) => {
+                let _ = writeln!(out, "\n### `{}`\n", git::repository_id(url));
+            }
+            None => out.push_str("\n### Other dependencies\n\n"),
+        }
+        for ((from, to), crates) in moves {
+            let _ = writeln!(
+                out,
+                "- {} {} moved from {} to {}.",
+                crates.len(),
+                if crates.len() == 1 { "crate" } else { "crates" },
+                from.describe(group.as_deref()),
+                to.describe(group.as_deref())
+            );
+        }
+    }
+
+    let relative = |path: &Path| manifest_path(path.strip_prefix(root).unwrap_or(path));
+    out.push_str("\n<details>\n<summary>Modified files</summary>\n\n");
+    for file in files {
+        let _ = writeln!(out, "- `{}`", relative(file));
+    }
+    for (from, to) in changes.moves() {
+        let _ = writeln!(out, "- `{}` moved to `{}`", relative(from), relative(to));
+    }
+    out.push_str("\n</details>\n");
+
+    out
+}
+
+/// Returns the package, the old and the new source of every dependency in both `old` and `new`
+/// whose source changed.
+fn moved_dependencies(old: &Document, new: &Document) -> Vec<(String, Source, Source)> {
+    let old_tables = dependency_tables(old)
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+
+    let mut moved = Vec::new();
+    for (table, deps) in dependency_tables(new) {
+        let Some(old_deps) = old_tables.get(&table) else {
+            continue;
+        };
+        for (key, item) in deps.iter() {
+            let Some(old_item) = old_deps.get(key) else {
+                continue;
+            };
+            let (from, to) = (Source::of(old_item), Source::of(item));
+            if from != to {
+                let package = item
+                    .as_table_like()
+                    .map_or(key, |dep| dependency_package(key, dep));
+                moved.push((package.to_string(), from, to));
+            }
+        }
+    }
+
+    moved
+}
@@ -0,0 +1,144 @@
+use crate::format::OutputFormat;
+use anyhow::Result;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// The number of slowest files that are reported.
+const OUTLIERS: usize = 10;
+
+/// A phase of a diener run whose duration is recorded by `--timings`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Phase {
+    /// Searching for `Cargo.toml` files.
+    Walk,
+    /// Reading and parsing manifests.
+    Parse,
+    /// Running `cargo metadata`.
+    Metadata,
+    /// Fetching from git remotes or the GitHub API.
+    Network,
+    /// Writing the modified files.
+    Write,
+}
+
+impl Phase {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Walk => "walk",
+            Self::Parse => "parse",
+            Self::Metadata => "metadata",
+            Self::Network => "network",
+            Self::Write => "write",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Timings {
+    phases: BTreeMap<Phase, (Duration, usize)>,
+    files: BTreeMap<PathBuf, Duration>,
+}
+
+/// The recorded timings, `None` if `--timings` isn't given.
+///
+/// This is global, so the phases can be measured deep down in the helpers without passing the
+/// recorder through every function.
+static TIMINGS: Mutex<Option<Timings>> = Mutex::new(None);
+
+/// Start recording timings.
+pub(crate) fn enable() {
+    *TIMINGS.lock().expect("Never poisoned; qed") = Some(Timings::default());
+}
+
+/// Run `f` and add its duration to `phase` and to `file` if given.
+pub(crate) fn measure<T>(phase: Phase, file: Option<&Path>, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let res = f();
+    let elapsed = start.elapsed();
+
+    if let Some(timings) = TIMINGS.lock().expect("Never poisoned; qed").as_mut() {
+        let (total, count) = timings.phases.entry(phase).or_default();
+        *total += elapsed;
+        *count += 1;
+        if let Some(file) = file {
+            *timings.files.entry(file.to_path_buf()).or_default() += elapsed;
+        }
+    }
+
+    res
+}
+
+/// Measure every call to `next` of `iter` as `phase`.
+pub(crate) fn measure_iter<I: Iterator>(
+    phase: Phase,
+    mut iter: I,
+) -> impl Iterator<Item = I::Item> {
+    std::iter::from_fn(move || measure(phase, None, || iter.next()))
+}
+
+/// Print the recorded timings to stderr, `total` is the duration of the whole run.
+///
+/// Does nothing if recording wasn't enabled.
+pub(crate) fn report(format: OutputFormat, total: Duration) -> Result<()> {
+    let Some(timings) = TIMINGS.lock().expect("Never poisoned; qed").take() else {
+        return Ok(());
+    };
+
+    let mut files = timings.files.into_iter().collect::<Vec<_>>();
+    files.sort_by_key(|(_, duration)| std::cmp::Reverse(*duration));
+    files.truncate(OUTLIERS);
+
+    match format {
+        OutputFormat::Json => {
+            let phases = timings
+                .phases
+                .iter()
+                .map(|(phase, (duration, count))| {
+                    (
+                        phase.name().to_string(),
+                        serde_json::json!({ "ms": millis(*duration), "count": count }),
+                    )
+                })
+                .collect::<serde_json::Map<_, _>>();
+            let files = files
+                .iter()
+                .map(
+                    |(path, duration)| serde_json::json!({ "path": path, "ms": millis(*duration) }),
+                )
+                .collect::<Vec<_>>();
+            let report = serde_json::json!({
+                "total_ms": millis(total),
+                "phases": phases,
+                "slowest_files": files,
+            });
+            eprintln!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        OutputFormat::Text | OutputFormat::Github => {
+            eprintln!("Timings (total {:.1}ms):", millis(total));
+            for (phase, (duration, count)) in &timings.phases {
+                eprintln!(
+                    "  {:<10} {:>10.1}ms {:>6}x",
+                    phase.name(),
+                    millis(*duration),
+                    count
+                );
+            }
+            if !files.is_empty() {
+                eprintln!("Slowest files:");
+                for (path, duration) in &files {
+                    eprintln!("  {:>10.1}ms {}", millis(*duration), path.display());
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
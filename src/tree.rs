@@ -0,0 +1,201 @@
+use crate::{
+    context::RunContext,
+    diff::{RED, RESET},
+    failure::{Classify, Failure},
+    update::RepoMatcher,
+};
+use anyhow::{anyhow, Result};
+use cargo_metadata::{Metadata, Package, PackageId};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+/// `tree` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct Tree {
+    /// The path to the workspace.
+    ///
+    /// Uses the working directory if none is supplied.
+    #[structopt(long)]
+    path: Option<PathBuf>,
+}
+
+/// The resolved dependency graph, restricted to the packages of the managed repositories.
+struct Graph<'a> {
+    packages: HashMap<&'a PackageId, &'a Package>,
+    /// The resolved dependencies of every package.
+    dependencies: HashMap<&'a PackageId, Vec<&'a PackageId>>,
+    repos: RepoMatcher,
+}
+
+impl<'a> Graph<'a> {
+    fn new(metadata: &'a Metadata, repos: RepoMatcher) -> Result<Self> {
+        let resolve = metadata.resolve.as_ref().ok_or_else(|| {
+            anyhow!("`cargo metadata` returned no dependency graph").failure(Failure::Cargo)
+        })?;
+
+        Ok(Self {
+            packages: metadata.packages.iter().map(|p| (&p.id, p)).collect(),
+            dependencies: resolve
+                .nodes
+                .iter()
+                .map(|n| (&n.id, n.deps.iter().map(|d| &d.pkg).collect()))
+                .collect(),
+            repos,
+        })
+    }
+
+    /// Returns the pin of the package `id` if it is taken from a managed repository.
+    fn pin(&self, id: &PackageId) -> Option<Pin> {
+        let source = self.packages.get(id)?.source.as_ref()?.repr.as_str();
+        let pin = Pin::parse(source)?;
+        self.repos.matches(&pin.url).then_some(pin)
+    }
+
+    /// Returns the managed packages `id` depends on, directly or through unmanaged packages.
+    fn managed_dependencies(&self, id: &'a PackageId) -> Vec<&'a PackageId> {
+        let mut found = BTreeSet::new();
+        let mut visited = HashSet::new();
+        let mut stack = self.dependencies.get(id).cloned().unwrap_or_default();
+        while let Some(dep) = stack.pop() {
+            if !visited.insert(dep) {
+                continue;
+            }
+            if self.pin(dep).is_some() {
+                found.insert(dep);
+            } else {
+                stack.extend(self.dependencies.get(dep).into_iter().flatten());
+            }
+        }
+        found.into_iter().collect()
+    }
+
+    /// Print the package `id` and its managed dependencies below it.
+    ///
+    /// Packages that were already printed are marked with `(*)` and not expanded again.
+    fn print(
+        &self,
+        id: &'a PackageId,
+        prefix: &str,
+        last: bool,
+        root: bool,
+        printed: &mut HashSet<&'a PackageId>,
+        color: bool,
+    ) {
+        let Some(package) = self.packages.get(id) else {
+            return;
+        };
+        let dependencies = self.managed_dependencies(id);
+
+        let branch = match (root, last) {
+            (true, _) => "",
+            (false, true) => "└── ",
+            (false, false) => "├── ",
+        };
+        let pin = match self.pin(id) {
+            Some(pin) => format!(" ({pin})"),
+            None => String::new(),
+        };
+        let pins = dependencies
+            .iter()
+            .filter_map(|d| self.pin(d))
+            .map(|p| p.to_string())
+            .collect::<BTreeSet<_>>();
+        let conflict = if pins.len() > 1 {
+            let conflict = format!(" [{} different pins meet]", pins.len());
+            if color {
+                format!("{RED}{conflict}{RESET}")
+            } else {
+                conflict
+            }
+        } else {
+            String::new()
+        };
+        let expanded = printed.insert(id);
+        let repeated = if !expanded && !dependencies.is_empty() {
+            " (*)"
+        } else {
+            ""
+        };
+
+        println!(
+            "{prefix}{branch}{} v{}{pin}{conflict}{repeated}",
+            package.name, package.version
+        );
+        if !expanded {
+            return;
+        }
+
+        let prefix = match (root, last) {
+            (true, _) => prefix.to_string(),
+            (false, true) => format!("{prefix}    "),
+            (false, false) => format!("{prefix}│   "),
+        };
+        for (i, dep) in dependencies.iter().enumerate() {
+            self.print(
+                dep,
+                &prefix,
+                i + 1 == dependencies.len(),
+                false,
+                printed,
+                color,
+            );
+        }
+    }
+}
+
+/// The git pin of a package, parsed from its source like
+/// `git+https://github.com/paritytech/polkadot-sdk?branch=master#<commit>`.
+struct Pin {
+    url: String,
+    /// The `branch`, `tag` or `rev` of the dependency, `None` for the default branch.
+    reference: Option<String>,
+    commit: String,
+}
+
+impl Pin {
+    fn parse(source: &str) -> Option<Self> {
+        let (url, commit) = source.strip_prefix("git+")?.split_once('#')?;
+        let (url, reference) = match url.split_once('?') {
+            Some((url, reference)) => (url, Some(reference.replacen('=', " = ", 1))),
+            None => (url, None),
+        };
+
+        Some(Self {
+            url: url.into(),
+            reference,
+            commit: commit.into(),
+        })
+    }
+}
+
+impl std::fmt::Display for Pin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let commit = self.commit.get(..8).unwrap_or(&self.commit);
+        match &self.reference {
+            Some(reference) => write!(f, "{}, {}, {}", self.url, reference, commit),
+            None => write!(f, "{}, {}", self.url, commit),
+        }
+    }
+}
+
+impl Tree {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        let workspace = ctx.workspace_dir(self.path.as_deref())?;
+        let metadata = ctx.metadata(&workspace)?;
+        let graph = Graph::new(&metadata, ctx.config.match_repos().into())?;
+
+        let mut printed = HashSet::new();
+        for (i, member) in metadata.workspace_members.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            graph.print(member, "", true, true, &mut printed, ctx.color);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,396 @@
+use crate::{
+    changes::Changes,
+    context::RunContext,
+    diff, editor,
+    filesystem::normalize,
+    update::{self, RepoMatcher, Version},
+    walk::Exclusions,
+    workspacify,
+};
+use anyhow::{anyhow, bail, ensure, Result};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use structopt::StructOpt;
+
+/// What the selected dependencies should be switched to.
+enum Target {
+    Version(Version),
+    /// Point the dependencies to the crates in the given local checkout, an absolute path.
+    Path(PathBuf),
+}
+
+/// `tui` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct Tui {
+    /// The path where Diener should search for `Cargo.toml` files.
+    ///
+    /// Can be passed multiple times and may be a glob pattern like `runtimes/*`.
+    /// If not given, the current directory will be taken.
+    #[structopt(long)]
+    path: Vec<PathBuf>,
+}
+
+/// A Polkadot SDK crate that is used by at least one manifest.
+struct Row {
+    /// The name of the package.
+    name: String,
+    /// The different sources, e.g. `branch = master`, the crate is currently pulled from.
+    sources: BTreeSet<String>,
+    /// All manifests that depend on this crate.
+    manifests: BTreeSet<PathBuf>,
+    selected: bool,
+}
+
+/// The state of the interactive session.
+struct Session {
+    /// The directory the paths of the commands are relative to, see [`RunContext::root`].
+    root: PathBuf,
+    rows: Vec<Row>,
+    target: Option<Target>,
+    git: Option<String>,
+    /// The repositories whose dependencies are listed.
+    repos: RepoMatcher,
+    /// Whether diffs are colored.
+    color: bool,
+}
+
+const HELP: &str = "\
+Commands:
+  <n> [<n>...]   toggle the selection of the given rows, ranges like `3-7` are supported
+  all | none     select all rows or clear the selection
+  branch <name>  switch the selected crates to the given branch
+  tag <name>     switch the selected crates to the given tag
+  rev <commit>   switch the selected crates to the given commit
+  path <dir>     switch the selected crates to the crates in the given local checkout
+  git <url>      additionally rewrite the `git` url (only for branch/tag/rev)
+  list           print the table again
+  diff           preview the changes
+  apply          write the changes and exit
+  quit           exit without writing anything
+  help           print this help";
+
+impl Tui {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        let manifests = ctx.manifests(&self.path)?;
+
+        let repos = ctx.config.match_repos().into();
+        let rows = discover(&manifests, &repos, &ctx.changes)?;
+        if rows.is_empty() {
+            println!("No Polkadot SDK dependencies found.");
+            return Ok(());
+        }
+
+        let mut session = Session {
+            root: ctx.root.clone(),
+            rows,
+            target: None,
+            git: None,
+            repos,
+            color: ctx.color,
+        };
+
+        session.print_table();
+        println!("\n{HELP}");
+
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            print!("\n{}> ", session.status());
+            io::stdout().flush()?;
+
+            let Some(line) = lines.next().transpose()? else {
+                return Ok(());
+            };
+
+            match session.handle_command(line.trim(), &mut ctx.changes) {
+                Ok(true) => return Ok(()),
+                Ok(false) => {}
+                Err(e) => println!("Error: {e:#}"),
+            }
+        }
+    }
+}
+
+impl Session {
+    /// Handle a single command. Returns `true` if the session should end.
+    fn handle_command(&mut self, line: &str, changes: &mut Changes) -> Result<bool> {
+        let (command, argument) = line
+            .split_once(char::is_whitespace)
+            .map(|(c, a)| (c, a.trim()))
+            .unwrap_or((line, ""));
+
+        match command {
+            "" => {}
+            "help" | "h" | "?" => println!("{HELP}"),
+            "list" | "ls" => self.print_table(),
+            "all" => {
+                self.rows.iter_mut().for_each(|r| r.selected = true);
+                self.print_table();
+            }
+            "none" => {
+                self.rows.iter_mut().for_each(|r| r.selected = false);
+                self.print_table();
+            }
+            "branch" => self.target = Some(Target::Version(Version::Branch(required(argument)?))),
+            "tag" => self.target = Some(Target::Version(Version::Tag(required(argument)?))),
+            "rev" => self.target = Some(Target::Version(Version::Rev(required(argument)?))),
+            "path" => {
+                let path = normalize(&self.root.join(required(argument)?));
+                ensure!(
+                    changes.fs().is_dir(&path),
+                    "Path '{}' is not a directory.",
+                    path.display()
+                );
+                self.target = Some(Target::Path(path));
+            }
+            "git" => self.git = Some(required(argument)?),
+            "diff" => {
+                let edits = self.edits(changes)?;
+                if edits.is_empty() {
+                    println!("Nothing would be changed.");
+                }
+                edits
+                    .iter()
+                    .map(|(path, old, new)| diff::unified_diff(path, old, new))
+                    .for_each(|diff| {
+                        if self.color {
+                            print!("{}", diff::colorize(&diff))
+                        } else {
+                            print!("{diff}")
+                        }
+                    });
+            }
+            "apply" => {
+                let edits = self.edits(changes)?;
+                let len = edits.len();
+                edits
+                    .into_iter()
+                    .for_each(|(path, _, new)| changes.write(path, new));
+                println!("Updated {len} manifest(s).");
+                return Ok(true);
+            }
+            "quit" | "q" | "exit" => return Ok(true),
+            _ => {
+                self.toggle(line)?;
+                self.print_table();
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Toggle the selection of all rows given as whitespace separated numbers or ranges.
+    fn toggle(&mut self, selection: &str) -> Result<()> {
+        let parse = |n: &str| {
+            usize::from_str(n)
+                .ok()
+                .filter(|n| (1..=self.rows.len()).contains(n))
+                .ok_or_else(|| anyhow!("Unknown command or invalid row `{n}`, see `help`."))
+        };
+
+        let mut indices = Vec::new();
+        for part in selection.split_whitespace() {
+            if let Some((start, end)) = part.split_once('-') {
+                indices.extend(parse(start)?..=parse(end)?);
+            } else {
+                indices.push(parse(part)?);
+            }
+        }
+
+        indices
+            .into_iter()
+            .for_each(|i| self.rows[i - 1].selected = !self.rows[i - 1].selected);
+        Ok(())
+    }
+
+    fn status(&self) -> String {
+        let selected = self.rows.iter().filter(|r| r.selected).count();
+        let target = match &self.target {
+            None => "no target".into(),
+            Some(Target::Version(Version::Branch(b))) => format!("branch = {b}"),
+            Some(Target::Version(Version::Tag(t))) => format!("tag = {t}"),
+            Some(Target::Version(Version::Rev(r))) => format!("rev = {r}"),
+            Some(Target::Path(p)) => format!("path = {}", p.display()),
+        };
+
+        format!("[{selected} selected, {target}]")
+    }
+
+    fn print_table(&self) {
+        let name_width = self.rows.iter().map(|r| r.name.len()).max().unwrap_or(0);
+        let sources = self
+            .rows
+            .iter()
+            .map(|r| r.sources.iter().cloned().collect::<Vec<_>>().join(", "))
+            .collect::<Vec<_>>();
+        let source_width = sources.iter().map(|s| s.len()).max().unwrap_or(0);
+        let index_width = self.rows.len().to_string().len();
+
+        println!(
+            "    {:>index_width$}  {:name_width$}  {:source_width$}  USED BY",
+            "#", "CRATE", "SOURCE"
+        );
+        for (i, (row, sources)) in self.rows.iter().zip(sources).enumerate() {
+            println!(
+                "[{}] {:>index_width$}  {:name_width$}  {:source_width$}  {} manifest(s)",
+                if row.selected { 'x' } else { ' ' },
+                i + 1,
+                row.name,
+                sources,
+                row.manifests.len(),
+            );
+        }
+    }
+
+    /// Calculate the edits for the current selection and target.
+    ///
+    /// Returns `(path, old content, new content)` for every manifest that changes.
+    fn edits(&self, changes: &Changes) -> Result<Vec<(PathBuf, String, String)>> {
+        let target = self
+            .target
+            .as_ref()
+            .ok_or_else(|| anyhow!("No target chosen, use `branch`, `tag`, `rev` or `path`."))?;
+
+        let selected = self
+            .rows
+            .iter()
+            .filter(|r| r.selected)
+            .map(|r| r.name.as_str())
+            .collect::<BTreeSet<_>>();
+        ensure!(!selected.is_empty(), "No crates selected.");
+
+        let manifests = self
+            .rows
+            .iter()
+            .filter(|r| r.selected)
+            .flat_map(|r| r.manifests.iter())
+            .collect::<BTreeSet<_>>();
+
+        let checkout = match target {
+            Target::Path(path) => Some(checkout_crates(changes, path)?),
+            Target::Version(_) => None,
+        };
+
+        let mut edits = Vec::new();
+        for manifest in manifests {
+            let mut doc = changes.read_toml(manifest)?;
+            let old = doc.to_string();
+
+            match (target, &checkout) {
+                (Target::Version(version), _) => update::update_document(
+                    &mut doc,
+                    &self.repos,
+                    |_| true,
+                    |n| selected.contains(n),
+                    &self.git,
+                    version,
+                ),
+                (Target::Path(_), Some(crates)) => {
+                    let manifest = normalize(&self.root.join(manifest));
+                    point_to_checkout(&manifest, &mut doc, &self.repos, &selected, crates)
+                }
+                (Target::Path(_), None) => unreachable!("Checkout is collected for path targets"),
+            }
+
+            let new = doc.to_string();
+            if new != old {
+                edits.push((manifest.clone(), old, new));
+            }
+        }
+
+        Ok(edits)
+    }
+}
+
+fn required(argument: &str) -> Result<String> {
+    if argument.is_empty() {
+        bail!("This command requires an argument, see `help`.");
+    }
+
+    Ok(argument.into())
+}
+
+/// Collect all Polkadot SDK dependencies of the `manifests`.
+fn discover(manifests: &[PathBuf], repos: &RepoMatcher, changes: &Changes) -> Result<Vec<Row>> {
+    let mut rows = BTreeMap::<String, Row>::new();
+
+    for manifest in manifests {
+        let mut doc = changes.read_toml(manifest)?;
+
+        update::for_each_managed_dependency(&mut doc, repos, |name, dep| {
+            let source = ["branch", "tag", "rev"]
+                .iter()
+                .find_map(|k| {
+                    dep.get(k)
+                        .and_then(|v| v.as_str())
+                        .map(|v| format!("{k} = {v}"))
+                })
+                .unwrap_or_else(|| "default branch".into());
+
+            let row = rows.entry(name.into()).or_insert_with(|| Row {
+                name: name.into(),
+                sources: Default::default(),
+                manifests: Default::default(),
+                selected: false,
+            });
+            row.sources.insert(source);
+            row.manifests.insert(manifest.clone());
+        });
+    }
+
+    Ok(rows.into_values().collect())
+}
+
+/// Returns a mapping of package name to crate directory for all crates in `checkout`.
+fn checkout_crates(changes: &Changes, checkout: &Path) -> Result<HashMap<String, PathBuf>> {
+    let mut crates = HashMap::new();
+    let fs = changes.fs();
+    for manifest in workspacify::manifest_iter(fs, checkout, &Exclusions::default()) {
+        if let Some(name) = workspacify::package_name(fs, &manifest)? {
+            let dir = manifest
+                .parent()
+                .expect("Manifests are files; qed")
+                .to_path_buf();
+            crates.insert(name, dir);
+        }
+    }
+
+    Ok(crates)
+}
+
+/// Rewrite all selected Polkadot SDK dependencies to `path` dependencies into the checkout.
+///
+/// `manifest` and the crates of the checkout are absolute paths.
+fn point_to_checkout(
+    manifest: &Path,
+    doc: &mut toml_edit::Document,
+    repos: &RepoMatcher,
+    selected: &BTreeSet<&str>,
+    crates: &HashMap<String, PathBuf>,
+) {
+    let manifest_dir = manifest.parent().expect("Manifests are files; qed");
+
+    update::for_each_managed_dependency(doc, repos, |name, dep| {
+        if !selected.contains(name) {
+            return;
+        }
+
+        let Some(relpath) = crates
+            .get(name)
+            .and_then(|dir| pathdiff::diff_paths(dir, manifest_dir))
+        else {
+            log::warn!("`{}` not found in the checkout, skipping it.", name);
+            return;
+        };
+
+        editor::to_path_dep(dep, &relpath);
+        if let Some(dep) = dep.as_inline_table_mut() {
+            dep.fmt();
+        }
+    });
+}
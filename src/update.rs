@@ -1,154 +1,1219 @@
-use anyhow::{bail, ensure, Context, Result};
+use crate::{
+    changes::{self, Changes},
+    config::Config,
+    context::RunContext,
+    editor,
+    engine::Reference,
+    failure::{Classify, Failure},
+    filesystem::Fs,
+    format::OutputFormat,
+    git::{self, GitRef},
+    incremental::IncrementalCache,
+    manifest, oplog,
+    walk::{self, Exclusions},
+    workspace_deps, workspacify,
+};
+use anyhow::{anyhow, bail, ensure, Context, Error, Result};
 use git_url_parse::GitUrl;
-use std::{env::current_dir, fs, path::PathBuf, str::FromStr};
+use glob::Pattern;
+use regex::Regex;
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    env::current_dir,
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 use structopt::StructOpt;
-use toml_edit::{Document, InlineTable, Value};
-use walkdir::{DirEntry, WalkDir};
+use toml_edit::{Document, Item, TableLike, Value};
 
 /// The version the dependencies should be switched to.
 #[derive(Debug, Clone)]
-enum Version {
+pub(crate) enum Version {
     Tag(String),
     Branch(String),
     Rev(String),
 }
 
+impl FromStr for Version {
+    type Err = Error;
+
+    /// Parses `branch:<name>`, `tag:<name>` or `rev:<rev>`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            Some(("branch", branch)) if !branch.is_empty() => Ok(Self::Branch(branch.into())),
+            Some(("tag", tag)) if !tag.is_empty() => Ok(Self::Tag(tag.into())),
+            Some(("rev", rev)) if !rev.is_empty() => Ok(Self::Rev(rev.into())),
+            _ => bail!(
+                "Invalid version `{s}`, expected `branch:<name>`, `tag:<name>` or `rev:<rev>`."
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for Version {
+    /// Formats like it is parsed, e.g. `branch:master`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Branch(branch) => write!(f, "branch:{branch}"),
+            Self::Tag(tag) => write!(f, "tag:{tag}"),
+            Self::Rev(rev) => write!(f, "rev:{rev}"),
+        }
+    }
+}
+
+/// A repository and the version its dependencies should be switched to, as given to `--repo`.
+#[derive(Debug)]
+struct RepoVersion {
+    /// The name of the repository, like `polkadot-sdk`.
+    name: String,
+    version: Version,
+}
+
+impl FromStr for RepoVersion {
+    type Err = Error;
+
+    /// Parses `<name>=<kind>:<value>`, e.g. `polkadot-sdk=branch:stable2407`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, version) = s
+            .split_once('=')
+            .filter(|(name, _)| !name.is_empty())
+            .ok_or_else(|| {
+                anyhow!("Invalid repository `{s}`, expected `<name>=<kind>:<value>`.")
+            })?;
+
+        Ok(Self {
+            name: name.into(),
+            version: version.parse()?,
+        })
+    }
+}
+
+/// The repositories to update, each with the version its dependencies should be switched to.
+pub(crate) type Targets = Vec<(RepoMatcher, Version)>;
+
+/// Name prefixes of crates that are most likely part of the Polkadot SDK.
+///
+/// Used to find `path` dependencies that may have been meant to be updated and the releases of
+/// the SDK in `Cargo.lock` files.
+pub(crate) const SDK_CRATE_PREFIXES: &[&str] = &[
+    "sp-",
+    "sc-",
+    "frame-",
+    "pallet-",
+    "cumulus-",
+    "polkadot-",
+    "staging-xcm",
+    "xcm",
+    "substrate-",
+];
+
+/// Why a dependency that looks like it should be managed was not updated.
+enum SkipReason {
+    /// The dependency is a `path` dependency.
+    PathDependency,
+    /// The dependency is declared as `[dependencies.foo]` table.
+    Table,
+    /// The dependency was filtered out, e.g. by `--dev-dependencies-only`.
+    FilteredOut,
+    /// The manifest could not be processed.
+    Error(String),
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PathDependency => write!(f, "path dependency"),
+            Self::Table => write!(f, "declared as `[..]` table"),
+            Self::FilteredOut => write!(f, "filtered out"),
+            Self::Error(e) => write!(f, "failed to process the manifest: {e}"),
+        }
+    }
+}
+
+/// What `--ensure-consistent` does if the dependencies already use different versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Consistency {
+    /// Fail before changing anything.
+    Fail,
+    /// Only warn and continue.
+    Warn,
+}
+
+impl FromStr for Consistency {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "fail" => Ok(Self::Fail),
+            "warn" => Ok(Self::Warn),
+            _ => bail!("Unknown value `{s}`, expected `fail` or `warn`."),
+        }
+    }
+}
+
+/// A dependency that was not updated, reported by `--report-skipped`.
+struct Skipped {
+    manifest: PathBuf,
+    /// The table and the key of the dependency, `None` if the whole manifest was skipped.
+    dependency: Option<(String, String)>,
+    reason: SkipReason,
+}
+
+/// The environment variable providing the default for `--branch`.
+///
+/// This isn't handled by structopt, as `--rev` or `--tag` would conflict with it.
+const BRANCH_ENV: &str = "DIENER_BRANCH";
+
 /// `update` subcommand options.
-#[derive(Debug, StructOpt)]
+#[derive(Debug, Default, StructOpt)]
 pub struct Update {
     /// The path where Diener should search for `Cargo.toml` files.
+    ///
+    /// Can be passed multiple times and may be a glob pattern like `runtimes/*`.
+    #[structopt(long, env = "DIENER_PATH")]
+    path: Vec<PathBuf>,
+
+    /// Read the manifests and directories to update from the given file, `-` for stdin.
+    ///
+    /// Expects one path per line, e.g. the output of `git diff --name-only`. Directories are
+    /// searched for `Cargo.toml` files, all other files that are not a `Cargo.toml` are ignored.
+    #[structopt(long, conflicts_with = "path")]
+    paths_from: Option<PathBuf>,
+
+    /// Only update the manifests that changed since the given git ref, e.g. `origin/master`.
+    ///
+    /// Takes the manifests of `git diff --name-only <REF>`, including the uncommitted
+    /// changes, and the untracked manifests. Deleted manifests are ignored.
+    #[structopt(long, value_name = "REF", conflicts_with_all = &[ "path", "paths-from" ])]
+    changed_since: Option<String>,
+
+    /// Skip all directories and manifests matching the given glob pattern.
+    ///
+    /// Can be passed multiple times, e.g. `--exclude-path 'tests/fixtures/**'`. The patterns
+    /// are relative to the working directory.
     #[structopt(long)]
-    path: Option<PathBuf>,
+    exclude_path: Vec<Pattern>,
 
     /// The `branch` that the dependencies should use.
-    #[structopt(long, conflicts_with_all = &[ "rev", "tag" ])]
+    ///
+    /// Falls back to `DIENER_BRANCH` and then to the `branch` of the configuration if none of
+    /// `--branch`, `--rev` and `--tag` is given.
+    #[structopt(long, conflicts_with_all = &[ "rev", "tag", "repo-versions" ])]
     branch: Option<String>,
 
     /// The `rev` that the dependencies should use.
-    #[structopt(long, conflicts_with_all = &[ "branch", "tag" ])]
+    #[structopt(long, conflicts_with_all = &[ "branch", "tag", "repo-versions" ])]
     rev: Option<String>,
 
+    /// Fail if a written `rev` is not an ancestor of the given branch of its repository.
+    ///
+    /// Guards against pinning commits of pull request branches that never get merged, e.g.
+    /// `--rev abc123 --verify-rev-on-branch master`. Applies to `--rev` and to the `--repo`
+    /// versions given as `rev:<rev>`.
+    #[structopt(long, value_name = "BRANCH")]
+    verify_rev_on_branch: Option<String>,
+
     /// The `tag` that the dependencies should use.
-    #[structopt(long, conflicts_with_all = &[ "rev", "branch" ])]
+    #[structopt(long, conflicts_with_all = &[ "rev", "branch", "repo-versions" ])]
     tag: Option<String>,
 
+    /// Update the repository to the given version, e.g. `polkadot-sdk=branch:stable2407`.
+    ///
+    /// Can be passed multiple times to update multiple repositories that must move together
+    /// in one run, e.g. `--repo polkadot-sdk=branch:stable2407 --repo frontier=rev:abc123`.
+    /// The version is one of `branch:<name>`, `tag:<name>` or `rev:<rev>`. Replaces
+    /// `--branch`, `--tag`, `--rev` and the matching of the repositories.
+    #[structopt(long = "repo", conflicts_with = "match-url-regex")]
+    repo_versions: Vec<RepoVersion>,
+
     /// Rewrite the `git` url to the give one.
-    #[structopt(long)]
+    ///
+    /// Defaults to the `git` url of the configuration.
+    #[structopt(long, env = "DIENER_GIT")]
     git: Option<String>,
+
+    /// The name of a repository whose git dependencies should be updated.
+    ///
+    /// Can be passed multiple times. Defaults to the `match-repos` of the configuration or
+    /// `polkadot-sdk`.
+    #[structopt(long = "match-repo", env = "DIENER_MATCH_REPO", use_delimiter = true)]
+    match_repos: Vec<String>,
+
+    /// A regex that the `git` url of the dependencies that should be updated must match.
+    ///
+    /// Takes precedence over the matching by repository name, e.g. to update forks under any
+    /// organization or self-hosted mirrors:
+    /// `--match-url-regex 'gitlab\.example\.com/[^/]+/polkadot-sdk'`.
+    #[structopt(long)]
+    match_url_regex: Option<Regex>,
+
+    /// Continue with the remaining manifests if a manifest can not be processed.
+    ///
+    /// All failed manifests are reported at the end and diener exits with an error.
+    #[structopt(long)]
+    keep_going: bool,
+
+    /// Only rewrite the dependencies in `[dev-dependencies]`.
+    ///
+    /// Production and build dependencies are left untouched.
+    #[structopt(long)]
+    dev_dependencies_only: bool,
+
+    /// Move the updated dependencies into `[workspace.dependencies]` of their workspace.
+    ///
+    /// The members inherit the dependencies with `workspace = true` afterwards, so the
+    /// `branch`, `tag` or `rev` is only declared once.
+    #[structopt(long, conflicts_with = "dev-dependencies-only")]
+    to_workspace_dep: bool,
+
+    /// List the dependencies that look like they should be updated, but were not touched.
+    ///
+    /// Reports `path` dependencies of Polkadot SDK crates, dependencies declared as table,
+    /// dependencies that were filtered out and manifests that failed with `--keep-going`.
+    #[structopt(long)]
+    report_skipped: bool,
+
+    /// Rewrite every updated dependency into a canonical key order and spacing.
+    ///
+    /// Repeated runs produce byte-identical manifests, independent of how the dependencies
+    /// were written before.
+    #[structopt(long)]
+    normalize: bool,
+
+    /// Parse every manifest, even the ones that don't mention any of the updated repositories.
+    ///
+    /// By default manifests whose content doesn't contain the name of any updated repository are
+    /// skipped without parsing them.
+    #[structopt(long)]
+    no_prefilter: bool,
+
+    /// Skip the manifests that didn't change since a previous run with the same options.
+    ///
+    /// The hashes of the processed manifests are stored under `.diener/cache` in the working
+    /// directory, only if the changes are applied.
+    #[structopt(long)]
+    incremental: bool,
+
+    /// Report the modified manifests for every workspace separately.
+    #[structopt(long)]
+    all_workspaces: bool,
+
+    /// Check that the dependencies of every repository use the same version before updating.
+    ///
+    /// This catches half finished previous updates. Fails if the dependencies of a repository
+    /// point to different branches, tags or revs, `--ensure-consistent=warn` only warns.
+    #[structopt(long, require_equals = true)]
+    ensure_consistent: Option<Option<Consistency>>,
+
+    /// Don't modify any file, fail if any manifest is not up to date instead.
+    #[structopt(long)]
+    check: bool,
+
+    /// The output format of `--check`, `text`, `json` or `github`.
+    ///
+    /// `github` emits GitHub Actions annotations. Defaults to the `format` of the configuration
+    /// or `text`.
+    #[structopt(long, requires = "check")]
+    format: Option<OutputFormat>,
 }
 
 impl Update {
-    /// Convert the options into the parts `Option<String>`, `Version`, `Option<PathBuf>`.
-    fn into_parts(self) -> Result<(Option<String>, Version, Option<PathBuf>)> {
+    /// The options of `diener update --path <path>... --git <git>` with `reference`.
+    ///
+    /// Unlike parsing the command line this doesn't fall back to the `DIENER_*` environment
+    /// variables, which is what the [`Engine`](crate::Engine) wants.
+    pub(crate) fn new(path: Vec<PathBuf>, reference: &Reference, git: Option<String>) -> Self {
+        let mut update = Self {
+            path,
+            git,
+            ..Default::default()
+        };
+        match reference.clone() {
+            Reference::Branch(branch) => update.branch = Some(branch),
+            Reference::Tag(tag) => update.tag = Some(tag),
+            Reference::Rev(rev) => update.rev = Some(rev),
+        }
+        update
+    }
+
+    /// Convert the options into the parts `Option<String>`, `Targets`, `Vec<PathBuf>`.
+    ///
+    /// `repos` are the repositories that are updated if no `--repo` is given, `config` provides
+    /// the defaults of `--git` and `--branch`.
+    fn into_parts(
+        self,
+        repos: RepoMatcher,
+        config: &Config,
+    ) -> Result<(Option<String>, Targets, Vec<PathBuf>)> {
+        if !self.repo_versions.is_empty() {
+            ensure!(
+                self.git.is_none(),
+                "`--git` can not be combined with `--repo`."
+            );
+
+            let mut names = HashSet::new();
+            let mut targets = Targets::new();
+            for repo in self.repo_versions {
+                ensure!(
+                    names.insert(repo.name.clone()),
+                    "`--repo {}` is given multiple times.",
+                    repo.name
+                );
+                targets.push((RepoMatcher::Names(vec![repo.name]), repo.version));
+            }
+
+            return Ok((None, targets, self.path));
+        }
+
         let version = if let Some(branch) = self.branch {
             Version::Branch(branch)
         } else if let Some(rev) = self.rev {
             Version::Rev(rev)
         } else if let Some(tag) = self.tag {
             Version::Tag(tag)
+        } else if let Ok(branch) = std::env::var(BRANCH_ENV) {
+            Version::Branch(branch)
+        } else if let Some(branch) = config.branch() {
+            Version::Branch(branch.into())
         } else {
-            bail!("You need to pass `--branch`, `--tag` or `--rev`");
+            bail!(
+                "You need to pass `--branch`, `--tag`, `--rev` or `--repo`, set `{}` or the \
+                 `branch` of the configuration",
+                BRANCH_ENV
+            );
         };
 
-        Ok((self.git, version, self.path))
+        let git = self.git.or_else(|| config.git().map(Into::into));
+        Ok((git, vec![(repos, version)], self.path))
     }
 
     /// Run this subcommand.
-    pub fn run(self) -> Result<()> {
-        let (git, version, path) = self.into_parts()?;
+    pub fn run(mut self, ctx: &mut RunContext) -> Result<()> {
+        let repos = if let Some(regex) = self.match_url_regex.take() {
+            RepoMatcher::UrlRegex(regex)
+        } else if self.match_repos.is_empty() {
+            ctx.config.match_repos().into()
+        } else {
+            std::mem::take(&mut self.match_repos).into()
+        };
+        let paths_from = self.paths_from.take();
+        let changed_since = self.changed_since.take();
+        let exclusions = ctx
+            .exclusions
+            .clone()
+            .with_patterns(std::mem::take(&mut self.exclude_path));
+        let keep_going = self.keep_going;
+        let dev_only = self.dev_dependencies_only;
+        let to_workspace_dep = self.to_workspace_dep;
+        let report_skipped = self.report_skipped;
+        let normalize = self.normalize;
+        // The skipped dependencies can only be reported for parsed manifests.
+        let prefilter = !self.no_prefilter && !report_skipped;
+        let incremental = self.incremental && !report_skipped;
+        let all_workspaces = self.all_workspaces;
+        let consistency = self
+            .ensure_consistent
+            .map(|c| c.unwrap_or(Consistency::Fail));
+        let check = self.check.then(|| {
+            self.format
+                .or(ctx.config.format())
+                .unwrap_or(OutputFormat::Text)
+        });
+        let verify_rev_on_branch = self.verify_rev_on_branch.take();
+        let (git, targets, path) = self.into_parts(repos, &ctx.config)?;
+        if verify_rev_on_branch.is_some() {
+            if !targets.iter().any(|(_, v)| matches!(v, Version::Rev(_))) {
+                return Err(anyhow!(
+                    "`--verify-rev-on-branch` requires `--rev` or a `--repo` with `rev:<rev>`."
+                )
+                .failure(Failure::Usage));
+            }
+            ctx.ensure_online("`--verify-rev-on-branch`")?;
+        }
+
+        // The manifests are found while they are processed, so big trees are never held in
+        // memory as a whole.
+        if ctx.manifest_list.is_some() && (paths_from.is_some() || changed_since.is_some()) {
+            return Err(anyhow!(
+                "`--manifest-list` can not be combined with `--paths-from` or `--changed-since`."
+            )
+            .failure(Failure::Usage));
+        }
+        let listed = match (paths_from, changed_since) {
+            (Some(paths_from), _) => Some(read_paths(&paths_from)?),
+            (None, Some(reference)) => Some(changed_paths(&reference)?),
+            (None, None) => None,
+        };
+        let mut manifests: Box<dyn Iterator<Item = PathBuf>> = match listed {
+            Some(listed) => {
+                Box::new(manifests_from(ctx.changes.fs(), &listed, &exclusions)?.into_iter())
+            }
+            None if ctx.manifest_list.is_some() => Box::new(ctx.manifests(&path)?.into_iter()),
+            None => Box::new(manifests_iter(
+                ctx.changes.fs(),
+                &search_dirs(ctx.changes.fs(), &path)?,
+                &exclusions,
+            )),
+        };
+
+        if let Some(consistency) = consistency {
+            let all = manifests.collect::<Vec<_>>();
+            ensure_consistent(&all, &targets, consistency, &ctx.changes)?;
+            manifests = Box::new(all.into_iter());
+        }
+
+        let mut cache = if incremental {
+            let working_dir = current_dir().with_context(|| "Working directory is invalid.")?;
+            let params = format!("{targets:?} {git:?} {dev_only} {normalize}");
+            Some(IncrementalCache::load(
+                ctx.changes.fs(),
+                &working_dir,
+                "update",
+                &params,
+            ))
+        } else {
+            None
+        };
+
+        let mut skipped = Vec::new();
+        let mut roots = BTreeSet::new();
+        // The manifests are read in batches of `--jobs` at the same time, but processed one by
+        // one. Only the modified manifests are kept in memory until they are written.
+        loop {
+            let batch = manifests
+                .by_ref()
+                .take(ctx.changes.jobs())
+                .collect::<Vec<_>>();
+            if batch.is_empty() {
+                break;
+            }
+            if to_workspace_dep || all_workspaces {
+                roots.extend(batch.iter().filter_map(|m| workspace_root(m, &ctx.changes)));
+            }
+
+            let contents = ctx.changes.read_all(&batch);
+            for (toml, content) in batch.into_iter().zip(contents) {
+                if let Ok(content) = &content {
+                    if prefilter && !may_need_update(content, &targets) {
+                        log::debug!(
+                            "Skipping {}, it doesn't mention any of the repositories.",
+                            toml.display()
+                        );
+                        ctx.changes.forget(&toml);
+                        continue;
+                    }
+                    if cache.as_ref().is_some_and(|c| c.is_current(&toml, content)) {
+                        log::debug!(
+                            "Skipping {}, it didn't change since the last run.",
+                            toml.display()
+                        );
+                        ctx.changes.forget(&toml);
+                        continue;
+                    }
+                }
+
+                let res = content.and_then(|content| {
+                    handle_toml_file(
+                        toml.clone(),
+                        changes::parse_toml(&toml, &content)?,
+                        &targets,
+                        dev_only,
+                        normalize,
+                        &git,
+                        &mut ctx.changes,
+                    )
+                });
+                match res {
+                    Err(e) if keep_going => {
+                        log::error!("Failed to process {}: {:#}", toml.display(), e);
+                        skipped.push(Skipped {
+                            manifest: toml.clone(),
+                            dependency: None,
+                            reason: SkipReason::Error(format!("{e:#}")),
+                        });
+                        ctx.failures.push((toml.clone(), e));
+                    }
+                    res => {
+                        skipped.extend(res?);
+                        if let Some(cache) = &mut cache {
+                            cache.record(&toml, &ctx.changes.read(&toml)?);
+                        }
+                    }
+                }
+                // Unmodified manifests are not needed anymore, modified ones are staged.
+                ctx.changes.forget(&toml);
+            }
+        }
+        if let Some(cache) = &cache {
+            cache.store(&ctx.changes.caches());
+        }
+
+        if to_workspace_dep {
+            for root in &roots {
+                update_workspace_dependencies(
+                    &root.join("Cargo.toml"),
+                    &targets,
+                    &git,
+                    &mut ctx.changes,
+                )?;
+                workspace_deps::hoist(
+                    root,
+                    0,
+                    |usage| {
+                        usage
+                            .git()
+                            .is_some_and(|git| targets.iter().any(|(repos, _)| repos.matches(git)))
+                    },
+                    false,
+                    ctx,
+                )?;
+            }
+        }
+
+        if let Some(branch) = &verify_rev_on_branch {
+            verify_revs_on_branch(&ctx.changes, &targets, branch)?;
+        }
+
+        if report_skipped {
+            print_skipped(&skipped);
+        }
+
+        if all_workspaces {
+            ctx.changes
+                .report_by_root(&roots.into_iter().collect::<Vec<_>>());
+        }
+
+        match check {
+            Some(format) => ctx.changes.check(
+                format,
+                ctx.color,
+                "Dependencies are not up to date, run `diener update`.",
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Fail if a `rev` of `targets` that was written to any manifest is not an ancestor of the
+/// branch `branch` of its repository.
+fn verify_revs_on_branch(changes: &Changes, targets: &Targets, branch: &str) -> Result<()> {
+    let mut revs = BTreeMap::<String, BTreeSet<String>>::new();
+    for (path, _, content) in changes.modified() {
+        let doc = changes::parse_toml(path, content)?;
+        for (_, deps) in manifest::dependency_tables(&doc) {
+            for (_, dep) in deps.iter() {
+                let Some(dep) = dep.as_table_like() else {
+                    continue;
+                };
+                let get = |k| dep.get(k).and_then(Item::as_str);
+                let (Some(git), Some(rev)) = (get("git"), get("rev")) else {
+                    continue;
+                };
+                let written = targets.iter().any(|(repos, version)| {
+                    matches!(version, Version::Rev(r) if r == rev) && repos.matches(git)
+                });
+                if written {
+                    revs.entry(git.into()).or_default().insert(rev.into());
+                }
+            }
+        }
+    }
+
+    let mut off_branch = 0;
+    for (url, revs) in &revs {
+        for rev in git::revs_not_on_branch(url, branch, revs.iter().map(String::as_str))? {
+            log::error!("`{}` is not on branch `{}` of {}.", rev, branch, url);
+            off_branch += 1;
+        }
+    }
+    ensure!(
+        off_branch == 0,
+        "{} rev(s) are not on branch `{}`, see above.",
+        off_branch,
+        branch
+    );
+    log::info!("Verified that all written revs are on branch `{}`.", branch);
+
+    Ok(())
+}
+
+/// Returns all `Cargo.toml` files below the given `path`, skipping excluded directories.
+pub(crate) fn manifest_paths(
+    fs: &dyn Fs,
+    path: &Path,
+    exclusions: &Exclusions,
+) -> impl Iterator<Item = PathBuf> {
+    walk::manifests(fs, path, true, exclusions)
+}
+
+/// Resolve the `--path` arguments of a subcommand into the directories to search.
+///
+/// Every path may be a glob pattern like `runtimes/*`. Returns the working directory if no path
+/// is given.
+pub(crate) fn search_dirs(fs: &dyn Fs, paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    if paths.is_empty() {
+        return Ok(vec![
+            current_dir().with_context(|| "Working directory is invalid.")?
+        ]);
+    }
+
+    let mut dirs = Vec::new();
+    for path in paths {
+        let pattern = path.to_string_lossy();
+
+        if !pattern.contains(['*', '?', '[']) {
+            ensure!(
+                fs.is_dir(path),
+                "Path '{}' is not a directory.",
+                path.display()
+            );
+            dirs.push(path.clone());
+            continue;
+        }
+
+        let matches = fs
+            .glob(&pattern)
+            .with_context(|| anyhow!("Invalid glob pattern `{}`", pattern))?
+            .into_iter()
+            .filter(|p| fs.is_dir(p))
+            .collect::<Vec<_>>();
+        ensure!(
+            !matches.is_empty(),
+            "Pattern `{}` doesn't match any directory.",
+            pattern
+        );
+        dirs.extend(matches);
+    }
+
+    Ok(dirs)
+}
+
+/// Returns all `Cargo.toml` files below any of the given `dirs`, every manifest only once.
+pub(crate) fn manifests_in(fs: &dyn Fs, dirs: &[PathBuf], exclusions: &Exclusions) -> Vec<PathBuf> {
+    manifests_iter(fs, dirs, exclusions).collect()
+}
 
-        let path = path
-            .map(Ok)
-            .unwrap_or_else(|| current_dir().with_context(|| "Working directory is invalid."))?;
+/// Like [`manifests_in`], but the directories are only searched while iterating.
+pub(crate) fn manifests_iter(
+    fs: &dyn Fs,
+    dirs: &[PathBuf],
+    exclusions: &Exclusions,
+) -> impl Iterator<Item = PathBuf> {
+    let walks = dirs
+        .iter()
+        .map(|dir| manifest_paths(fs, dir, exclusions))
+        .collect::<Vec<_>>();
+    let mut seen = HashSet::new();
+    walks
+        .into_iter()
+        .flatten()
+        .filter(move |m| seen.insert(m.canonicalize().unwrap_or_else(|_| m.clone())))
+}
+
+/// Read the paths listed in `source`, one per line, `-` reads from stdin.
+fn read_paths(source: &Path) -> Result<String> {
+    if source == Path::new("-") {
+        io::read_to_string(io::stdin()).with_context(|| "Failed to read paths from stdin.")
+    } else {
+        fs::read_to_string(source)
+            .with_context(|| anyhow!("Failed to read paths from {}", source.display()))
+    }
+}
+
+/// Returns the manifests listed in the file `source` of `--manifest-list`, `-` for stdin.
+///
+/// Every line is the path of a `Cargo.toml`, which must exist. Manifests listed more than once
+/// are returned only once.
+pub(crate) fn read_manifest_list(fs: &dyn Fs, source: &Path) -> Result<Vec<PathBuf>> {
+    let mut manifests = Vec::new();
+    for path in read_paths(source)?
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+    {
+        let path = PathBuf::from(path);
         ensure!(
-            path.is_dir(),
-            "Path '{}' is not a directory.",
+            path.ends_with("Cargo.toml"),
+            "The `--manifest-list` entry {} is not a `Cargo.toml`.",
             path.display()
         );
+        ensure!(
+            fs.is_file(&path),
+            "Manifest {} does not exist.",
+            path.display()
+        );
+        if !manifests.contains(&path) {
+            manifests.push(path);
+        }
+    }
+    log::debug!(
+        "{} manifest(s) listed in {}.",
+        manifests.len(),
+        source.display()
+    );
 
-        let is_hidden = |entry: &DirEntry| {
-            entry
-                .file_name()
-                .to_str()
-                .map(|s| s.starts_with('.'))
-                .unwrap_or(false)
-        };
+    Ok(manifests)
+}
 
-        WalkDir::new(path)
-            .follow_links(true)
-            .into_iter()
-            .filter_entry(|e| !is_hidden(e))
-            .filter_map(|e| e.ok())
-            .filter(|e| {
-                e.file_type().is_file() && e.file_name().to_string_lossy().ends_with("Cargo.toml")
-            })
-            .try_for_each(|toml| handle_toml_file(toml.into_path(), &git, &version))
+/// Returns the paths that changed since `reference`, one per line and relative to the working
+/// directory.
+///
+/// These are the modified and added files of the working tree compared to `reference` and the
+/// untracked files.
+fn changed_paths(reference: &str) -> Result<String> {
+    let working_dir = current_dir().with_context(|| "Working directory is invalid.")?;
+    let changed = git::run(
+        &working_dir,
+        &[
+            "diff",
+            "--name-only",
+            "--relative",
+            "--diff-filter=d",
+            reference,
+            "--",
+        ],
+    )
+    .with_context(|| anyhow!("Failed to find the files changed since `{}`", reference))?;
+    let untracked = git::run(
+        &working_dir,
+        &["ls-files", "--others", "--exclude-standard"],
+    )?;
+    log::debug!(
+        "{} file(s) changed since `{}`, {} untracked.",
+        changed.lines().count(),
+        reference,
+        untracked.lines().count()
+    );
+
+    Ok(changed + &untracked)
+}
+
+/// Returns all manifests referenced by the paths listed in `content`, one per line.
+fn manifests_from(fs: &dyn Fs, content: &str, exclusions: &Exclusions) -> Result<Vec<PathBuf>> {
+    let mut manifests = BTreeSet::new();
+    for path in content.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let path = PathBuf::from(path);
+
+        if fs.is_dir(&path) {
+            manifests.extend(manifest_paths(fs, &path, exclusions));
+        } else if exclusions.is_excluded(&path) {
+            log::debug!(
+                "Ignoring {}, it is in an excluded directory.",
+                path.display()
+            );
+        } else if path.ends_with("Cargo.toml") {
+            ensure!(
+                fs.is_file(&path),
+                "Manifest {} does not exist.",
+                path.display()
+            );
+            manifests.insert(path);
+        } else {
+            log::debug!("Ignoring {}, it is not a manifest.", path.display());
+        }
     }
+
+    Ok(manifests.into_iter().collect())
+}
+
+/// Decides which git repositories are managed by diener.
+#[derive(Debug, Clone)]
+pub(crate) enum RepoMatcher {
+    /// Repositories with one of the given names, like `polkadot-sdk`.
+    Names(Vec<String>),
+    /// Repositories whose url matches the regex.
+    UrlRegex(Regex),
+}
+
+impl RepoMatcher {
+    /// Returns `true` if the repository at `url` is managed.
+    pub(crate) fn matches(&self, url: &str) -> bool {
+        match self {
+            Self::Names(names) => GitUrl::parse(url).is_ok_and(|git| names.contains(&git.name)),
+            Self::UrlRegex(regex) => regex.is_match(url),
+        }
+    }
+
+    /// Returns `false` if `content` can not contain the url of a managed repository.
+    ///
+    /// This is a cheap substring check to skip files without parsing them.
+    pub(crate) fn may_match(&self, content: &str) -> bool {
+        match self {
+            Self::Names(names) => names.iter().any(|name| content.contains(name.as_str())),
+            // The regex may be anchored to the start or end of the url.
+            Self::UrlRegex(_) => true,
+        }
+    }
+}
+
+impl From<Vec<String>> for RepoMatcher {
+    fn from(names: Vec<String>) -> Self {
+        Self::Names(names)
+    }
+}
+
+/// Returns `true` if the given dependency is a git dependency on one of the given repositories.
+pub(crate) fn is_managed_dependency(dep: &dyn TableLike, repos: &RepoMatcher) -> bool {
+    dep.get("git")
+        .and_then(|v| v.as_str())
+        .is_some_and(|git| repos.matches(git))
+}
+
+/// Calls `f` with the package name and the item of every git dependency on one of the given
+/// repositories that is declared as inline table.
+pub(crate) fn for_each_managed_dependency(
+    doc: &mut Document,
+    repos: &RepoMatcher,
+    mut f: impl FnMut(&str, &mut Item),
+) {
+    doc.iter_mut()
+        .filter(|(k, _)| k.contains("dependencies"))
+        .filter_map(|(_, v)| v.as_table_mut())
+        .flat_map(|deps| deps.iter_mut())
+        .filter(|(_, v)| {
+            v.as_inline_table()
+                .is_some_and(|t| is_managed_dependency(t, repos))
+        })
+        .for_each(|(k, v)| {
+            let name = v
+                .get("package")
+                .and_then(|p| p.as_str())
+                .unwrap_or_else(|| k.get())
+                .to_string();
+            f(&name, v)
+        });
 }
 
 /// Handle a given dependency.
 ///
-/// This directly modifies the given `dep` in the requested way.
-fn handle_dependency(name: &str, dep: &mut InlineTable, git: &Option<String>, version: &Version) {
-    if !dep
-        .get("git")
-        .and_then(|v| v.as_str())
-        .and_then(|d| GitUrl::parse(d).ok())
-        .is_some_and(|git| git.name == "polkadot-sdk")
-    {
+/// This directly modifies the given `dep` in the requested way. `dep` is either an inline
+/// table or a table of dotted keys, whose style is kept.
+fn handle_dependency(
+    name: &str,
+    dep: &mut Item,
+    repos: &RepoMatcher,
+    git: &Option<String>,
+    version: &Version,
+) {
+    if !manifest::dependency_mut(dep).is_some_and(|dep| is_managed_dependency(dep, repos)) {
         return;
     }
 
+    let from = editor::REFERENCE_KEYS
+        .iter()
+        .find_map(|key| Some(format!("{key}:{}", dep.get(key)?.as_str()?)));
+
     if let Some(new_git) = git {
-        *dep.get_or_insert("git", "") = Value::from(new_git.as_str()).decorated(" ", "");
+        editor::set_git(dep, new_git);
+    }
+    match version {
+        Version::Tag(tag) => editor::set_reference(dep, "tag", tag),
+        Version::Branch(branch) => editor::set_reference(dep, "branch", branch),
+        Version::Rev(rev) => editor::set_reference(dep, "rev", rev),
+    }
+    log::debug!("  updated: {:?} <= {}", version, name);
+    let to = version.to_string();
+    if git.is_some() || from.as_ref() != Some(&to) {
+        oplog::record(
+            "entry-rewritten",
+            json!({ "dependency": name, "from": from, "to": to, "git": git }),
+        );
     }
+}
 
-    dep.remove("tag");
-    dep.remove("branch");
-    dep.remove("rev");
+/// Handle the given `Cargo.toml` at `path`, parsed as `toml_doc`.
+///
+/// This means scanning all dependencies and rewrite the requested onces. With `dev_only` only
+/// the `dev-dependencies` are rewritten, with `normalize` they are brought into the canonical
+/// form. Returns all dependencies that are not rewritten, but look like they should be.
+fn handle_toml_file(
+    path: PathBuf,
+    mut toml_doc: Document,
+    targets: &Targets,
+    dev_only: bool,
+    normalize: bool,
+    git: &Option<String>,
+    changes: &mut Changes,
+) -> Result<Vec<Skipped>> {
+    log::info!("Processing: {}", path.display());
+    oplog::record("file-processing", json!({ "path": path }));
 
-    match version {
-        Version::Tag(tag) => {
-            *dep.get_or_insert("tag", "") = Value::from(tag.as_str()).decorated(" ", " ");
+    let table_filter = |table: &str| !dev_only || table == "dev-dependencies";
+
+    let skipped = skipped_dependencies(&path, &toml_doc, targets, dev_only);
+    for (repos, version) in targets {
+        update_document(&mut toml_doc, repos, table_filter, |_| true, git, version);
+        if normalize {
+            normalize_document(&mut toml_doc, repos, table_filter);
         }
-        Version::Branch(branch) => {
-            *dep.get_or_insert("branch", "") = Value::from(branch.as_str()).decorated(" ", " ");
+    }
+
+    changes.write(path, toml_doc.to_string());
+    Ok(skipped)
+}
+
+/// Returns `false` if the manifest `content` can not contain a dependency on any of the
+/// repositories of `targets`, without parsing it.
+fn may_need_update(content: &str, targets: &Targets) -> bool {
+    targets.iter().any(|(repos, _)| repos.may_match(content))
+}
+
+/// Check that the managed dependencies of every repository use the same version.
+///
+/// All repositories with dependencies on different versions are reported, with
+/// [`Consistency::Fail`] this is an error.
+fn ensure_consistent(
+    manifests: &[PathBuf],
+    targets: &Targets,
+    consistency: Consistency,
+    changes: &Changes,
+) -> Result<()> {
+    let mut versions = BTreeMap::<String, BTreeMap<GitRef, BTreeSet<&Path>>>::new();
+    for manifest in manifests {
+        // Manifests that can not be parsed are reported when they are updated.
+        let Ok(doc) = changes.read_toml(manifest) else {
+            continue;
+        };
+
+        doc.iter()
+            .filter(|(k, _)| k.contains("dependencies"))
+            .filter_map(|(_, v)| v.as_table())
+            .flat_map(|deps| deps.iter())
+            .filter(|(_, v)| v.is_inline_table() || v.as_table().is_some_and(|t| t.is_dotted()))
+            .filter_map(|(_, v)| v.as_table_like())
+            .for_each(|dep| {
+                let Some(url) = dep.get("git").and_then(|g| g.as_str()) else {
+                    return;
+                };
+                if targets.iter().any(|(repos, _)| repos.matches(url)) {
+                    versions
+                        .entry(git::repository_id(url))
+                        .or_default()
+                        .entry(GitRef::from_dependency(dep))
+                        .or_default()
+                        .insert(manifest);
+                }
+            });
+    }
+
+    let inconsistent = versions
+        .iter()
+        .filter(|(_, refs)| refs.len() > 1)
+        .collect::<Vec<_>>();
+    for (repo, refs) in &inconsistent {
+        let mut report = format!("`{}` is used with {} different versions:", repo, refs.len());
+        for (reference, manifests) in refs.iter() {
+            let manifests = manifests
+                .iter()
+                .map(|m| m.display().to_string())
+                .collect::<Vec<_>>();
+            report.push_str(&format!("\n  {}: {}", reference, manifests.join(", ")));
         }
-        Version::Rev(rev) => {
-            *dep.get_or_insert("rev", "") = Value::from(rev.as_str()).decorated(" ", " ");
+
+        match consistency {
+            Consistency::Fail => log::error!("{}", report),
+            Consistency::Warn => log::warn!("{}", report),
         }
     }
-    log::debug!("  updated: {:?} <= {}", version, name);
+
+    ensure!(
+        inconsistent.is_empty() || consistency == Consistency::Warn,
+        "The dependencies of {} repository(ies) use different versions, finish the previous \
+         update first or pass `--ensure-consistent=warn`.",
+        inconsistent.len()
+    );
+
+    Ok(())
 }
 
-/// Handle a given `Cargo.toml`.
-///
-/// This means scanning all dependencies and rewrite the requested onces.
-fn handle_toml_file(path: PathBuf, git: &Option<String>, version: &Version) -> Result<()> {
-    log::info!("Processing: {}", path.display());
+/// Collect the dependencies of `doc` that look like they should be updated, but are not.
+fn skipped_dependencies(
+    manifest: &Path,
+    doc: &Document,
+    targets: &Targets,
+    dev_only: bool,
+) -> Vec<Skipped> {
+    let is_managed = |git: Option<&str>| {
+        git.is_some_and(|git| targets.iter().any(|(repos, _)| repos.matches(git)))
+    };
+
+    let mut skipped = Vec::new();
+    for (table, deps) in doc
+        .iter()
+        .filter(|(k, _)| k.contains("dependencies"))
+        .filter_map(|(k, v)| v.as_table().map(|t| (k, t)))
+    {
+        for (key, dep) in deps.iter() {
+            let Some(dep_table) = dep.as_table_like() else {
+                continue;
+            };
+            let name = dep_table
+                .get("package")
+                .and_then(|p| p.as_str())
+                .unwrap_or(key);
+            let git = dep_table.get("git").and_then(|g| g.as_str());
+
+            let reason = if is_managed(git) && dep.as_table().is_some_and(|t| !t.is_dotted()) {
+                SkipReason::Table
+            } else if is_managed(git) && dev_only && table != "dev-dependencies" {
+                SkipReason::FilteredOut
+            } else if dep_table.contains_key("path")
+                && SDK_CRATE_PREFIXES.iter().any(|p| name.starts_with(p))
+            {
+                SkipReason::PathDependency
+            } else {
+                continue;
+            };
 
-    let mut toml_doc = Document::from_str(&fs::read_to_string(&path)?)?;
+            skipped.push(Skipped {
+                manifest: manifest.into(),
+                dependency: Some((table.into(), key.into())),
+                reason,
+            });
+        }
+    }
+
+    skipped
+}
 
-    // Iterate over all tables in the document
+/// Print the dependencies that were skipped.
+fn print_skipped(skipped: &[Skipped]) {
+    if skipped.is_empty() {
+        println!("No dependencies were skipped.");
+        return;
+    }
+
+    println!("{} dependencies or manifests were skipped:", skipped.len());
+    for skipped in skipped {
+        match &skipped.dependency {
+            Some((table, key)) => println!(
+                "  {}: `{}` in `[{}]`: {}",
+                skipped.manifest.display(),
+                key,
+                table,
+                skipped.reason
+            ),
+            None => println!("  {}: {}", skipped.manifest.display(), skipped.reason),
+        }
+    }
+}
+
+/// Rewrite all git dependencies on one of the given repositories into the canonical key order
+/// and spacing.
+///
+/// Only the dependency tables for which `table_filter` returns `true` are considered.
+fn normalize_document(
+    toml_doc: &mut Document,
+    repos: &RepoMatcher,
+    table_filter: impl Fn(&str) -> bool,
+) {
     toml_doc
-        .clone()
+        .iter_mut()
+        .filter(|(k, _)| k.contains("dependencies") && table_filter(k))
+        .filter_map(|(_, v)| v.as_table_mut())
+        .flat_map(|deps| deps.iter_mut())
+        .for_each(|(mut key, item)| {
+            if !manifest::dependency_mut(item).is_some_and(|dep| is_managed_dependency(dep, repos))
+            {
+                return;
+            }
+
+            // Only the spacing around `=` is touched, to keep comments in front of the keys.
+            workspacify::sort_dep_keys(item);
+            match item {
+                Item::Value(Value::InlineTable(dep)) => {
+                    key.decor_mut().set_suffix(" ");
+                    dep.fmt();
+                    dep.decor_mut().set_prefix(" ");
+                }
+                Item::Table(dep) => dep.iter_mut().for_each(|(mut key, value)| {
+                    key.decor_mut().set_suffix(" ");
+                    if let Some(value) = value.as_value_mut() {
+                        value.decor_mut().set_prefix(" ");
+                    }
+                }),
+                _ => {}
+            }
+        });
+}
+
+/// Returns the directory of the workspace the manifest at `manifest` belongs to.
+fn workspace_root(manifest: &Path, changes: &Changes) -> Option<PathBuf> {
+    manifest
+        .ancestors()
+        .skip(1)
+        .map(|dir| dir.join("Cargo.toml"))
+        .find(|m| {
+            changes.exists(m)
+                && changes
+                    .read_toml(m)
+                    .is_ok_and(|doc| doc.contains_key("workspace"))
+        })
+        .and_then(|m| m.parent().map(Into::into))
+}
+
+/// Rewrite the git dependencies on the `targets` in `[workspace.dependencies]` of `manifest`.
+pub(crate) fn update_workspace_dependencies(
+    manifest: &Path,
+    targets: &Targets,
+    git: &Option<String>,
+    changes: &mut Changes,
+) -> Result<()> {
+    let mut doc = changes.read_toml(manifest)?;
+    let Some(deps) = doc
+        .get_mut("workspace")
+        .and_then(|w| w.get_mut("dependencies"))
+        .and_then(|d| d.as_table_mut())
+    else {
+        return Ok(());
+    };
+
+    for (name, dep) in deps.iter_mut() {
+        for (repos, version) in targets {
+            handle_dependency(name.get(), dep, repos, git, version);
+        }
+    }
+
+    changes.write(manifest, doc.to_string());
+    Ok(())
+}
+
+/// Rewrite all git dependencies on one of the given repositories in the given document for
+/// which `filter` returns `true`.
+///
+/// Only the dependency tables for which `table_filter` returns `true` are considered, it is
+/// called with the name of the table, e.g. `dev-dependencies`. `filter` is called with the name
+/// of the package, which is the `package` key for renamed dependencies and the dependency key
+/// otherwise.
+pub(crate) fn update_document(
+    toml_doc: &mut Document,
+    repos: &RepoMatcher,
+    table_filter: impl Fn(&str) -> bool,
+    filter: impl Fn(&str) -> bool,
+    git: &Option<String>,
+    version: &Version,
+) {
+    // Collect the dependencies first, the document is modified while they are handled.
+    let deps = toml_doc
         .iter()
         // filter out everything that is not a dependency table
-        .filter(|(k, _)| k.contains("dependencies"))
+        .filter(|(k, _)| k.contains("dependencies") && table_filter(k))
         .filter_map(|(k, v)| v.as_table().map(|t| (k, t)))
-        .for_each(|(k, t)| {
+        .flat_map(|(k, t)| {
             t.iter()
-                // Filter everything that is not an inline table (`{ foo = bar }`)
-                .filter_map(|v| v.1.as_inline_table().map(|_| v.0))
-                .for_each(|dn| {
-                    // Get the actual inline table from the document that we modify
-                    let table = toml_doc[k][dn]
-                        .as_inline_table_mut()
-                        .expect("We filter by `is_inline_table`; qed");
-                    handle_dependency(dn, table, git, version);
-                })
-        });
+                // Filter everything that is not an inline table (`{ foo = bar }`) or uses
+                // dotted keys (`foo.git = ".."`)
+                .filter(|(_, v)| v.is_inline_table() || v.as_table().is_some_and(|t| t.is_dotted()))
+                .filter_map(|v| v.1.as_table_like().map(|d| (v.0, d)))
+                .filter(|(dn, d)| filter(d.get("package").and_then(|p| p.as_str()).unwrap_or(dn)))
+                .map(move |(dn, _)| (k.to_string(), dn.to_string()))
+        })
+        .collect::<Vec<_>>();
 
-    fs::write(&path, toml_doc.to_string())?;
-    Ok(())
+    for (k, dn) in deps {
+        // Get the actual dependency from the document that we modify
+        handle_dependency(&dn, &mut toml_doc[&k][&dn], repos, git, version);
+    }
 }
@@ -0,0 +1,238 @@
+use crate::{
+    context::RunContext,
+    failure::{Classify, Failure},
+    git::GitRef,
+    manifest::{dependency_tables_mut, patch_tables_mut},
+    network,
+    workspacify::manifest_iter,
+};
+use anyhow::{anyhow, Context, Result};
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+use structopt::StructOpt;
+use toml_edit::Table;
+
+/// `verify` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct Verify {
+    /// The path to the workspace that should be verified.
+    ///
+    /// If not given, the current directory will be taken.
+    #[structopt(long)]
+    path: Option<PathBuf>,
+
+    /// Also run `cargo check` after `cargo metadata` succeeded.
+    #[structopt(long)]
+    check: bool,
+}
+
+/// A patch or dependency entry of a manifest that diener manages.
+struct Entry {
+    manifest: PathBuf,
+    /// The table the entry is part of, e.g. `patch.crates-io`.
+    table: String,
+    /// The name of the package.
+    name: String,
+    /// The resolved `path` of the entry, if any.
+    path: Option<PathBuf>,
+    /// The source of the entry as written in the manifest.
+    source: String,
+}
+
+impl Entry {
+    fn is_patch(&self) -> bool {
+        self.table.starts_with("patch.")
+    }
+
+    /// Returns `true` if the cargo output mentions this entry.
+    fn is_mentioned_in(&self, output: &str) -> bool {
+        output.contains(&format!("`{}`", self.name))
+            || output.contains(&format!("`{} v", self.name))
+            || self
+                .path
+                .as_ref()
+                .is_some_and(|p| output.contains(&p.display().to_string()))
+    }
+
+    fn describe(&self) -> String {
+        if self.is_patch() {
+            format!(
+                "Your patch of crate `{}` at {} (`[{}]` in {})",
+                self.name,
+                self.source,
+                self.table,
+                self.manifest.display()
+            )
+        } else {
+            format!(
+                "The dependency `{}` on {} (`[{}]` in {})",
+                self.name,
+                self.source,
+                self.table,
+                self.manifest.display()
+            )
+        }
+    }
+}
+
+impl Verify {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &RunContext) -> Result<()> {
+        let workspace = ctx.workspace_dir(self.path.as_deref())?;
+
+        let entries = managed_entries(&workspace, ctx)?;
+
+        let offline = ctx.offline.then_some("--offline");
+        let mut metadata = vec!["metadata", "--format-version", "1"];
+        if let Some(platform) = &ctx.filter_platform {
+            metadata.extend(["--filter-platform", platform]);
+        }
+        let (success, output) = cargo(&workspace, &metadata, offline)?;
+        let unused_patches = unused_patches(&output, &entries);
+        if !success {
+            diagnose("cargo metadata", &output, &entries);
+            return Err(anyhow!("`cargo metadata` failed.").failure(Failure::Cargo));
+        }
+
+        if self.check {
+            let (success, output) = cargo(&workspace, &["check", "-q", "--workspace"], offline)?;
+            if !success {
+                diagnose("cargo check", &output, &entries);
+                return Err(anyhow!("`cargo check` failed.").failure(Failure::Cargo));
+            }
+        }
+
+        if unused_patches > 0 {
+            return Err(
+                anyhow!("{} patch(es) didn't resolve.", unused_patches).failure(Failure::Cargo)
+            );
+        }
+
+        log::info!("Verification successful.");
+        Ok(())
+    }
+}
+
+/// Run `cargo` with the given `args` in `workspace`.
+///
+/// `offline` is appended to the arguments if given.
+///
+/// Returns if the command was successful and its stderr.
+fn cargo(workspace: &Path, args: &[&str], offline: Option<&str>) -> Result<(bool, String)> {
+    let args = args.iter().copied().chain(offline).collect::<Vec<_>>();
+    log::info!("Running `cargo {}`.", args.join(" "));
+
+    let output = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()))
+        .envs(network::env())
+        .args(&args)
+        .current_dir(workspace)
+        .stdout(Stdio::null())
+        .output()
+        .with_context(|| "Failed to execute `cargo`.")
+        .failure(Failure::Cargo)?;
+
+    Ok((
+        output.status.success(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    ))
+}
+
+/// Collect all patch entries and all git/path dependencies of the workspace.
+fn managed_entries(workspace: &Path, ctx: &RunContext) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+
+    for manifest in manifest_iter(ctx.changes.fs(), workspace, &ctx.exclusions) {
+        let mut doc = ctx.changes.read_toml(&manifest)?;
+        let dir = manifest.parent().expect("Manifests are files; qed");
+
+        let mut collect = |table_name: String, table: &Table| {
+            for (key, dep) in table.iter() {
+                let Some(dep) = dep.as_table_like() else {
+                    continue;
+                };
+                let get = |k| dep.get(k).and_then(|v| v.as_str());
+
+                let (source, path) = if let Some(path) = get("path") {
+                    (format!("path `{path}`"), Some(dir.join(path)))
+                } else if let Some(git) = get("git") {
+                    let reference = GitRef::from_dependency(dep);
+                    (format!("git `{git}` ({reference})"), None)
+                } else {
+                    continue;
+                };
+
+                entries.push(Entry {
+                    manifest: manifest.clone(),
+                    table: table_name.clone(),
+                    name: get("package").unwrap_or(key).into(),
+                    path: path.map(|p| p.canonicalize().unwrap_or(p)),
+                    source,
+                });
+            }
+        };
+
+        for (target, table) in patch_tables_mut(&mut doc) {
+            collect(format!("patch.{target}"), table);
+        }
+        for (name, table) in dependency_tables_mut(&mut doc) {
+            collect(name, table);
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Report all patches that cargo reports as not being used.
+///
+/// Returns the number of unused patches.
+fn unused_patches(output: &str, entries: &[Entry]) -> usize {
+    output
+        .lines()
+        .filter(|l| l.contains("was not used in the crate graph"))
+        // Older cargo versions start the message with an uppercase `Patch`.
+        .filter_map(|l| l.split("atch `").nth(1))
+        .filter_map(|l| l.split(" v").next())
+        .map(|name| {
+            let entry = entries.iter().find(|e| e.is_patch() && e.name == name);
+            match entry {
+                Some(entry) => log::error!(
+                    "{} didn't resolve: it is not used in the crate graph. Make sure the version \
+                     matches the requirements and the patch is added for the right source.",
+                    entry.describe()
+                ),
+                None => log::error!(
+                    "The patch of crate `{}` is not used in the crate graph.",
+                    name
+                ),
+            }
+        })
+        .count()
+}
+
+/// Print the entries that are mentioned in the failed cargo `output`.
+fn diagnose(command: &str, output: &str, entries: &[Entry]) {
+    let mentioned = entries
+        .iter()
+        .filter(|e| e.is_mentioned_in(output))
+        .collect::<Vec<_>>();
+
+    if mentioned.is_empty() {
+        log::error!(
+            "`{}` failed, but no patch or dependency managed by diener is involved.",
+            command
+        );
+    } else {
+        log::error!("`{}` failed, the following entries are involved:", command);
+        mentioned.iter().for_each(|e| {
+            if e.is_patch() {
+                log::error!("  {} didn't resolve.", e.describe())
+            } else {
+                log::error!("  {}", e.describe())
+            }
+        });
+    }
+
+    log::error!("Output of `{}`:\n{}", command, output.trim_end());
+}
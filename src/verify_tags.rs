@@ -0,0 +1,162 @@
+use crate::{
+    context::RunContext,
+    git::GitRef,
+    manifest::{dependency_package, dependency_tables},
+    update::{is_managed_dependency, RepoMatcher},
+};
+use anyhow::{ensure, Result};
+use glob::Pattern;
+use std::{collections::BTreeMap, path::PathBuf};
+use structopt::StructOpt;
+
+/// `verify-tags` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct VerifyTags {
+    /// The path where Diener should search for `Cargo.toml` files.
+    ///
+    /// Can be passed multiple times and may be a glob pattern like `runtimes/*`.
+    /// If not given, the current directory will be taken.
+    #[structopt(long)]
+    path: Vec<PathBuf>,
+
+    /// The glob pattern all tags must match, e.g. `polkadot-stable2407*`.
+    ///
+    /// Defaults to the family of the most used tag.
+    #[structopt(long)]
+    family: Option<Pattern>,
+}
+
+/// A Polkadot SDK dependency.
+struct Dependency {
+    manifest: PathBuf,
+    table: String,
+    /// The name of the package.
+    name: String,
+    reference: GitRef,
+}
+
+impl VerifyTags {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &RunContext) -> Result<()> {
+        let repos: RepoMatcher = ctx.config.match_repos().into();
+
+        let mut dependencies = Vec::new();
+        for manifest in ctx.manifests(&self.path)? {
+            let doc = ctx.changes.read_toml(&manifest)?;
+            for (table, deps) in dependency_tables(&doc) {
+                for (key, dep) in deps.iter() {
+                    let Some(dep) = dep
+                        .as_table_like()
+                        .filter(|dep| is_managed_dependency(*dep, &repos))
+                    else {
+                        continue;
+                    };
+                    dependencies.push(Dependency {
+                        manifest: manifest.clone(),
+                        table: table.clone(),
+                        name: dependency_package(key, dep).into(),
+                        reference: GitRef::from_dependency(dep),
+                    });
+                }
+            }
+        }
+
+        if dependencies.is_empty() {
+            log::info!("No Polkadot SDK git dependencies found.");
+            return Ok(());
+        }
+
+        let family = match self.family {
+            Some(pattern) => Family::Pattern(pattern),
+            None => {
+                let mut families = BTreeMap::<&str, usize>::new();
+                for dependency in &dependencies {
+                    if let GitRef::Tag(tag) = &dependency.reference {
+                        *families.entry(tag_family(tag)).or_default() += 1;
+                    }
+                }
+                // The most used family, ties are won by the later one, like `stable2409`
+                // over `stable2407`.
+                match families.into_iter().max_by_key(|(_, count)| *count) {
+                    Some((family, _)) => Family::Derived(family.into()),
+                    None => Family::Derived(String::new()),
+                }
+            }
+        };
+
+        let mut outliers = 0;
+        for dependency in &dependencies {
+            let problem = match &dependency.reference {
+                GitRef::Tag(tag) if family.contains(tag) => continue,
+                GitRef::Tag(tag) => format!("uses tag `{tag}`"),
+                reference => format!("is not pinned to a tag, but uses {reference}"),
+            };
+            log::error!(
+                "{}: `{}` in `[{}]` {}, expected {}.",
+                dependency.manifest.display(),
+                dependency.name,
+                dependency.table,
+                problem,
+                family
+            );
+            outliers += 1;
+        }
+
+        ensure!(
+            outliers == 0,
+            "Found {} of {} Polkadot SDK dependencies outside of {}.",
+            outliers,
+            dependencies.len(),
+            family
+        );
+        log::info!(
+            "All {} Polkadot SDK dependencies use {}.",
+            dependencies.len(),
+            family
+        );
+        Ok(())
+    }
+}
+
+/// The tag family the tags must be part of.
+enum Family {
+    /// Given by `--family`.
+    Pattern(Pattern),
+    /// Derived from the tags, see [`tag_family`].
+    Derived(String),
+}
+
+impl Family {
+    fn contains(&self, tag: &str) -> bool {
+        match self {
+            Self::Pattern(pattern) => pattern.matches(tag),
+            Self::Derived(family) => tag_family(tag) == family,
+        }
+    }
+}
+
+impl std::fmt::Display for Family {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pattern(pattern) => write!(f, "tags matching `{pattern}`"),
+            Self::Derived(family) if family.is_empty() => write!(f, "tags"),
+            Self::Derived(family) => write!(f, "the `{family}` tag family"),
+        }
+    }
+}
+
+/// Returns the family of `tag`, the tag without its numeric patch suffix.
+///
+/// `polkadot-stable2407-2` and `polkadot-stable2407` are both part of `polkadot-stable2407`.
+fn tag_family(tag: &str) -> &str {
+    match tag.rsplit_once('-') {
+        Some((family, patch))
+            if !family.is_empty()
+                && !patch.is_empty()
+                && patch.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            family
+        }
+        _ => tag,
+    }
+}
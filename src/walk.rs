@@ -0,0 +1,166 @@
+use crate::{
+    filesystem::Fs,
+    timings::{self, Phase},
+};
+use glob::Pattern;
+use std::{
+    ffi::OsStr,
+    path::{Component, Path, PathBuf},
+};
+use toml_edit::Document;
+
+/// Directory names that are never searched for manifests.
+///
+/// Hidden directories are always skipped as well.
+pub(crate) const DEFAULT_EXCLUDED_DIRS: &[&str] = &["target", "node_modules", "vendor"];
+
+/// The directories that are skipped when searching for manifests.
+///
+/// Shared by all subcommands, extended by the `exclude-dirs` and `exclude` configuration,
+/// `--exclude-dir` and `update --exclude-path`.
+#[derive(Debug, Clone)]
+pub(crate) struct Exclusions {
+    /// Directory names that are skipped wherever they appear.
+    names: Vec<String>,
+    /// Directories that are skipped including everything below them.
+    paths: Vec<PathBuf>,
+    /// Glob patterns of directories and manifests that are skipped.
+    patterns: Vec<Pattern>,
+}
+
+impl Default for Exclusions {
+    fn default() -> Self {
+        Self {
+            names: DEFAULT_EXCLUDED_DIRS
+                .iter()
+                .map(|n| n.to_string())
+                .collect(),
+            paths: Vec::new(),
+            patterns: Vec::new(),
+        }
+    }
+}
+
+impl Exclusions {
+    /// Skip directories with the given `names` and the given `paths` besides the defaults.
+    pub(crate) fn new(
+        names: impl IntoIterator<Item = String>,
+        paths: impl IntoIterator<Item = PathBuf>,
+    ) -> Self {
+        let mut exclusions = Self::default();
+        exclusions.names.extend(names);
+        exclusions
+            .paths
+            .extend(paths.into_iter().map(|p| p.canonicalize().unwrap_or(p)));
+        exclusions
+    }
+
+    /// The directory names that are skipped wherever they appear, including the defaults.
+    pub(crate) fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Also skip all directories and manifests matching one of the glob `patterns`.
+    ///
+    /// The patterns are matched against the path relative to the working directory and the
+    /// path as found.
+    pub(crate) fn with_patterns(mut self, patterns: impl IntoIterator<Item = Pattern>) -> Self {
+        self.patterns.extend(patterns);
+        self
+    }
+
+    /// Also skip the given `paths` including everything below them.
+    pub(crate) fn with_paths(mut self, paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.paths
+            .extend(paths.into_iter().map(|p| p.canonicalize().unwrap_or(p)));
+        self
+    }
+
+    /// Returns `true` if a directory with the given `name` is skipped.
+    fn is_excluded_name(&self, name: &OsStr) -> bool {
+        let name = name.to_string_lossy();
+        name.starts_with('.') || self.names.iter().any(|n| *n == name)
+    }
+
+    /// Returns `true` if the directory at `dir` is skipped.
+    fn is_excluded_dir(&self, dir: &Path) -> bool {
+        dir.file_name().is_some_and(|n| self.is_excluded_name(n))
+            || (!self.paths.is_empty() && self.is_excluded_path(dir))
+            || self.matches_pattern(dir)
+    }
+
+    /// Returns `true` if `path` matches one of the glob patterns.
+    fn matches_pattern(&self, path: &Path) -> bool {
+        if self.patterns.is_empty() {
+            return false;
+        }
+
+        let relative = std::env::current_dir()
+            .ok()
+            .and_then(|cwd| path.strip_prefix(cwd).ok())
+            .unwrap_or(path);
+        let relative = relative.strip_prefix(".").unwrap_or(relative);
+        self.patterns
+            .iter()
+            .any(|p| p.matches_path(relative) || p.matches_path(path))
+    }
+
+    /// Returns `true` if `path` is in one of the excluded directories.
+    fn is_excluded_path(&self, path: &Path) -> bool {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.paths.iter().any(|p| path.starts_with(p))
+    }
+
+    /// Returns `true` if the file at `path` is inside of a skipped directory or matches one of
+    /// the glob patterns.
+    ///
+    /// Only the directories of `path` itself are checked, e.g. `../vendor/foo/Cargo.toml`
+    /// would be skipped, but a `vendor` directory above the working directory isn't.
+    pub(crate) fn is_excluded(&self, path: &Path) -> bool {
+        let dirs = path.parent().unwrap_or(path);
+        dirs.components().any(|c| match c {
+            Component::Normal(name) => self.is_excluded_name(name),
+            _ => false,
+        }) || (!self.paths.is_empty() && self.is_excluded_path(path))
+            || path.ancestors().any(|p| self.matches_pattern(p))
+    }
+}
+
+/// Returns the directories of all workspace roots below `root`, including `root` itself.
+///
+/// A workspace root is a directory with a `Cargo.toml` that contains a `[workspace]`.
+pub(crate) fn workspace_roots(fs: &dyn Fs, root: &Path, exclusions: &Exclusions) -> Vec<PathBuf> {
+    manifests(fs, root, false, exclusions)
+        .filter(|m| {
+            fs.read(m)
+                .ok()
+                .and_then(|c| c.parse::<Document>().ok())
+                .is_some_and(|doc| doc.contains_key("workspace"))
+        })
+        .filter_map(|m| m.parent().map(Path::to_path_buf))
+        .collect()
+}
+
+/// Returns all `Cargo.toml` files below `root`, skipping all excluded directories.
+///
+/// `root` itself is never skipped.
+pub(crate) fn manifests(
+    fs: &dyn Fs,
+    root: &Path,
+    follow_links: bool,
+    exclusions: &Exclusions,
+) -> impl Iterator<Item = PathBuf> {
+    let exclusions = exclusions.clone();
+
+    let skip = Box::new(move |path: &Path, is_dir: bool| {
+        if is_dir {
+            exclusions.is_excluded_dir(path)
+        } else {
+            exclusions.matches_pattern(path)
+        }
+    });
+    let manifests = fs
+        .walk(root, follow_links, skip)
+        .filter(|p| p.file_name().is_some_and(|n| n == "Cargo.toml"));
+    timings::measure_iter(Phase::Walk, manifests)
+}
@@ -0,0 +1,192 @@
+use crate::{
+    context::RunContext,
+    format::OutputFormat,
+    git::GitRef,
+    manifest::{dependency_package, dependency_tables},
+};
+use anyhow::{ensure, Result};
+use serde::Serialize;
+use std::{fmt, path::PathBuf};
+use structopt::StructOpt;
+use toml_edit::{Item, TableLike};
+
+/// `where` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct Where {
+    /// The path where Diener should search for `Cargo.toml` files.
+    ///
+    /// Can be passed multiple times and may be a glob pattern like `runtimes/*`.
+    /// If not given, the current directory will be taken.
+    #[structopt(long)]
+    path: Vec<PathBuf>,
+
+    /// The output format, `text` or `json`.
+    ///
+    /// Defaults to the `format` of the configuration or `text`.
+    #[structopt(long)]
+    format: Option<OutputFormat>,
+
+    /// The name of the package to search for, e.g. `sp-core`.
+    package: String,
+}
+
+/// Where a declaration takes the package from.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum Source {
+    Git {
+        url: String,
+        reference: String,
+    },
+    Path {
+        path: String,
+    },
+    Registry {
+        version: Option<String>,
+        registry: Option<String>,
+    },
+    /// Inherited from `[workspace.dependencies]`.
+    Workspace,
+}
+
+impl Source {
+    fn from_dependency(dep: &Item) -> Self {
+        let Some(table) = dep.as_table_like() else {
+            return Self::Registry {
+                version: dep.as_str().map(Into::into),
+                registry: None,
+            };
+        };
+        let get = |key| -> Option<String> { table.get(key)?.as_str().map(Into::into) };
+
+        if table.get("workspace").and_then(|w| w.as_bool()) == Some(true) {
+            Self::Workspace
+        } else if let Some(url) = get("git") {
+            Self::Git {
+                url,
+                reference: GitRef::from_dependency(table).to_string(),
+            }
+        } else if let Some(path) = get("path") {
+            Self::Path { path }
+        } else {
+            Self::Registry {
+                version: get("version"),
+                registry: get("registry"),
+            }
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Git { url, reference } => write!(f, "git {url} ({reference})"),
+            Self::Path { path } => write!(f, "path {path}"),
+            Self::Registry { version, registry } => {
+                write!(f, "{}", registry.as_deref().unwrap_or("crates.io"))?;
+                match version {
+                    Some(version) => write!(f, " {version}"),
+                    None => write!(f, " without version"),
+                }
+            }
+            Self::Workspace => write!(f, "inherited from `[workspace.dependencies]`"),
+        }
+    }
+}
+
+/// A dependency on or a patch of the package.
+#[derive(Debug, Serialize)]
+struct Declaration {
+    manifest: PathBuf,
+    /// The table, e.g. `dependencies` or `patch.crates-io`.
+    table: String,
+    /// The key, which differs from the package name for renamed dependencies.
+    key: String,
+    source: Source,
+}
+
+impl Where {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &RunContext) -> Result<()> {
+        let format = self
+            .format
+            .or(ctx.config.format())
+            .unwrap_or(OutputFormat::Text);
+        ensure!(
+            format != OutputFormat::Github,
+            "`where` doesn't support the `github` format."
+        );
+
+        let mut declarations = Vec::new();
+        for manifest in ctx.manifests(&self.path)? {
+            let doc = ctx.changes.read_toml(&manifest)?;
+
+            let patches = doc
+                .get("patch")
+                .and_then(Item::as_table_like)
+                .into_iter()
+                .flat_map(|patch| {
+                    // Use the key as written, e.g. `"https://.."`, like `dependency_tables`.
+                    patch.iter().filter_map(|(target, t)| {
+                        let target = patch.get_key_value(target)?.0;
+                        Some((format!("patch.{target}"), t.as_table_like()?))
+                    })
+                });
+            let tables = dependency_tables(&doc)
+                .into_iter()
+                .map(|(name, table)| (name, table as &dyn TableLike))
+                .chain(patches);
+
+            for (table, deps) in tables {
+                for (key, dep) in deps.iter() {
+                    let package = match dep.as_table_like() {
+                        Some(dep) => dependency_package(key, dep),
+                        None => key,
+                    };
+                    if package != self.package {
+                        continue;
+                    }
+
+                    declarations.push(Declaration {
+                        manifest: manifest.clone(),
+                        table: table.clone(),
+                        key: key.into(),
+                        source: Source::from_dependency(dep),
+                    });
+                }
+            }
+        }
+
+        match format {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&declarations)?),
+            OutputFormat::Text | OutputFormat::Github => {
+                if declarations.is_empty() {
+                    println!("`{}` is not declared anywhere.", self.package);
+                    return Ok(());
+                }
+
+                println!(
+                    "`{}` is declared {} time(s):",
+                    self.package,
+                    declarations.len()
+                );
+                for declaration in &declarations {
+                    let renamed = if declaration.key == self.package {
+                        String::new()
+                    } else {
+                        format!(" as `{}`", declaration.key)
+                    };
+                    println!(
+                        "  {}: `[{}]`{}: {}",
+                        declaration.manifest.display(),
+                        declaration.table,
+                        renamed,
+                        declaration.source
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,382 @@
+use crate::{context::RunContext, editor, manifest::manifest_path, workspacify::manifest_iter};
+use anyhow::{anyhow, ensure, Result};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+use toml_edit::{Array, Document, Item, Table, Value};
+
+/// The keys of a dependency entry that define where the dependency is coming from.
+///
+/// These are moved into `[workspace.dependencies]`, everything else stays in the member.
+const SOURCE_KEYS: &[&str] = &[
+    "package", "path", "version", "git", "branch", "tag", "rev", "registry",
+];
+
+/// `workspace-deps` subcommand options.
+#[derive(Debug, StructOpt)]
+pub struct WorkspaceDeps {
+    /// The path to the workspace root directory.
+    ///
+    /// This is the directory where your workspace `Cargo.toml` is located.
+    /// Uses the working directory if none is supplied.
+    #[structopt(long)]
+    path: Option<PathBuf>,
+
+    /// Only dependencies that are used by more than this number of members are moved into
+    /// `[workspace.dependencies]`.
+    #[structopt(long, default_value = "1")]
+    threshold: usize,
+}
+
+/// A single declaration of an external dependency in a member manifest.
+pub(crate) struct Usage {
+    manifest: PathBuf,
+    /// The dependency table, e.g. `dev-dependencies`.
+    table: String,
+    /// The source keys of the declaration in the order of [`SOURCE_KEYS`].
+    source: Vec<(String, Value)>,
+    default_features: bool,
+    features: BTreeSet<String>,
+}
+
+impl Usage {
+    /// The `git` url of the dependency, if it is a git dependency.
+    pub(crate) fn git(&self) -> Option<&str> {
+        self.source
+            .iter()
+            .find(|(k, _)| k == "git")
+            .and_then(|(_, v)| v.as_str())
+    }
+
+    /// The `path` of the dependency relative to the workspace root, if it is in-tree.
+    pub(crate) fn path(&self) -> Option<&str> {
+        self.source
+            .iter()
+            .find(|(k, _)| k == "path")
+            .and_then(|(_, v)| v.as_str())
+    }
+
+    /// The source in a comparable form.
+    fn source_id(&self) -> String {
+        self.source
+            .iter()
+            .map(|(k, v)| format!("{k}={}", v.to_string().trim()))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+impl WorkspaceDeps {
+    /// Run this subcommand.
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        let workspace = ctx.workspace_dir(self.path.as_deref())?;
+
+        hoist(&workspace, self.threshold, |_| true, false, ctx)
+    }
+}
+
+/// Move the external dependencies of the members of `workspace` into
+/// `[workspace.dependencies]`.
+///
+/// Only dependencies that are used by more than `threshold` members and for which `filter`
+/// returns `true` for every usage are moved. With `paths` the in-tree dependencies are moved
+/// as well, their `path` is made relative to the workspace root.
+pub(crate) fn hoist(
+    workspace: &Path,
+    threshold: usize,
+    filter: impl Fn(&Usage) -> bool,
+    paths: bool,
+    ctx: &mut RunContext,
+) -> Result<()> {
+    let root_manifest = workspace.join("Cargo.toml");
+    let mut docs = BTreeMap::<PathBuf, Document>::new();
+    docs.insert(
+        root_manifest.clone(),
+        ctx.changes.read_toml(&root_manifest)?,
+    );
+    for manifest in
+        manifest_iter(ctx.changes.fs(), workspace, &ctx.exclusions).filter(|m| *m != root_manifest)
+    {
+        docs.insert(manifest.clone(), ctx.changes.read_toml(&manifest)?);
+    }
+
+    ensure!(
+        docs[&root_manifest].get("workspace").is_some(),
+        "`{}` is not a workspace manifest.",
+        root_manifest.display()
+    );
+
+    let mut usages = BTreeMap::<String, Vec<Usage>>::new();
+    for (manifest, doc) in docs.iter().filter(|(_, d)| d.get("package").is_some()) {
+        collect_usages(manifest, doc, paths.then_some(workspace), &mut usages);
+    }
+
+    let originals = docs
+        .iter()
+        .map(|(p, d)| (p.clone(), d.to_string()))
+        .collect::<BTreeMap<_, _>>();
+
+    for (key, usages) in usages {
+        let members = usages.iter().map(|u| &u.manifest).collect::<BTreeSet<_>>();
+        if members.len() <= threshold || !usages.iter().all(&filter) {
+            continue;
+        }
+
+        let sources = usages
+            .iter()
+            .map(|u| u.source_id())
+            .collect::<BTreeSet<_>>();
+        if sources.len() > 1 {
+            log::warn!(
+                "Not moving `{}`, members use different sources: {}",
+                key,
+                sources.into_iter().collect::<Vec<_>>().join(" | ")
+            );
+            continue;
+        }
+
+        let root = docs
+            .get_mut(&root_manifest)
+            .expect("Root is inserted above; qed");
+        let Some((default_features, features)) = workspace_entry(root, &key, &usages)? else {
+            continue;
+        };
+
+        log::info!(
+            "Moving `{}` into `[workspace.dependencies]` (used by {} members).",
+            key,
+            members.len()
+        );
+
+        for usage in &usages {
+            let doc = docs
+                .get_mut(&usage.manifest)
+                .expect("Usages are collected from `docs`; qed");
+            let item = &mut doc[&usage.table][&key];
+            inherit(item, default_features, usage, &features);
+        }
+    }
+
+    for (path, doc) in docs {
+        let content = doc.to_string();
+        if originals[&path] != content {
+            ctx.changes.write(path, content);
+        }
+    }
+
+    Ok(())
+}
+
+/// Collect all external dependencies declared in the given member manifest.
+///
+/// With `workspace` the in-tree dependencies are collected as well.
+fn collect_usages(
+    manifest: &Path,
+    doc: &Document,
+    workspace: Option<&Path>,
+    usages: &mut BTreeMap<String, Vec<Usage>>,
+) {
+    doc.iter()
+        .filter(|(k, _)| k.contains("dependencies"))
+        .filter_map(|(k, v)| v.as_table().map(|t| (k, t)))
+        .for_each(|(table, deps)| {
+            for (key, dep) in deps.iter() {
+                let usage = if let Some(version) = dep.as_str() {
+                    Usage {
+                        manifest: manifest.into(),
+                        table: table.into(),
+                        source: vec![("version".into(), Value::from(version))],
+                        default_features: true,
+                        features: Default::default(),
+                    }
+                } else if let Some(dep) = dep.as_table_like() {
+                    // In-tree and already inherited dependencies are not external.
+                    if dep.contains_key("workspace") {
+                        continue;
+                    }
+                    let path = match (dep.get("path").and_then(|p| p.as_str()), workspace) {
+                        (None, _) => None,
+                        (Some(path), Some(workspace)) => {
+                            match workspace_relative(manifest, path, workspace) {
+                                Some(path) => Some(Value::from(path)),
+                                None => continue,
+                            }
+                        }
+                        (Some(_), None) => continue,
+                    };
+
+                    Usage {
+                        manifest: manifest.into(),
+                        table: table.into(),
+                        source: SOURCE_KEYS
+                            .iter()
+                            .filter_map(|k| {
+                                let value = if *k == "path" {
+                                    path.clone()
+                                } else {
+                                    dep.get(k).and_then(|v| v.as_value()).cloned()
+                                };
+                                value.map(|v| (k.to_string(), v.decorated("", "")))
+                            })
+                            .collect(),
+                        default_features: dep
+                            .get("default-features")
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or(true),
+                        features: features(dep.get("features")),
+                    }
+                } else {
+                    continue;
+                };
+
+                usages.entry(key.into()).or_default().push(usage);
+            }
+        });
+}
+
+/// Returns the dependency at `path` of the member at `manifest` relative to `workspace`.
+pub(crate) fn workspace_relative(manifest: &Path, path: &str, workspace: &Path) -> Option<String> {
+    let dir = manifest.parent()?.join(path).canonicalize().ok()?;
+    let workspace = workspace.canonicalize().ok()?;
+    let path = pathdiff::diff_paths(dir, workspace)?;
+    Some(manifest_path(&path))
+}
+
+fn features(item: Option<&Item>) -> BTreeSet<String> {
+    item.and_then(|f| f.as_array())
+        .map(|f| {
+            f.iter()
+                .filter_map(|f| f.as_str())
+                .map(Into::into)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Ensure that `[workspace.dependencies]` contains an entry for `key`.
+///
+/// Returns whether the workspace entry enables the default features and the features it
+/// enables, or `None` if the existing entry is not compatible with the usages.
+fn workspace_entry(
+    root: &mut Document,
+    key: &str,
+    usages: &[Usage],
+) -> Result<Option<(bool, BTreeSet<String>)>> {
+    let ws_deps = root["workspace"]
+        .as_table_mut()
+        .ok_or_else(|| anyhow!("`workspace` is not a table"))?
+        .entry("dependencies")
+        .or_insert(Item::Table(Table::new()))
+        .as_table_like_mut()
+        .ok_or_else(|| anyhow!("`workspace.dependencies` is not a table"))?;
+
+    let all_default_features = usages.iter().all(|u| u.default_features);
+
+    if let Some(existing) = ws_deps.get(key) {
+        let existing_source = existing
+            .as_str()
+            .map(|v| format!("version=\"{v}\""))
+            .or_else(|| {
+                existing.as_table_like().map(|t| {
+                    SOURCE_KEYS
+                        .iter()
+                        .filter_map(|k| t.get(k).and_then(|v| v.as_value()).map(|v| (k, v)))
+                        .map(|(k, v)| format!("{k}={}", v.to_string().trim()))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                })
+            })
+            .unwrap_or_default();
+
+        if existing_source != usages[0].source_id() {
+            log::warn!(
+                "Not moving `{}`, `[workspace.dependencies]` already declares it with a different source.",
+                key
+            );
+            return Ok(None);
+        }
+
+        let default_features = existing
+            .as_table_like()
+            .and_then(|t| t.get("default-features"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+        if default_features && !all_default_features {
+            log::warn!(
+                "Not moving `{}`, `[workspace.dependencies]` enables default features, but some members disable them.",
+                key
+            );
+            return Ok(None);
+        }
+
+        let features = features(existing.as_table_like().and_then(|t| t.get("features")));
+        if !usages.iter().all(|u| features.is_subset(&u.features)) {
+            log::warn!(
+                "Not moving `{}`, `[workspace.dependencies]` enables features not all members enable.",
+                key
+            );
+            return Ok(None);
+        }
+
+        return Ok(Some((default_features, features)));
+    }
+
+    let common_features = usages
+        .iter()
+        .map(|u| u.features.clone())
+        .reduce(|a, b| a.intersection(&b).cloned().collect())
+        .unwrap_or_default();
+
+    let mut entry = usages[0].source.clone();
+    if !all_default_features {
+        entry.push(("default-features".into(), false.into()));
+    }
+    if !common_features.is_empty() {
+        entry.push((
+            "features".into(),
+            Value::Array(common_features.iter().collect()),
+        ));
+    }
+    editor::add_dependency(ws_deps, key, entry);
+
+    Ok(Some((all_default_features, common_features)))
+}
+
+/// Rewrite the member declaration to inherit the dependency from the workspace.
+///
+/// Keeps all member specific keys like `optional` and the features on top of the
+/// `workspace_features`.
+fn inherit(
+    item: &mut Item,
+    workspace_default_features: bool,
+    usage: &Usage,
+    workspace_features: &BTreeSet<String>,
+) {
+    let mut entries = vec![("workspace".to_string(), Value::from(true))];
+
+    if !workspace_default_features && usage.default_features {
+        entries.push(("default-features".into(), Value::from(true)));
+    }
+
+    let features = usage
+        .features
+        .difference(workspace_features)
+        .collect::<Array>();
+    if !features.is_empty() {
+        entries.push(("features".into(), Value::Array(features)));
+    }
+
+    // Keep everything else, e.g. `optional`.
+    if let Some(dep) = item.as_table_like() {
+        dep.iter()
+            .filter(|(k, _)| {
+                !SOURCE_KEYS.contains(k) && !["default-features", "features"].contains(k)
+            })
+            .filter_map(|(k, v)| v.as_value().map(|v| (k.to_string(), v.clone())))
+            .for_each(|e| entries.push(e));
+    }
+
+    editor::replace_dependency(item, entries);
+}
@@ -1,18 +1,89 @@
+use crate::{
+    changes::{self, Changes},
+    context::RunContext,
+    editor,
+    filesystem::Fs,
+    format::OutputFormat,
+    manifest::{dependency_mut, dependency_package, manifest_path, push_formatted_like_last},
+    rename_crate,
+    walk::{self, Exclusions},
+    workspace_deps,
+};
 use anyhow::{anyhow, bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
-    env::current_dir,
-    fs::{self, OpenOptions},
-    io::Read,
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{self, BufRead, Write},
     path::{Path, PathBuf},
     str::FromStr,
 };
 use structopt::StructOpt;
-use toml_edit::{value, Array, Document, Formatted, InlineTable, Item, KeyMut, Table, Value};
-use walkdir::WalkDir;
+use toml_edit::{value, Array, Document, Formatted, Item, Key, KeyMut, Table, Value};
 
 const FILES_HAVE_PARENTS: &str = "This is a file. Every file has a parent; qed";
 
+/// The file `--export-packages` writes to, relative to the workspace root.
+const PACKAGES_FILE: &str = ".diener/packages.json";
+
+/// What `workspacify` does with crates that have the same name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum DedupeStrategy {
+    /// Abort.
+    #[default]
+    Fail,
+    /// Rename all but one copy, appending the names of their parent directories.
+    SuffixDir,
+    /// Leave all but one copy out of the workspace.
+    Skip,
+    /// Ask which copy to keep, the others are skipped.
+    Interactive,
+}
+
+impl FromStr for DedupeStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fail" => Ok(Self::Fail),
+            "suffix-dir" => Ok(Self::SuffixDir),
+            "skip" => Ok(Self::Skip),
+            "interactive" => Ok(Self::Interactive),
+            _ => bail!(
+                "Unknown dedupe strategy `{s}`, expected `fail`, `suffix-dir`, `skip` or \
+                 `interactive`."
+            ),
+        }
+    }
+}
+
+/// How the paths of the dependencies are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum PathStyle {
+    /// The shortest relative path, e.g. `../foo` or `foo`.
+    #[default]
+    Relative,
+    /// Like `relative`, but paths that don't leave the directory start with `./`, e.g. `./foo`.
+    Dot,
+    /// Up to the workspace root and from there to the crate, e.g. `../../crates/foo`.
+    ///
+    /// The common prefix of both crates isn't collapsed.
+    FromRoot,
+}
+
+impl FromStr for PathStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "relative" => Ok(Self::Relative),
+            "dot" => Ok(Self::Dot),
+            "from-root" => Ok(Self::FromRoot),
+            _ => bail!("Unknown path style `{s}`, expected `relative`, `dot` or `from-root`."),
+        }
+    }
+}
+
 #[derive(Debug, StructOpt)]
 pub struct Workspacify {
     /// The path to the workspace root directory.
@@ -21,71 +92,363 @@ pub struct Workspacify {
     /// Uses the working directory if none is supplied.
     #[structopt(long)]
     path: Option<PathBuf>,
+
+    /// Don't modify any file, fail if any manifest is not up to date instead.
+    #[structopt(long)]
+    check: bool,
+
+    /// The output format of `--check`, `text`, `json` or `github`.
+    ///
+    /// `github` emits GitHub Actions annotations. Defaults to the `format` of the configuration
+    /// or `text`.
+    #[structopt(long, requires = "check")]
+    format: Option<OutputFormat>,
+
+    /// Only add the crates that are missing in `workspace.members` and rewrite their manifests.
+    ///
+    /// All other manifests and the formatting of the member list are left untouched.
+    #[structopt(long)]
+    add_missing_only: bool,
+
+    /// Remove the `version` of the dev-dependencies between the crates that close a cycle.
+    ///
+    /// `cargo publish` requires every dependency with a `version` to be published first, so such
+    /// a cycle can not be published. Without `version` cargo ignores the dev-dependency when
+    /// publishing.
+    #[structopt(long)]
+    break_dev_cycles: bool,
+
+    /// How the paths of the dependencies are written, `relative`, `dot` or `from-root`.
+    ///
+    /// `relative` writes the shortest path like `../foo`, `dot` starts paths that don't leave
+    /// the directory with `./` and `from-root` goes up to the workspace root, e.g.
+    /// `../../crates/foo`. Defaults to the `path-style` of the configuration or `relative`.
+    /// Paths always use `/` as separator.
+    #[structopt(long)]
+    path_style: Option<PathStyle>,
+
+    /// What to do with crates that have the same name, `fail`, `suffix-dir`, `skip` or
+    /// `interactive`.
+    ///
+    /// The copy closest to the workspace root is kept. `suffix-dir` renames the other copies
+    /// by appending the names of their parent directories and sets `package` in the
+    /// dependents referring to them by path. `skip` leaves the other copies out of the
+    /// workspace. `interactive` asks which copy to keep and skips the others. Defaults to
+    /// `fail`.
+    #[structopt(long)]
+    dedupe_strategy: Option<DedupeStrategy>,
+
+    /// Declare every in-tree dependency once in `[workspace.dependencies]` and let the members
+    /// inherit it with `workspace = true`.
+    ///
+    /// Member specific keys like `features` and `optional` stay in the members.
+    #[structopt(long)]
+    inherit_deps: bool,
+
+    /// Workspacify every workspace below `--path` separately.
+    ///
+    /// Every directory with a `Cargo.toml` containing a `[workspace]` is a workspace root, the
+    /// crates below a nested root belong to it and not to the outer workspace.
+    #[structopt(long)]
+    all_workspaces: bool,
+
+    /// Write the name, manifest and version of every crate to `.diener/packages.json` in the
+    /// workspace.
+    #[structopt(long)]
+    export_packages: bool,
 }
 
 impl Workspacify {
-    pub fn run(self) -> Result<()> {
-        let workspace = self
-            .path
-            .map(Ok)
-            .unwrap_or_else(|| current_dir().with_context(|| "Working directory is invalid."))?;
-        ensure!(
-            workspace.is_dir(),
-            "Path '{}' is not a directory.",
-            workspace.display()
-        );
+    pub fn run(self, ctx: &mut RunContext) -> Result<()> {
+        let path_style = self
+            .path_style
+            .or(ctx.config.path_style())
+            .unwrap_or_default();
+        let check = self.check.then(|| {
+            self.format
+                .or(ctx.config.format())
+                .unwrap_or(OutputFormat::Text)
+        });
+        let workspace = ctx.workspace_dir(self.path.as_deref())?;
+
+        let roots = if self.all_workspaces {
+            let roots = walk::workspace_roots(ctx.changes.fs(), &workspace, &ctx.exclusions);
+            ensure!(
+                !roots.is_empty(),
+                "No workspace found in {}.",
+                workspace.display()
+            );
+            roots
+        } else {
+            vec![workspace]
+        };
+
+        for root in &roots {
+            // The crates of nested workspaces are not part of this one.
+            let nested = roots
+                .iter()
+                .filter(|r| *r != root && r.starts_with(root))
+                .cloned();
+            let exclusions = ctx.exclusions.clone().with_paths(nested);
+            let exclusions = std::mem::replace(&mut ctx.exclusions, exclusions);
+            let res = self.workspacify(root, path_style, ctx);
+            ctx.exclusions = exclusions;
+            res.with_context(|| anyhow!("Failed to workspacify {}", root.display()))?;
+        }
+
+        if self.all_workspaces {
+            ctx.changes.report_by_root(&roots);
+        }
+
+        match check {
+            Some(format) => ctx.changes.check(
+                format,
+                ctx.color,
+                "Manifest is not workspacified, run `diener workspacify`.",
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Workspacify the workspace at `workspace`.
+    fn workspacify(
+        &self,
+        workspace: &Path,
+        path_style: PathStyle,
+        ctx: &mut RunContext,
+    ) -> Result<()> {
+        let workspace = workspace.to_path_buf();
 
         // Create a mapping of package_name -> manifest
-        let mut packages = HashMap::<String, PathBuf>::new();
-        let mut duplicates = HashMap::<String, Vec<String>>::new();
-        for manifest in manifest_iter(&workspace) {
-            if let Some(name) = package_name(&manifest)? {
-                if let Some(existing) = packages.insert(name.clone(), manifest.clone()) {
-                    duplicates
-                        .entry(name)
-                        .or_insert_with(|| vec![existing.display().to_string()])
-                        .push(manifest.display().to_string());
-                }
+        let mut found = BTreeMap::<String, Vec<PathBuf>>::new();
+        for manifest in manifest_iter(ctx.changes.fs(), &workspace, &ctx.exclusions) {
+            if let Some(name) = package_name(ctx.changes.fs(), &manifest)? {
+                found.entry(name).or_default().push(manifest);
             }
         }
-        if !duplicates.is_empty() {
-            bail!("Duplicate crates detected:\n{:#?}", duplicates);
-        }
+        let packages = dedupe(
+            &workspace,
+            found,
+            self.dedupe_strategy.unwrap_or_default(),
+            ctx,
+        )?;
 
         // make sure all crates are recorded in the workspace manifest
-        update_workspace_members(&workspace, &packages)
-            .context("Failed to update member list in workspace manifest.")?;
+        let rewrite = if self.add_missing_only {
+            add_missing_members(&workspace, &packages, &mut ctx.changes)
+        } else {
+            update_workspace_members(&workspace, &packages, &mut ctx.changes)
+                .map(|_| packages.keys().cloned().collect())
+        }
+        .context("Failed to update member list in workspace manifest.")?;
 
         // transform every package manifest to point to the correct place
         // and use the correct version
-        for (name, path) in packages.iter() {
-            rewrite_manifest(path, &packages).with_context(|| {
-                anyhow!(
-                    "Failed to rewrite manifest for {} at {}",
-                    name,
-                    path.display()
-                )
-            })?;
+        for (name, path) in packages.iter().filter(|(name, _)| rewrite.contains(*name)) {
+            rewrite_manifest(path, &packages, (&workspace, path_style), &mut ctx.changes)
+                .with_context(|| {
+                    anyhow!(
+                        "Failed to rewrite manifest for {} at {}",
+                        name,
+                        path.display()
+                    )
+                })?;
+        }
+
+        if self.break_dev_cycles {
+            break_dev_cycles(&packages, &mut ctx.changes)?;
+        }
+
+        if self.inherit_deps {
+            workspace_deps::hoist(&workspace, 0, |usage| usage.path().is_some(), true, ctx)?;
+        }
+
+        if self.export_packages {
+            export_packages(&workspace, &packages, &mut ctx.changes)?;
         }
 
         Ok(())
     }
 }
 
-fn manifest_iter(workspace: &Path) -> impl Iterator<Item = PathBuf> {
-    WalkDir::new(workspace)
-        .follow_links(false)
+/// Write the manifest, relative to `workspace`, and the version of every package to
+/// [`PACKAGES_FILE`].
+fn export_packages(
+    workspace: &Path,
+    packages: &HashMap<String, PathBuf>,
+    changes: &mut Changes,
+) -> Result<()> {
+    let root = workspace.join("Cargo.toml");
+    let workspace_version = changes.read_toml(&root).ok().and_then(|doc| {
+        doc.get("workspace")?
+            .get("package")?
+            .get("version")?
+            .as_str()
+            .map(ToString::to_string)
+    });
+
+    let mut exported = BTreeMap::new();
+    for (name, manifest) in packages {
+        let doc = changes.read_toml(manifest)?;
+        let version = match doc.get("package").and_then(|p| p.get("version")) {
+            Some(v) if v.as_str().is_some() => v.as_str().map(ToString::to_string),
+            // `version.workspace = true`
+            Some(v) if v.get("workspace").and_then(|w| w.as_bool()) == Some(true) => {
+                workspace_version.clone()
+            }
+            _ => None,
+        };
+
+        exported.insert(
+            name.clone(),
+            serde_json::json!({
+                "manifest": manifest_path(manifest.strip_prefix(workspace).unwrap_or(manifest)),
+                "version": version,
+            }),
+        );
+    }
+
+    let file = workspace.join(PACKAGES_FILE);
+    log::info!("Writing {} packages to {}.", exported.len(), file.display());
+    changes.write(file, serde_json::to_string_pretty(&exported)? + "\n");
+    Ok(())
+}
+
+/// Resolve the crates in `found` that have multiple manifests using `strategy`.
+///
+/// Returns the mapping of package name to manifest of all crates that are part of the
+/// workspace.
+fn dedupe(
+    workspace: &Path,
+    found: BTreeMap<String, Vec<PathBuf>>,
+    strategy: DedupeStrategy,
+    ctx: &mut RunContext,
+) -> Result<HashMap<String, PathBuf>> {
+    let names = found.keys().cloned().collect::<HashSet<_>>();
+    let (mut packages, duplicates): (HashMap<_, _>, BTreeMap<_, _>) = {
+        let (unique, duplicates): (BTreeMap<_, _>, BTreeMap<_, _>) =
+            found.into_iter().partition(|(_, m)| m.len() == 1);
+        let unique = unique
+            .into_iter()
+            .map(|(name, mut manifests)| (name, manifests.remove(0)))
+            .collect();
+        (unique, duplicates)
+    };
+
+    if strategy == DedupeStrategy::Fail && !duplicates.is_empty() {
+        let duplicates = duplicates
+            .into_iter()
+            .map(|(name, m)| (name, m.iter().map(|m| m.display().to_string()).collect()))
+            .collect::<BTreeMap<_, Vec<_>>>();
+        bail!("Duplicate crates detected:\n{:#?}", duplicates);
+    }
+
+    for (name, mut manifests) in duplicates {
+        manifests.sort_by_key(|m| (m.components().count(), m.clone()));
+
+        if strategy == DedupeStrategy::Interactive {
+            let keep = ask_copy_to_keep(&name, &manifests)?;
+            let keep = manifests.remove(keep);
+            manifests.insert(0, keep);
+        }
+
+        let mut manifests = manifests.into_iter();
+        let keep = manifests
+            .next()
+            .expect("Duplicates have multiple manifests; qed");
+        log::info!("Keeping `{}` at {}.", name, keep.display());
+        packages.insert(name.clone(), keep);
+
+        for manifest in manifests {
+            if strategy != DedupeStrategy::SuffixDir {
+                log::info!("Skipping `{}` at {}.", name, manifest.display());
+                continue;
+            }
+
+            let new = suffixed_name(workspace, &name, &manifest, |n| {
+                packages.contains_key(n) || names.contains(n)
+            });
+            rename_crate::rename_copy(workspace, &manifest, &name, &new, ctx)?;
+            packages.insert(new, manifest);
+        }
+    }
+
+    Ok(packages)
+}
+
+/// Returns `name` with the names of the parent directories of the crate at `manifest`
+/// appended, as many as required for a name that isn't `taken`.
+fn suffixed_name(
+    workspace: &Path,
+    name: &str,
+    manifest: &Path,
+    taken: impl Fn(&str) -> bool,
+) -> String {
+    let crate_dir = manifest.parent().expect(FILES_HAVE_PARENTS);
+    let parents = crate_dir
+        .strip_prefix(workspace)
+        .unwrap_or(crate_dir)
+        .parent()
         .into_iter()
-        .filter_entry(|e| {
-            !(e.file_name() == "target" || e.file_name().to_string_lossy().starts_with('.'))
+        .flat_map(|p| p.components().rev())
+        .map(|c| {
+            c.as_os_str()
+                .to_string_lossy()
+                .replace(['.', ' ', '_'], "-")
         })
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file() && e.file_name().to_string_lossy() == "Cargo.toml")
-        .map(|dir| dir.into_path())
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<_>>();
+
+    let mut new = name.to_string();
+    for parent in &parents {
+        new = format!("{new}-{parent}");
+        if !taken(&new) {
+            return new;
+        }
+    }
+
+    (2..)
+        .map(|i| format!("{new}-{i}"))
+        .find(|n| !taken(n))
+        .expect("There is always a free name; qed")
 }
 
-fn package_name(path: &Path) -> Result<Option<String>> {
-    let ret = read_toml(path, false)?
+/// Ask on stdin which of the `manifests` of `name` should be kept, returns its index.
+fn ask_copy_to_keep(name: &str, manifests: &[PathBuf]) -> Result<usize> {
+    println!("Found multiple crates named `{name}`:");
+    for (i, manifest) in manifests.iter().enumerate() {
+        println!("  {}: {}", i + 1, manifest.display());
+    }
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("Which one should be kept? [1-{}] ", manifests.len());
+        io::stdout().flush()?;
+
+        let Some(line) = lines.next().transpose()? else {
+            bail!("No copy of `{}` was chosen.", name);
+        };
+        match line.trim().parse::<usize>() {
+            Ok(i) if (1..=manifests.len()).contains(&i) => return Ok(i - 1),
+            _ => println!("Please enter a number between 1 and {}.", manifests.len()),
+        }
+    }
+}
+
+pub(crate) fn manifest_iter(
+    fs: &dyn Fs,
+    workspace: &Path,
+    exclusions: &Exclusions,
+) -> impl Iterator<Item = PathBuf> {
+    walk::manifests(fs, workspace, false, exclusions)
+}
+
+pub(crate) fn package_name(fs: &dyn Fs, path: &Path) -> Result<Option<String>> {
+    let content = fs
+        .read(path)
+        .with_context(|| anyhow!("Failed to to read manifest at: {}", path.display()))?;
+    let ret = changes::parse_toml(path, &content)?
         .get("package")
         .and_then(|p| p.as_table())
         .and_then(|p| p.get("name"))
@@ -94,7 +457,71 @@ fn package_name(path: &Path) -> Result<Option<String>> {
     Ok(ret)
 }
 
-fn update_workspace_members(workspace: &Path, packages: &HashMap<String, PathBuf>) -> Result<()> {
+/// Returns the entry of `workspace.members` for the package at `manifest`.
+fn member_path(workspace: &Path, manifest: &Path) -> String {
+    let member = manifest
+        .parent()
+        .expect(FILES_HAVE_PARENTS)
+        .strip_prefix(workspace)
+        .expect("All packages are within the workspace root dir; qed");
+    manifest_path(member)
+}
+
+/// Append the `packages` that are not matched by `workspace.members` yet to it.
+///
+/// Returns the names of the appended packages. Falls back to [`update_workspace_members`] if
+/// there is no member list yet.
+fn add_missing_members(
+    workspace: &Path,
+    packages: &HashMap<String, PathBuf>,
+    changes: &mut Changes,
+) -> Result<HashSet<String>> {
+    let manifest = workspace.join("Cargo.toml");
+    let mut toml = if changes.exists(&manifest) {
+        changes
+            .read_toml(&manifest)
+            .context("Failed to parse workspace manifest")?
+    } else {
+        Document::new()
+    };
+
+    let Some(members) = toml
+        .get_mut("workspace")
+        .and_then(|w| w.get_mut("members"))
+        .and_then(|m| m.as_array_mut())
+    else {
+        update_workspace_members(workspace, packages, changes)?;
+        return Ok(packages.keys().cloned().collect());
+    };
+
+    let existing = members
+        .iter()
+        .filter_map(|m| m.as_str())
+        .filter_map(|m| glob::Pattern::new(m.trim_end_matches('/')).ok())
+        .collect::<Vec<_>>();
+    let mut missing = packages
+        .iter()
+        .map(|(name, path)| (member_path(workspace, path), name))
+        .filter(|(member, _)| !existing.iter().any(|p| p.matches(member)))
+        .collect::<Vec<_>>();
+    missing.sort_unstable();
+
+    for (member, name) in &missing {
+        log::info!("Adding `{}` to the workspace members.", name);
+        push_formatted_like_last(members, member.as_str());
+    }
+
+    if !missing.is_empty() {
+        changes.write(manifest, toml.to_string());
+    }
+    Ok(missing.into_iter().map(|(_, name)| name.clone()).collect())
+}
+
+fn update_workspace_members(
+    workspace: &Path,
+    packages: &HashMap<String, PathBuf>,
+    changes: &mut Changes,
+) -> Result<()> {
     let manifest = workspace.join("Cargo.toml");
 
     // turn packages into a sorted array of pathes
@@ -104,14 +531,7 @@ fn update_workspace_members(workspace: &Path, packages: &HashMap<String, PathBuf
         let mut members: Array = members
             .iter()
             .map(|path| {
-                let member = path
-                    .parent()
-                    .expect(FILES_HAVE_PARENTS)
-                    .strip_prefix(workspace)
-                    .expect("All packages are within the workspace root dir; qed")
-                    .display()
-                    .to_string();
-                let mut formatted = Formatted::new(member);
+                let mut formatted = Formatted::new(member_path(workspace, path));
                 formatted.decor_mut().set_prefix("\n\t");
                 Value::String(formatted)
             })
@@ -121,39 +541,69 @@ fn update_workspace_members(workspace: &Path, packages: &HashMap<String, PathBuf
         members
     };
 
-    let mut toml = read_toml(&manifest, true).context("Failed to parse workspace manifest")?;
+    let mut toml = if changes.exists(&manifest) {
+        changes
+            .read_toml(&manifest)
+            .context("Failed to parse workspace manifest")?
+    } else {
+        Document::new()
+    };
     toml.entry("workspace")
         .or_insert(Item::Table(Table::new()))
         .as_table_mut()
         .ok_or_else(|| anyhow!("`workspace` is not a table"))?
         .insert("members", value(members));
 
-    fs::write(&manifest, toml.to_string()).context("Failed to write workspace manifest")
+    changes.write(manifest, toml.to_string());
+    Ok(())
 }
 
-fn rewrite_manifest(path: &Path, packages: &HashMap<String, PathBuf>) -> Result<()> {
-    let mut toml = read_toml(path, false)?;
+/// `style` is the workspace root and the style of the written paths.
+fn rewrite_manifest(
+    path: &Path,
+    packages: &HashMap<String, PathBuf>,
+    style: (&Path, PathStyle),
+    changes: &mut Changes,
+) -> Result<()> {
+    let mut toml = changes.read_toml(path)?;
 
     toml.iter_mut()
         .filter(|(k, _)| k.contains("dependencies"))
         .filter_map(|(_, v)| v.as_table_mut())
         .flat_map(|deps| deps.iter_mut())
-        .filter_map(|dep| dep.1.as_inline_table_mut().map(|v| (dep.0, v)))
-        .try_for_each(|dep| handle_dep((dep.0, dep.1, path), packages))?;
+        .try_for_each(|(key, item)| {
+            if dependency_mut(item).is_none() {
+                return Ok(());
+            }
+            if handle_dep((key, item, path), packages, style)? {
+                sort_dep_keys(item);
+            }
+            Ok::<_, anyhow::Error>(())
+        })?;
 
-    fs::write(path, toml.to_string())
-        .with_context(|| anyhow!("Failed to write manifest to {}", path.display()))
+    changes.write(path, toml.to_string());
+    Ok(())
 }
 
+/// Point the dependency to the package in the workspace, if it is part of it.
+///
+/// Renamed dependencies are looked up by the name of their package and keep their alias and
+/// `package` key. Dependencies inherited from the workspace are left alone, `path` can't be
+/// combined with `workspace = true`.
+///
+/// Returns `true` if the dependency was changed.
 fn handle_dep(
-    dep: (KeyMut, &mut InlineTable, &Path),
+    dep: (KeyMut, &mut Item, &Path),
     packages: &HashMap<String, PathBuf>,
-) -> Result<()> {
-    let name = dep
-        .1
-        .get("package")
-        .and_then(|p| p.as_str())
-        .unwrap_or_else(|| dep.0.get());
+    (workspace, style): (&Path, PathStyle),
+) -> Result<bool> {
+    let Some(table) = dep.1.as_table_like() else {
+        return Ok(false);
+    };
+    if table.get("workspace").and_then(|w| w.as_bool()) == Some(true) {
+        return Ok(false);
+    }
+    let name = dependency_package(dep.0.get(), table);
 
     // dependency exists within this workspace
     let (dependee, dependency) = if let Some(path) = packages.get(name) {
@@ -161,44 +611,177 @@ fn handle_dep(
         let dependency = dep.2.parent().expect(FILES_HAVE_PARENTS);
         (dependee, dependency)
     } else {
-        return Ok(());
+        return Ok(false);
     };
 
     // path in manifests are relative
-    let relpath = pathdiff::diff_paths(dependee, dependency).ok_or_else(|| {
+    let relpath = relative_path(dependee, dependency, workspace, style)?;
+    editor::to_path_dep(dep.1, &relpath);
+
+    Ok(true)
+}
+
+/// Returns the path of `dependee` relative to `dependency` in the given `style`.
+fn relative_path(
+    dependee: &Path,
+    dependency: &Path,
+    workspace: &Path,
+    style: PathStyle,
+) -> Result<PathBuf> {
+    let cannot = || {
         anyhow!(
             "Cannot make {} relative to {}",
             dependee.display(),
             dependency.display()
         )
-    })?;
-    dep.1.remove("git");
-    dep.1.remove("branch");
-    dep.1.remove("version");
-    dep.1
-        .insert("path", Value::from(relpath.to_string_lossy().as_ref()));
-    dep.1
-        .sort_values_by(|k0, _, k1, _| dep_key_order(k0).cmp(&dep_key_order(k1)));
+    };
+
+    let relpath = match style {
+        PathStyle::FromRoot => {
+            let up = dependency.strip_prefix(workspace).map_err(|_| cannot())?;
+            let down = dependee.strip_prefix(workspace).map_err(|_| cannot())?;
+            up.components()
+                .map(|_| Path::new(".."))
+                .collect::<PathBuf>()
+                .join(down)
+        }
+        PathStyle::Relative | PathStyle::Dot => {
+            pathdiff::diff_paths(dependee, dependency).ok_or_else(cannot)?
+        }
+    };
+
+    if style == PathStyle::Dot && !relpath.starts_with("..") {
+        Ok(Path::new(".").join(relpath))
+    } else {
+        Ok(relpath)
+    }
+}
+
+/// A dependency between two crates of the workspace that has a `version`.
+struct Edge {
+    from: String,
+    to: String,
+    /// The key of the dependency in the `dev-dependencies`, `None` for other dependencies.
+    dev_key: Option<String>,
+    active: bool,
+}
+
+/// Remove the `version` of every dev-dependency that closes a cycle of dependencies with a
+/// `version` between the `packages`.
+///
+/// The dev-dependencies are cut one after another in a stable order, until no cycle is left.
+fn break_dev_cycles(packages: &HashMap<String, PathBuf>, changes: &mut Changes) -> Result<()> {
+    let mut edges = Vec::new();
+    let mut names = packages.iter().collect::<Vec<_>>();
+    names.sort_unstable();
+    for (name, path) in names {
+        let toml = changes.read_toml(path)?;
+        for (table, deps) in toml
+            .iter()
+            .filter(|(k, _)| k.contains("dependencies"))
+            .filter_map(|(k, v)| Some((k, v.as_table_like()?)))
+        {
+            for (key, dep) in deps.iter() {
+                let Some(dep) = dep.as_table_like().filter(|d| d.contains_key("version")) else {
+                    continue;
+                };
+                let to = dependency_package(key, dep);
+                if packages.contains_key(to) {
+                    edges.push(Edge {
+                        from: name.clone(),
+                        to: to.to_string(),
+                        dev_key: (table == "dev-dependencies").then(|| key.to_string()),
+                        active: true,
+                    });
+                }
+            }
+        }
+    }
+
+    for i in 0..edges.len() {
+        if edges[i].dev_key.is_none() || !reaches(&edges, i, &edges[i].to, &edges[i].from) {
+            continue;
+        }
+
+        let Edge { from, dev_key, .. } = &edges[i];
+        let dev_key = dev_key
+            .as_deref()
+            .expect("Checked above to be a dev-dependency; qed");
+        let path = &packages[from];
+        let mut toml = changes.read_toml(path)?;
+        let Some(dep) = toml
+            .get_mut("dev-dependencies")
+            .and_then(|d| d.get_mut(dev_key))
+            .filter(|d| d.get("version").is_some())
+        else {
+            continue;
+        };
+        editor::remove_key(dep, "version");
+        log::info!(
+            "Removed the `version` of the dev-dependency `{}` of `{}`, it closes a cycle.",
+            dev_key,
+            from
+        );
+        changes.write(path, toml.to_string());
+        edges[i].active = false;
+    }
 
     Ok(())
 }
 
-fn read_toml(path: &Path, create: bool) -> Result<Document> {
-    let mut content = String::new();
-    OpenOptions::new()
-        .read(true)
-        .create(create)
-        .write(create)
-        .open(path)
-        .with_context(|| anyhow!("Failed to to open manifest at: {}", path.display()))?
-        .read_to_string(&mut content)
-        .with_context(|| anyhow!("Failed to to read manifest at: {}", path.display()))?;
+/// Returns `true` if `to` can be reached from `from` by the active `edges`, except `skip`.
+fn reaches(edges: &[Edge], skip: usize, from: &str, to: &str) -> bool {
+    let mut visited = HashSet::new();
+    let mut queue = vec![from];
+    while let Some(current) = queue.pop() {
+        if current == to {
+            return true;
+        }
+        if !visited.insert(current) {
+            continue;
+        }
+        queue.extend(
+            edges
+                .iter()
+                .enumerate()
+                .filter(|(i, e)| *i != skip && e.active && e.from == current)
+                .map(|(_, e)| e.to.as_str()),
+        );
+    }
+
+    false
+}
+
+/// Sort the keys of the inline or dotted dependency `item` by [`dep_key_order`].
+pub(crate) fn sort_dep_keys(item: &mut Item) {
+    let order = |k0: &Key, k1: &Key| dep_key_order(k0).cmp(&dep_key_order(k1));
+    match item {
+        Item::Value(Value::InlineTable(dep)) => {
+            // The space before the closing brace belongs to the last value, keep it at the end.
+            let trailing = dep
+                .iter()
+                .last()
+                .and_then(|(_, v)| v.decor().suffix()?.as_str().map(ToString::to_string));
+            dep.sort_values_by(|k0, _, k1, _| order(k0, k1));
 
-    Document::from_str(&content)
-        .with_context(|| anyhow!("Failed to to parse manifest at: {}", path.display()))
+            if let Some(trailing) = trailing {
+                for (_, v) in dep.iter_mut() {
+                    if v.decor().suffix().and_then(|s| s.as_str()) == Some(&trailing) {
+                        v.decor_mut().set_suffix("");
+                    }
+                }
+                if let Some((_, last)) = dep.iter_mut().last() {
+                    last.decor_mut().set_suffix(trailing);
+                }
+            }
+        }
+        Item::Table(dep) => dep.sort_values_by(|k0, _, k1, _| order(k0, k1)),
+        _ => {}
+    }
 }
 
-fn dep_key_order(dep_key: &str) -> u32 {
+/// The position of `dep_key` in the canonical order of the keys of a dependency.
+pub(crate) fn dep_key_order(dep_key: &str) -> u32 {
     match dep_key {
         "package" => 0,
 
@@ -218,3 +801,81 @@ fn dep_key_order(dep_key: &str) -> u32 {
         _ => u32::MAX,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::MemoryFs;
+    use std::sync::Arc;
+
+    #[test]
+    fn dev_cycles_are_broken_in_all_forms() {
+        let fs = MemoryFs::new()
+            .with_file(
+                "/ws/a/Cargo.toml",
+                "[package]\nname = \"a\"\n\n[dependencies]\nb = { path = \"../b\", version = \"0.1\" }\n",
+            )
+            .with_file(
+                "/ws/b/Cargo.toml",
+                "[package]\nname = \"b\"\n\n[dependencies]\nc = { path = \"../c\", version = \"0.1\" }\n\n\
+                 [dev-dependencies.a]\npath = \"../a\"\nversion = \"0.1\"\n",
+            )
+            .with_file(
+                "/ws/c/Cargo.toml",
+                "[package]\nname = \"c\"\n\n[dev-dependencies]\nb = { path = \"../b\", version = \"0.1\" }\n",
+            );
+        let mut changes = Changes::new(Arc::new(fs));
+        let packages = ["a", "b", "c"]
+            .into_iter()
+            .map(|n| (n.to_string(), PathBuf::from(format!("/ws/{n}/Cargo.toml"))))
+            .collect();
+
+        break_dev_cycles(&packages, &mut changes).unwrap();
+
+        let b = changes.read(Path::new("/ws/b/Cargo.toml")).unwrap();
+        assert!(
+            b.ends_with("[dev-dependencies.a]\npath = \"../a\"\n"),
+            "{b}"
+        );
+        let c = changes.read(Path::new("/ws/c/Cargo.toml")).unwrap();
+        assert!(c.ends_with("b = { path = \"../b\" }\n"), "{c}");
+    }
+
+    #[test]
+    fn aliased_dependencies_are_found_by_their_package() {
+        let fs = MemoryFs::new().with_file(
+            "/ws/app/Cargo.toml",
+            "[package]\nname = \"app\"\n\n[dependencies]\n\
+             codec = { package = \"parity-scale-codec\", version = \"3.6\", default-features = false }\n\
+             core.package = \"sp-core\"\n\
+             core.git = \"https://github.com/paritytech/polkadot-sdk\"\n\
+             io = { package = \"sp-io\", workspace = true }\n\
+             sp-runtime = { package = \"sp-runtime-fork\", version = \"1\" }\n",
+        );
+        let mut changes = Changes::new(Arc::new(fs));
+        let packages = ["parity-scale-codec", "sp-core", "sp-io", "sp-runtime"]
+            .into_iter()
+            .map(|n| (n.to_string(), PathBuf::from(format!("/ws/{n}/Cargo.toml"))))
+            .collect();
+
+        let manifest = Path::new("/ws/app/Cargo.toml");
+        rewrite_manifest(
+            manifest,
+            &packages,
+            (Path::new("/ws"), PathStyle::Relative),
+            &mut changes,
+        )
+        .unwrap();
+
+        let app = changes.read(manifest).unwrap();
+        assert_eq!(
+            app,
+            "[package]\nname = \"app\"\n\n[dependencies]\n\
+             codec = { package = \"parity-scale-codec\", path = \"../parity-scale-codec\", default-features = false }\n\
+             core.package = \"sp-core\"\n\
+             core.path = \"../sp-core\"\n\
+             io = { package = \"sp-io\", workspace = true }\n\
+             sp-runtime = { package = \"sp-runtime-fork\", version = \"1\" }\n",
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! Tests of the output formats of `--check`, run against the `diener` binary.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// A manifest depending on the Polkadot SDK at branch `master`.
+const MANIFEST: &str = r#"[package]
+name = "runtime"
+version = "0.1.0"
+
+[dependencies]
+sp-core = { git = "https://github.com/paritytech/polkadot-sdk", branch = "master" }
+"#;
+
+const MESSAGE: &str = "Dependencies are not up to date, run `diener update`.";
+
+/// Create the directory `name` with the manifest [`MANIFEST`].
+fn workspace(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("check-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("Cargo.toml"), MANIFEST).unwrap();
+    dir
+}
+
+/// Run `diener update --check --branch <branch> --format <format>` in `dir`.
+///
+/// Returns the exit code and the output, after making sure that the manifest is unchanged.
+fn check(dir: &Path, branch: &str, format: &str) -> (i32, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_diener"))
+        .args(["update", "--check", "--branch", branch, "--format", format])
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.join("Cargo.toml")).unwrap(),
+        MANIFEST
+    );
+    fs::remove_dir_all(dir).unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn text_prints_the_diff() {
+    let dir = workspace("text");
+    let (code, output) = check(&dir, "stable2407", "text");
+    assert_eq!(code, 3);
+    let manifest = dir.join("Cargo.toml").display().to_string();
+    assert!(output.starts_with(&format!("--- {manifest}\n+++ {manifest}\n")));
+    assert!(output.contains("\n-sp-core = { git = \"https://github.com/paritytech/polkadot-sdk\", branch = \"master\" }\n"));
+    assert!(output.contains("\n+sp-core = { git = \"https://github.com/paritytech/polkadot-sdk\", branch = \"stable2407\" }\n"));
+}
+
+#[test]
+fn json_lists_the_changed_lines() {
+    let dir = workspace("json");
+    let (code, output) = check(&dir, "stable2407", "json");
+    assert_eq!(code, 3);
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&output).unwrap(),
+        serde_json::json!([{
+            "path": dir.join("Cargo.toml"),
+            "lines": [[6, 6]],
+            "message": MESSAGE,
+        }])
+    );
+}
+
+#[test]
+fn github_emits_annotations_relative_to_the_working_directory() {
+    let dir = workspace("github");
+    let (code, output) = check(&dir, "stable2407", "github");
+    assert_eq!(code, 3);
+    assert_eq!(
+        output,
+        format!("::warning file=Cargo.toml,line=6,endLine=6::{MESSAGE}\n")
+    );
+}
+
+#[test]
+fn up_to_date_manifests_pass() {
+    let dir = workspace("up-to-date-text");
+    assert_eq!(check(&dir, "master", "text"), (0, String::new()));
+    let dir = workspace("up-to-date-json");
+    assert_eq!(check(&dir, "master", "json"), (0, "[]\n".into()));
+    let dir = workspace("up-to-date-github");
+    assert_eq!(check(&dir, "master", "github"), (0, String::new()));
+}
@@ -0,0 +1,67 @@
+//! Tests of dependencies declared with dotted keys, run through the `Engine` on a `MemoryFs`.
+
+use diener::{Engine, MemoryFs, Operation, Reference};
+use std::{path::Path, sync::Arc};
+
+/// A manifest declaring its SDK dependencies with dotted keys at the top level and in a table.
+const MANIFEST: &str = r#"package.name = "runtime"
+package.version = "0.1.0"
+dependencies.sp-core.git = "https://github.com/paritytech/polkadot-sdk"
+dependencies.sp-core.branch = "master"
+
+[dev-dependencies]
+sp-io.git = "https://github.com/paritytech/polkadot-sdk"
+sp-io.branch = "master"
+sp-io.default-features = false
+"#;
+
+/// Run `operation` in a workspace with the given `files` and return the content of `manifest`.
+fn run(files: &[(&str, &str)], operation: Operation, manifest: &str) -> String {
+    let fs = files.iter().fold(MemoryFs::new(), |fs, (path, content)| {
+        fs.with_file(*path, *content)
+    });
+    let changes = Engine::with_fs("/ws", Arc::new(fs)).run(operation).unwrap();
+    let (_, _, content) = changes
+        .files()
+        .find(|(path, _, _)| *path == Path::new(manifest))
+        .unwrap();
+    content.to_string()
+}
+
+#[test]
+fn update_keeps_the_dotted_keys() {
+    let manifest = run(
+        &[("/ws/Cargo.toml", MANIFEST)],
+        Operation::Update {
+            paths: Vec::new(),
+            reference: Reference::Tag("polkadot-stable2407".into()),
+            git: None,
+        },
+        "/ws/Cargo.toml",
+    );
+    assert_eq!(
+        manifest,
+        MANIFEST.replace("branch = \"master\"", "tag = \"polkadot-stable2407\"")
+    );
+}
+
+#[test]
+fn workspacify_keeps_the_dotted_keys() {
+    let member = "[package]\nname = \"b\"\nversion = \"0.1.0\"\n\n[dependencies]\na.version = \"0.1\"\na.default-features = false\n";
+    let manifest = run(
+        &[
+            ("/ws/Cargo.toml", "[workspace]\nmembers = [\"a\", \"b\"]\n"),
+            (
+                "/ws/a/Cargo.toml",
+                "[package]\nname = \"a\"\nversion = \"0.1.0\"\n",
+            ),
+            ("/ws/b/Cargo.toml", member),
+        ],
+        Operation::Workspacify { path: None },
+        "/ws/b/Cargo.toml",
+    );
+    assert_eq!(
+        manifest,
+        member.replace("a.version = \"0.1\"", "a.path = \"../a\"")
+    );
+}
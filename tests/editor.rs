@@ -0,0 +1,208 @@
+//! Snapshot tests of `ManifestEditor` on manifests of the Polkadot SDK and of projects using it.
+//!
+//! Every test edits a manifest of `tests/manifests` and compares the result with the snapshot of
+//! the same name in `tests/snapshots`. Run with `DIENER_BLESS=1` to write the snapshots after
+//! an intended change of the formatting, and review their diff.
+
+use diener::{ManifestEditor, PatchSource, Reference};
+use std::{fs, path::Path};
+use toml_edit::Document;
+
+/// Parse the manifest `name` of `tests/manifests`.
+fn manifest(name: &str) -> (ManifestEditor, Document) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/manifests")
+        .join(format!("{name}.toml"));
+    let content = fs::read_to_string(&path).unwrap();
+    (
+        ManifestEditor::parse(&content).unwrap(),
+        content.parse().unwrap(),
+    )
+}
+
+/// Compare `editor` with the snapshot `name`.
+fn assert_snapshot(name: &str, editor: &ManifestEditor) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.toml"));
+    let actual = editor.to_string();
+    if std::env::var_os("DIENER_BLESS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("No snapshot {}, run with `DIENER_BLESS=1`", path.display()));
+    assert!(
+        actual == expected,
+        "{} differs from the snapshot:\n{actual}",
+        path.display()
+    );
+}
+
+/// Returns the table and key of all dependencies of the table `table` of `doc` that have a `git`
+/// url containing `repository`.
+fn git_dependencies(doc: &Document, table: &str, repository: &str) -> Vec<(String, String)> {
+    let deps = table
+        .split('.')
+        .try_fold(doc.as_item(), |item, key| item.get(key));
+    deps.and_then(|deps| deps.as_table_like())
+        .into_iter()
+        .flat_map(|deps| deps.iter())
+        .filter(|(_, dep)| {
+            dep.get("git")
+                .and_then(|g| g.as_str())
+                .is_some_and(|g| g.contains(repository))
+        })
+        .map(|(key, _)| (table.to_string(), key.to_string()))
+        .collect()
+}
+
+#[test]
+fn parachain_node_to_tag() {
+    let (mut editor, doc) = manifest("parachain-template-node");
+    let reference = Reference::Tag("polkadot-stable2409".into());
+    for table in ["dependencies", "build-dependencies"] {
+        for (table, key) in git_dependencies(&doc, table, "polkadot-sdk") {
+            editor.set_reference(&table, &key, &reference).unwrap();
+        }
+    }
+    assert_snapshot("parachain-template-node.tag", &editor);
+}
+
+#[test]
+fn parachain_node_to_local_checkout() {
+    let (mut editor, _) = manifest("parachain-template-node");
+    for (key, path) in [
+        ("sp-core", "../../polkadot-sdk/substrate/primitives/core"),
+        ("sp-io", "../../polkadot-sdk/substrate/primitives/io"),
+        ("xcm", "../../polkadot-sdk/polkadot/xcm"),
+        ("log", "../../log"),
+    ] {
+        editor
+            .to_path_dep("dependencies", key, path.as_ref())
+            .unwrap();
+    }
+    assert_snapshot("parachain-template-node.local", &editor);
+}
+
+#[test]
+fn workspace_to_rev_with_patches() {
+    let (mut editor, doc) = manifest("parachain-workspace");
+    let reference = Reference::Rev("8c8bb1371e1d97b6e872cc8bd871fcad9649c1d6".into());
+    for (table, key) in git_dependencies(&doc, "workspace.dependencies", "polkadot-sdk") {
+        editor.set_reference(&table, &key, &reference).unwrap();
+    }
+    editor
+        .add_patch_entry(
+            "https://github.com/paritytech/polkadot-sdk",
+            "sp-core",
+            &PatchSource::Path("../polkadot-sdk/substrate/primitives/core".into()),
+        )
+        .unwrap();
+    editor
+        .add_patch_entry(
+            "crates-io",
+            "parity-scale-codec",
+            &PatchSource::Git {
+                url: "https://github.com/paritytech/parity-scale-codec".into(),
+                reference: Reference::Branch("master".into()),
+            },
+        )
+        .unwrap();
+    assert_snapshot("parachain-workspace.rev", &editor);
+}
+
+#[test]
+fn workspace_dependency_keys() {
+    let (mut editor, _) = manifest("parachain-workspace");
+    let table = "workspace.dependencies";
+    editor
+        .set_git(
+            table,
+            "pallet-aura",
+            "https://github.com/example/polkadot-sdk",
+        )
+        .unwrap();
+    editor
+        .set_key(
+            table,
+            "smallvec",
+            "features",
+            toml_edit::Value::from_iter(["union"]),
+        )
+        .unwrap();
+    editor
+        .remove_key(table, "codec", "default-features")
+        .unwrap();
+    editor
+        .set_package(table, "hex-literal", "hex-literal")
+        .unwrap();
+    editor
+        .rename_dependency(table, "xcm", "staging-xcm")
+        .unwrap();
+    assert_snapshot("parachain-workspace.keys", &editor);
+}
+
+#[test]
+fn pallet_to_versions() {
+    let (mut editor, _) = manifest("pallet-balances");
+    for (table, key, version) in [
+        ("dependencies", "frame-benchmarking", "38.0.0"),
+        ("dependencies", "frame-support", "38.0.0"),
+        ("dependencies", "frame-system", "38.0.0"),
+        ("dependencies", "sp-runtime", "39.0.1"),
+        ("dev-dependencies", "sp-core", "34.0.0"),
+    ] {
+        editor.to_version_dep(table, key, version).unwrap();
+    }
+    assert_snapshot("pallet-balances.versions", &editor);
+}
+
+#[test]
+fn runtime_to_polkadot_sdk() {
+    let (mut editor, doc) = manifest("node-template-runtime");
+    for table in ["dependencies", "build-dependencies"] {
+        for (table, key) in git_dependencies(&doc, table, "substrate") {
+            editor
+                .set_git(&table, &key, "https://github.com/paritytech/polkadot-sdk")
+                .unwrap();
+            editor.set_branch(&table, &key, "stable2407").unwrap();
+            editor.remove_key(&table, &key, "version").unwrap();
+        }
+    }
+    editor
+        .to_git_dep(
+            "dependencies",
+            "scale-info",
+            "https://github.com/paritytech/scale-info",
+            &Reference::Tag("v2.11.3".into()),
+        )
+        .unwrap();
+    assert_snapshot("node-template-runtime.polkadot-sdk", &editor);
+}
+
+#[test]
+fn unknown_dependency_is_an_error() {
+    let (mut editor, _) = manifest("pallet-balances");
+    assert!(editor
+        .set_branch("dependencies", "sp-core", "master")
+        .is_err());
+    assert!(editor
+        .rename_dependency("build-dependencies", "frame-support", "support")
+        .is_err());
+}
+
+#[test]
+fn unmodified_manifests_are_unchanged() {
+    for name in [
+        "node-template-runtime",
+        "pallet-balances",
+        "parachain-template-node",
+        "parachain-workspace",
+    ] {
+        let (editor, doc) = manifest(name);
+        assert_eq!(editor.to_string(), doc.to_string(), "{name}");
+    }
+}
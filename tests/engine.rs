@@ -0,0 +1,151 @@
+//! Tests of the `Engine`, run against workspaces in the temporary directory or in a `MemoryFs`.
+
+use diener::{Engine, MemoryFs, Operation, Reference};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const MANIFEST: &str = r#"[package]
+name = "project"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+sp-core = { git = "https://github.com/paritytech/polkadot-sdk", branch = "master" }
+"#;
+
+/// Create a project with the given `manifest` and return its directory.
+fn workspace(name: &str, manifest: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("engine-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+    dir
+}
+
+fn update(branch: &str) -> Operation {
+    Operation::Update {
+        paths: Vec::new(),
+        reference: Reference::Branch(branch.into()),
+        git: None,
+    }
+}
+
+#[test]
+fn update_ignores_the_environment() {
+    std::env::set_var("DIENER_PATH", "/does/not/exist");
+    std::env::set_var("DIENER_GIT", "https://example.com/polkadot-sdk");
+    std::env::set_var("DIENER_MATCH_REPO", "frontier");
+    let dir = workspace("environment", MANIFEST);
+
+    let changes = Engine::new(&dir)
+        .unwrap()
+        .run(update("stable2407"))
+        .unwrap();
+    let files = changes.files().collect::<Vec<_>>();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        files,
+        [(
+            &*dir.join("Cargo.toml"),
+            Some(MANIFEST),
+            &*MANIFEST.replace("master", "stable2407")
+        )]
+    );
+}
+
+#[test]
+fn failures_are_the_source_of_the_error() {
+    let dir = workspace(
+        "failure",
+        &MANIFEST.replace("polkadot-sdk\",", "polkadot-sdk\""),
+    );
+
+    let Err(error) = Engine::new(&dir).unwrap().run(update("stable2407")) else {
+        panic!("Updating a broken manifest succeeded");
+    };
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(error.to_string(), "The operation failed");
+    let source = error.source().unwrap().to_string();
+    assert!(source.starts_with("Failed to parse manifest"), "{source}");
+}
+
+#[test]
+fn update_resolves_glob_paths_on_the_filesystem_of_the_engine() {
+    let fs = Arc::new(
+        MemoryFs::new()
+            .with_file("/ws/runtimes/a/Cargo.toml", MANIFEST)
+            .with_file("/ws/runtimes/b/Cargo.toml", MANIFEST)
+            .with_file("/ws/node/Cargo.toml", MANIFEST),
+    );
+    let mut engine = Engine::with_fs("/ws", fs);
+
+    let changes = engine
+        .run(Operation::Update {
+            paths: vec!["runtimes/*".into()],
+            reference: Reference::Branch("stable2407".into()),
+            git: None,
+        })
+        .unwrap();
+    let files = changes.files().map(|(path, _, _)| path).collect::<Vec<_>>();
+    assert_eq!(
+        files,
+        [
+            Path::new("/ws/runtimes/a/Cargo.toml"),
+            Path::new("/ws/runtimes/b/Cargo.toml")
+        ]
+    );
+}
+
+#[test]
+fn change_sets_are_only_written_when_applied() {
+    let fs = Arc::new(MemoryFs::new().with_file("/ws/Cargo.toml", MANIFEST));
+    let mut engine = Engine::with_fs("/ws", fs.clone());
+
+    let changes = engine.run(update("stable2407")).unwrap();
+    assert!(!changes.is_empty());
+    assert!(changes.diff().contains("+sp-core = { git = \"https://github.com/paritytech/polkadot-sdk\", branch = \"stable2407\" }"));
+    assert_eq!(fs.files()[Path::new("/ws/Cargo.toml")], MANIFEST);
+
+    changes.apply().unwrap();
+    assert_eq!(
+        fs.files()[Path::new("/ws/Cargo.toml")],
+        MANIFEST.replace("master", "stable2407")
+    );
+    assert!(engine.run(update("stable2407")).unwrap().is_empty());
+}
+
+#[test]
+fn operations_are_read_from_json() {
+    let fs = Arc::new(MemoryFs::new().with_file("/ws/Cargo.toml", MANIFEST));
+    let operation = serde_json::from_str::<Operation>(
+        r#"{ "command": "set", "packages": ["sp-*"], "assignments": [["default-features", "false"]] }"#,
+    )
+    .unwrap();
+
+    let changes = Engine::with_fs("/ws", fs).run(operation).unwrap();
+    let (_, _, content) = changes.files().next().unwrap();
+    assert!(content.contains("default-features = false"), "{content}");
+}
+
+#[test]
+fn invalid_operations_are_rejected() {
+    let fs = Arc::new(MemoryFs::new().with_file("/ws/Cargo.toml", MANIFEST));
+    let operation = Operation::Set {
+        paths: Vec::new(),
+        packages: vec!["sp-[".into()],
+        assignments: vec![("default-features".into(), "false".into())],
+        remove: Vec::new(),
+    };
+
+    let Err(error) = Engine::with_fs("/ws", fs).run(operation) else {
+        panic!("An invalid glob pattern was accepted");
+    };
+    assert!(
+        matches!(error, diener::Error::InvalidOperation(_)),
+        "{error:?}"
+    );
+}
@@ -0,0 +1,101 @@
+//! Tests of the exit codes of the failure classes, run against the `diener` binary.
+
+use std::{fs, path::PathBuf, process::Command};
+
+/// A manifest depending on the Polkadot SDK at branch `master`.
+const MANIFEST: &str = r#"[package]
+name = "runtime"
+version = "0.1.0"
+
+[dependencies]
+sp-core = { git = "https://github.com/paritytech/polkadot-sdk", branch = "master" }
+"#;
+
+/// Create the directory `name` with the manifest `manifest`.
+fn workspace(name: &str, manifest: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("exit-codes-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+    dir
+}
+
+/// Run diener with `args` in `dir` and return its exit code.
+fn diener(dir: &PathBuf, args: &[&str], envs: &[(&str, &str)]) -> i32 {
+    let status = Command::new(env!("CARGO_BIN_EXE_diener"))
+        .args(args)
+        .envs(envs.iter().copied())
+        .current_dir(dir)
+        .status()
+        .unwrap();
+    fs::remove_dir_all(dir).unwrap();
+    status.code().unwrap()
+}
+
+#[test]
+fn success_exits_with_0() {
+    let dir = workspace("success", MANIFEST);
+    assert_eq!(diener(&dir, &["update", "--branch", "stable2407"], &[]), 0);
+}
+
+#[test]
+fn invalid_command_line_exits_with_2() {
+    let dir = workspace("usage", MANIFEST);
+    assert_eq!(diener(&dir, &["update", "--no-such-flag"], &[]), 2);
+}
+
+#[test]
+fn outdated_manifests_exit_with_3() {
+    let dir = workspace("check", MANIFEST);
+    assert_eq!(
+        diener(&dir, &["update", "--branch", "stable2407", "--check"], &[]),
+        3
+    );
+}
+
+#[test]
+fn unchanged_manifests_exit_with_4() {
+    let dir = workspace("unchanged", MANIFEST);
+    assert_eq!(
+        diener(
+            &dir,
+            &["update", "--branch", "master", "--fail-if-unchanged"],
+            &[]
+        ),
+        4
+    );
+}
+
+#[test]
+fn invalid_manifests_exit_with_5() {
+    let manifest = MANIFEST.replace("polkadot-sdk\",", "polkadot-sdk\"");
+    let dir = workspace("parse", &manifest);
+    assert_eq!(diener(&dir, &["update", "--branch", "stable2407"], &[]), 5);
+}
+
+#[test]
+fn network_access_with_offline_exits_with_6() {
+    let dir = workspace("offline", MANIFEST);
+    let rev = "8c8bb1371e1d97b6e872cc8bd871fcad9649c1d6";
+    assert_eq!(
+        diener(
+            &dir,
+            &[
+                "--offline",
+                "update",
+                "--rev",
+                rev,
+                "--verify-rev-on-branch",
+                "master"
+            ],
+            &[]
+        ),
+        6
+    );
+}
+
+#[test]
+fn failing_cargo_exits_with_7() {
+    let dir = workspace("cargo", MANIFEST);
+    assert_eq!(diener(&dir, &["verify"], &[("CARGO", "false")]), 7);
+}
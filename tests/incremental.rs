@@ -0,0 +1,87 @@
+//! Tests of `diener update --incremental`, run against the `diener` binary.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// A manifest depending on the Polkadot SDK at branch `master`.
+const MANIFEST: &str = r#"[package]
+name = "runtime"
+version = "0.1.0"
+
+[dependencies]
+sp-core = { git = "https://github.com/paritytech/polkadot-sdk", branch = "master" }
+"#;
+
+/// Create the directory `name` with the manifest [`MANIFEST`].
+fn workspace(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("incremental-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("Cargo.toml"), MANIFEST).unwrap();
+    dir
+}
+
+/// Run `diener [global] update --incremental --branch <branch>` in `dir` and return its log.
+fn update(dir: &Path, global: &[&str], branch: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_diener"))
+        .args(global)
+        .args(["update", "--incremental", "--branch", branch])
+        .env("RUST_LOG", "debug")
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    let log = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(output.status.success(), "{log}");
+    log
+}
+
+fn manifest(dir: &Path) -> String {
+    fs::read_to_string(dir.join("Cargo.toml")).unwrap()
+}
+
+const SKIPPED: &str = "it didn't change since the last run";
+
+#[test]
+fn unchanged_manifests_are_skipped() {
+    let dir = workspace("skipped");
+
+    assert!(!update(&dir, &[], "stable2407").contains(SKIPPED));
+    assert_eq!(manifest(&dir), MANIFEST.replace("master", "stable2407"));
+    assert!(update(&dir, &[], "stable2407").contains(SKIPPED));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn modified_manifests_are_processed_again() {
+    let dir = workspace("modified");
+
+    update(&dir, &[], "stable2407");
+    fs::write(dir.join("Cargo.toml"), MANIFEST).unwrap();
+    assert!(!update(&dir, &[], "stable2407").contains(SKIPPED));
+    assert_eq!(manifest(&dir), MANIFEST.replace("master", "stable2407"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn other_options_do_not_use_the_hashes() {
+    let dir = workspace("options");
+
+    update(&dir, &[], "stable2407");
+    assert!(!update(&dir, &[], "stable2409").contains(SKIPPED));
+    assert_eq!(manifest(&dir), MANIFEST.replace("master", "stable2409"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dry_runs_do_not_store_the_hashes() {
+    let dir = workspace("dry-run");
+
+    update(&dir, &["--dry-run"], "stable2407");
+    assert!(!dir.join(".diener").exists());
+    assert_eq!(manifest(&dir), MANIFEST);
+    assert!(!update(&dir, &[], "stable2407").contains(SKIPPED));
+    fs::remove_dir_all(&dir).unwrap();
+}
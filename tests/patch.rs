@@ -0,0 +1,136 @@
+//! Tests of `diener patch --prune`, run against the `diener` binary and `cargo metadata`.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Create a Polkadot SDK like workspace with the members `foo` and `bar` and the excluded crate
+/// `old` and a project next to it with the given `manifest`.
+///
+/// `{sdk}` in `manifest` is replaced by the path of the workspace. The workspace is committed
+/// to a git repository if `git` is set. Returns the directory of the project.
+fn fixture(name: &str, git: bool, manifest: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("patch-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let sdk = dir.join("sdk");
+    write(
+        &sdk.join("Cargo.toml"),
+        "[workspace]\nmembers = [\"foo\", \"bar\"]\nexclude = [\"old\"]\n",
+    );
+    for name in ["foo", "bar", "old"] {
+        write(
+            &sdk.join(name).join("Cargo.toml"),
+            &format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n"),
+        );
+        write(&sdk.join(name).join("src/lib.rs"), "");
+    }
+    if git {
+        for args in [
+            &["init", "-q"][..],
+            &["add", "-A"],
+            &["commit", "-qm", "init"],
+        ] {
+            let status = Command::new("git")
+                .args(args)
+                .current_dir(&sdk)
+                .env("GIT_AUTHOR_NAME", "diener")
+                .env("GIT_AUTHOR_EMAIL", "diener@example.com")
+                .env("GIT_COMMITTER_NAME", "diener")
+                .env("GIT_COMMITTER_EMAIL", "diener@example.com")
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+    }
+
+    let project = dir.join("project");
+    write(
+        &project.join("Cargo.toml"),
+        &manifest.replace("{sdk}", &sdk.display().to_string()),
+    );
+    write(&project.join("src/lib.rs"), "");
+    project
+}
+
+fn write(path: &Path, content: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+/// Run `diener patch` with `args` in `project` and return the patched manifest.
+///
+/// Git sources can not be checked out in offline mode, so `offline` is only set for the
+/// projects without git dependencies.
+fn patch(project: &Path, offline: bool, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_diener"))
+        .args(offline.then_some("--offline"))
+        .args(["patch", "--crates-to-patch", "../sdk", "--prune"])
+        .args(args)
+        .current_dir(project)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let manifest = fs::read_to_string(project.join("Cargo.toml")).unwrap();
+    fs::remove_dir_all(project.parent().unwrap()).unwrap();
+    manifest
+}
+
+#[test]
+fn prune_keeps_the_patches_skipped_by_skip_path_deps() {
+    let project = fixture(
+        "skip-path-deps",
+        false,
+        r#"[package]
+name = "project"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+foo = "0.1"
+bar = { path = "../sdk/bar" }
+old = "0.1"
+
+[patch.crates-io]
+foo = { path = "{sdk}/foo" }
+bar = { path = "{sdk}/bar" }
+old = { path = "{sdk}/old" }
+"#,
+    );
+
+    let manifest = patch(&project, true, &["--crates", "--skip-path-deps"]);
+    assert!(manifest.contains("bar = { path = "), "{manifest}");
+    assert!(manifest.contains("foo = { path = "), "{manifest}");
+    assert!(!manifest.contains("old = { path = "), "{manifest}");
+}
+
+#[test]
+fn prune_keeps_the_patches_moved_by_auto_target() {
+    let project = fixture(
+        "auto-target",
+        true,
+        r#"[package]
+name = "project"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+foo = { git = "file://{sdk}" }
+bar = { git = "file://{sdk}" }
+
+[patch."file://{sdk}"]
+foo = { path = "{sdk}/foo" }
+"#,
+    );
+
+    // `foo` already resolves to the patch, its source is not the git repository anymore.
+    let manifest = patch(&project, false, &["--auto-target"]);
+    assert!(manifest.contains("bar = { path = "), "{manifest}");
+    assert!(manifest.contains("foo = { path = "), "{manifest}");
+}
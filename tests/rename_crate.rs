@@ -0,0 +1,80 @@
+//! Tests of `rename-crate`, run through the `Engine` on a `MemoryFs`.
+
+use diener::{Engine, MemoryFs, Operation};
+use std::{collections::BTreeMap, sync::Arc};
+
+/// Rename `foo` of a workspace with the members `foo` and `bar` depending on it to `new`.
+///
+/// Returns the modified files, relative to the workspace.
+fn rename(new: &str, rename_key: bool) -> Result<BTreeMap<String, String>, diener::Error> {
+    let fs = MemoryFs::new()
+        .with_file(
+            "/ws/Cargo.toml",
+            "[workspace]\nmembers = [\"foo\", \"bar\"]\n\n[workspace.dependencies]\nfoo = { path = \"foo\" }\n",
+        )
+        .with_file(
+            "/ws/foo/Cargo.toml",
+            "[package]\nname = \"foo\" # The crate.\nversion = \"0.1.0\"\n",
+        )
+        .with_file(
+            "/ws/bar/Cargo.toml",
+            "[package]\nname = \"bar\"\nversion = \"0.1.0\"\n\n[dependencies]\nfoo = { path = \"../foo\", default-features = false }\n\n[dev-dependencies]\nfoo.workspace = true\n",
+        );
+
+    let changes = Engine::with_fs("/ws", Arc::new(fs)).run(Operation::RenameCrate {
+        path: None,
+        old: "foo".into(),
+        new: new.into(),
+        rename_key,
+    })?;
+    let files = changes
+        .files()
+        .map(|(path, _, content)| {
+            let path = path.strip_prefix("/ws").unwrap().display().to_string();
+            (path, content.to_string())
+        })
+        .collect();
+    Ok(files)
+}
+
+#[test]
+fn dependents_keep_the_key_by_default() {
+    let files = rename("baz", false).unwrap();
+    assert_eq!(
+        files["foo/Cargo.toml"],
+        "[package]\nname = \"baz\" # The crate.\nversion = \"0.1.0\"\n"
+    );
+    assert_eq!(
+        files["bar/Cargo.toml"],
+        "[package]\nname = \"bar\"\nversion = \"0.1.0\"\n\n[dependencies]\nfoo = { path = \"../foo\", default-features = false, package = \"baz\" }\n\n[dev-dependencies]\nfoo.workspace = true\n"
+    );
+    assert_eq!(
+        files["Cargo.toml"],
+        "[workspace]\nmembers = [\"foo\", \"bar\"]\n\n[workspace.dependencies]\nfoo = { path = \"foo\", package = \"baz\" }\n"
+    );
+}
+
+#[test]
+fn rename_key_renames_the_dependencies() {
+    let files = rename("baz", true).unwrap();
+    assert_eq!(
+        files["bar/Cargo.toml"],
+        "[package]\nname = \"bar\"\nversion = \"0.1.0\"\n\n[dependencies]\nbaz = { path = \"../foo\", default-features = false }\n\n[dev-dependencies]\nbaz.workspace = true\n"
+    );
+    assert_eq!(
+        files["Cargo.toml"],
+        "[workspace]\nmembers = [\"foo\", \"bar\"]\n\n[workspace.dependencies]\nbaz = { path = \"foo\" }\n"
+    );
+}
+
+#[test]
+fn existing_crates_are_not_overwritten() {
+    let Err(error) = rename("bar", false) else {
+        panic!("Renaming to an existing crate succeeded");
+    };
+    let source = std::error::Error::source(&error).unwrap().to_string();
+    assert!(
+        source.starts_with("A crate named `bar` already exists at /ws/bar/Cargo.toml"),
+        "{source}"
+    );
+}
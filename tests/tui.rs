@@ -0,0 +1,89 @@
+//! Tests of `diener tui`, run against the `diener` binary with the commands on stdin.
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+fn write(path: &Path, content: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+/// Create a project depending on `sp-core` and `sp-io` of the Polkadot SDK and a checkout of
+/// the SDK next to it. Returns the directory of the project.
+fn fixture(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tui-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let primitives = dir.join("polkadot-sdk/substrate/primitives");
+    for (name, krate) in [("sp-core", "core"), ("sp-io", "io")] {
+        write(
+            &primitives.join(krate).join("Cargo.toml"),
+            &format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n"),
+        );
+    }
+
+    let project = dir.join("project");
+    write(
+        &project.join("Cargo.toml"),
+        r#"[package]
+name = "project"
+version = "0.1.0"
+
+[dependencies]
+sp-core = { git = "https://github.com/paritytech/polkadot-sdk", branch = "master" }
+sp-io = { git = "https://github.com/paritytech/polkadot-sdk", branch = "master" }
+"#,
+    );
+    project
+}
+
+/// Run `diener tui` in `project` with the given `commands` and return the manifest.
+fn tui(project: &Path, commands: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_diener"))
+        .arg("tui")
+        .current_dir(project)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(commands.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let manifest = fs::read_to_string(project.join("Cargo.toml")).unwrap();
+    fs::remove_dir_all(project.parent().unwrap()).unwrap();
+    manifest
+}
+
+#[test]
+fn path_points_the_selected_crates_to_the_checkout() {
+    let project = fixture("path");
+
+    let manifest = tui(&project, "1\npath ../polkadot-sdk/./substrate/..\napply\n");
+    assert!(
+        manifest.contains(r#"sp-core = { path = "../polkadot-sdk/substrate/primitives/core" }"#),
+        "{manifest}"
+    );
+    assert!(manifest.contains(r#"sp-io = { git = "#), "{manifest}");
+}
+
+#[test]
+fn path_must_be_a_directory() {
+    let project = fixture("missing");
+
+    let manifest = tui(&project, "1\npath ../missing\napply\nquit\n");
+    assert!(!manifest.contains("path = "), "{manifest}");
+}